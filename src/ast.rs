@@ -1,5 +1,59 @@
 //src/ast.rs
 pub mod unchecked {
+    use crate::common::SourceLine;
+
+    /// 表示 C 语言中的类型
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum CType {
+        Int,
+        /// 64 位整数
+        Long,
+        /// 32 位无符号整数
+        UInt,
+        /// 64 位无符号整数，也是 `sizeof` 的结果类型
+        ULong,
+        /// `_Bool`：任何存入它的值都会被规范化为 0 或 1
+        Bool,
+        /// 有符号的 `char`：和 `_Bool` 一样占 1 字节，参与运算时先提升为 int
+        Char,
+        /// 结构体类型，保存（标识符解析后唯一的）结构体标签
+        Struct(String),
+        /// 函数类型，记录每个参数的类型和返回类型
+        Function {
+            param_types: Vec<CType>,
+            return_type: Box<CType>,
+        },
+    }
+
+    impl std::fmt::Display for CType {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self {
+                CType::Int => write!(f, "int"),
+                CType::Long => write!(f, "long"),
+                CType::UInt => write!(f, "unsigned int"),
+                CType::ULong => write!(f, "unsigned long"),
+                CType::Bool => write!(f, "_Bool"),
+                CType::Char => write!(f, "char"),
+                // 标签在标识符解析后形如 `Point.3`，显示时去掉唯一后缀
+                CType::Struct(tag) => {
+                    write!(f, "struct {}", tag.split('.').next().unwrap_or(tag))
+                }
+                // 采用 C 的写法，例如 `long (int, long)`
+                CType::Function {
+                    param_types,
+                    return_type,
+                } => {
+                    let params: Vec<String> = param_types.iter().map(|t| t.to_string()).collect();
+                    if params.is_empty() {
+                        write!(f, "{} (void)", return_type)
+                    } else {
+                        write!(f, "{} ({})", return_type, params.join(", "))
+                    }
+                }
+            }
+        }
+    }
+
     // Program 现在包含一个声明列表
     #[derive(Debug, PartialEq)]
     pub struct Program {
@@ -12,15 +66,39 @@ pub mod unchecked {
         // 函数声明/定义
         Function {
             name: String,
-            params: Vec<String>, // 参数列表
+            params: Vec<String>,     // 参数列表
+            param_types: Vec<CType>, // 与 params 一一对应的参数类型
+            return_type: CType,
             body: Option<Block>, // Option<Block> 可以区分声明和定义
+            line: SourceLine,    // 函数名所在的行，用于错误信息
         },
         // 变量声明 (用于全局变量)
         Variable {
             name: String,
             init: Option<Expression>,
+            var_type: CType,
+            storage_class: Option<StorageClass>,
+        },
+        // 结构体类型声明，例如 `struct Point { int x; int y; };`
+        Struct {
+            tag: String,
+            members: Vec<MemberDeclaration>,
         },
     }
+
+    /// 变量声明的存储类说明符
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum StorageClass {
+        /// 静态存储期：变量存放在 .data/.bss 中，在多次调用之间保持它的值
+        Static,
+    }
+
+    /// 结构体中的一个成员
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct MemberDeclaration {
+        pub name: String,
+        pub member_type: CType,
+    }
     // Block 和 BlockItem 的定义是正确的
     #[derive(Debug, PartialEq)]
     pub struct Block {
@@ -61,6 +139,13 @@ pub mod unchecked {
         },
         Break,
         Continue,
+        /// `goto label;`
+        Goto(String),
+        /// 带标签的语句 `label: <statement>`
+        Labeled {
+            name: String,
+            stmt: Box<Statement>,
+        },
     }
 
     #[derive(Debug, PartialEq)]
@@ -70,6 +155,13 @@ pub mod unchecked {
         Not,
     }
 
+    /// 自增/自减运算符，可以作为前缀或后缀使用
+    #[derive(Debug, PartialEq)]
+    pub enum IncDecOperator {
+        Increment,
+        Decrement,
+    }
+
     #[derive(Debug, PartialEq)]
     pub enum BinaryOperator {
         Add,
@@ -77,6 +169,11 @@ pub mod unchecked {
         Multiply,
         Divide,
         Remainder,
+        BitAnd,
+        BitOr,
+        BitXor,
+        ShiftLeft,
+        ShiftRight,
         And,
         Or,
         Equal,
@@ -90,6 +187,9 @@ pub mod unchecked {
     #[derive(Debug, PartialEq)]
     pub enum Expression {
         Constant(i32),
+        LongConstant(i64),
+        UIntConstant(u32),
+        ULongConstant(u64),
         Unary {
             operator: UnaryOperator,
             expression: Box<Expression>,
@@ -99,7 +199,11 @@ pub mod unchecked {
             left: Box<Expression>,
             right: Box<Expression>,
         },
-        Var(String),
+        // 变量引用；`line` 是它在源文件中的行号，用于报告错误
+        Var {
+            name: String,
+            line: SourceLine,
+        },
         Assign {
             left: Box<Expression>,
             right: Box<Expression>,
@@ -109,10 +213,185 @@ pub mod unchecked {
             left: Box<Expression>,
             right: Box<Expression>,
         },
+        // 逗号运算符 `left, right`：先求值 left 并丢弃结果，值为 right
+        Comma {
+            left: Box<Expression>,
+            right: Box<Expression>,
+        },
         FunctionCall {
             name: String,
             args: Vec<Expression>,
+            line: SourceLine,
+        },
+        // 前缀 `++x`/`--x`，值为更新后的值
+        Prefix {
+            operator: IncDecOperator,
+            expression: Box<Expression>,
+        },
+        // 后缀 `x++`/`x--`，值为更新前的值
+        Postfix {
+            operator: IncDecOperator,
+            expression: Box<Expression>,
+        },
+        // 结构体成员访问 `base.member`
+        Member {
+            base: Box<Expression>,
+            member: String,
+        },
+        // 类型转换。目前只由类型检查器插入，用于表示隐式转换
+        Cast {
+            target_type: CType,
+            expression: Box<Expression>,
         },
+        // `sizeof`，由类型检查器折叠为常量
+        SizeOf(SizeOfOperand),
+    }
+
+    /// `sizeof` 的操作数：`sizeof(type)` 或 `sizeof expr`（表达式不会被求值）
+    #[derive(Debug, PartialEq)]
+    pub enum SizeOfOperand {
+        Type(CType),
+        Expression(Box<Expression>),
+    }
+
+    /// 只读的 AST 遍历器。
+    ///
+    /// `visit_*` 是每种节点的入口，默认直接调用对应的 `walk_*`；
+    /// `walk_*` 负责递归访问子节点。实现者只需覆盖关心的 `visit_*`，
+    /// 并在需要继续向下遍历时调用 `walk_*`。
+    pub trait Visitor {
+        fn visit_program(&mut self, prog: &Program) {
+            self.walk_program(prog);
+        }
+        fn visit_declaration(&mut self, decl: &Declaration) {
+            self.walk_declaration(decl);
+        }
+        fn visit_block(&mut self, block: &Block) {
+            self.walk_block(block);
+        }
+        fn visit_block_item(&mut self, item: &BlockItem) {
+            self.walk_block_item(item);
+        }
+        fn visit_statement(&mut self, stmt: &Statement) {
+            self.walk_statement(stmt);
+        }
+        fn visit_expression(&mut self, expr: &Expression) {
+            self.walk_expression(expr);
+        }
+
+        fn walk_program(&mut self, prog: &Program) {
+            for decl in &prog.declarations {
+                self.visit_declaration(decl);
+            }
+        }
+        fn walk_declaration(&mut self, decl: &Declaration) {
+            match decl {
+                Declaration::Function { body, .. } => {
+                    if let Some(block) = body {
+                        self.visit_block(block);
+                    }
+                }
+                Declaration::Variable { init, .. } => {
+                    if let Some(expr) = init {
+                        self.visit_expression(expr);
+                    }
+                }
+                Declaration::Struct { .. } => {}
+            }
+        }
+        fn walk_block(&mut self, block: &Block) {
+            for item in &block.blocks {
+                self.visit_block_item(item);
+            }
+        }
+        fn walk_block_item(&mut self, item: &BlockItem) {
+            match item {
+                BlockItem::S(stmt) => self.visit_statement(stmt),
+                BlockItem::D(decl) => self.visit_declaration(decl),
+            }
+        }
+        fn walk_statement(&mut self, stmt: &Statement) {
+            match stmt {
+                Statement::Return(expr) | Statement::Expression(expr) => {
+                    self.visit_expression(expr)
+                }
+                Statement::If {
+                    condition,
+                    then_stat,
+                    else_stat,
+                } => {
+                    self.visit_expression(condition);
+                    self.visit_statement(then_stat);
+                    if let Some(else_stat) = else_stat {
+                        self.visit_statement(else_stat);
+                    }
+                }
+                Statement::Compound(block) => self.visit_block(block),
+                Statement::While { condition, body } | Statement::DoWhile { body, condition } => {
+                    self.visit_expression(condition);
+                    self.visit_statement(body);
+                }
+                Statement::For {
+                    init,
+                    condition,
+                    post,
+                    body,
+                } => {
+                    if let Some(init) = init {
+                        self.visit_block_item(init);
+                    }
+                    if let Some(condition) = condition {
+                        self.visit_expression(condition);
+                    }
+                    if let Some(post) = post {
+                        self.visit_expression(post);
+                    }
+                    self.visit_statement(body);
+                }
+                Statement::Labeled { stmt, .. } => self.visit_statement(stmt),
+                Statement::Empty | Statement::Break | Statement::Continue | Statement::Goto(_) => {}
+            }
+        }
+        fn walk_expression(&mut self, expr: &Expression) {
+            match expr {
+                Expression::Constant(_)
+                | Expression::LongConstant(_)
+                | Expression::UIntConstant(_)
+                | Expression::ULongConstant(_)
+                | Expression::Var { .. } => {}
+                Expression::Unary { expression, .. }
+                | Expression::Prefix { expression, .. }
+                | Expression::Postfix { expression, .. }
+                | Expression::Cast { expression, .. }
+                | Expression::Member {
+                    base: expression, ..
+                } => self.visit_expression(expression),
+                Expression::Binary { left, right, .. }
+                | Expression::Assign { left, right }
+                | Expression::Comma { left, right } => {
+                    self.visit_expression(left);
+                    self.visit_expression(right);
+                }
+                Expression::Conditional {
+                    condition,
+                    left,
+                    right,
+                } => {
+                    self.visit_expression(condition);
+                    self.visit_expression(left);
+                    self.visit_expression(right);
+                }
+                Expression::FunctionCall { args, .. } => {
+                    for arg in args {
+                        self.visit_expression(arg);
+                    }
+                }
+                Expression::SizeOf(SizeOfOperand::Expression(expression)) => {
+                    self.visit_expression(expression)
+                }
+                Expression::SizeOf(SizeOfOperand::Type(_)) => {}
+            }
+        }
     }
 }
 
@@ -132,7 +411,10 @@ pub mod checked {
 
     // Expression 和 Operator 可以直接复用，因为它们不包含 Statement
     // 为了简单起见，我们可以在这里重新声明它们，或者在转换时处理
-    pub use super::unchecked::{BinaryOperator, Expression, UnaryOperator};
+    pub use super::unchecked::{
+        BinaryOperator, CType, Expression, IncDecOperator, MemberDeclaration, SizeOfOperand,
+        StorageClass, UnaryOperator,
+    };
 
     #[derive(Debug, PartialEq)]
     pub struct Program {
@@ -153,6 +435,12 @@ pub mod checked {
             // 注意：init 表达式也应该是 checked 的，
             // 但因为 Expression 没有子 Statement，所以可以直接复用
             init: Option<Expression>,
+            var_type: CType,
+            storage_class: Option<StorageClass>,
+        },
+        Struct {
+            tag: String,
+            members: Vec<MemberDeclaration>,
         },
     }
 
@@ -208,5 +496,197 @@ pub mod checked {
         Continue {
             target_id: LoopId, // 指向目标循环
         },
+        // 标签名已经由 LabelResolver 改写为函数内唯一的名字
+        Goto(String),
+        Labeled {
+            name: String,
+            stmt: Box<Statement>,
+        },
+    }
+
+    /// checked AST 的只读遍历器，用法与 `unchecked::Visitor` 相同。
+    ///
+    /// 表达式两边共用，`walk_expression` 直接复用 `unchecked::Visitor` 的实现，
+    /// 子表达式仍然交给这里的 `visit_expression`。
+    pub trait Visitor {
+        fn visit_program(&mut self, prog: &Program) {
+            self.walk_program(prog);
+        }
+        fn visit_declaration(&mut self, decl: &Declaration) {
+            self.walk_declaration(decl);
+        }
+        fn visit_block(&mut self, block: &Block) {
+            self.walk_block(block);
+        }
+        fn visit_block_item(&mut self, item: &BlockItem) {
+            self.walk_block_item(item);
+        }
+        fn visit_statement(&mut self, stmt: &Statement) {
+            self.walk_statement(stmt);
+        }
+        fn visit_expression(&mut self, expr: &Expression) {
+            self.walk_expression(expr);
+        }
+
+        fn walk_program(&mut self, prog: &Program) {
+            for decl in &prog.declarations {
+                self.visit_declaration(decl);
+            }
+        }
+        fn walk_declaration(&mut self, decl: &Declaration) {
+            match decl {
+                Declaration::Function { body, .. } => {
+                    if let Some(block) = body {
+                        self.visit_block(block);
+                    }
+                }
+                Declaration::Variable { init, .. } => {
+                    if let Some(expr) = init {
+                        self.visit_expression(expr);
+                    }
+                }
+                Declaration::Struct { .. } => {}
+            }
+        }
+        fn walk_block(&mut self, block: &Block) {
+            for item in &block.blocks {
+                self.visit_block_item(item);
+            }
+        }
+        fn walk_block_item(&mut self, item: &BlockItem) {
+            match item {
+                BlockItem::S(stmt) => self.visit_statement(stmt),
+                BlockItem::D(decl) => self.visit_declaration(decl),
+            }
+        }
+        fn walk_statement(&mut self, stmt: &Statement) {
+            match stmt {
+                Statement::Return(expr) | Statement::Expression(expr) => {
+                    self.visit_expression(expr)
+                }
+                Statement::If {
+                    condition,
+                    then_stat,
+                    else_stat,
+                } => {
+                    self.visit_expression(condition);
+                    self.visit_statement(then_stat);
+                    if let Some(else_stat) = else_stat {
+                        self.visit_statement(else_stat);
+                    }
+                }
+                Statement::Compound(block) => self.visit_block(block),
+                Statement::While {
+                    condition, body, ..
+                }
+                | Statement::DoWhile {
+                    body, condition, ..
+                } => {
+                    self.visit_expression(condition);
+                    self.visit_statement(body);
+                }
+                Statement::For {
+                    init,
+                    condition,
+                    post,
+                    body,
+                    ..
+                } => {
+                    if let Some(init) = init {
+                        self.visit_block_item(init);
+                    }
+                    if let Some(condition) = condition {
+                        self.visit_expression(condition);
+                    }
+                    if let Some(post) = post {
+                        self.visit_expression(post);
+                    }
+                    self.visit_statement(body);
+                }
+                Statement::Labeled { stmt, .. } => self.visit_statement(stmt),
+                Statement::Empty
+                | Statement::Break { .. }
+                | Statement::Continue { .. }
+                | Statement::Goto(_) => {}
+            }
+        }
+        fn walk_expression(&mut self, expr: &Expression) {
+            super::unchecked::Visitor::walk_expression(&mut Expressions(self), expr);
+        }
+    }
+
+    /// 让 `unchecked::Visitor::walk_expression` 把子表达式转交给 checked 遍历器
+    struct Expressions<'v, V: ?Sized>(&'v mut V);
+
+    impl<V: Visitor + ?Sized> super::unchecked::Visitor for Expressions<'_, V> {
+        fn visit_expression(&mut self, expr: &Expression) {
+            self.0.visit_expression(expr);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::checked;
+    use super::unchecked::{Expression, Visitor};
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    use crate::test_support::check_source;
+
+    /// 统计程序中函数调用的个数
+    struct CallCounter {
+        count: usize,
+    }
+
+    impl Visitor for CallCounter {
+        fn visit_expression(&mut self, expr: &Expression) {
+            if let Expression::FunctionCall { .. } = expr {
+                self.count += 1;
+            }
+            self.walk_expression(expr);
+        }
+    }
+
+    #[test]
+    fn test_visitor_counts_function_calls() {
+        let source = r#"
+            int f(int a);
+            int main(void) {
+                int x = f(f(1));
+                while (x) {
+                    x = x - f(2);
+                }
+                return x ? f(3) : 0;
+            }
+        "#;
+        let tokens: Vec<_> = Lexer::new(source).map(|t| t.unwrap()).collect();
+        let program = Parser::new(&tokens).parse().unwrap();
+
+        let mut counter = CallCounter { count: 0 };
+        counter.visit_program(&program);
+        assert_eq!(counter.count, 4);
+    }
+
+    #[test]
+    fn test_checked_visitor_reaches_nested_calls() {
+        struct CheckedCallCounter {
+            count: usize,
+        }
+
+        impl checked::Visitor for CheckedCallCounter {
+            fn visit_expression(&mut self, expr: &Expression) {
+                if let Expression::FunctionCall { .. } = expr {
+                    self.count += 1;
+                }
+                self.walk_expression(expr);
+            }
+        }
+
+        let checked = check_source(
+            "int f(int a); int main(void) { for (int i = f(0); i; i = f(i)) break; return f(f(1)); }",
+        );
+        let mut counter = CheckedCallCounter { count: 0 };
+        checked::Visitor::visit_program(&mut counter, &checked.ast);
+        assert_eq!(counter.count, 4);
     }
 }
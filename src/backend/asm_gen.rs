@@ -1,17 +1,36 @@
 // src/backend/asm_gen.rs
 
+use crate::ast::checked::CType;
+use crate::backend::reg_alloc;
 use crate::ir::{assembly, tacky};
+use crate::semantics::type_checker::{StructLayout, Symbol};
 use std::collections::HashMap;
 
 /// 负责将 TACKY IR 转换为最终的汇编 AST。
 /// 这个过程分为三个阶段，现在针对整个程序进行。
-pub struct AsmGenerator {
-    // 这个结构体仍然可以是无状态的
+pub struct AsmGenerator<'a> {
+    /// 符号表（包括 TACKY 生成阶段登记的临时变量），用于确定每个操作数的大小
+    symbols: &'a HashMap<String, Symbol>,
+    /// 结构体布局，用于为结构体变量分配栈空间
+    struct_types: &'a HashMap<String, StructLayout>,
+    /// 每个函数的栈帧大小（已对齐到 16 字节），按函数出现的顺序记录
+    pub stack_usage: Vec<(String, u32)>,
+    /// 优化级别（对应 -O0 / -O1）
+    opt_level: u8,
 }
 
-impl AsmGenerator {
-    pub fn new() -> Self {
-        AsmGenerator {}
+impl<'a> AsmGenerator<'a> {
+    pub fn new(
+        symbols: &'a HashMap<String, Symbol>,
+        struct_types: &'a HashMap<String, StructLayout>,
+        opt_level: u8,
+    ) -> Self {
+        AsmGenerator {
+            symbols,
+            struct_types,
+            stack_usage: Vec::new(),
+            opt_level,
+        }
     }
 
     /// 主入口：将 TACKY 程序转换为汇编程序。
@@ -27,19 +46,49 @@ impl AsmGenerator {
             // 将单个 TACKY 函数转换为汇编函数
             let mut asm_func = self.convert_tacky_to_asm_pass1(&tacky_func)?;
 
+            // -O1 下先把常用的伪寄存器分配到寄存器中，剩下的再交给 Pass 2
+            let saved_registers = if self.opt_level >= 1 {
+                reg_alloc::allocate_registers(&mut asm_func, |name| {
+                    self.symbols.get(name).is_some_and(|s| {
+                        s.static_init.is_none() && !matches!(s.c_type, CType::Struct(_))
+                    })
+                })
+            } else {
+                Vec::new()
+            };
+
             // --- PASS 2: Replace Pseudoregisters -> Stack slots ---
             // 为当前函数分配栈空间，并返回所需字节数
             let stack_bytes_needed = self.replace_pseudo_with_stack_pass2(&mut asm_func)?;
 
             // --- PASS 3: Fix up instructions ---
             // 修复当前函数的指令，并添加函数序言/尾言所需的 AllocateStack
-            self.fixup_instructions_pass3(&mut asm_func, stack_bytes_needed);
+            let frame_size =
+                self.fixup_instructions_pass3(&mut asm_func, stack_bytes_needed, &saved_registers);
+            self.stack_usage.push((asm_func.name.clone(), frame_size));
+
+            // --- PASS 4: Peephole (仅 -O1) ---
+            if self.opt_level >= 1 {
+                remove_redundant_moves(&mut asm_func.instructions);
+            }
 
             final_functions.push(asm_func);
         }
 
+        let static_variables = tacky_program
+            .static_variables
+            .into_iter()
+            .map(|var| assembly::StaticVariable {
+                name: var.name,
+                size: var.size,
+                alignment: var.alignment,
+                init: var.init,
+                global: var.global,
+            })
+            .collect();
         Ok(assembly::Program {
             functions: final_functions,
+            static_variables,
         })
     }
 
@@ -57,7 +106,18 @@ impl AsmGenerator {
         self.copy_params_to_pseudo(&tacky_func.params, &mut instructions);
 
         // 遍历函数体中的每条 TACKY 指令
-        for tacky_inst in &tacky_func.body {
+        let mut i = 0;
+        while i < tacky_func.body.len() {
+            // -O1 下，尝试把简单的三元表达式转换为条件传送
+            if self.opt_level >= 1
+                && let Some(consumed) =
+                    self.try_convert_conditional_move(&tacky_func.body[i..], &mut instructions)
+            {
+                i += consumed;
+                continue;
+            }
+            let tacky_inst = &tacky_func.body[i];
+            i += 1;
             match tacky_inst {
                 // --- 【核心修改】处理 FunCall ---
                 tacky::Instruction::FunCall { name, args, dst } => {
@@ -67,13 +127,58 @@ impl AsmGenerator {
                 // --- 简单直接的转换 (基本不变) ---
                 tacky::Instruction::Return(val) => {
                     instructions.push(assembly::Instruction::Mov {
+                        asm_type: self.asm_type_of(val),
                         src: self.convert_tacky_val(val),
                         dst: assembly::Operand::Reg(assembly::Register::AX),
                     });
                     instructions.push(assembly::Instruction::Ret);
                 }
                 tacky::Instruction::Copy { src, dst } => {
+                    instructions.push(self.convert_copy(
+                        src,
+                        self.convert_tacky_val(dst),
+                        self.asm_type_of(dst),
+                    ));
+                }
+                tacky::Instruction::CopyToOffset { src, dst, offset } => {
+                    instructions.push(self.convert_copy(
+                        src,
+                        assembly::Operand::PseudoMem(dst.clone(), *offset as i32),
+                        self.member_asm_type(dst, *offset),
+                    ));
+                }
+                tacky::Instruction::CopyFromOffset { src, offset, dst } => {
                     instructions.push(assembly::Instruction::Mov {
+                        asm_type: self.asm_type_of(dst),
+                        src: assembly::Operand::PseudoMem(src.clone(), *offset as i32),
+                        dst: self.convert_tacky_val(dst),
+                    });
+                }
+                tacky::Instruction::SignExtend { src, dst } => {
+                    instructions.push(assembly::Instruction::Movsx {
+                        src_type: self.asm_type_of(src),
+                        dst_type: assembly::AsmType::Quadword,
+                        src: self.convert_tacky_val(src),
+                        dst: self.convert_tacky_val(dst),
+                    });
+                }
+                tacky::Instruction::ZeroExtend { src, dst } => {
+                    // movl 写入 32 位寄存器时会把高 32 位清零
+                    instructions.push(assembly::Instruction::Mov {
+                        asm_type: assembly::AsmType::Longword,
+                        src: self.convert_tacky_val(src),
+                        dst: assembly::Operand::Reg(assembly::Register::R11),
+                    });
+                    instructions.push(assembly::Instruction::Mov {
+                        asm_type: assembly::AsmType::Quadword,
+                        src: assembly::Operand::Reg(assembly::Register::R11),
+                        dst: self.convert_tacky_val(dst),
+                    });
+                }
+                tacky::Instruction::Truncate { src, dst } => {
+                    // 截断只需要复制低 4 字节
+                    instructions.push(assembly::Instruction::Mov {
+                        asm_type: assembly::AsmType::Longword,
                         src: self.convert_tacky_val(src),
                         dst: self.convert_tacky_val(dst),
                     });
@@ -88,6 +193,7 @@ impl AsmGenerator {
                 // --- 涉及比较和跳转的转换 (基本不变) ---
                 tacky::Instruction::JumpIfZero { condition, target } => {
                     instructions.push(assembly::Instruction::Cmp {
+                        asm_type: self.asm_type_of(condition),
                         src1: assembly::Operand::Imm(0),
                         src2: self.convert_tacky_val(condition),
                     });
@@ -98,6 +204,7 @@ impl AsmGenerator {
                 }
                 tacky::Instruction::JumpIfNotZero { condition, target } => {
                     instructions.push(assembly::Instruction::Cmp {
+                        asm_type: self.asm_type_of(condition),
                         src1: assembly::Operand::Imm(0),
                         src2: self.convert_tacky_val(condition),
                     });
@@ -127,6 +234,131 @@ impl AsmGenerator {
         })
     }
 
+    /// 识别由 `cond ? a : b` 生成的无副作用 TACKY 序列：
+    ///
+    /// ```text
+    /// [c = src1 <relop> src2]
+    /// JumpIfZero(c, else)
+    /// Copy(a, r)
+    /// Jump(end)
+    /// Label(else)
+    /// Copy(b, r)
+    /// Label(end)
+    /// ```
+    ///
+    /// 并将其转换为 `cmp` + `cmovCC`，消除跳转。
+    /// 匹配成功时返回消耗的 TACKY 指令条数。
+    fn try_convert_conditional_move(
+        &self,
+        body: &[tacky::Instruction],
+        instructions: &mut Vec<assembly::Instruction>,
+    ) -> Option<usize> {
+        // 条件是一个关系运算的结果时，直接复用这次比较的标志位
+        let (compare, rest) = match body {
+            [
+                tacky::Instruction::Binary {
+                    op,
+                    src1,
+                    src2,
+                    dst: tacky::Val::Var(cond_name),
+                },
+                rest @ ..,
+            ] if matches!(
+                rest.first(),
+                Some(tacky::Instruction::JumpIfZero {
+                    condition: tacky::Val::Var(name),
+                    ..
+                }) if name == cond_name
+            ) =>
+            {
+                (Some((relational_cond_code(op)?, src1, src2)), rest)
+            }
+            _ => (None, body),
+        };
+
+        let [
+            tacky::Instruction::JumpIfZero {
+                condition,
+                target: else_target,
+            },
+            tacky::Instruction::Copy {
+                src: then_val,
+                dst: then_dst,
+            },
+            tacky::Instruction::Jump(end_target),
+            tacky::Instruction::Label(else_label),
+            tacky::Instruction::Copy {
+                src: else_val,
+                dst: else_dst,
+            },
+            tacky::Instruction::Label(end_label),
+            ..,
+        ] = rest
+        else {
+            return None;
+        };
+        let (tacky::Val::Var(then_name), tacky::Val::Var(else_name)) = (then_dst, else_dst) else {
+            return None;
+        };
+        if else_target != else_label || end_target != end_label || then_name != else_name {
+            return None;
+        }
+
+        // cmov 没有 1 字节的形式
+        let asm_type = self.asm_type_of(then_dst);
+        if asm_type == assembly::AsmType::Byte {
+            return None;
+        }
+        // 1. 设置标志位
+        let cond = match compare {
+            Some((cond, src1, src2)) => {
+                instructions.push(assembly::Instruction::Cmp {
+                    asm_type: self.asm_type_of(src1),
+                    src1: self.convert_tacky_val(src2),
+                    src2: self.convert_tacky_val(src1),
+                });
+                cond
+            }
+            None => {
+                instructions.push(assembly::Instruction::Cmp {
+                    asm_type: self.asm_type_of(condition),
+                    src1: assembly::Operand::Imm(0),
+                    src2: self.convert_tacky_val(condition),
+                });
+                assembly::CondCode::NE
+            }
+        };
+        // 2. 先假设条件不成立，取 else 分支的值（mov 不影响标志位）
+        instructions.push(assembly::Instruction::Mov {
+            asm_type,
+            src: self.convert_tacky_val(else_val),
+            dst: assembly::Operand::Reg(assembly::Register::R11),
+        });
+        // 3. 条件成立时改用 then 分支的值；cmov 的源操作数不能是立即数
+        let mut then_operand = self.convert_tacky_val(then_val);
+        if let assembly::Operand::Imm(_) = then_operand {
+            instructions.push(assembly::Instruction::Mov {
+                asm_type,
+                src: then_operand,
+                dst: assembly::Operand::Reg(assembly::Register::R10),
+            });
+            then_operand = assembly::Operand::Reg(assembly::Register::R10);
+        }
+        instructions.push(assembly::Instruction::CMovCC {
+            cond,
+            asm_type,
+            src: then_operand,
+            dst: assembly::Operand::Reg(assembly::Register::R11),
+        });
+        instructions.push(assembly::Instruction::Mov {
+            asm_type,
+            src: assembly::Operand::Reg(assembly::Register::R11),
+            dst: self.convert_tacky_val(then_dst),
+        });
+
+        Some(body.len() - rest.len() + 6)
+    }
+
     /// 【新增辅助函数】根据函数调用伪代码实现 FunCall 转换
     fn convert_funcall(
         &self,
@@ -166,6 +398,7 @@ impl AsmGenerator {
         // 2. 通过寄存器传递参数
         for (i, arg) in register_args.iter().enumerate() {
             instructions.push(assembly::Instruction::Mov {
+                asm_type: self.asm_type_of(arg),
                 src: self.convert_tacky_val(arg),
                 dst: assembly::Operand::Reg(arg_registers[i]),
             });
@@ -182,6 +415,7 @@ impl AsmGenerator {
                 _ => {
                     // Pseudo, or later Stack
                     instructions.push(assembly::Instruction::Mov {
+                        asm_type: self.asm_type_of(arg),
                         src: assembly_arg,
                         dst: assembly::Operand::Reg(assembly::Register::AX),
                     });
@@ -203,6 +437,7 @@ impl AsmGenerator {
 
         // 6. 获取返回值
         instructions.push(assembly::Instruction::Mov {
+            asm_type: self.asm_type_of(dst),
             src: assembly::Operand::Reg(assembly::Register::AX),
             dst: self.convert_tacky_val(dst),
         });
@@ -235,6 +470,7 @@ impl AsmGenerator {
             };
 
             instructions.push(assembly::Instruction::Mov {
+                asm_type: self.asm_type_of_name(param_name),
                 src: src_operand,
                 dst: assembly::Operand::Pseudo(param_name.clone()),
             });
@@ -254,10 +490,12 @@ impl AsmGenerator {
         match op {
             tacky::UnaryOperator::Not => {
                 instructions.push(assembly::Instruction::Cmp {
+                    asm_type: self.asm_type_of(src),
                     src1: assembly::Operand::Imm(0),
                     src2: self.convert_tacky_val(src),
                 });
                 instructions.push(assembly::Instruction::Mov {
+                    asm_type: self.asm_type_of(dst),
                     src: assembly::Operand::Imm(0),
                     dst: dst_operand.clone(),
                 });
@@ -272,12 +510,15 @@ impl AsmGenerator {
                     tacky::UnaryOperator::Complement => assembly::UnaryOperator::Not,
                     _ => unreachable!(),
                 };
+                let asm_type = self.asm_type_of(src);
                 instructions.push(assembly::Instruction::Mov {
+                    asm_type,
                     src: self.convert_tacky_val(src),
                     dst: dst_operand.clone(),
                 });
                 instructions.push(assembly::Instruction::Unary {
                     op: asm_op,
+                    asm_type,
                     operand: dst_operand,
                 });
             }
@@ -295,72 +536,85 @@ impl AsmGenerator {
         let dst_operand = self.convert_tacky_val(dst);
         let src1_operand = self.convert_tacky_val(src1);
         let src2_operand = self.convert_tacky_val(src2);
+        // 类型检查器保证两个源操作数的类型相同
+        let src_type = self.asm_type_of(src1);
+
+        if let Some(cond_code) = relational_cond_code(op) {
+            instructions.push(assembly::Instruction::Cmp {
+                asm_type: src_type,
+                src1: src2_operand,
+                src2: src1_operand,
+            });
+            instructions.push(assembly::Instruction::Mov {
+                asm_type: self.asm_type_of(dst),
+                src: assembly::Operand::Imm(0),
+                dst: dst_operand.clone(),
+            });
+            instructions.push(assembly::Instruction::SetCC(cond_code, dst_operand));
+            return;
+        }
 
         match op {
-            tacky::BinaryOperator::Equal
-            | tacky::BinaryOperator::NotEqual
-            | tacky::BinaryOperator::LessThan
-            | tacky::BinaryOperator::LessOrEqual
-            | tacky::BinaryOperator::GreaterThan
-            | tacky::BinaryOperator::GreaterEqual => {
-                let cond_code = match op {
-                    tacky::BinaryOperator::Equal => assembly::CondCode::E,
-                    tacky::BinaryOperator::NotEqual => assembly::CondCode::NE,
-                    tacky::BinaryOperator::LessThan => assembly::CondCode::L,
-                    tacky::BinaryOperator::LessOrEqual => assembly::CondCode::LE,
-                    tacky::BinaryOperator::GreaterThan => assembly::CondCode::G,
-                    tacky::BinaryOperator::GreaterEqual => assembly::CondCode::GE,
-                    _ => unreachable!(),
-                };
-                instructions.push(assembly::Instruction::Cmp {
-                    src1: src2_operand,
-                    src2: src1_operand,
-                });
-                instructions.push(assembly::Instruction::Mov {
-                    src: assembly::Operand::Imm(0),
-                    dst: dst_operand.clone(),
-                });
-                instructions.push(assembly::Instruction::SetCC(cond_code, dst_operand));
-            }
-            tacky::BinaryOperator::Divide => {
-                instructions.push(assembly::Instruction::Mov {
-                    src: src1_operand,
-                    dst: assembly::Operand::Reg(assembly::Register::AX),
-                });
-                instructions.push(assembly::Instruction::Cdq);
-                instructions.push(assembly::Instruction::Idiv(src2_operand));
-                instructions.push(assembly::Instruction::Mov {
-                    src: assembly::Operand::Reg(assembly::Register::AX),
-                    dst: dst_operand,
-                });
-            }
-            tacky::BinaryOperator::Remainder => {
+            tacky::BinaryOperator::Divide
+            | tacky::BinaryOperator::Remainder
+            | tacky::BinaryOperator::UnsignedDivide
+            | tacky::BinaryOperator::UnsignedRemainder => {
                 instructions.push(assembly::Instruction::Mov {
+                    asm_type: src_type,
                     src: src1_operand,
                     dst: assembly::Operand::Reg(assembly::Register::AX),
                 });
-                instructions.push(assembly::Instruction::Cdq);
-                instructions.push(assembly::Instruction::Idiv(src2_operand));
+                if matches!(
+                    op,
+                    tacky::BinaryOperator::Divide | tacky::BinaryOperator::Remainder
+                ) {
+                    instructions.push(assembly::Instruction::Cdq(src_type));
+                    instructions.push(assembly::Instruction::Idiv(src_type, src2_operand));
+                } else {
+                    // 无符号除法的被除数高位是 0 而不是符号位
+                    instructions.push(assembly::Instruction::Mov {
+                        asm_type: src_type,
+                        src: assembly::Operand::Imm(0),
+                        dst: assembly::Operand::Reg(assembly::Register::DX),
+                    });
+                    instructions.push(assembly::Instruction::Div(src_type, src2_operand));
+                }
+                // 商在 %eax 中，余数在 %edx 中
+                let result = match op {
+                    tacky::BinaryOperator::Divide | tacky::BinaryOperator::UnsignedDivide => {
+                        assembly::Register::AX
+                    }
+                    _ => assembly::Register::DX,
+                };
                 instructions.push(assembly::Instruction::Mov {
-                    src: assembly::Operand::Reg(assembly::Register::DX),
+                    asm_type: src_type,
+                    src: assembly::Operand::Reg(result),
                     dst: dst_operand,
                 });
             }
-            tacky::BinaryOperator::Add
-            | tacky::BinaryOperator::Subtract
-            | tacky::BinaryOperator::Multiply => {
+            _ => {
+                // 移位的结果类型由左操作数决定，这里的 src_type 正是左操作数的类型；
+                // 移位次数不必与之同类型，Pass 3 会把它放进 %cl
                 let asm_op = match op {
                     tacky::BinaryOperator::Add => assembly::BinaryOperator::Add,
                     tacky::BinaryOperator::Subtract => assembly::BinaryOperator::Subtract,
                     tacky::BinaryOperator::Multiply => assembly::BinaryOperator::Multiply,
-                    _ => unreachable!(),
+                    tacky::BinaryOperator::BitAnd => assembly::BinaryOperator::And,
+                    tacky::BinaryOperator::BitOr => assembly::BinaryOperator::Or,
+                    tacky::BinaryOperator::BitXor => assembly::BinaryOperator::Xor,
+                    tacky::BinaryOperator::ShiftLeft => assembly::BinaryOperator::Sal,
+                    tacky::BinaryOperator::ShiftRight => assembly::BinaryOperator::Sar,
+                    tacky::BinaryOperator::UnsignedShiftRight => assembly::BinaryOperator::Shr,
+                    _ => unreachable!("relational operators are handled above"),
                 };
                 instructions.push(assembly::Instruction::Mov {
+                    asm_type: src_type,
                     src: src1_operand,
                     dst: dst_operand.clone(),
                 });
                 instructions.push(assembly::Instruction::Binary {
                     op: asm_op,
+                    asm_type: src_type,
                     src: src2_operand,
                     dst: dst_operand,
                 });
@@ -368,14 +622,88 @@ impl AsmGenerator {
         }
     }
 
-    /// 辅助函数：将 tacky::Val 转换为 assembly::Operand。 (不变)
+    /// 辅助函数：将 tacky::Val 转换为 assembly::Operand。
     fn convert_tacky_val(&self, val: &tacky::Val) -> assembly::Operand {
         match val {
-            tacky::Val::Constant(i) => assembly::Operand::Imm(*i),
+            tacky::Val::Constant(i) => assembly::Operand::Imm(*i as i64),
+            tacky::Val::LongConstant(i) => assembly::Operand::Imm(*i),
+            // 4 字节操作数只关心位模式，按有符号数保存可以让立即数保持在 32 位范围内
+            tacky::Val::UIntConstant(u) => assembly::Operand::Imm(*u as i32 as i64),
+            tacky::Val::ULongConstant(u) => assembly::Operand::Imm(*u as i64),
             tacky::Val::Var(name) => assembly::Operand::Pseudo(name.clone()),
         }
     }
 
+    /// 辅助函数：根据 tacky::Val 的类型确定汇编操作数的大小。
+    fn asm_type_of(&self, val: &tacky::Val) -> assembly::AsmType {
+        match val {
+            tacky::Val::Constant(_) | tacky::Val::UIntConstant(_) => assembly::AsmType::Longword,
+            tacky::Val::LongConstant(_) | tacky::Val::ULongConstant(_) => {
+                assembly::AsmType::Quadword
+            }
+            tacky::Val::Var(name) => self.asm_type_of_name(name),
+        }
+    }
+
+    /// 辅助函数：查询符号表，确定一个变量的汇编类型。
+    fn asm_type_of_name(&self, name: &str) -> assembly::AsmType {
+        match self.symbols.get(name) {
+            Some(symbol) => asm_type_of_c_type(&symbol.c_type),
+            None => assembly::AsmType::Longword,
+        }
+    }
+
+    /// 辅助函数：结构体变量 `name` 中偏移为 `offset` 的成员的汇编类型。
+    fn member_asm_type(&self, name: &str, offset: u32) -> assembly::AsmType {
+        let member = match self.symbols.get(name).map(|s| &s.c_type) {
+            Some(CType::Struct(tag)) => self.struct_types[tag]
+                .members
+                .iter()
+                .find(|m| m.offset == offset),
+            _ => None,
+        };
+        match member {
+            Some(member) => asm_type_of_c_type(&member.member_type),
+            None => panic!("'{}' has no member at offset {}", name, offset),
+        }
+    }
+
+    /// 把 `src` 复制到类型为 `dst_type` 的 `dst`。常量传播后源操作数可能是常量，
+    /// 所以大小以目标为准。1 字节的值写入更大的目标时需要扩展：
+    /// char 做符号扩展，_Bool 做零扩展；反过来只需要复制最低的字节。
+    fn convert_copy(
+        &self,
+        src: &tacky::Val,
+        dst: assembly::Operand,
+        dst_type: assembly::AsmType,
+    ) -> assembly::Instruction {
+        let src_type = self.asm_type_of(src);
+        let is_bool = matches!(src, tacky::Val::Var(name)
+            if self.symbols.get(name).is_some_and(|s| s.c_type == CType::Bool));
+        let src = self.convert_tacky_val(src);
+        if src_type != assembly::AsmType::Byte || dst_type == assembly::AsmType::Byte {
+            assembly::Instruction::Mov {
+                asm_type: dst_type,
+                src,
+                dst,
+            }
+        } else if is_bool {
+            assembly::Instruction::MovZeroExtend {
+                src_type,
+                dst_type,
+                src,
+                dst,
+            }
+        } else {
+            assembly::Instruction::Movsx {
+                src_type,
+                dst_type,
+                src,
+                dst,
+            }
+        }
+    }
+
     // =================================================================
     // PASS 2: Replace Pseudoregisters with Stack Slots (基本不变, 但现在处理新指令)
     // =================================================================
@@ -394,7 +722,10 @@ impl AsmGenerator {
             };
 
             match inst {
-                assembly::Instruction::Mov { src, dst } => {
+                assembly::Instruction::Mov { src, dst, .. }
+                | assembly::Instruction::Movsx { src, dst, .. }
+                | assembly::Instruction::MovZeroExtend { src, dst, .. }
+                | assembly::Instruction::CMovCC { src, dst, .. } => {
                     assign(src);
                     assign(dst);
                 }
@@ -405,10 +736,11 @@ impl AsmGenerator {
                     assign(src);
                     assign(dst);
                 }
-                assembly::Instruction::Idiv(operand) => {
+                assembly::Instruction::Idiv(_, operand)
+                | assembly::Instruction::Div(_, operand) => {
                     assign(operand);
                 }
-                assembly::Instruction::Cmp { src1, src2 } => {
+                assembly::Instruction::Cmp { src1, src2, .. } => {
                     assign(src1);
                     assign(src2);
                 }
@@ -423,19 +755,61 @@ impl AsmGenerator {
             }
         }
         // 参数也计入栈大小，所以这个逻辑是正确的
-        Ok(current_offset.abs() as u32)
+        Ok(current_offset.unsigned_abs())
     }
 
-    /// 辅助函数：如果操作数是 Pseudo，就给它分配一个栈偏移量。(不变)
+    /// 辅助函数：如果操作数是 Pseudo 或 PseudoMem，就给它分配一个栈偏移量。
+    /// 静态变量不在栈上，而是替换为对它的 RIP 相对引用。
     fn assign_stack_offset(
         &self,
         op: &mut assembly::Operand,
         var_map: &mut HashMap<String, i32>,
         current_offset: &mut i32,
     ) {
+        let (name, offset) = match op {
+            assembly::Operand::Pseudo(name) => (name, 0),
+            assembly::Operand::PseudoMem(name, offset) => (name, *offset),
+            _ => return,
+        };
+        if self
+            .symbols
+            .get(name.as_str())
+            .is_some_and(|s| s.static_init.is_some())
+        {
+            *op = assembly::Operand::Data(std::mem::take(name), offset);
+            return;
+        }
+        if let assembly::Operand::PseudoMem(name, member_offset) = op {
+            // 结构体整体占用一块按其对齐要求对齐的栈空间，成员相对它的起始地址寻址
+            let layout = match self.symbols.get(name.as_str()).map(|s| &s.c_type) {
+                Some(CType::Struct(tag)) => &self.struct_types[tag],
+                other => panic!("'{}' is not a struct variable: {:?}", name, other),
+            };
+            let base = *var_map.entry(name.clone()).or_insert_with(|| {
+                *current_offset -= layout.size as i32;
+                *current_offset &= !(layout.alignment as i32 - 1);
+                *current_offset
+            });
+            *op = assembly::Operand::Stack(base + *member_offset);
+            return;
+        }
         if let assembly::Operand::Pseudo(name) = op {
+            let asm_type = self.asm_type_of_name(name);
             let offset = *var_map.entry(name.clone()).or_insert_with(|| {
-                *current_offset -= 4; // 每个变量/参数占 4 字节
+                match asm_type {
+                    // _Bool 和 char 占 1 字节
+                    assembly::AsmType::Byte => *current_offset -= 1,
+                    // int 占 4 字节，前面可能有 1 字节的变量，需要重新对齐
+                    assembly::AsmType::Longword => {
+                        *current_offset -= 4;
+                        *current_offset &= !3;
+                    }
+                    // long 占 8 字节，并且需要 8 字节对齐
+                    assembly::AsmType::Quadword => {
+                        *current_offset -= 8;
+                        *current_offset &= !7;
+                    }
+                }
                 *current_offset
             });
             *op = assembly::Operand::Stack(offset);
@@ -446,101 +820,292 @@ impl AsmGenerator {
     // PASS 3: Fix Up Instructions (基本不变，但要处理新指令)
     // =================================================================
 
-    fn fixup_instructions_pass3(&self, asm_func: &mut assembly::Function, stack_bytes: u32) {
+    /// 返回对齐后的栈帧大小（包括保存被调用者保存寄存器的空间）。
+    fn fixup_instructions_pass3(
+        &self,
+        asm_func: &mut assembly::Function,
+        stack_bytes: u32,
+        saved_registers: &[assembly::Register],
+    ) -> u32 {
         let mut new_instructions = Vec::new();
 
-        // 1. 添加 AllocateStack 指令
-        if stack_bytes > 0 {
-            // 【核心修改】向上取整到 16 的倍数
-            let aligned_bytes = (stack_bytes + 15) & !15;
+        // 【核心修改】局部变量加上保存的寄存器，向上取整到 16 的倍数，
+        // 这样调用其他函数时 %rsp 仍然是 16 字节对齐的
+        let saved_bytes = saved_registers.len() as u32 * 8;
+        let aligned_bytes = (stack_bytes + saved_bytes + 15) & !15;
+        // 1. 添加 AllocateStack 指令，然后保存寄存器
+        if aligned_bytes > saved_bytes {
             new_instructions.push(assembly::Instruction::AllocateStack {
-                bytes: aligned_bytes,
+                bytes: aligned_bytes - saved_bytes,
             });
         }
+        for reg in saved_registers {
+            new_instructions.push(assembly::Instruction::Push(assembly::Operand::Reg(*reg)));
+        }
 
         for inst in &asm_func.instructions {
             match inst {
-                // ... 所有之前的修复逻辑保持不变 ...
+                // 返回前按相反的顺序恢复保存的寄存器
+                assembly::Instruction::Ret => {
+                    for reg in saved_registers.iter().rev() {
+                        new_instructions.push(assembly::Instruction::Pop(*reg));
+                    }
+                    new_instructions.push(assembly::Instruction::Ret);
+                }
+                // movl 只使用立即数的低 32 位（long 截断为 int 时会出现这种情况）
                 assembly::Instruction::Mov {
-                    src: assembly::Operand::Stack(src_offset),
-                    dst: assembly::Operand::Stack(dst_offset),
-                } => {
+                    asm_type: assembly::AsmType::Longword,
+                    src: assembly::Operand::Imm(val),
+                    dst,
+                } if !fits_in_i32(*val) => {
                     new_instructions.push(assembly::Instruction::Mov {
-                        src: assembly::Operand::Stack(*src_offset),
+                        asm_type: assembly::AsmType::Longword,
+                        src: assembly::Operand::Imm(*val as i32 as i64),
+                        dst: dst.clone(),
+                    });
+                }
+                // movb 同理只使用立即数的最低字节
+                assembly::Instruction::Mov {
+                    asm_type: assembly::AsmType::Byte,
+                    src: assembly::Operand::Imm(val),
+                    dst,
+                } if i8::try_from(*val).is_err() => {
+                    new_instructions.push(assembly::Instruction::Mov {
+                        asm_type: assembly::AsmType::Byte,
+                        src: assembly::Operand::Imm(*val as i8 as i64),
+                        dst: dst.clone(),
+                    });
+                }
+                // 超出 32 位的立即数只能通过 movq 移入寄存器
+                assembly::Instruction::Mov {
+                    asm_type: assembly::AsmType::Quadword,
+                    src: assembly::Operand::Imm(val),
+                    dst,
+                } if !fits_in_i32(*val) && is_memory(dst) => {
+                    new_instructions.push(assembly::Instruction::Mov {
+                        asm_type: assembly::AsmType::Quadword,
+                        src: assembly::Operand::Imm(*val),
                         dst: assembly::Operand::Reg(assembly::Register::R10),
                     });
                     new_instructions.push(assembly::Instruction::Mov {
+                        asm_type: assembly::AsmType::Quadword,
                         src: assembly::Operand::Reg(assembly::Register::R10),
-                        dst: assembly::Operand::Stack(*dst_offset),
+                        dst: dst.clone(),
                     });
                 }
-                assembly::Instruction::Binary {
-                    op: op @ (assembly::BinaryOperator::Add | assembly::BinaryOperator::Subtract),
-                    src: assembly::Operand::Stack(src_offset),
-                    dst: assembly::Operand::Stack(dst_offset),
-                } => {
+                assembly::Instruction::Mov { asm_type, src, dst }
+                    if is_memory(src) && is_memory(dst) =>
+                {
                     new_instructions.push(assembly::Instruction::Mov {
-                        src: assembly::Operand::Stack(*src_offset),
+                        asm_type: *asm_type,
+                        src: src.clone(),
                         dst: assembly::Operand::Reg(assembly::Register::R10),
                     });
-                    new_instructions.push(assembly::Instruction::Binary {
-                        op: *op,
+                    new_instructions.push(assembly::Instruction::Mov {
+                        asm_type: *asm_type,
                         src: assembly::Operand::Reg(assembly::Register::R10),
-                        dst: assembly::Operand::Stack(*dst_offset),
+                        dst: dst.clone(),
                     });
                 }
+                // movsx/movzx 的源操作数不能是立即数，目标操作数必须是寄存器
+                assembly::Instruction::Movsx {
+                    src_type,
+                    dst_type,
+                    src,
+                    dst,
+                }
+                | assembly::Instruction::MovZeroExtend {
+                    src_type,
+                    dst_type,
+                    src,
+                    dst,
+                } => {
+                    let mut s = src.clone();
+                    if let assembly::Operand::Imm(val) = src {
+                        new_instructions.push(assembly::Instruction::Mov {
+                            asm_type: *src_type,
+                            src: assembly::Operand::Imm(*val),
+                            dst: assembly::Operand::Reg(assembly::Register::R10),
+                        });
+                        s = assembly::Operand::Reg(assembly::Register::R10);
+                    }
+                    let d = if is_memory(dst) {
+                        assembly::Operand::Reg(assembly::Register::R11)
+                    } else {
+                        dst.clone()
+                    };
+                    let extend = |src, dst| match inst {
+                        assembly::Instruction::Movsx { .. } => assembly::Instruction::Movsx {
+                            src_type: *src_type,
+                            dst_type: *dst_type,
+                            src,
+                            dst,
+                        },
+                        _ => assembly::Instruction::MovZeroExtend {
+                            src_type: *src_type,
+                            dst_type: *dst_type,
+                            src,
+                            dst,
+                        },
+                    };
+                    new_instructions.push(extend(s, d.clone()));
+                    if is_memory(dst) {
+                        new_instructions.push(assembly::Instruction::Mov {
+                            asm_type: *dst_type,
+                            src: d,
+                            dst: dst.clone(),
+                        });
+                    }
+                }
                 assembly::Instruction::Binary {
-                    op: assembly::BinaryOperator::Multiply,
+                    op:
+                        op @ (assembly::BinaryOperator::Sal
+                        | assembly::BinaryOperator::Sar
+                        | assembly::BinaryOperator::Shr),
+                    asm_type,
                     src,
-                    dst: assembly::Operand::Stack(dst_offset),
+                    dst,
                 } => {
-                    new_instructions.push(assembly::Instruction::Mov {
-                        src: assembly::Operand::Stack(*dst_offset),
-                        dst: assembly::Operand::Reg(assembly::Register::R11),
-                    });
+                    // 移位次数只能是 8 位立即数或 %cl。硬件只使用次数的低 5 位（64 位时低 6 位），
+                    // 立即数按同样的规则截断；其余操作数的低 32 位足以确定 %cl
+                    let count = match src {
+                        assembly::Operand::Imm(val) => {
+                            let mask = match asm_type {
+                                assembly::AsmType::Quadword => 63,
+                                _ => 31,
+                            };
+                            assembly::Operand::Imm(val & mask)
+                        }
+                        _ => {
+                            new_instructions.push(assembly::Instruction::Mov {
+                                asm_type: assembly::AsmType::Longword,
+                                src: src.clone(),
+                                dst: assembly::Operand::Reg(assembly::Register::CX),
+                            });
+                            assembly::Operand::Reg(assembly::Register::CX)
+                        }
+                    };
                     new_instructions.push(assembly::Instruction::Binary {
-                        op: assembly::BinaryOperator::Multiply,
-                        src: src.clone(),
-                        dst: assembly::Operand::Reg(assembly::Register::R11),
+                        op: *op,
+                        asm_type: *asm_type,
+                        src: count,
+                        dst: dst.clone(),
                     });
+                }
+                assembly::Instruction::Binary {
+                    op,
+                    asm_type,
+                    src,
+                    dst,
+                } => {
+                    let mut s = src.clone();
+                    // 算术和按位运算的立即数最多 32 位；除 imul 外都不能同时访问两个内存操作数
+                    let src_is_large_imm =
+                        matches!(src, assembly::Operand::Imm(val) if !fits_in_i32(*val));
+                    let both_in_memory = is_memory(src)
+                        && is_memory(dst)
+                        && matches!(
+                            op,
+                            assembly::BinaryOperator::Add
+                                | assembly::BinaryOperator::Subtract
+                                | assembly::BinaryOperator::And
+                                | assembly::BinaryOperator::Or
+                                | assembly::BinaryOperator::Xor
+                        );
+                    if src_is_large_imm || both_in_memory {
+                        new_instructions.push(assembly::Instruction::Mov {
+                            asm_type: *asm_type,
+                            src: src.clone(),
+                            dst: assembly::Operand::Reg(assembly::Register::R10),
+                        });
+                        s = assembly::Operand::Reg(assembly::Register::R10);
+                    }
+                    // imul 的目标操作数必须是寄存器
+                    if matches!(op, assembly::BinaryOperator::Multiply) && is_memory(dst) {
+                        new_instructions.push(assembly::Instruction::Mov {
+                            asm_type: *asm_type,
+                            src: dst.clone(),
+                            dst: assembly::Operand::Reg(assembly::Register::R11),
+                        });
+                        new_instructions.push(assembly::Instruction::Binary {
+                            op: *op,
+                            asm_type: *asm_type,
+                            src: s,
+                            dst: assembly::Operand::Reg(assembly::Register::R11),
+                        });
+                        new_instructions.push(assembly::Instruction::Mov {
+                            asm_type: *asm_type,
+                            src: assembly::Operand::Reg(assembly::Register::R11),
+                            dst: dst.clone(),
+                        });
+                    } else {
+                        new_instructions.push(assembly::Instruction::Binary {
+                            op: *op,
+                            asm_type: *asm_type,
+                            src: s,
+                            dst: dst.clone(),
+                        });
+                    }
+                }
+                assembly::Instruction::Idiv(asm_type, assembly::Operand::Imm(val)) => {
                     new_instructions.push(assembly::Instruction::Mov {
-                        src: assembly::Operand::Reg(assembly::Register::R11),
-                        dst: assembly::Operand::Stack(*dst_offset),
+                        asm_type: *asm_type,
+                        src: assembly::Operand::Imm(*val),
+                        dst: assembly::Operand::Reg(assembly::Register::R10),
                     });
+                    new_instructions.push(assembly::Instruction::Idiv(
+                        *asm_type,
+                        assembly::Operand::Reg(assembly::Register::R10),
+                    ));
                 }
-                assembly::Instruction::Idiv(assembly::Operand::Imm(val)) => {
+                assembly::Instruction::Div(asm_type, assembly::Operand::Imm(val)) => {
                     new_instructions.push(assembly::Instruction::Mov {
+                        asm_type: *asm_type,
                         src: assembly::Operand::Imm(*val),
                         dst: assembly::Operand::Reg(assembly::Register::R10),
                     });
-                    new_instructions.push(assembly::Instruction::Idiv(assembly::Operand::Reg(
-                        assembly::Register::R10,
-                    )));
+                    new_instructions.push(assembly::Instruction::Div(
+                        *asm_type,
+                        assembly::Operand::Reg(assembly::Register::R10),
+                    ));
                 }
-                assembly::Instruction::Cmp { src1, src2 } => {
+                assembly::Instruction::Cmp {
+                    asm_type,
+                    src1,
+                    src2,
+                } => {
                     let mut s1 = src1.clone();
                     let mut s2 = src2.clone();
-                    if let (assembly::Operand::Stack(o1), assembly::Operand::Stack(_)) = (&s1, &s2)
-                    {
+                    let src1_is_large_imm =
+                        matches!(&s1, assembly::Operand::Imm(val) if !fits_in_i32(*val));
+                    let both_in_memory = is_memory(&s1) && is_memory(&s2);
+                    if src1_is_large_imm || both_in_memory {
                         new_instructions.push(assembly::Instruction::Mov {
-                            src: assembly::Operand::Stack(*o1),
+                            asm_type: *asm_type,
+                            src: s1,
                             dst: assembly::Operand::Reg(assembly::Register::R10),
                         });
                         s1 = assembly::Operand::Reg(assembly::Register::R10);
                     }
                     if let assembly::Operand::Imm(val) = &s2 {
                         new_instructions.push(assembly::Instruction::Mov {
+                            asm_type: *asm_type,
                             src: assembly::Operand::Imm(*val),
                             dst: assembly::Operand::Reg(assembly::Register::R11),
                         });
                         s2 = assembly::Operand::Reg(assembly::Register::R11);
                     }
-                    new_instructions.push(assembly::Instruction::Cmp { src1: s1, src2: s2 });
+                    new_instructions.push(assembly::Instruction::Cmp {
+                        asm_type: *asm_type,
+                        src1: s1,
+                        src2: s2,
+                    });
                 }
 
                 // 【新增】修复 push imm (x86_64 `pushq` 不直接支持32位立即数，需要先mov)
                 assembly::Instruction::Push(assembly::Operand::Imm(val)) => {
                     new_instructions.push(assembly::Instruction::Mov {
+                        asm_type: assembly::AsmType::Quadword,
                         src: assembly::Operand::Imm(*val),
                         dst: assembly::Operand::Reg(assembly::Register::R10),
                     });
@@ -557,5 +1122,191 @@ impl AsmGenerator {
         }
 
         asm_func.instructions = new_instructions;
+        aligned_bytes
+    }
+}
+
+// =================================================================
+// PASS 4: Peephole
+// =================================================================
+
+/// 删除多余的 mov：
+/// - `mov X, X` 什么也不做（4 字节的寄存器自身传送会清零高 32 位，但 int 值从不读取它们）；
+/// - `mov A, B` 紧接着 `mov B, A`，第二条写回的正是 A 现有的值。
+///
+/// 只比较相邻的两条指令：中间的任何指令（包括隐式写入 %eax/%edx 的 idiv、call 等）
+/// 都可能改写 A 或 B，跨指令匹配就不安全了。
+pub fn remove_redundant_moves(instructions: &mut Vec<assembly::Instruction>) {
+    let mut result: Vec<assembly::Instruction> = Vec::with_capacity(instructions.len());
+    for inst in instructions.drain(..) {
+        if let assembly::Instruction::Mov { asm_type, src, dst } = &inst {
+            if src == dst {
+                continue;
+            }
+            if let Some(assembly::Instruction::Mov {
+                asm_type: prev_type,
+                src: prev_src,
+                dst: prev_dst,
+            }) = result.last()
+                && prev_type == asm_type
+                && prev_src == dst
+                && prev_dst == src
+            {
+                continue;
+            }
+        }
+        result.push(inst);
+    }
+    *instructions = result;
+}
+
+/// 标量类型的汇编类型。
+fn asm_type_of_c_type(c_type: &CType) -> assembly::AsmType {
+    match c_type {
+        CType::Long | CType::ULong => assembly::AsmType::Quadword,
+        CType::Bool | CType::Char => assembly::AsmType::Byte,
+        _ => assembly::AsmType::Longword,
+    }
+}
+
+/// 关系运算对应的条件码；其他运算返回 None。
+fn relational_cond_code(op: &tacky::BinaryOperator) -> Option<assembly::CondCode> {
+    Some(match op {
+        tacky::BinaryOperator::Equal => assembly::CondCode::E,
+        tacky::BinaryOperator::NotEqual => assembly::CondCode::NE,
+        tacky::BinaryOperator::LessThan => assembly::CondCode::L,
+        tacky::BinaryOperator::LessOrEqual => assembly::CondCode::LE,
+        tacky::BinaryOperator::GreaterThan => assembly::CondCode::G,
+        tacky::BinaryOperator::GreaterEqual => assembly::CondCode::GE,
+        tacky::BinaryOperator::UnsignedLessThan => assembly::CondCode::B,
+        tacky::BinaryOperator::UnsignedLessOrEqual => assembly::CondCode::BE,
+        tacky::BinaryOperator::UnsignedGreaterThan => assembly::CondCode::A,
+        tacky::BinaryOperator::UnsignedGreaterEqual => assembly::CondCode::AE,
+        _ => return None,
+    })
+}
+
+/// 操作数是否位于内存中（栈或静态存储区）。x86 指令最多只能有一个内存操作数。
+fn is_memory(op: &assembly::Operand) -> bool {
+    matches!(
+        op,
+        assembly::Operand::Stack(_) | assembly::Operand::Data(..)
+    )
+}
+
+/// 判断一个立即数能否直接编码为 32 位有符号立即数。
+fn fits_in_i32(val: i64) -> bool {
+    i32::try_from(val).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::emitter;
+    use crate::test_support::asm_for_source;
+
+    /// 对文本形式的函数运行 Pass 3（栈帧为 16 字节，不保存寄存器），返回修复后的文本
+    fn fix_up(text: &str) -> String {
+        let mut program: assembly::Program = text.parse().unwrap();
+        let (symbols, struct_types) = (HashMap::new(), HashMap::new());
+        let generator = AsmGenerator::new(&symbols, &struct_types, 0);
+        generator.fixup_instructions_pass3(&mut program.functions[0], 16, &[]);
+        program.to_string()
+    }
+
+    #[test]
+    fn test_shift_lowering() {
+        let program = asm_for_source("int f(int x, int n) { return (x << 2) + (x << n); }");
+        let text = program.functions[0].to_string();
+        // 常量次数直接作为立即数
+        assert!(text.contains("    binary sal l $2 [-"), "{}", text);
+        // 变量次数先移入 %ecx，再用 %cl 移位
+        let count_move = text.find("    mov l [-8] %cx\n").expect(&text);
+        let shift = text.find("    binary sal l %cx [-").expect(&text);
+        assert!(count_move < shift, "{}", text);
+    }
+
+    #[test]
+    fn test_shift_counts_are_fixed_up() {
+        // 立即数次数按操作数大小截断；其他次数先移入 %ecx，移位指令使用 %cl
+        let fixed = fix_up(
+            "function f
+            binary sal l $34 [-4]
+            binary sal l [-8] [-4]
+            binary sar q $66 %ax",
+        );
+        assert_eq!(
+            fixed,
+            "function f
+    allocate_stack 16
+    binary sal l $2 [-4]
+    mov l [-8] %cx
+    binary sal l %cx [-4]
+    binary sar q $2 %ax
+"
+        );
+        let program: assembly::Program = fixed.parse().unwrap();
+        let output = emitter::emit_assembly(program, &emitter::EmitOptions::default()).unwrap();
+        assert!(
+            output.contains(
+                "    sall $2, -4(%rbp)\n    movl -8(%rbp), %ecx\n    sall %cl, -4(%rbp)\n"
+            ),
+            "{}",
+            output
+        );
+    }
+
+    #[test]
+    fn test_byte_moves_are_fixed_up() {
+        // 扩展指令的目标必须是寄存器、源不能是立即数；movb 只取立即数的最低字节
+        assert_eq!(
+            fix_up(
+                "function f
+                movzx b l [-1] [-8]
+                movsx b q $-3 [-16]
+                mov b $300 [-2]
+                mov b [-1] [-2]"
+            ),
+            "function f
+    allocate_stack 16
+    movzx b l [-1] %r11
+    mov l %r11 [-8]
+    mov b $-3 %r10
+    movsx b q %r10 %r11
+    mov q %r11 [-16]
+    mov b $44 [-2]
+    mov b [-1] %r10
+    mov b %r10 [-2]
+"
+        );
+    }
+
+    #[test]
+    fn test_redundant_moves_are_removed() {
+        let mut program: assembly::Program = "function f
+            mov l %di %bx
+            mov l %bx %bx
+            mov l [-4] %r10
+            mov l %r10 [-4]
+            mov q [-8] %r10
+            mov l %r10 [-8]
+            mov l %bx %ax
+            ret"
+        .parse()
+        .unwrap();
+        let func = &mut program.functions[0];
+        remove_redundant_moves(&mut func.instructions);
+        // 大小不同的来回传送不是多余的：movl 只写回了低 4 字节
+        assert_eq!(
+            func.to_string(),
+            "function f
+    mov l %di %bx
+    mov l [-4] %r10
+    mov q [-8] %r10
+    mov l %r10 [-8]
+    mov l %bx %ax
+    ret
+"
+        );
     }
 }
@@ -0,0 +1,138 @@
+// src/backend/cfg.rs
+
+//! TACKY 函数的控制流图（`--dump-cfg`），可以序列化为 Graphviz DOT。
+//!
+//! 基本块在每个标签处开始，在每条跳转或 return 之后结束。
+//! 边来自跳转目标和相邻块之间的顺序执行：无条件跳转一条边，
+//! 条件跳转两条边（跳转和不跳转），return 没有后继。
+
+use crate::ir::tacky::{Function, Instruction};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// 一个函数的控制流图；块按它们在函数体中出现的顺序编号。
+#[derive(Debug)]
+pub struct Cfg<'a> {
+    pub name: &'a str,
+    pub blocks: Vec<&'a [Instruction]>,
+    /// `(from, to)`，都是 `blocks` 中的下标
+    pub edges: Vec<(usize, usize)>,
+}
+
+impl<'a> Cfg<'a> {
+    /// 把函数体切分为基本块并连接它们。
+    pub fn build(func: &'a Function) -> Self {
+        let body = &func.body;
+        let mut blocks = Vec::new();
+        let mut start = 0;
+        for (i, inst) in body.iter().enumerate() {
+            match inst {
+                Instruction::Label(_) if i > start => {
+                    blocks.push(&body[start..i]);
+                    start = i;
+                }
+                Instruction::Jump(_)
+                | Instruction::JumpIfZero { .. }
+                | Instruction::JumpIfNotZero { .. }
+                | Instruction::Return(_) => {
+                    blocks.push(&body[start..=i]);
+                    start = i + 1;
+                }
+                _ => {}
+            }
+        }
+        if start < body.len() {
+            blocks.push(&body[start..]);
+        }
+
+        let block_of_label: HashMap<&str, usize> = blocks
+            .iter()
+            .enumerate()
+            .filter_map(|(i, block)| match block.first() {
+                Some(Instruction::Label(name)) => Some((name.as_str(), i)),
+                _ => None,
+            })
+            .collect();
+        let mut edges = Vec::new();
+        for (i, block) in blocks.iter().enumerate() {
+            let next = (i + 1 < blocks.len()).then_some(i + 1);
+            match block.last() {
+                Some(Instruction::Return(_)) => {}
+                Some(Instruction::Jump(target)) => {
+                    edges.extend(block_of_label.get(target.as_str()).map(|&to| (i, to)));
+                }
+                Some(
+                    Instruction::JumpIfZero { target, .. }
+                    | Instruction::JumpIfNotZero { target, .. },
+                ) => {
+                    edges.extend(block_of_label.get(target.as_str()).map(|&to| (i, to)));
+                    edges.extend(next.map(|to| (i, to)));
+                }
+                _ => edges.extend(next.map(|to| (i, to))),
+            }
+        }
+
+        Cfg {
+            name: &func.name,
+            blocks,
+            edges,
+        }
+    }
+
+    /// 序列化为 Graphviz DOT；每个块显示为一个方框，内容是其中的 TACKY 指令。
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::new();
+        writeln!(dot, "digraph \"{}\" {{", escape(self.name)).unwrap();
+        writeln!(dot, "    node [shape=box, fontname=\"monospace\"];").unwrap();
+        for (i, block) in self.blocks.iter().enumerate() {
+            // `\l` 让每一行左对齐
+            let text: String = block
+                .iter()
+                .map(|inst| format!("{}\\l", escape(&inst.to_string())))
+                .collect();
+            writeln!(dot, "    b{} [label=\"{}\"];", i, text).unwrap();
+        }
+        for (from, to) in &self.edges {
+            writeln!(dot, "    b{} -> b{};", from, to).unwrap();
+        }
+        writeln!(dot, "}}").unwrap();
+        dot
+    }
+}
+
+/// 转义 DOT 字符串中的引号和反斜杠
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::tacky_for_source;
+
+    #[test]
+    fn test_if_else_blocks_and_edges() {
+        let program =
+            tacky_for_source("int pick(int a) { int r; if (a) r = 1; else r = 2; return r; }");
+        let cfg = Cfg::build(&program.functions[0]);
+        // 条件跳转 | then 分支 + 跳到末尾 | else 分支 | 汇合后 return
+        assert_eq!(cfg.blocks.len(), 4, "{}", program);
+        // 条件块两条边，then 块跳到汇合点，else 块顺序执行到汇合点，return 后没有边
+        assert_eq!(cfg.edges, [(0, 2), (0, 1), (1, 3), (2, 3)], "{}", program);
+
+        let dot = cfg.to_dot();
+        assert!(dot.starts_with("digraph \"pick\" {\n"), "{}", dot);
+        assert_eq!(dot.matches(" -> ").count(), 4, "{}", dot);
+        assert!(dot.contains("    b0 -> b2;\n"), "{}", dot);
+    }
+
+    #[test]
+    fn test_goto_edges_skip_the_jumped_over_block() {
+        let program =
+            tacky_for_source("int main(void) { int a = 1; goto done; a = 2; done: return a; }");
+        let cfg = Cfg::build(&program.functions[0]);
+        // goto 所在的块 | 被跳过的赋值 | 标签处的 return
+        assert_eq!(cfg.blocks.len(), 3, "{}", program);
+        assert_eq!(cfg.edges, [(0, 2), (1, 2)], "{}", program);
+    }
+}
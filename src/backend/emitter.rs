@@ -1,7 +1,8 @@
 // src/backend/emitter.rs
 
 use crate::ir::assembly::{
-    BinaryOperator, CondCode, Function, Instruction, Operand, Program, Register, UnaryOperator,
+    AsmType, BinaryOperator, CondCode, Function, Instruction, Operand, Program, Register,
+    StaticVariable, UnaryOperator,
 };
 use std::collections::HashSet; // 用于跟踪文件中定义的函数
 use std::fmt::Write;
@@ -38,11 +39,46 @@ impl PlatformConfig {
     }
 }
 
+/// 控制汇编输出的可选项。
+#[derive(Debug, Default, Clone)]
+pub struct EmitOptions {
+    /// 在函数序言/尾言周围发射 CFI 指令，便于调试器回溯调用栈
+    pub emit_cfi: bool,
+    /// 在每个函数入口发射 `endbr64`，作为启用 CET（间接跳转追踪）时合法的间接调用目标
+    pub cet: bool,
+}
+
 /// 将汇编 AST 转换为最终的汇编代码字符串。
-pub fn emit_assembly(asm_program: Program) -> Result<String, Box<dyn std::error::Error>> {
+pub fn emit_assembly(
+    asm_program: Program,
+    options: &EmitOptions,
+) -> Result<String, Box<dyn std::error::Error>> {
     let mut output = String::new();
     let config = PlatformConfig::new();
 
+    // 循环发射每个函数的代码
+    for (_, code) in emit_functions(&asm_program, options)? {
+        output.push_str(&code);
+    }
+    for var in &asm_program.static_variables {
+        emit_static_variable(&mut output, var, &config)?;
+    }
+
+    // 根据项目要求，在 Linux 上添加 .section 指令
+    #[cfg(target_os = "linux")]
+    writeln!(&mut output, r#".section .note.GNU-stack,"",@progbits"#)?;
+
+    Ok(output)
+}
+
+/// 分别发射每个函数的汇编代码，按定义顺序返回 `(函数名, 代码)`。
+/// `--listing` 用它把汇编代码和对应函数的源代码放在一起。
+pub fn emit_functions(
+    asm_program: &Program,
+    options: &EmitOptions,
+) -> Result<Vec<(String, String)>, std::fmt::Error> {
+    let config = PlatformConfig::new();
+
     // 【核心修改】创建一个集合，存储所有在当前文件中定义的函数名。
     // 这对于决定 `call` 指令是否需要 `@PLT` 后缀至关重要。
     let defined_functions: HashSet<_> = asm_program
@@ -51,16 +87,41 @@ pub fn emit_assembly(asm_program: Program) -> Result<String, Box<dyn std::error:
         .map(|f| f.name.clone())
         .collect();
 
-    // 循环发射每个函数的代码
-    for func in &asm_program.functions {
-        emit_function(&mut output, func, &config, &defined_functions)?;
-    }
-
-    // 根据项目要求，在 Linux 上添加 .section 指令
-    #[cfg(target_os = "linux")]
-    writeln!(&mut output, r#".section .note.GNU-stack,"",@progbits"#)?;
+    asm_program
+        .functions
+        .iter()
+        .map(|func| {
+            let mut code = String::new();
+            emit_function(&mut code, func, &config, &defined_functions, options)?;
+            Ok((func.name.clone(), code))
+        })
+        .collect()
+}
 
-    Ok(output)
+/// 发射一个静态变量。初始值为 0 的变量放在 .bss 中，只占用空间而不占用文件大小。
+/// 全局变量有外部链接，额外发射 `.globl`；静态局部变量只在本文件内可见，不发射。
+fn emit_static_variable(
+    output: &mut String,
+    var: &StaticVariable,
+    config: &PlatformConfig,
+) -> Result<(), std::fmt::Error> {
+    let label = config.format_global_label(&var.name);
+    writeln!(
+        output,
+        "    {}",
+        if var.init == 0 { ".bss" } else { ".data" }
+    )?;
+    if var.global {
+        writeln!(output, ".globl {}", label)?;
+    }
+    writeln!(output, "    .balign {}", var.alignment)?;
+    writeln!(output, "{}:", label)?;
+    match (var.init, var.size) {
+        (0, size) => writeln!(output, "    .zero {}", size),
+        (init, 1) => writeln!(output, "    .byte {}", init),
+        (init, 8) => writeln!(output, "    .quad {}", init),
+        (init, _) => writeln!(output, "    .long {}", init),
+    }
 }
 
 /// 发射单个函数的汇编代码。
@@ -69,63 +130,156 @@ fn emit_function(
     func: &Function,
     config: &PlatformConfig,
     defined_functions: &HashSet<String>, // 接收定义的函数集合
+    options: &EmitOptions,
 ) -> Result<(), std::fmt::Error> {
     let function_name = config.format_global_label(&func.name);
 
     writeln!(output, ".globl {}", function_name)?;
     writeln!(output, "{}:", function_name)?;
+    if options.emit_cfi {
+        writeln!(output, "    .cfi_startproc")?;
+    }
+    // endbr64 必须是函数的第一条指令，CFI 伪指令不占用指令字节
+    if options.cet {
+        writeln!(output, "    endbr64")?;
+    }
     writeln!(output, "    pushq %rbp")?;
+    if options.emit_cfi {
+        // 压入 %rbp 后，CFA 距离 %rsp 16 字节，旧的 %rbp 保存在 CFA-16 处
+        writeln!(output, "    .cfi_def_cfa_offset 16")?;
+        writeln!(output, "    .cfi_offset %rbp, -16")?;
+    }
     writeln!(output, "    movq %rsp, %rbp")?;
+    if options.emit_cfi {
+        writeln!(output, "    .cfi_def_cfa_register %rbp")?;
+    }
 
     for instruction in &func.instructions {
         match instruction {
             // --- 指令发射逻辑，与之前类似 ---
-            Instruction::Mov { src, dst } => {
-                // movl 用于 4 字节操作
+            Instruction::Mov { asm_type, src, dst } => {
+                // movl 用于 4 字节操作，movq 用于 8 字节操作
+                let size = operand_size(asm_type);
                 writeln!(
                     output,
-                    "    movl {}, {}",
-                    format_operand(src, 4), // 使用 4 字节格式
-                    format_operand(dst, 4)
+                    "    mov{} {}, {}",
+                    format_suffix(asm_type),
+                    format_operand(src, size),
+                    format_operand(dst, size)
                 )?;
             }
-            Instruction::Unary { op, operand } => {
+            // 例如 movsbl、movslq、movzbq：源和目标的大小都写在助记符中
+            Instruction::Movsx {
+                src_type,
+                dst_type,
+                src,
+                dst,
+            }
+            | Instruction::MovZeroExtend {
+                src_type,
+                dst_type,
+                src,
+                dst,
+            } => {
+                let extension = match instruction {
+                    Instruction::Movsx { .. } => "movs",
+                    _ => "movz",
+                };
                 writeln!(
                     output,
-                    "    {} {}",
+                    "    {}{}{} {}, {}",
+                    extension,
+                    format_suffix(src_type),
+                    format_suffix(dst_type),
+                    format_operand(src, operand_size(src_type)),
+                    format_operand(dst, operand_size(dst_type))
+                )?;
+            }
+            Instruction::Unary {
+                op,
+                asm_type,
+                operand,
+            } => {
+                writeln!(
+                    output,
+                    "    {}{} {}",
                     format_unary_operator(op),
-                    format_operand(operand, 4) // 一元操作通常是 4 字节
+                    format_suffix(asm_type),
+                    format_operand(operand, operand_size(asm_type))
                 )?;
             }
-            Instruction::Binary { op, src, dst } => {
+            Instruction::Binary {
+                op,
+                asm_type,
+                src,
+                dst,
+            } => {
+                let size = operand_size(asm_type);
+                // 移位次数寄存器总是 %cl
+                let src_size = match op {
+                    BinaryOperator::Sal | BinaryOperator::Sar | BinaryOperator::Shr => 1,
+                    _ => size,
+                };
                 writeln!(
                     output,
-                    "    {} {}, {}",
+                    "    {}{} {}, {}",
                     format_binary_operator(op),
-                    format_operand(src, 4),
-                    format_operand(dst, 4)
+                    format_suffix(asm_type),
+                    format_operand(src, src_size),
+                    format_operand(dst, size)
                 )?;
             }
-            Instruction::Idiv(operand) => {
-                writeln!(output, "    idivl {}", format_operand(operand, 4))?;
+            Instruction::Idiv(asm_type, operand) => {
+                writeln!(
+                    output,
+                    "    idiv{} {}",
+                    format_suffix(asm_type),
+                    format_operand(operand, operand_size(asm_type))
+                )?;
             }
-            Instruction::Cdq => {
-                writeln!(output, "    cdq")?;
+            Instruction::Div(asm_type, operand) => {
+                writeln!(
+                    output,
+                    "    div{} {}",
+                    format_suffix(asm_type),
+                    format_operand(operand, operand_size(asm_type))
+                )?;
             }
+            Instruction::Cdq(asm_type) => match asm_type {
+                AsmType::Quadword => writeln!(output, "    cqo")?,
+                // 1 字节的值在运算前总是被提升为 int
+                AsmType::Byte | AsmType::Longword => writeln!(output, "    cdq")?,
+            },
             Instruction::AllocateStack { bytes } => {
                 writeln!(output, "    subq ${}, %rsp", bytes)?;
             }
             Instruction::Ret => {
+                if options.emit_cfi {
+                    // ret 之后可能还有其他代码，它们仍处于建立好栈帧的状态
+                    writeln!(output, "    .cfi_remember_state")?;
+                }
                 writeln!(output, "    movq %rbp, %rsp")?;
                 writeln!(output, "    popq %rbp")?;
+                if options.emit_cfi {
+                    writeln!(output, "    .cfi_def_cfa %rsp, 8")?;
+                }
                 writeln!(output, "    ret")?;
+                if options.emit_cfi {
+                    writeln!(output, "    .cfi_restore_state")?;
+                }
             }
-            Instruction::Cmp { src1, src2 } => {
+            Instruction::Cmp {
+                asm_type,
+                src1,
+                src2,
+            } => {
+                let size = operand_size(asm_type);
                 writeln!(
                     output,
-                    "    cmpl {}, {}",
-                    format_operand(src1, 4),
-                    format_operand(src2, 4)
+                    "    cmp{} {}, {}",
+                    format_suffix(asm_type),
+                    format_operand(src1, size),
+                    format_operand(src2, size)
                 )?;
             }
             Instruction::Label(name) => {
@@ -151,6 +305,23 @@ fn emit_function(
                 )?;
             }
 
+            Instruction::CMovCC {
+                cond,
+                asm_type,
+                src,
+                dst,
+            } => {
+                // 目标总是寄存器，操作数大小由寄存器名决定，因此不加后缀
+                let size = operand_size(asm_type);
+                writeln!(
+                    output,
+                    "    cmov{} {}, {}",
+                    format_cond_code(cond),
+                    format_operand(src, size),
+                    format_operand(dst, size)
+                )?;
+            }
+
             // --- 【核心修改】处理新指令 ---
             Instruction::DeallocateStack(bytes) => {
                 writeln!(output, "    addq ${}, %rsp", bytes)?;
@@ -159,6 +330,9 @@ fn emit_function(
                 // pushq 操作 8 字节
                 writeln!(output, "    pushq {}", format_operand(operand, 8))?;
             }
+            Instruction::Pop(reg) => {
+                writeln!(output, "    popq {}", format_register(reg, 8))?;
+            }
             Instruction::Call(name) => {
                 let mut call_target = config.format_global_label(name);
                 // 检查是否需要 @PLT
@@ -169,9 +343,30 @@ fn emit_function(
             }
         }
     }
+    if options.emit_cfi {
+        writeln!(output, "    .cfi_endproc")?;
+    }
     Ok(())
 }
 
+/// 辅助函数：根据操作数类型返回指令后缀。
+fn format_suffix(asm_type: &AsmType) -> &'static str {
+    match asm_type {
+        AsmType::Byte => "b",
+        AsmType::Longword => "l",
+        AsmType::Quadword => "q",
+    }
+}
+
+/// 辅助函数：返回操作数类型对应的字节数。
+fn operand_size(asm_type: &AsmType) -> u8 {
+    match asm_type {
+        AsmType::Byte => 1,
+        AsmType::Longword => 4,
+        AsmType::Quadword => 8,
+    }
+}
+
 /// 辅助函数：将 CondCode 转换为指令后缀。 (不变)
 fn format_cond_code(cc: &CondCode) -> &'static str {
     match cc {
@@ -181,6 +376,10 @@ fn format_cond_code(cc: &CondCode) -> &'static str {
         CondCode::LE => "le",
         CondCode::G => "g",
         CondCode::GE => "ge",
+        CondCode::A => "a",
+        CondCode::AE => "ae",
+        CondCode::B => "b",
+        CondCode::BE => "be",
     }
 }
 
@@ -191,7 +390,14 @@ fn format_operand(op: &Operand, size_in_bytes: u8) -> String {
         Operand::Imm(value) => format!("${}", value),
         Operand::Reg(reg) => format_register(reg, size_in_bytes),
         Operand::Stack(offset) => format!("{}(%rbp)", offset),
-        Operand::Pseudo(name) => {
+        Operand::Data(name, offset) => {
+            let label = PlatformConfig::new().format_global_label(name);
+            match offset {
+                0 => format!("{}(%rip)", label),
+                _ => format!("{}+{}(%rip)", label, offset),
+            }
+        }
+        Operand::Pseudo(name) | Operand::PseudoMem(name, _) => {
             panic!(
                 "Error: Pseudoregister '{}' was not replaced before code emission.",
                 name
@@ -213,6 +419,11 @@ fn format_register(reg: &Register, size_in_bytes: u8) -> String {
         Register::R9 => ("%r9", "%r9d", "%r9b"),
         Register::R10 => ("%r10", "%r10d", "%r10b"),
         Register::R11 => ("%r11", "%r11d", "%r11b"),
+        Register::BX => ("%rbx", "%ebx", "%bl"),
+        Register::R12 => ("%r12", "%r12d", "%r12b"),
+        Register::R13 => ("%r13", "%r13d", "%r13b"),
+        Register::R14 => ("%r14", "%r14d", "%r14b"),
+        Register::R15 => ("%r15", "%r15d", "%r15b"),
     };
 
     let name_str = match size_in_bytes {
@@ -224,19 +435,232 @@ fn format_register(reg: &Register, size_in_bytes: u8) -> String {
     name_str.to_string()
 }
 
-/// 辅助函数：将 UnaryOperator 枚举格式化为指令名。(不变)
+/// 辅助函数：将 UnaryOperator 枚举格式化为指令名（不含大小后缀）。
 fn format_unary_operator(op: &UnaryOperator) -> &'static str {
     match op {
-        UnaryOperator::Neg => "negl",
-        UnaryOperator::Not => "notl",
+        UnaryOperator::Neg => "neg",
+        UnaryOperator::Not => "not",
     }
 }
 
-/// 辅助函数：将 BinaryOperator 枚举格式化为指令名。(不变)
+/// 辅助函数：将 BinaryOperator 枚举格式化为指令名（不含大小后缀）。
 fn format_binary_operator(op: &BinaryOperator) -> &'static str {
     match op {
-        BinaryOperator::Add => "addl",
-        BinaryOperator::Subtract => "subl",
-        BinaryOperator::Multiply => "imull",
+        BinaryOperator::Add => "add",
+        BinaryOperator::Subtract => "sub",
+        BinaryOperator::Multiply => "imul",
+        BinaryOperator::And => "and",
+        BinaryOperator::Or => "or",
+        BinaryOperator::Xor => "xor",
+        BinaryOperator::Sal => "sal",
+        BinaryOperator::Sar => "sar",
+        BinaryOperator::Shr => "shr",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn return_zero_program() -> Program {
+        Program {
+            functions: vec![Function {
+                name: "main".to_string(),
+                instructions: vec![
+                    Instruction::Mov {
+                        asm_type: AsmType::Longword,
+                        src: Operand::Imm(0),
+                        dst: Operand::Reg(Register::AX),
+                    },
+                    Instruction::Ret,
+                ],
+            }],
+            static_variables: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_emit_cfi_directives() {
+        let options = EmitOptions {
+            emit_cfi: true,
+            ..EmitOptions::default()
+        };
+        let output = emit_assembly(return_zero_program(), &options).unwrap();
+        assert_eq!(output.matches(".cfi_startproc").count(), 1);
+        assert_eq!(output.matches(".cfi_endproc").count(), 1);
+        assert!(output.find(".cfi_startproc") < output.find(".cfi_endproc"));
+
+        // 默认不发射 CFI 指令
+        let output = emit_assembly(return_zero_program(), &EmitOptions::default()).unwrap();
+        assert!(!output.contains(".cfi"));
+    }
+
+    #[test]
+    fn test_emit_positive_stack_offset() {
+        // 栈上传递的参数位于调用者的栈帧中，偏移量为正
+        let program = Program {
+            functions: vec![Function {
+                name: "f".to_string(),
+                instructions: vec![Instruction::Mov {
+                    asm_type: AsmType::Longword,
+                    src: Operand::Stack(16),
+                    dst: Operand::Reg(Register::R10),
+                }],
+            }],
+            static_variables: Vec::new(),
+        };
+        let output = emit_assembly(program, &EmitOptions::default()).unwrap();
+        assert!(output.contains("movl 16(%rbp), %r10d"));
+    }
+
+    #[test]
+    fn test_emit_negative_immediate() {
+        let program = Program {
+            functions: vec![Function {
+                name: "f".to_string(),
+                instructions: vec![Instruction::Mov {
+                    asm_type: AsmType::Longword,
+                    src: Operand::Imm(-5),
+                    dst: Operand::Stack(-4),
+                }],
+            }],
+            static_variables: Vec::new(),
+        };
+        let output = emit_assembly(program, &EmitOptions::default()).unwrap();
+        assert!(output.contains("movl $-5, -4(%rbp)"), "{}", output);
+    }
+
+    #[test]
+    fn test_emit_byte_operands() {
+        let program: Program = "function f
+            movsx b l [-1] %ax
+            movzx b q [-2] %r11
+            movsx l q %ax %r11
+            mov b %r10 [-3]
+            static c.0 1 1 -3"
+            .parse()
+            .unwrap();
+        let output = emit_assembly(program, &EmitOptions::default()).unwrap();
+        assert!(output.contains("movsbl -1(%rbp), %eax"), "{}", output);
+        assert!(output.contains("movzbq -2(%rbp), %r11"), "{}", output);
+        assert!(output.contains("movslq %eax, %r11"), "{}", output);
+        assert!(output.contains("movb %r10b, -3(%rbp)"), "{}", output);
+        assert!(output.contains("c.0:\n    .byte -3\n"), "{}", output);
+    }
+
+    #[test]
+    fn test_emit_shift_count_in_cl() {
+        let program = Program {
+            functions: vec![Function {
+                name: "f".to_string(),
+                instructions: vec![
+                    Instruction::Binary {
+                        op: BinaryOperator::Sal,
+                        asm_type: AsmType::Longword,
+                        src: Operand::Reg(Register::CX),
+                        dst: Operand::Stack(-4),
+                    },
+                    Instruction::Binary {
+                        op: BinaryOperator::Sar,
+                        asm_type: AsmType::Quadword,
+                        src: Operand::Imm(2),
+                        dst: Operand::Reg(Register::AX),
+                    },
+                ],
+            }],
+            static_variables: Vec::new(),
+        };
+        let output = emit_assembly(program, &EmitOptions::default()).unwrap();
+        assert!(output.contains("sall %cl, -4(%rbp)"), "{}", output);
+        assert!(output.contains("sarq $2, %rax"), "{}", output);
+    }
+
+    #[test]
+    fn test_emit_static_variables() {
+        let mut program = return_zero_program();
+        program.functions[0].instructions[0] = Instruction::Mov {
+            asm_type: AsmType::Longword,
+            src: Operand::Data("count.0".to_string(), 0),
+            dst: Operand::Data("p.1".to_string(), 4),
+        };
+        program.static_variables = vec![
+            StaticVariable {
+                name: "count.0".to_string(),
+                size: 4,
+                alignment: 4,
+                init: 0,
+                global: false,
+            },
+            StaticVariable {
+                name: "n.2".to_string(),
+                size: 8,
+                alignment: 8,
+                init: -3000000000,
+                global: false,
+            },
+            StaticVariable {
+                name: "g".to_string(),
+                size: 4,
+                alignment: 4,
+                init: 7,
+                global: true,
+            },
+        ];
+        let output = emit_assembly(program, &EmitOptions::default()).unwrap();
+        let config = PlatformConfig::new();
+        let count = config.format_global_label("count.0");
+        let n = config.format_global_label("n.2");
+        assert!(
+            output.contains(&format!(
+                "movl {}(%rip), {}+4(%rip)",
+                count,
+                config.format_global_label("p.1")
+            )),
+            "{}",
+            output
+        );
+        // 零初始化的变量放在 .bss 中
+        assert!(
+            output.contains(&format!(
+                "    .bss\n    .balign 4\n{}:\n    .zero 4\n",
+                count
+            )),
+            "{}",
+            output
+        );
+        assert!(
+            output.contains(&format!(
+                "    .data\n    .balign 8\n{}:\n    .quad -3000000000\n",
+                n
+            )),
+            "{}",
+            output
+        );
+        // 静态局部变量没有外部链接
+        assert!(!output.contains(&format!(".globl {}", count)), "{}", output);
+        let g = config.format_global_label("g");
+        assert!(
+            output.contains(&format!(
+                "    .data\n.globl {}\n    .balign 4\n{}:\n    .long 7\n",
+                g, g
+            )),
+            "{}",
+            output
+        );
+    }
+
+    #[test]
+    fn test_emit_endbr64_with_cet() {
+        let options = EmitOptions {
+            cet: true,
+            ..EmitOptions::default()
+        };
+        let output = emit_assembly(return_zero_program(), &options).unwrap();
+        let label = format!("{}:\n", PlatformConfig::new().format_global_label("main"));
+        let body = &output[output.find(&label).unwrap() + label.len()..];
+        assert!(body.starts_with("    endbr64\n"), "{}", output);
+
+        let output = emit_assembly(return_zero_program(), &EmitOptions::default()).unwrap();
+        assert!(!output.contains("endbr64"));
     }
 }
@@ -1,4 +1,7 @@
 // src/backend/mod.rs
 pub mod asm_gen;
+pub mod cfg;
 pub mod emitter;
+pub mod reg_alloc;
 pub mod tacky_gen;
+pub mod tacky_opt;
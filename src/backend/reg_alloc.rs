@@ -0,0 +1,285 @@
+// src/backend/reg_alloc.rs
+
+//! 寄存器分配（在 -O1 下启用，位于 Pass 1 和 Pass 2 之间）。
+//!
+//! 1. 活跃性分析：在函数的指令列表上反向迭代到不动点，求出每条指令之后仍然活跃的伪寄存器。
+//! 2. 干涉图：一条指令写入的伪寄存器与此时仍然活跃的其他伪寄存器互相干涉。
+//! 3. 着色：按使用次数从多到少，贪心地给每个伪寄存器挑一个邻居没有占用的寄存器；
+//!    分不到寄存器的伪寄存器保持原样，由 Pass 2 放到栈上。
+//!
+//! 只分配被调用者保存的寄存器：函数调用不会破坏它们，其他 pass 也不会隐式使用它们
+//! （%r10/%r11 留给 Pass 3 修复指令），所以干涉图里只有伪寄存器。
+//! 代价是用到的寄存器需要在序言中保存、在返回前恢复。
+
+use crate::ir::assembly::{Function, Instruction, Operand, Register};
+use std::collections::{HashMap, HashSet};
+
+/// 可供分配的寄存器，按优先顺序排列。
+pub const ALLOCATABLE: [Register; 5] = [
+    Register::BX,
+    Register::R12,
+    Register::R13,
+    Register::R14,
+    Register::R15,
+];
+
+/// 把能放进寄存器的伪寄存器替换为寄存器，返回用到的寄存器（按 `ALLOCATABLE` 的顺序）。
+///
+/// `is_candidate` 判断一个伪寄存器能否放进寄存器；静态变量和结构体不行。
+pub fn allocate_registers(
+    func: &mut Function,
+    is_candidate: impl Fn(&str) -> bool,
+) -> Vec<Register> {
+    let live_out = analyze_liveness(&func.instructions, &is_candidate);
+
+    // 干涉图，同时统计每个伪寄存器出现的次数
+    let mut neighbors: HashMap<&str, HashSet<&str>> = HashMap::new();
+    let mut uses: HashMap<&str, usize> = HashMap::new();
+    let mut order: Vec<&str> = Vec::new();
+    for (inst, live) in func.instructions.iter().zip(&live_out) {
+        let (read, written) = operands(inst);
+        for op in read.iter().chain(&written) {
+            if let Some(name) = pseudo_name(op, &is_candidate) {
+                let count = uses.entry(name).or_insert_with(|| {
+                    order.push(name);
+                    0
+                });
+                *count += 1;
+                neighbors.entry(name).or_default();
+            }
+        }
+        for op in &written {
+            let Some(def) = pseudo_name(op, &is_candidate) else {
+                continue;
+            };
+            for other in live {
+                if *other != def {
+                    neighbors.entry(def).or_default().insert(other);
+                    neighbors.entry(other).or_default().insert(def);
+                }
+            }
+        }
+    }
+
+    // 使用次数多的先挑寄存器；次数相同时按出现顺序，保证输出稳定
+    order.sort_by_key(|name| std::cmp::Reverse(uses[name]));
+    let mut colors: HashMap<&str, Register> = HashMap::new();
+    for name in order {
+        let taken: Vec<Register> = neighbors[name]
+            .iter()
+            .filter_map(|neighbor| colors.get(neighbor).copied())
+            .collect();
+        if let Some(reg) = ALLOCATABLE.iter().find(|reg| !taken.contains(reg)) {
+            colors.insert(name, *reg);
+        }
+    }
+
+    let used: Vec<Register> = ALLOCATABLE
+        .iter()
+        .copied()
+        .filter(|reg| colors.values().any(|r| r == reg))
+        .collect();
+    let colors: HashMap<String, Register> = colors
+        .into_iter()
+        .map(|(name, reg)| (name.to_string(), reg))
+        .collect();
+    for inst in &mut func.instructions {
+        for_each_operand_mut(inst, |op| {
+            if let Operand::Pseudo(name) = op
+                && let Some(reg) = colors.get(name.as_str())
+            {
+                *op = Operand::Reg(*reg);
+            }
+        });
+    }
+    used
+}
+
+/// 返回每条指令执行之后仍然活跃的候选伪寄存器。
+fn analyze_liveness<'a>(
+    instructions: &'a [Instruction],
+    is_candidate: &impl Fn(&str) -> bool,
+) -> Vec<HashSet<&'a str>> {
+    let labels: HashMap<&str, usize> = instructions
+        .iter()
+        .enumerate()
+        .filter_map(|(i, inst)| match inst {
+            Instruction::Label(name) => Some((name.as_str(), i)),
+            _ => None,
+        })
+        .collect();
+    let successors: Vec<Vec<usize>> = instructions
+        .iter()
+        .enumerate()
+        .map(|(i, inst)| {
+            let next = (i + 1 < instructions.len()).then_some(i + 1);
+            match inst {
+                Instruction::Ret => Vec::new(),
+                Instruction::Jmp(target) => {
+                    labels.get(target.as_str()).copied().into_iter().collect()
+                }
+                Instruction::JmpCC(_, target) => labels
+                    .get(target.as_str())
+                    .copied()
+                    .into_iter()
+                    .chain(next)
+                    .collect(),
+                _ => next.into_iter().collect(),
+            }
+        })
+        .collect();
+
+    let mut live_in: Vec<HashSet<&str>> = vec![HashSet::new(); instructions.len()];
+    let mut live_out: Vec<HashSet<&str>> = vec![HashSet::new(); instructions.len()];
+    // 反向遍历收敛最快；有循环时重复直到不再变化
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for i in (0..instructions.len()).rev() {
+            let out: HashSet<&str> = successors[i]
+                .iter()
+                .flat_map(|&s| live_in[s].iter().copied())
+                .collect();
+            let (read, written) = operands(&instructions[i]);
+            let mut live: HashSet<&str> = out.clone();
+            for op in &written {
+                if let Some(name) = pseudo_name(op, is_candidate) {
+                    live.remove(name);
+                }
+            }
+            for op in &read {
+                if let Some(name) = pseudo_name(op, is_candidate) {
+                    live.insert(name);
+                }
+            }
+            if live != live_in[i] {
+                live_in[i] = live;
+                changed = true;
+            }
+            live_out[i] = out;
+        }
+    }
+    live_out
+}
+
+fn pseudo_name<'a>(op: &'a Operand, is_candidate: &impl Fn(&str) -> bool) -> Option<&'a str> {
+    match op {
+        Operand::Pseudo(name) if is_candidate(name) => Some(name),
+        _ => None,
+    }
+}
+
+/// 返回指令读取和写入的操作数。
+/// 只写入一部分的指令（`setcc` 只写最低字节、`cmov` 可能不写）把目标同时算作读取。
+fn operands(inst: &Instruction) -> (Vec<&Operand>, Vec<&Operand>) {
+    match inst {
+        Instruction::Mov { src, dst, .. }
+        | Instruction::Movsx { src, dst, .. }
+        | Instruction::MovZeroExtend { src, dst, .. } => (vec![src], vec![dst]),
+        Instruction::Unary { operand, .. } | Instruction::SetCC(_, operand) => {
+            (vec![operand], vec![operand])
+        }
+        Instruction::Binary { src, dst, .. } | Instruction::CMovCC { src, dst, .. } => {
+            (vec![src, dst], vec![dst])
+        }
+        Instruction::Cmp { src1, src2, .. } => (vec![src1, src2], Vec::new()),
+        Instruction::Idiv(_, operand)
+        | Instruction::Div(_, operand)
+        | Instruction::Push(operand) => (vec![operand], Vec::new()),
+        _ => (Vec::new(), Vec::new()),
+    }
+}
+
+fn for_each_operand_mut(inst: &mut Instruction, mut f: impl FnMut(&mut Operand)) {
+    match inst {
+        Instruction::Mov { src, dst, .. }
+        | Instruction::Movsx { src, dst, .. }
+        | Instruction::MovZeroExtend { src, dst, .. }
+        | Instruction::Binary { src, dst, .. }
+        | Instruction::CMovCC { src, dst, .. } => {
+            f(src);
+            f(dst);
+        }
+        Instruction::Cmp { src1, src2, .. } => {
+            f(src1);
+            f(src2);
+        }
+        Instruction::Unary { operand, .. }
+        | Instruction::SetCC(_, operand)
+        | Instruction::Idiv(_, operand)
+        | Instruction::Div(_, operand)
+        | Instruction::Push(operand) => f(operand),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::assembly::Program;
+
+    fn allocate(text: &str) -> (Function, Vec<Register>) {
+        let mut program: Program = text.parse().unwrap();
+        let mut func = program.functions.remove(0);
+        let used = allocate_registers(&mut func, |name| !name.starts_with("static."));
+        (func, used)
+    }
+
+    #[test]
+    fn test_loop_variables_live_in_registers() {
+        let (func, used) = allocate(
+            "function f
+                mov l $0 @i
+                mov l $0 @sum
+                label loop
+                cmp l $10 @i
+                jmpcc ge end
+                binary add l @i @sum
+                binary add l $1 @i
+                jmp loop
+                label end
+                mov l @sum %ax
+                ret",
+        );
+        let text = func.to_string();
+        assert!(!text.contains('@'), "{}", text);
+        // i 和 sum 在整个循环中同时活跃，必须放在不同的寄存器里
+        assert_eq!(used, [Register::BX, Register::R12], "{}", text);
+    }
+
+    #[test]
+    fn test_values_that_are_not_live_together_share_a_register() {
+        let (func, used) = allocate(
+            "function f
+                mov l $1 @a
+                mov l @a %ax
+                mov l $2 @b
+                mov l @b %dx
+                ret",
+        );
+        assert_eq!(used, [Register::BX], "{}", func);
+    }
+
+    #[test]
+    fn test_spills_when_registers_run_out_and_skips_non_candidates() {
+        let mut text = String::from("function f\n");
+        for i in 0..6 {
+            text.push_str(&format!("mov l ${} @v{}\n", i, i));
+        }
+        // v5 比其他变量用得多，最先分到寄存器；其余按出现顺序，v4 排在最后
+        text.push_str("binary add l @v5 @v5\n");
+        for i in 0..6 {
+            text.push_str(&format!("binary add l @v{} @static.x\n", i));
+        }
+        text.push_str("ret\n");
+        let (func, used) = allocate(&text);
+
+        let result = func.to_string();
+        let pseudos: HashSet<&str> = result
+            .split_whitespace()
+            .filter(|token| token.starts_with('@'))
+            .collect();
+        assert_eq!(used, ALLOCATABLE, "{}", result);
+        assert_eq!(pseudos, HashSet::from(["@v4", "@static.x"]), "{}", result);
+    }
+}
@@ -1,46 +1,148 @@
 // src/backend/tacky_gen.rs
 
-use crate::ast::checked::{self, Block};
+use crate::ast::checked::{self, Block, CType};
 // 导入我们需要的数据结构
 use crate::common::UniqueIdGenerator;
 use crate::ir::tacky;
+use crate::semantics::control_flow::block_always_returns;
+use crate::semantics::type_checker::{StructLayout, Symbol, is_unsigned, scalar_size};
+use std::collections::HashMap;
 
 const LOOP_START_PREFIX: &str = "loop_start";
 const CONTINUE_LABEL_PREFIX: &str = "continue";
 const BREAK_LABEL_PREFIX: &str = "break";
+
+/// TACKY 中临时变量和标签的命名方式，便于和其他代码生成器的输出放在一起时避免冲突。
+#[derive(Debug, Clone)]
+pub struct NamingScheme {
+    /// 临时变量名的前缀，后面直接跟唯一编号，例如默认的 `tmp.3`
+    pub temp_prefix: String,
+    /// 标签名的前缀，后面跟标签种类和编号，例如默认的 `_and_false_3`
+    pub label_prefix: String,
+}
+
+impl Default for NamingScheme {
+    fn default() -> Self {
+        NamingScheme {
+            temp_prefix: "tmp.".to_string(),
+            label_prefix: "_".to_string(),
+        }
+    }
+}
+
 /// 负责将 C AST 转换为 TACKY IR 的生成器。
 pub struct TackyGenerator<'a> {
     /// 用于生成唯一标签名的计数器。
     label_counter: usize,
     id_generator: &'a mut UniqueIdGenerator,
+    /// 类型检查阶段构建的符号表，用于查询变量的类型。
+    /// 新生成的临时变量也会登记到这里，以便后端确定操作数的大小。
+    symbols: &'a mut HashMap<String, Symbol>,
+    /// 类型检查阶段计算出的结构体布局，用于确定成员的偏移量。
+    struct_types: &'a HashMap<String, StructLayout>,
+    /// 是否在缺少结尾 return 的函数末尾补上 `return 0`（C99 对 main 的规定）。
+    /// 关闭后，这样的函数会被报告为错误。
+    pub implicit_return: bool,
+    /// 临时变量和标签的命名方式
+    pub naming: NamingScheme,
+    /// 在函数体中遇到的静态局部变量，它们不属于任何函数的栈帧
+    static_variables: Vec<tacky::StaticVariable>,
+    /// 为当前函数创建的临时变量
+    temporaries: Vec<String>,
 }
 
 impl<'a> TackyGenerator<'a> {
     /// 创建一个新的 TackyGenerator 实例。
-    pub fn new(id_generator: &'a mut UniqueIdGenerator) -> Self {
+    pub fn new(
+        id_generator: &'a mut UniqueIdGenerator,
+        symbols: &'a mut HashMap<String, Symbol>,
+        struct_types: &'a HashMap<String, StructLayout>,
+    ) -> Self {
         TackyGenerator {
             id_generator,
             label_counter: 0, // 初始化标签计数器
+            symbols,
+            struct_types,
+            implicit_return: true,
+            naming: NamingScheme::default(),
+            static_variables: Vec::new(),
+            temporaries: Vec::new(),
         }
     }
 
-    /// 生成一个唯一的临时变量名，例如 "tmp.0", "tmp.1"。
-    fn make_temporary(&mut self) -> String {
+    /// 生成一个唯一的临时变量名，例如 "tmp.0", "tmp.1"，并在符号表中登记它的类型。
+    fn make_temporary(&mut self, c_type: CType) -> String {
         let id = self.id_generator.next();
-        let name = format!("tmp.{}", id);
+        let name = format!("{}{}", self.naming.temp_prefix, id);
+        self.temporaries.push(name.clone());
+        self.symbols.insert(
+            name.clone(),
+            Symbol {
+                c_type,
+                defined: true,
+                static_init: None,
+            },
+        );
         name
     }
 
+    /// 查询一个 TACKY 值的类型。
+    fn val_type(&self, val: &tacky::Val) -> CType {
+        match val {
+            tacky::Val::Constant(_) => CType::Int,
+            tacky::Val::LongConstant(_) => CType::Long,
+            tacky::Val::UIntConstant(_) => CType::UInt,
+            tacky::Val::ULongConstant(_) => CType::ULong,
+            tacky::Val::Var(name) => self
+                .symbols
+                .get(name)
+                .map_or(CType::Int, |s| s.c_type.clone()),
+        }
+    }
+
+    /// 计算成员访问表达式所在的位置：(结构体变量名, 字节偏移, 成员类型)。
+    fn member_location(&self, exp: &checked::Expression) -> Result<(String, u32, CType), String> {
+        let checked::Expression::Member { base, member } = exp else {
+            return Err(format!("Expected a member access, found {:?}", exp));
+        };
+        let (name, base_offset, base_type) = match &**base {
+            checked::Expression::Var { name, .. } => (
+                name.clone(),
+                0,
+                self.val_type(&tacky::Val::Var(name.clone())),
+            ),
+            inner @ checked::Expression::Member { .. } => self.member_location(inner)?,
+            other => {
+                return Err(format!("Unsupported base for member access: {:?}", other));
+            }
+        };
+        let layout_member = match &base_type {
+            CType::Struct(tag) => self
+                .struct_types
+                .get(tag)
+                .and_then(|layout| layout.member(member)),
+            _ => None,
+        }
+        .ok_or_else(|| format!("'{}' has no member named '{}'", base_type, member))?;
+        Ok((
+            name,
+            base_offset + layout_member.offset,
+            layout_member.member_type.clone(),
+        ))
+    }
+
     /// 生成一个唯一的标签名，例如 "_L0", "_L1"。
     /// 使用下划线和字母开头，确保是合法的汇编标签。
     fn make_label_with_prefix(&mut self, prefix: &str) -> String {
-        let label = format!("_{}_{}", prefix, self.label_counter);
+        let label = format!(
+            "{}{}_{}",
+            self.naming.label_prefix, prefix, self.label_counter
+        );
         self.label_counter += 1;
         label
     }
     fn make_label_with_id(&mut self, prefix: &str, id: usize) -> String {
-        let label = format!("_{}_{}", prefix, id);
-        label
+        format!("{}{}_{}", self.naming.label_prefix, prefix, id)
     }
 
     /// 将 checked AST 中的 UnaryOperator 转换为 tacky IR 中的 UnaryOperator。
@@ -52,31 +154,97 @@ impl<'a> TackyGenerator<'a> {
         }
     }
 
-    /// 将 checked AST 中的 BinaryOperator 转换为 tacky IR 中的 BinaryOperator。
-    /// 注意：这个函数只处理非短路的二元运算符。
-    fn convert_binaryop(
-        &self,
-        op: &checked::BinaryOperator,
-    ) -> Result<tacky::BinaryOperator, String> {
-        match op {
-            checked::BinaryOperator::Add => Ok(tacky::BinaryOperator::Add),
-            checked::BinaryOperator::Subtract => Ok(tacky::BinaryOperator::Subtract),
-            checked::BinaryOperator::Multiply => Ok(tacky::BinaryOperator::Multiply),
-            checked::BinaryOperator::Divide => Ok(tacky::BinaryOperator::Divide),
-            checked::BinaryOperator::Remainder => Ok(tacky::BinaryOperator::Remainder),
-            checked::BinaryOperator::Equal => Ok(tacky::BinaryOperator::Equal),
-            checked::BinaryOperator::NotEqual => Ok(tacky::BinaryOperator::NotEqual),
-            checked::BinaryOperator::LessThan => Ok(tacky::BinaryOperator::LessThan),
-            checked::BinaryOperator::LessOrEqual => Ok(tacky::BinaryOperator::LessOrEqual),
-            checked::BinaryOperator::GreaterThan => Ok(tacky::BinaryOperator::GreaterThan),
-            checked::BinaryOperator::GreaterOrEqual => Ok(tacky::BinaryOperator::GreaterEqual),
-            // And 和 Or 是特殊情况，不应通过此函数处理
-            checked::BinaryOperator::And | checked::BinaryOperator::Or => Err(
-                "Logical AND/OR should be handled separately and not converted directly."
-                    .to_string(),
-            ),
+    /// 生成自增/自减：读出旧值，计算新值并写回。
+    /// 前缀形式的值是新值，后缀形式的值是旧值。
+    fn generate_tacky_for_inc_dec(
+        &mut self,
+        operator: &checked::IncDecOperator,
+        target: &checked::Expression,
+        is_prefix: bool,
+        instructions: &mut Vec<tacky::Instruction>,
+    ) -> Result<tacky::Val, String> {
+        let current = self.generate_tacky_for_expression(target, instructions)?;
+        let target_type = self.val_type(&current);
+        // 变量本身会被改写，后缀形式需要先保存旧值
+        let old = if is_prefix {
+            current.clone()
+        } else {
+            let old = tacky::Val::Var(self.make_temporary(target_type.clone()));
+            instructions.push(tacky::Instruction::Copy {
+                src: current.clone(),
+                dst: old.clone(),
+            });
+            old
+        };
+
+        let new = tacky::Val::Var(self.make_temporary(target_type.clone()));
+        match (&target_type, operator) {
+            // _Bool 自增后总是 1，自减则是对旧值取反
+            (CType::Bool, checked::IncDecOperator::Increment) => {
+                instructions.push(tacky::Instruction::Copy {
+                    src: tacky::Val::Constant(1),
+                    dst: new.clone(),
+                });
+            }
+            (CType::Bool, checked::IncDecOperator::Decrement) => {
+                instructions.push(tacky::Instruction::Unary {
+                    op: tacky::UnaryOperator::Not,
+                    src: current,
+                    dst: new.clone(),
+                });
+            }
+            _ => {
+                let one = match target_type {
+                    CType::Long => tacky::Val::LongConstant(1),
+                    CType::ULong => tacky::Val::ULongConstant(1),
+                    _ => tacky::Val::Constant(1),
+                };
+                let op = match operator {
+                    checked::IncDecOperator::Increment => tacky::BinaryOperator::Add,
+                    checked::IncDecOperator::Decrement => tacky::BinaryOperator::Subtract,
+                };
+                // char 先扩展为 int 再运算，结果再截断回 char
+                let (src1, sum) = match target_type {
+                    CType::Char => {
+                        let widened = tacky::Val::Var(self.make_temporary(CType::Int));
+                        instructions.push(tacky::Instruction::Copy {
+                            src: current,
+                            dst: widened.clone(),
+                        });
+                        (widened, tacky::Val::Var(self.make_temporary(CType::Int)))
+                    }
+                    _ => (current, new.clone()),
+                };
+                instructions.push(tacky::Instruction::Binary {
+                    op,
+                    src1,
+                    src2: one,
+                    dst: sum.clone(),
+                });
+                if target_type == CType::Char {
+                    self.generate_tacky_for_char_truncation(sum, &new, instructions);
+                }
+            }
+        }
+
+        match target {
+            checked::Expression::Var { name, .. } => instructions.push(tacky::Instruction::Copy {
+                src: new.clone(),
+                dst: tacky::Val::Var(name.clone()),
+            }),
+            checked::Expression::Member { .. } => {
+                let (name, offset, _) = self.member_location(target)?;
+                instructions.push(tacky::Instruction::CopyToOffset {
+                    src: new.clone(),
+                    dst: name,
+                    offset,
+                });
+            }
+            _ => return Err("Invalid operand for increment/decrement.".to_string()),
         }
+        Ok(if is_prefix { new } else { old })
     }
+
     fn generate_tacky_for_block(
         &mut self,
         block: &checked::Block,
@@ -95,28 +263,71 @@ impl<'a> TackyGenerator<'a> {
         instructions: &mut Vec<tacky::Instruction>,
     ) -> Result<tacky::Val, String> {
         match exp {
-            checked::Expression::Var(name) => Ok(tacky::Val::Var(name.clone())),
+            checked::Expression::Var { name, .. } => Ok(tacky::Val::Var(name.clone())),
             checked::Expression::Assign { left, right } => {
                 let rhs_val = self.generate_tacky_for_expression(right, instructions)?;
 
-                if let checked::Expression::Var(var_name) = &**left {
-                    let dst_var = tacky::Val::Var(var_name.clone());
-                    instructions.push(tacky::Instruction::Copy {
-                        src: rhs_val.clone(),
-                        dst: dst_var,
-                    });
-                    Ok(rhs_val)
-                } else {
-                    Err("Invalid left-hand side in assignment.".to_string())
+                match &**left {
+                    checked::Expression::Var { name: var_name, .. } => {
+                        let dst_var = tacky::Val::Var(var_name.clone());
+                        instructions.push(tacky::Instruction::Copy {
+                            src: rhs_val.clone(),
+                            dst: dst_var,
+                        });
+                        Ok(rhs_val)
+                    }
+                    member @ checked::Expression::Member { .. } => {
+                        let (name, offset, _) = self.member_location(member)?;
+                        instructions.push(tacky::Instruction::CopyToOffset {
+                            src: rhs_val.clone(),
+                            dst: name,
+                            offset,
+                        });
+                        Ok(rhs_val)
+                    }
+                    _ => Err("Invalid left-hand side in assignment.".to_string()),
                 }
             }
+            checked::Expression::Member { .. } => {
+                let (name, offset, member_type) = self.member_location(exp)?;
+                let dst = tacky::Val::Var(self.make_temporary(member_type));
+                instructions.push(tacky::Instruction::CopyFromOffset {
+                    src: name,
+                    offset,
+                    dst: dst.clone(),
+                });
+                Ok(dst)
+            }
             checked::Expression::Constant(i) => Ok(tacky::Val::Constant(*i)),
+            checked::Expression::LongConstant(i) => Ok(tacky::Val::LongConstant(*i)),
+            checked::Expression::UIntConstant(i) => Ok(tacky::Val::UIntConstant(*i)),
+            checked::Expression::ULongConstant(i) => Ok(tacky::Val::ULongConstant(*i)),
+            checked::Expression::Cast {
+                target_type,
+                expression,
+            } => {
+                let src = self.generate_tacky_for_expression(expression, instructions)?;
+                self.generate_tacky_for_cast(src, target_type, instructions)
+            }
+            checked::Expression::Prefix {
+                operator,
+                expression,
+            } => self.generate_tacky_for_inc_dec(operator, expression, true, instructions),
+            checked::Expression::Postfix {
+                operator,
+                expression,
+            } => self.generate_tacky_for_inc_dec(operator, expression, false, instructions),
             checked::Expression::Unary {
                 operator,
                 expression,
             } => {
                 let src = self.generate_tacky_for_expression(expression, instructions)?;
-                let dst_name = self.make_temporary();
+                // 逻辑非的结果是 int，其他一元运算的结果与操作数同类型
+                let dst_type = match operator {
+                    checked::UnaryOperator::Not => CType::Int,
+                    _ => self.val_type(&src),
+                };
+                let dst_name = self.make_temporary(dst_type);
                 let dst = tacky::Val::Var(dst_name);
                 let tacky_op = self.convert_unop(operator);
                 instructions.push(tacky::Instruction::Unary {
@@ -131,8 +342,32 @@ impl<'a> TackyGenerator<'a> {
                 left,
                 right,
             } => match operator {
+                // 左操作数是常量时，结果要么已经确定，要么只取决于右操作数；
+                // 被短路的右操作数根本不会生成，它的副作用也就不会发生
+                checked::BinaryOperator::And | checked::BinaryOperator::Or
+                    if constant_truth(left).is_some() =>
+                {
+                    let is_and = matches!(operator, checked::BinaryOperator::And);
+                    if constant_truth(left) != Some(is_and) {
+                        // `0 && x` 为 0，`1 || x` 为 1
+                        return Ok(tacky::Val::Constant(!is_and as i32));
+                    }
+                    // `1 && x` 和 `0 || x` 都等价于 `x != 0`
+                    let v2 = self.generate_tacky_for_expression(right, instructions)?;
+                    if let Some(truth) = constant_val_truth(&v2) {
+                        return Ok(tacky::Val::Constant(truth as i32));
+                    }
+                    let result_var = tacky::Val::Var(self.make_temporary(CType::Int));
+                    instructions.push(tacky::Instruction::Binary {
+                        op: tacky::BinaryOperator::NotEqual,
+                        src1: v2,
+                        src2: tacky::Val::Constant(0),
+                        dst: result_var.clone(),
+                    });
+                    Ok(result_var)
+                }
                 checked::BinaryOperator::And => {
-                    let result_var_name = self.make_temporary();
+                    let result_var_name = self.make_temporary(CType::Int);
                     let result_var = tacky::Val::Var(result_var_name);
                     let false_label = self.make_label_with_prefix("and_false");
                     let end_label = self.make_label_with_prefix("and_end");
@@ -160,7 +395,7 @@ impl<'a> TackyGenerator<'a> {
                     Ok(result_var)
                 }
                 checked::BinaryOperator::Or => {
-                    let result_var_name = self.make_temporary();
+                    let result_var_name = self.make_temporary(CType::Int);
                     let result_var = tacky::Val::Var(result_var_name);
                     let true_label = self.make_label_with_prefix("or_true");
                     let end_label = self.make_label_with_prefix("or_end");
@@ -190,9 +425,25 @@ impl<'a> TackyGenerator<'a> {
                 _ => {
                     let src1 = self.generate_tacky_for_expression(left, instructions)?;
                     let src2 = self.generate_tacky_for_expression(right, instructions)?;
-                    let dst_name = self.make_temporary();
+                    // 类型检查器已经保证两个操作数类型相同（移位除外）；
+                    // 算术运算的结果与（左）操作数同类型，关系运算的结果是 int
+                    let dst_type = match operator {
+                        checked::BinaryOperator::Add
+                        | checked::BinaryOperator::Subtract
+                        | checked::BinaryOperator::Multiply
+                        | checked::BinaryOperator::Divide
+                        | checked::BinaryOperator::Remainder
+                        | checked::BinaryOperator::BitAnd
+                        | checked::BinaryOperator::BitOr
+                        | checked::BinaryOperator::BitXor
+                        | checked::BinaryOperator::ShiftLeft
+                        | checked::BinaryOperator::ShiftRight => self.val_type(&src1),
+                        _ => CType::Int,
+                    };
+                    let dst_name = self.make_temporary(dst_type);
                     let dst = tacky::Val::Var(dst_name);
-                    let tacky_op = self.convert_binaryop(operator)?;
+                    let unsigned = is_unsigned(&self.val_type(&src1));
+                    let tacky_op = convert_binaryop(operator, unsigned)?;
                     instructions.push(tacky::Instruction::Binary {
                         op: tacky_op,
                         src1: src1.clone(),
@@ -202,12 +453,20 @@ impl<'a> TackyGenerator<'a> {
                     Ok(dst)
                 }
             },
+            // 类型检查器已经把 sizeof 折叠为常量
+            checked::Expression::SizeOf(_) => {
+                Err("sizeof should have been folded by the type checker".to_string())
+            }
+            checked::Expression::Comma { left, right } => {
+                // 左侧只为副作用求值，它的结果被丢弃
+                self.generate_tacky_for_expression(left, instructions)?;
+                self.generate_tacky_for_expression(right, instructions)
+            }
             checked::Expression::Conditional {
                 condition,
                 left,
                 right,
             } => {
-                let result_var = tacky::Val::Var(self.make_temporary());
                 let else_label = self.make_label_with_prefix("cond_else");
                 let end_label = self.make_label_with_prefix("cond_end");
                 let cond_val = self.generate_tacky_for_expression(condition, instructions)?;
@@ -216,6 +475,9 @@ impl<'a> TackyGenerator<'a> {
                     target: else_label.clone(),
                 });
                 let then_val = self.generate_tacky_for_expression(left, instructions)?;
+                // 两个分支已被转换为相同类型，结果变量也是这个类型
+                let result_type = self.val_type(&then_val);
+                let result_var = tacky::Val::Var(self.make_temporary(result_type));
                 instructions.push(tacky::Instruction::Copy {
                     src: then_val,
                     dst: result_var.clone(),
@@ -233,7 +495,7 @@ impl<'a> TackyGenerator<'a> {
             // =========================================================
             //  【核心修改点】处理函数调用
             // =========================================================
-            checked::Expression::FunctionCall { name, args } => {
+            checked::Expression::FunctionCall { name, args, .. } => {
                 // 1. 为每个参数表达式生成指令，并收集结果 Val
                 let mut arg_vals = Vec::new();
                 for arg_expr in args {
@@ -242,8 +504,12 @@ impl<'a> TackyGenerator<'a> {
                 }
 
                 // 2. 创建一个新的临时变量来存储函数的返回值。
-                //    这就是 FunCall 指令的 `dst`。
-                let result_dst_name = self.make_temporary();
+                //    这就是 FunCall 指令的 `dst`，它的类型是函数的返回类型。
+                let return_type = match self.symbols.get(name).map(|s| &s.c_type) {
+                    Some(CType::Function { return_type, .. }) => *return_type.clone(),
+                    _ => CType::Int,
+                };
+                let result_dst_name = self.make_temporary(return_type);
                 let result_dst = tacky::Val::Var(result_dst_name);
 
                 // 3. 生成 FunCall 指令
@@ -259,6 +525,101 @@ impl<'a> TackyGenerator<'a> {
         }
     }
 
+    /// 为类型转换生成 TACKY 指令。
+    fn generate_tacky_for_cast(
+        &mut self,
+        src: tacky::Val,
+        target_type: &CType,
+        instructions: &mut Vec<tacky::Instruction>,
+    ) -> Result<tacky::Val, String> {
+        let src_type = self.val_type(&src);
+        if src_type == *target_type {
+            return Ok(src);
+        }
+        let dst = tacky::Val::Var(self.make_temporary(target_type.clone()));
+        match (&src_type, target_type) {
+            // 转换为 _Bool：任何非零值都变成 1
+            (_, CType::Bool) => instructions.push(tacky::Instruction::Binary {
+                op: tacky::BinaryOperator::NotEqual,
+                src1: src,
+                src2: tacky::Val::Constant(0),
+                dst: dst.clone(),
+            }),
+            // 转换为 char：先截断到 4 字节，再保留低 8 位并做符号扩展
+            (CType::Long | CType::ULong, CType::Char) => {
+                let truncated = tacky::Val::Var(self.make_temporary(CType::Int));
+                instructions.push(tacky::Instruction::Truncate {
+                    src,
+                    dst: truncated.clone(),
+                });
+                self.generate_tacky_for_char_truncation(truncated, &dst, instructions);
+            }
+            (CType::Int | CType::UInt, CType::Char) => {
+                self.generate_tacky_for_char_truncation(src, &dst, instructions);
+            }
+            (CType::Int | CType::Bool | CType::Char, CType::Long | CType::ULong) => instructions
+                .push(tacky::Instruction::SignExtend {
+                    src,
+                    dst: dst.clone(),
+                }),
+            (CType::UInt, CType::Long | CType::ULong) => {
+                instructions.push(tacky::Instruction::ZeroExtend {
+                    src,
+                    dst: dst.clone(),
+                })
+            }
+            (CType::Long | CType::ULong, CType::Int | CType::UInt) => {
+                instructions.push(tacky::Instruction::Truncate {
+                    src,
+                    dst: dst.clone(),
+                })
+            }
+            // 大小相同的类型之间只是换一种解释方式，直接复制；
+            // 0 和 1 本来就是合法的 char，char 的值已经做过符号扩展
+            (CType::Bool | CType::UInt | CType::Char, CType::Int)
+            | (CType::Bool | CType::Int | CType::Char, CType::UInt)
+            | (CType::Bool, CType::Char)
+            | (CType::Long, CType::ULong)
+            | (CType::ULong, CType::Long) => instructions.push(tacky::Instruction::Copy {
+                src,
+                dst: dst.clone(),
+            }),
+            (from, to) => {
+                return Err(format!("Unsupported cast from {:?} to {:?}", from, to));
+            }
+        }
+        Ok(dst)
+    }
+
+    /// 把一个 4 字节的值截断为 char 存入 `dst`：左移 24 位再算术右移 24 位，
+    /// 只保留低 8 位并做符号扩展。这样常量传播得到的也是截断后的值。
+    fn generate_tacky_for_char_truncation(
+        &mut self,
+        src: tacky::Val,
+        dst: &tacky::Val,
+        instructions: &mut Vec<tacky::Instruction>,
+    ) {
+        let shifted = tacky::Val::Var(self.make_temporary(CType::Int));
+        instructions.push(tacky::Instruction::Binary {
+            op: tacky::BinaryOperator::ShiftLeft,
+            src1: src,
+            src2: tacky::Val::Constant(24),
+            dst: shifted.clone(),
+        });
+        let truncated = tacky::Val::Var(self.make_temporary(CType::Int));
+        instructions.push(tacky::Instruction::Binary {
+            op: tacky::BinaryOperator::ShiftRight,
+            src1: shifted,
+            src2: tacky::Val::Constant(24),
+            dst: truncated.clone(),
+        });
+        // 截断后的值已经在 char 的范围内，写入 1 字节的 `dst` 只需复制最低的字节
+        instructions.push(tacky::Instruction::Copy {
+            src: truncated,
+            dst: dst.clone(),
+        });
+    }
+
     /// 为单个块项目生成 TACKY 指令
     fn generate_tacky_for_block_item(
         &mut self,
@@ -270,10 +631,21 @@ impl<'a> TackyGenerator<'a> {
                 match declaration {
                     // 函数声明/定义不会出现在函数体内部（标准C），
                     // 顶层函数定义在 generate_tacky 中单独处理。
-                    checked::Declaration::Function { .. } => {
+                    // 结构体声明只描述类型，不产生指令
+                    checked::Declaration::Function { .. } | checked::Declaration::Struct { .. } => {
                         // 此处无需处理
                     }
-                    checked::Declaration::Variable { name, init } => {
+                    // 静态变量的初始值在程序启动前就已经在 .data 中，
+                    // 每次执行到声明时都不能重新初始化
+                    checked::Declaration::Variable {
+                        name,
+                        var_type,
+                        storage_class: Some(checked::StorageClass::Static),
+                        ..
+                    } => {
+                        self.push_static_variable(name, var_type, false);
+                    }
+                    checked::Declaration::Variable { name, init, .. } => {
                         // 只处理有初始化器的声明
                         if let Some(init_expr) = init {
                             // 这等同于一个赋值语句: `var = init_expr`
@@ -365,6 +737,14 @@ impl<'a> TackyGenerator<'a> {
                 ));
                 Ok(())
             }
+            checked::Statement::Goto(label) => {
+                instructions.push(tacky::Instruction::Jump(label.clone()));
+                Ok(())
+            }
+            checked::Statement::Labeled { name, stmt } => {
+                instructions.push(tacky::Instruction::Label(name.clone()));
+                self.generate_tacky_for_statement(stmt, instructions)
+            }
 
             &checked::Statement::DoWhile {
                 ref body,
@@ -375,9 +755,9 @@ impl<'a> TackyGenerator<'a> {
                 let continue_label = self.make_label_with_id(CONTINUE_LABEL_PREFIX, id);
                 let break_label = self.make_label_with_id(BREAK_LABEL_PREFIX, id);
                 instructions.push(tacky::Instruction::Label(start_label.clone()));
-                self.generate_tacky_for_statement(&*body, instructions)?;
+                self.generate_tacky_for_statement(body, instructions)?;
                 instructions.push(tacky::Instruction::Label(continue_label));
-                let cond_val = self.generate_tacky_for_expression(&condition, instructions)?;
+                let cond_val = self.generate_tacky_for_expression(condition, instructions)?;
                 instructions.push(tacky::Instruction::JumpIfNotZero {
                     condition: cond_val,
                     target: start_label,
@@ -393,12 +773,12 @@ impl<'a> TackyGenerator<'a> {
                 let continue_label = self.make_label_with_id(CONTINUE_LABEL_PREFIX, id);
                 let break_label = self.make_label_with_id(BREAK_LABEL_PREFIX, id);
                 instructions.push(tacky::Instruction::Label(continue_label.clone()));
-                let cond_val = self.generate_tacky_for_expression(&condition, instructions)?;
+                let cond_val = self.generate_tacky_for_expression(condition, instructions)?;
                 instructions.push(tacky::Instruction::JumpIfZero {
                     condition: cond_val,
                     target: break_label.clone(),
                 });
-                self.generate_tacky_for_statement(&*body, instructions)?;
+                self.generate_tacky_for_statement(body, instructions)?;
                 instructions.push(tacky::Instruction::Jump(continue_label));
                 instructions.push(tacky::Instruction::Label(break_label));
 
@@ -445,13 +825,21 @@ impl<'a> TackyGenerator<'a> {
     ) -> Result<Option<tacky::Function>, String> {
         // 只处理函数定义（有函数体），忽略函数声明
         if let Some(b) = body {
+            // 与 `--analyze` 的缺少 return 警告使用同一个分析
+            if !self.implicit_return && !block_always_returns(&b) {
+                return Err(format!(
+                    "Function '{}' can reach the end without returning a value",
+                    name
+                ));
+            }
             let mut instructions = Vec::new();
             self.generate_tacky_for_block(&b, &mut instructions)?;
 
-            // 确保函数总有返回值
-            if !instructions
-                .last()
-                .map_or(false, |inst| matches!(inst, tacky::Instruction::Return(_)))
+            // 确保函数总有返回值；末尾不可达时这条 return 也无害
+            if self.implicit_return
+                && !instructions
+                    .last()
+                    .is_some_and(|inst| matches!(inst, tacky::Instruction::Return(_)))
             {
                 instructions.push(tacky::Instruction::Return(tacky::Val::Constant(0)));
             }
@@ -459,6 +847,13 @@ impl<'a> TackyGenerator<'a> {
                 name,
                 params,
                 body: instructions,
+                temporaries: std::mem::take(&mut self.temporaries),
+                static_variables: self
+                    .symbols
+                    .iter()
+                    .filter(|(_, symbol)| symbol.static_init.is_some())
+                    .map(|(name, _)| name.clone())
+                    .collect(),
             }))
         } else {
             // 函数声明（无函数体）在 TACKY 生成阶段被丢弃
@@ -466,6 +861,24 @@ impl<'a> TackyGenerator<'a> {
         }
     }
 
+    /// 记录一个具有静态存储期的变量；`global` 表示它是外部可见的全局变量
+    fn push_static_variable(&mut self, name: &str, var_type: &CType, global: bool) {
+        let (size, alignment) = match var_type {
+            CType::Struct(tag) => {
+                let layout = &self.struct_types[tag];
+                (layout.size, layout.alignment)
+            }
+            scalar => (scalar_size(scalar), scalar_size(scalar)),
+        };
+        self.static_variables.push(tacky::StaticVariable {
+            name: name.to_string(),
+            size,
+            alignment,
+            init: self.symbols[name].static_init.unwrap_or(0),
+            global,
+        });
+    }
+
     /// 主入口：将整个 C 程序 AST 转换为 TACKY 程序。
     pub fn generate_tacky(&mut self, c_ast: checked::Program) -> Result<tacky::Program, String> {
         let mut funs = Vec::new();
@@ -479,10 +892,398 @@ impl<'a> TackyGenerator<'a> {
                         funs.push(tacky_function);
                     }
                 }
-                // 顶层变量声明在 TACKY 阶段被忽略
-                checked::Declaration::Variable { .. } => {}
+                // 全局变量的初始值由类型检查器算出，放在 .data/.bss 中；
+                // 同一个变量的多个声明只产生一个定义
+                checked::Declaration::Variable { name, var_type, .. } => {
+                    if !self.static_variables.iter().any(|var| var.name == name) {
+                        self.push_static_variable(&name, &var_type, true);
+                    }
+                }
+                // 结构体声明在 TACKY 阶段被忽略
+                checked::Declaration::Struct { .. } => {}
+            }
+        }
+        Ok(tacky::Program {
+            functions: funs,
+            static_variables: std::mem::take(&mut self.static_variables),
+        })
+    }
+}
+
+/// 如果表达式是一个整数常量，返回它作为条件时的真假。
+fn constant_truth(expr: &checked::Expression) -> Option<bool> {
+    match expr {
+        checked::Expression::Constant(i) => Some(*i != 0),
+        checked::Expression::LongConstant(i) => Some(*i != 0),
+        checked::Expression::UIntConstant(u) => Some(*u != 0),
+        checked::Expression::ULongConstant(u) => Some(*u != 0),
+        _ => None,
+    }
+}
+
+/// 同 `constant_truth`，但作用于已经生成的 TACKY 值。
+fn constant_val_truth(val: &tacky::Val) -> Option<bool> {
+    match val {
+        tacky::Val::Constant(i) => Some(*i != 0),
+        tacky::Val::LongConstant(i) => Some(*i != 0),
+        tacky::Val::UIntConstant(u) => Some(*u != 0),
+        tacky::Val::ULongConstant(u) => Some(*u != 0),
+        tacky::Val::Var(_) => None,
+    }
+}
+
+/// 将 checked AST 中的 BinaryOperator 转换为 tacky IR 中的 BinaryOperator。
+/// `unsigned` 表示（左）操作数是无符号类型，此时除法、取余、右移和大小比较
+/// 使用对应的无符号运算符。
+/// 注意：这个函数只处理非短路的二元运算符。类型检查器折叠常量初始化器时也用它。
+pub(crate) fn convert_binaryop(
+    op: &checked::BinaryOperator,
+    unsigned: bool,
+) -> Result<tacky::BinaryOperator, String> {
+    if unsigned {
+        match op {
+            checked::BinaryOperator::Divide => {
+                return Ok(tacky::BinaryOperator::UnsignedDivide);
+            }
+            checked::BinaryOperator::Remainder => {
+                return Ok(tacky::BinaryOperator::UnsignedRemainder);
+            }
+            checked::BinaryOperator::ShiftRight => {
+                return Ok(tacky::BinaryOperator::UnsignedShiftRight);
+            }
+            checked::BinaryOperator::LessThan => {
+                return Ok(tacky::BinaryOperator::UnsignedLessThan);
+            }
+            checked::BinaryOperator::LessOrEqual => {
+                return Ok(tacky::BinaryOperator::UnsignedLessOrEqual);
+            }
+            checked::BinaryOperator::GreaterThan => {
+                return Ok(tacky::BinaryOperator::UnsignedGreaterThan);
+            }
+            checked::BinaryOperator::GreaterOrEqual => {
+                return Ok(tacky::BinaryOperator::UnsignedGreaterEqual);
+            }
+            _ => {}
+        }
+    }
+    match op {
+        checked::BinaryOperator::Add => Ok(tacky::BinaryOperator::Add),
+        checked::BinaryOperator::Subtract => Ok(tacky::BinaryOperator::Subtract),
+        checked::BinaryOperator::Multiply => Ok(tacky::BinaryOperator::Multiply),
+        checked::BinaryOperator::Divide => Ok(tacky::BinaryOperator::Divide),
+        checked::BinaryOperator::Remainder => Ok(tacky::BinaryOperator::Remainder),
+        checked::BinaryOperator::BitAnd => Ok(tacky::BinaryOperator::BitAnd),
+        checked::BinaryOperator::BitOr => Ok(tacky::BinaryOperator::BitOr),
+        checked::BinaryOperator::BitXor => Ok(tacky::BinaryOperator::BitXor),
+        checked::BinaryOperator::ShiftLeft => Ok(tacky::BinaryOperator::ShiftLeft),
+        checked::BinaryOperator::ShiftRight => Ok(tacky::BinaryOperator::ShiftRight),
+        checked::BinaryOperator::Equal => Ok(tacky::BinaryOperator::Equal),
+        checked::BinaryOperator::NotEqual => Ok(tacky::BinaryOperator::NotEqual),
+        checked::BinaryOperator::LessThan => Ok(tacky::BinaryOperator::LessThan),
+        checked::BinaryOperator::LessOrEqual => Ok(tacky::BinaryOperator::LessOrEqual),
+        checked::BinaryOperator::GreaterThan => Ok(tacky::BinaryOperator::GreaterThan),
+        checked::BinaryOperator::GreaterOrEqual => Ok(tacky::BinaryOperator::GreaterEqual),
+        // And 和 Or 是特殊情况，不应通过此函数处理
+        checked::BinaryOperator::And | checked::BinaryOperator::Or => Err(
+            "Logical AND/OR should be handled separately and not converted directly.".to_string(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{tacky_for_source, try_tacky_for_source};
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_custom_naming_scheme() {
+        let program = try_tacky_for_source(
+            "int main(void) { int a = 1; int b = 0; return a && b; }",
+            |generator| {
+                generator.naming.temp_prefix = "t_".to_string();
+                generator.naming.label_prefix = ".L".to_string();
+            },
+        )
+        .unwrap();
+
+        let text = program.to_string();
+        assert!(!text.contains("tmp."), "{}", text);
+        assert!(text.contains("t_"), "{}", text);
+        assert!(text.contains(".Land_false_"), "{}", text);
+        // 临时变量由生成器记录，与前缀无关：只有 `&&` 的结果需要一个
+        assert_eq!(program.functions[0].temporary_count(), 1, "{}", text);
+    }
+
+    #[test]
+    fn test_temporary_count_ignores_locals_named_like_temporaries() {
+        // 局部变量 tmp 被 Validator 改名为 `tmp.N`，但它不是生成器创建的临时变量
+        let program =
+            tacky_for_source("int main(void) { int tmp = 4; tmp = tmp + 1; return tmp; }");
+        assert_eq!(program.functions[0].temporary_count(), 1, "{}", program);
+    }
+
+    #[test]
+    fn test_empty_body_gets_implicit_return() {
+        let program = tacky_for_source("int main(void) {}");
+        assert!(matches!(
+            program.functions[0].body.as_slice(),
+            [tacky::Instruction::Return(tacky::Val::Constant(0))]
+        ));
+
+        // 关闭隐式返回后，空函数体被当作缺少 return 报告
+        let err = try_tacky_for_source("int f(void) {} int main(void) { return 0; }", |g| {
+            g.implicit_return = false
+        })
+        .unwrap_err();
+        assert_eq!(
+            err,
+            "Function 'f' can reach the end without returning a value"
+        );
+
+        // 两个分支都返回时末尾不可达，即使最后一条指令不是 return 也不报错
+        let program = try_tacky_for_source(
+            "int f(int x) { if (x) return 1; else return 2; } int main(void) { return f(1); }",
+            |g| g.implicit_return = false,
+        )
+        .unwrap();
+        assert!(
+            !matches!(
+                program.functions[0].body.last(),
+                Some(tacky::Instruction::Return(_))
+            ),
+            "{}",
+            program
+        );
+    }
+
+    #[test]
+    fn test_lowers_each_definition_and_skips_prototypes() {
+        let program = tacky_for_source(
+            r#"
+            int add(int a, int b);
+            int add(int a, int b) { return a + b; }
+            int main(void) { return add(1, 2); }
+            "#,
+        );
+
+        // 原型没有函数体，不生成 TACKY 函数
+        let names: Vec<&str> = program.functions.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, vec!["add", "main"]);
+        assert_eq!(program.functions[0].params.len(), 2);
+        assert!(program.functions[1].params.is_empty());
+        assert!(
+            program.functions[1].body.iter().any(
+                |inst| matches!(inst, tacky::Instruction::FunCall { name, .. } if name == "add")
+            )
+        );
+    }
+
+    #[test]
+    fn test_nested_conditionals_use_one_temporary_each() {
+        let program = tacky_for_source(
+            r#"
+            int main(void) {
+                int a = 1; int b = 1; int c = 0; int d = 1;
+                return a ? (b ? (c ? (d ? 1 : 2) : 3) : 4) : 5;
+            }
+            "#,
+        );
+
+        let mut temporaries = HashSet::new();
+        for inst in &program.functions[0].body {
+            if let tacky::Instruction::Copy {
+                dst: tacky::Val::Var(name),
+                ..
+            } = inst
+                && name.starts_with("tmp.")
+            {
+                temporaries.insert(name.clone());
+            }
+        }
+        // 四层嵌套的条件表达式，每层只需要一个结果临时变量
+        assert_eq!(temporaries.len(), 4);
+    }
+
+    #[test]
+    fn test_call_lowers_to_fun_call_with_all_arguments() {
+        let program = tacky_for_source(
+            r#"
+            int add3(int a, int b, int c);
+            int main(void) { return add3(1, 2, 3); }
+            "#,
+        );
+
+        let calls: Vec<usize> = program.functions[0]
+            .body
+            .iter()
+            .filter_map(|inst| match inst {
+                tacky::Instruction::FunCall { name, args, .. } if name == "add3" => {
+                    Some(args.len())
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(calls, vec![3]);
+    }
+
+    #[test]
+    fn test_chained_conditionals_use_distinct_labels() {
+        // `a ? b : c ? d : e` 按右结合解析，内层条件表达式有自己的一对标签
+        let program = tacky_for_source("int main(void) { return 1 ? 2 : 0 ? 3 : 4; }");
+        let labels: Vec<&str> = program.functions[0]
+            .body
+            .iter()
+            .filter_map(|inst| match inst {
+                tacky::Instruction::Label(name) => Some(name.as_str()),
+                _ => None,
+            })
+            .collect();
+        let unique: HashSet<&str> = labels.iter().copied().collect();
+        assert_eq!(labels.len(), 4, "{}", program);
+        assert_eq!(unique.len(), labels.len(), "{}", program);
+    }
+
+    #[test]
+    fn test_static_local_initializer_is_not_copied_on_entry() {
+        let program = tacky_for_source(
+            r#"
+            int counter(void) {
+                static int n = 10;
+                n = n + 1;
+                return n;
+            }
+            "#,
+        );
+
+        assert_eq!(program.static_variables.len(), 1);
+        assert_eq!(program.static_variables[0].init, 10);
+        assert!(!program.static_variables[0].global);
+        // 初始值只存在于 .data 中，函数体里不能有把 10 写回静态变量的 Copy
+        let reinit = program.functions[0].body.iter().any(|inst| {
+            matches!(
+                inst,
+                tacky::Instruction::Copy {
+                    src: tacky::Val::Constant(10),
+                    ..
+                }
+            )
+        });
+        assert!(!reinit, "{}", program);
+    }
+
+    #[test]
+    fn test_unsigned_operands_select_unsigned_operators() {
+        let program = tacky_for_source(
+            r#"
+            int main(void) {
+                unsigned a = 7u;
+                int b = 2;
+                long wide = a;
+                return (a / b > 1u) + (b / 2 < 1) + (a >> 1 == wide);
+            }
+            "#,
+        );
+
+        let ops: Vec<String> = program.functions[0]
+            .body
+            .iter()
+            .filter_map(|inst| match inst {
+                tacky::Instruction::Binary { op, .. } => Some(op.to_string()),
+                tacky::Instruction::ZeroExtend { .. } => Some("zero_extend".to_string()),
+                _ => None,
+            })
+            .collect();
+        // int 的 b 与 unsigned 的 a 相除时按无符号运算；b / 2 仍然是有符号运算
+        assert_eq!(
+            ops,
+            vec![
+                "zero_extend",
+                "/u",
+                ">u",
+                "/",
+                "<",
+                "+",
+                ">>u",
+                "zero_extend",
+                "==",
+                "+"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_logical_operators_with_constant_left_operand_fold() {
+        let body_of = |expr: &str| {
+            let source = format!(
+                "int f(void); int g(void); int main(void) {{ int x = f(); return {}; }}",
+                expr
+            );
+            let program = tacky_for_source(&source);
+            program.functions[0]
+                .body
+                .iter()
+                .map(|inst| inst.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        // 被短路的调用不会出现在 TACKY 中
+        for (expr, value) in [
+            ("0 && g()", 0),
+            ("1 || g()", 1),
+            ("1 && 0", 0),
+            ("0 || 7", 1),
+        ] {
+            let body = body_of(expr);
+            assert!(!body.iter().any(|inst| inst.contains("g(")), "{:?}", body);
+            assert_eq!(
+                body.last().unwrap(),
+                &format!("return {}", value),
+                "{}",
+                expr
+            );
+        }
+        // 右操作数不是常量时只剩一次与 0 的比较，不需要跳转
+        let body = body_of("1 && x");
+        assert!(body.iter().any(|inst| inst.contains("!= 0")), "{:?}", body);
+        assert!(
+            !body.iter().any(|inst| inst.starts_with("jump")),
+            "{:?}",
+            body
+        );
+    }
+
+    #[test]
+    fn test_call_arguments_are_evaluated_once_left_to_right() {
+        let program = tacky_for_source(
+            r#"
+            int id(int x);
+            int sum(int a, int b, int c, int d, int e, int f, int g, int h);
+            int main(void) {
+                return sum(id(1), id(2), id(3), id(4), id(5), id(6), id(7), id(8));
+            }
+            "#,
+        );
+
+        let main = program.functions.iter().find(|f| f.name == "main").unwrap();
+        let mut id_results = Vec::new();
+        let mut sum_args = None;
+        for inst in &main.body {
+            if let tacky::Instruction::FunCall { name, args, dst } = inst {
+                if name == "id" {
+                    // 每个参数表达式恰好求值一次，并且按从左到右的顺序
+                    let tacky::Val::Constant(n) = args[0] else {
+                        panic!("unexpected argument {:?}", args[0]);
+                    };
+                    assert_eq!(n as usize, id_results.len() + 1);
+                    id_results.push(dst.to_string());
+                } else {
+                    sum_args = Some(args.iter().map(|a| a.to_string()).collect::<Vec<_>>());
+                }
             }
         }
-        Ok(tacky::Program { functions: funs })
+        assert_eq!(id_results.len(), 8);
+        // 传给 sum 的正是这些结果，顺序不变，后两个经栈传递时也不会重新求值
+        assert_eq!(sum_args, Some(id_results));
     }
 }
@@ -0,0 +1,817 @@
+// src/backend/tacky_opt.rs
+
+//! TACKY 层面的优化（在 -O1 下启用）。
+//!
+//! 1. 基本块内的常量传播与折叠：记录“变量 = 常量”的事实，把后续对该变量的
+//!    使用替换为常量，再把操作数全是常量的指令折叠成一次 Copy。
+//!    遇到标签（控制流汇合点）时清空已知事实，变量被重新赋值时让它的事实失效。
+//! 2. 死代码消除，分为两个 pass：
+//!    - 不可达代码：删除无条件跳转或 return 之后、下一个标签之前的指令，
+//!      以及没有被任何跳转引用的标签；
+//!    - 死存储：删除结果从未被读取、且没有副作用的指令。先删掉不可达代码，
+//!      只在那里被读取的变量也就成了死存储。
+//!
+//! 静态变量在函数返回后仍然存在，也可能在（递归）调用中被修改，
+//! pass 通过 `Function::static_variables` 区分它们和普通局部变量。
+
+use crate::ir::tacky::{BinaryOperator, Function, Instruction, Program, UnaryOperator, Val};
+use std::collections::{HashMap, HashSet};
+
+/// 一个优化 pass：就地改写单个函数。
+pub type Pass = fn(&mut Function);
+
+/// 优化流水线：按注册顺序对每个函数依次运行各个 pass。
+///
+/// 内建 pass 和使用者自定义的 pass 以同样的方式注册，例如：
+/// `Optimizer::with_default_passes().register(my_pass).run(&mut program)`。
+#[derive(Debug, Clone, Default)]
+pub struct Optimizer {
+    passes: Vec<Pass>,
+}
+
+impl Optimizer {
+    /// 创建一个不含任何 pass 的流水线。
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 创建包含 -O1 内建 pass 的流水线：常量传播，然后是死代码消除的两个 pass。
+    pub fn with_default_passes() -> Self {
+        let mut optimizer = Self::new();
+        optimizer
+            .register(propagate_constants)
+            .register(eliminate_unreachable_code)
+            .register(eliminate_dead_stores);
+        optimizer
+    }
+
+    /// 在流水线末尾追加一个 pass。
+    pub fn register(&mut self, pass: Pass) -> &mut Self {
+        self.passes.push(pass);
+        self
+    }
+
+    /// 对程序中的每个函数运行所有 pass。
+    pub fn run(&self, program: &mut Program) {
+        for func in &mut program.functions {
+            for pass in &self.passes {
+                pass(func);
+            }
+        }
+    }
+}
+
+/// 用内建 pass 优化整个程序。
+pub fn optimize_program(program: &mut Program) {
+    Optimizer::with_default_passes().run(program);
+}
+
+/// 基本块内的常量传播与折叠。
+pub fn propagate_constants(func: &mut Function) {
+    // 变量名 -> 当前已知的常量值
+    let mut known: HashMap<String, Val> = HashMap::new();
+    let mut optimized = Vec::with_capacity(func.body.len());
+
+    for inst in std::mem::take(&mut func.body) {
+        let inst = match inst {
+            Instruction::Label(name) => {
+                // 其他跳转可能到达这里，之前的事实不再可靠
+                known.clear();
+                Instruction::Label(name)
+            }
+            Instruction::Return(val) => Instruction::Return(substitute(&known, val)),
+            Instruction::SignExtend { src, dst } => {
+                let src = substitute(&known, src);
+                match int_bits(&src) {
+                    Some(i) => Instruction::Copy {
+                        src: Val::LongConstant(i as i64),
+                        dst,
+                    },
+                    None => Instruction::SignExtend { src, dst },
+                }
+            }
+            Instruction::ZeroExtend { src, dst } => {
+                let src = substitute(&known, src);
+                match int_bits(&src) {
+                    Some(i) => Instruction::Copy {
+                        src: Val::LongConstant(i as u32 as i64),
+                        dst,
+                    },
+                    None => Instruction::ZeroExtend { src, dst },
+                }
+            }
+            Instruction::Truncate { src, dst } => {
+                let src = substitute(&known, src);
+                match long_bits(&src) {
+                    Some(i) => Instruction::Copy {
+                        src: Val::Constant(i as i32),
+                        dst,
+                    },
+                    None => Instruction::Truncate { src, dst },
+                }
+            }
+            Instruction::Unary { op, src, dst } => {
+                let src = substitute(&known, src);
+                match fold_unary(op, &src) {
+                    Some(value) => Instruction::Copy { src: value, dst },
+                    None => Instruction::Unary { op, src, dst },
+                }
+            }
+            Instruction::Binary {
+                op,
+                src1,
+                src2,
+                dst,
+            } => {
+                let src1 = substitute(&known, src1);
+                let src2 = substitute(&known, src2);
+                match fold_binary(op, &src1, &src2) {
+                    Some(value) => Instruction::Copy { src: value, dst },
+                    None => Instruction::Binary {
+                        op,
+                        src1,
+                        src2,
+                        dst,
+                    },
+                }
+            }
+            Instruction::Copy { src, dst } => Instruction::Copy {
+                src: substitute(&known, src),
+                dst,
+            },
+            Instruction::CopyToOffset { src, dst, offset } => Instruction::CopyToOffset {
+                src: substitute(&known, src),
+                dst,
+                offset,
+            },
+            inst @ Instruction::CopyFromOffset { .. } => inst,
+            Instruction::JumpIfZero { condition, target } => {
+                match constant_value(&substitute(&known, condition.clone())) {
+                    Some(0) => Instruction::Jump(target),
+                    Some(_) => continue, // 永远不会跳转
+                    None => Instruction::JumpIfZero { condition, target },
+                }
+            }
+            Instruction::JumpIfNotZero { condition, target } => {
+                match constant_value(&substitute(&known, condition.clone())) {
+                    Some(0) => continue, // 永远不会跳转
+                    Some(_) => Instruction::Jump(target),
+                    None => Instruction::JumpIfNotZero { condition, target },
+                }
+            }
+            Instruction::FunCall { name, args, dst } => Instruction::FunCall {
+                name,
+                args: args
+                    .into_iter()
+                    .map(|arg| substitute(&known, arg))
+                    .collect(),
+                dst,
+            },
+            Instruction::Jump(target) => Instruction::Jump(target),
+        };
+
+        // 被调用的函数可能修改静态变量
+        if let Instruction::FunCall { .. } = &inst {
+            known.retain(|name, _| !func.static_variables.contains(name));
+        }
+        // 更新事实：被写入的变量要么变成新的常量，要么失效
+        if let Some(Val::Var(name)) = destination(&inst) {
+            match &inst {
+                Instruction::Copy { src, .. } if constant_value(src).is_some() => {
+                    known.insert(name.clone(), src.clone());
+                }
+                _ => {
+                    known.remove(name);
+                }
+            }
+        }
+        optimized.push(inst);
+    }
+
+    func.body = optimized;
+}
+
+/// 删除永远不会执行的指令：无条件跳转或 return 之后、下一个标签之前的部分。
+/// 没有被任何跳转引用的标签也一并删除，它前后的代码因此连成一段。
+pub fn eliminate_unreachable_code(func: &mut Function) {
+    // 删掉的跳转可能让某个标签不再被引用，所以重复直到不再变化
+    loop {
+        let targets: HashSet<String> = func
+            .body
+            .iter()
+            .filter_map(|inst| match inst {
+                Instruction::Jump(target)
+                | Instruction::JumpIfZero { target, .. }
+                | Instruction::JumpIfNotZero { target, .. } => Some(target.clone()),
+                _ => None,
+            })
+            .collect();
+
+        let before = func.body.len();
+        let mut reachable = true;
+        func.body.retain(|inst| match inst {
+            Instruction::Label(name) => {
+                if !targets.contains(name) {
+                    return false;
+                }
+                reachable = true;
+                true
+            }
+            _ if !reachable => false,
+            Instruction::Jump(_) | Instruction::Return(_) => {
+                reachable = false;
+                true
+            }
+            _ => true,
+        });
+        if func.body.len() == before {
+            break;
+        }
+    }
+}
+
+/// 删除结果从未被读取、且没有副作用的指令。
+/// 写入静态变量的指令总是保留，它的值在下次调用时还会被读取。
+pub fn eliminate_dead_stores(func: &mut Function) {
+    // 删除一条指令可能让它读取的变量也变成死的，所以重复直到不再变化
+    loop {
+        let mut read = HashSet::new();
+        for inst in &func.body {
+            for val in sources(inst) {
+                if let Val::Var(name) = val {
+                    read.insert(name.clone());
+                }
+            }
+        }
+
+        let before = func.body.len();
+        func.body.retain(|inst| {
+            // 函数调用可能有副作用，即使结果没人用也必须保留
+            if matches!(inst, Instruction::FunCall { .. }) {
+                return true;
+            }
+            match destination(inst) {
+                Some(Val::Var(name)) => read.contains(name) || func.static_variables.contains(name),
+                _ => true,
+            }
+        });
+        if func.body.len() == before {
+            break;
+        }
+    }
+}
+
+/// 返回指令读取的所有值。
+/// 聚合对象只会被 CopyToOffset 写入，而它不会被删除，所以这里不需要列出它们。
+fn sources(inst: &Instruction) -> Vec<&Val> {
+    match inst {
+        Instruction::Return(val) => vec![val],
+        Instruction::SignExtend { src, .. }
+        | Instruction::ZeroExtend { src, .. }
+        | Instruction::Truncate { src, .. }
+        | Instruction::Unary { src, .. }
+        | Instruction::Copy { src, .. }
+        | Instruction::CopyToOffset { src, .. } => vec![src],
+        Instruction::CopyFromOffset { .. } => Vec::new(),
+        Instruction::Binary { src1, src2, .. } => vec![src1, src2],
+        Instruction::JumpIfZero { condition, .. }
+        | Instruction::JumpIfNotZero { condition, .. } => {
+            vec![condition]
+        }
+        Instruction::FunCall { args, .. } => args.iter().collect(),
+        Instruction::Jump(_) | Instruction::Label(_) => Vec::new(),
+    }
+}
+
+/// 如果变量的值已知，则替换为对应的常量。
+fn substitute(known: &HashMap<String, Val>, val: Val) -> Val {
+    match &val {
+        Val::Var(name) => known.get(name).cloned().unwrap_or(val),
+        _ => val,
+    }
+}
+
+fn constant_value(val: &Val) -> Option<i64> {
+    match val {
+        Val::Constant(i) => Some(*i as i64),
+        Val::LongConstant(i) => Some(*i),
+        Val::UIntConstant(u) => Some(*u as i64),
+        Val::ULongConstant(u) => Some(*u as i64),
+        Val::Var(_) => None,
+    }
+}
+
+/// 返回 4 字节常量的位模式。int 和 unsigned int 的常量在汇编中没有区别，
+/// 常量传播可能让两者出现在同一条指令中，符号只由运算符决定。
+fn int_bits(val: &Val) -> Option<i32> {
+    match val {
+        Val::Constant(i) => Some(*i),
+        Val::UIntConstant(u) => Some(*u as i32),
+        _ => None,
+    }
+}
+
+/// 返回 8 字节常量的位模式，long 和 unsigned long 的关系与 `int_bits` 相同。
+fn long_bits(val: &Val) -> Option<i64> {
+    match val {
+        Val::LongConstant(i) => Some(*i),
+        Val::ULongConstant(u) => Some(*u as i64),
+        _ => None,
+    }
+}
+
+/// 返回指令写入的目标值（如果有）。
+fn destination(inst: &Instruction) -> Option<&Val> {
+    match inst {
+        Instruction::SignExtend { dst, .. }
+        | Instruction::ZeroExtend { dst, .. }
+        | Instruction::Truncate { dst, .. }
+        | Instruction::Unary { dst, .. }
+        | Instruction::Binary { dst, .. }
+        | Instruction::Copy { dst, .. }
+        | Instruction::CopyFromOffset { dst, .. }
+        | Instruction::FunCall { dst, .. } => Some(dst),
+        _ => None,
+    }
+}
+
+/// 折叠一元运算。取负使用补码回绕：`-INT_MIN` 的结果仍是 `INT_MIN`，
+/// 与生成的 `negl` 指令在运行时的行为一致，而不会让编译器 panic。
+fn fold_unary(op: UnaryOperator, src: &Val) -> Option<Val> {
+    Some(match (op, src) {
+        (UnaryOperator::Negate, Val::Constant(i)) => Val::Constant(i.wrapping_neg()),
+        (UnaryOperator::Negate, Val::LongConstant(i)) => Val::LongConstant(i.wrapping_neg()),
+        (UnaryOperator::Complement, Val::Constant(i)) => Val::Constant(!i),
+        (UnaryOperator::Complement, Val::LongConstant(i)) => Val::LongConstant(!i),
+        (UnaryOperator::Negate, Val::UIntConstant(u)) => Val::UIntConstant(u.wrapping_neg()),
+        (UnaryOperator::Complement, Val::UIntConstant(u)) => Val::UIntConstant(!u),
+        (UnaryOperator::Negate, Val::ULongConstant(u)) => Val::ULongConstant(u.wrapping_neg()),
+        (UnaryOperator::Complement, Val::ULongConstant(u)) => Val::ULongConstant(!u),
+        // `!` 的结果总是 int
+        (UnaryOperator::Not, _) => Val::Constant((constant_value(src)? == 0) as i32),
+        _ => return None,
+    })
+}
+
+/// 折叠二元运算。类型检查器计算全局和静态变量的初始值时也使用这些规则，
+/// 保证编译期常量和 -O1 下折叠的结果与运行时一致。
+pub(crate) fn fold_binary(op: BinaryOperator, src1: &Val, src2: &Val) -> Option<Val> {
+    match (src1, src2) {
+        (Val::Constant(_) | Val::UIntConstant(_), Val::Constant(_) | Val::UIntConstant(_)) => {
+            let (a, b) = (&int_bits(src1)?, &int_bits(src2)?);
+            let (ua, ub) = (*a as u32, *b as u32);
+            let result = fold_comparison(op, *a as i64, *b as i64)
+                .or_else(|| fold_unsigned_comparison(op, ua as u64, ub as u64));
+            if result.is_some() {
+                return result;
+            }
+            let bits = match op {
+                // 溢出按补码回绕，与运行时的 addl/subl/imull 一致
+                BinaryOperator::Add => a.wrapping_add(*b),
+                BinaryOperator::Subtract => a.wrapping_sub(*b),
+                BinaryOperator::Multiply => a.wrapping_mul(*b),
+                // 除以 0 或溢出的除法留到运行时，保持原有行为
+                BinaryOperator::Divide => a.checked_div(*b)?,
+                BinaryOperator::Remainder => a.checked_rem(*b)?,
+                BinaryOperator::BitAnd => a & b,
+                BinaryOperator::BitOr => a | b,
+                BinaryOperator::BitXor => a ^ b,
+                // 与 sal/sar 一样，移位次数只取低 5 位
+                BinaryOperator::ShiftLeft => a.wrapping_shl(*b as u32),
+                BinaryOperator::ShiftRight => a.wrapping_shr(*b as u32),
+                BinaryOperator::UnsignedDivide => ua.checked_div(ub)? as i32,
+                BinaryOperator::UnsignedRemainder => ua.checked_rem(ub)? as i32,
+                BinaryOperator::UnsignedShiftRight => ua.wrapping_shr(ub) as i32,
+                _ => return None,
+            };
+            // 结果的类型跟随操作数：只要有一个是无符号常量，结果就是无符号常量
+            if matches!(src1, Val::UIntConstant(_)) || matches!(src2, Val::UIntConstant(_)) {
+                Some(Val::UIntConstant(bits as u32))
+            } else {
+                Some(Val::Constant(bits))
+            }
+        }
+        (
+            Val::LongConstant(_) | Val::ULongConstant(_),
+            Val::LongConstant(_) | Val::ULongConstant(_),
+        ) => {
+            let (a, b) = (&long_bits(src1)?, &long_bits(src2)?);
+            let result = fold_comparison(op, *a, *b)
+                .or_else(|| fold_unsigned_comparison(op, *a as u64, *b as u64));
+            if result.is_some() {
+                return result;
+            }
+            let bits = match op {
+                BinaryOperator::Add => a.wrapping_add(*b),
+                BinaryOperator::Subtract => a.wrapping_sub(*b),
+                BinaryOperator::Multiply => a.wrapping_mul(*b),
+                BinaryOperator::Divide => a.checked_div(*b)?,
+                BinaryOperator::Remainder => a.checked_rem(*b)?,
+                BinaryOperator::BitAnd => a & b,
+                BinaryOperator::BitOr => a | b,
+                BinaryOperator::BitXor => a ^ b,
+                BinaryOperator::ShiftLeft => a.wrapping_shl(*b as u32),
+                BinaryOperator::ShiftRight => a.wrapping_shr(*b as u32),
+                BinaryOperator::UnsignedDivide => (*a as u64).checked_div(*b as u64)? as i64,
+                BinaryOperator::UnsignedRemainder => (*a as u64).checked_rem(*b as u64)? as i64,
+                BinaryOperator::UnsignedShiftRight => (*a as u64).wrapping_shr(*b as u32) as i64,
+                _ => return None,
+            };
+            if matches!(src1, Val::ULongConstant(_)) || matches!(src2, Val::ULongConstant(_)) {
+                Some(Val::ULongConstant(bits as u64))
+            } else {
+                Some(Val::LongConstant(bits))
+            }
+        }
+        _ => None,
+    }
+}
+
+/// 比较运算的结果总是 int 类型的 0 或 1。
+fn fold_comparison(op: BinaryOperator, a: i64, b: i64) -> Option<Val> {
+    let result = match op {
+        BinaryOperator::Equal => a == b,
+        BinaryOperator::NotEqual => a != b,
+        BinaryOperator::LessThan => a < b,
+        BinaryOperator::LessOrEqual => a <= b,
+        BinaryOperator::GreaterThan => a > b,
+        BinaryOperator::GreaterEqual => a >= b,
+        _ => return None,
+    };
+    Some(Val::Constant(result as i32))
+}
+
+/// 无符号比较：操作数按无符号数解释，结果同样是 int 类型的 0 或 1。
+fn fold_unsigned_comparison(op: BinaryOperator, a: u64, b: u64) -> Option<Val> {
+    let result = match op {
+        BinaryOperator::UnsignedLessThan => a < b,
+        BinaryOperator::UnsignedLessOrEqual => a <= b,
+        BinaryOperator::UnsignedGreaterThan => a > b,
+        BinaryOperator::UnsignedGreaterEqual => a >= b,
+        _ => return None,
+    };
+    Some(Val::Constant(result as i32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::tacky_for_source;
+
+    // 一个辅助函数：运行完整的前端，生成并优化 TACKY
+    fn optimized_tacky_for_source(source: &str) -> Program {
+        let mut program = tacky_for_source(source);
+        optimize_program(&mut program);
+        program
+    }
+
+    fn main_body(program: &Program) -> &[Instruction] {
+        &program
+            .functions
+            .iter()
+            .find(|f| f.name == "main")
+            .unwrap()
+            .body
+    }
+
+    #[test]
+    fn test_initialized_local_is_propagated_and_folded() {
+        let program = optimized_tacky_for_source(
+            r#"
+            int main(void) {
+                int x = 5;
+                return x + 3;
+            }
+            "#,
+        );
+        let body = main_body(&program);
+        assert!(
+            body.iter()
+                .any(|inst| matches!(inst, Instruction::Return(Val::Constant(8)))),
+            "expected `return 8` in:\n{}",
+            program
+        );
+    }
+
+    #[test]
+    fn test_constant_expression_folds_to_a_single_constant() {
+        let program = optimized_tacky_for_source("int main(void) { return 2 + 3 * 4; }");
+        // 乘法和加法的结果只在临时变量之间传递，折叠后都被删除
+        assert!(
+            matches!(
+                main_body(&program).first(),
+                Some(Instruction::Return(Val::Constant(14)))
+            ),
+            "{}",
+            program
+        );
+        assert!(
+            !main_body(&program)
+                .iter()
+                .any(|inst| matches!(inst, Instruction::Binary { .. })),
+            "{}",
+            program
+        );
+    }
+
+    #[test]
+    fn test_reassigned_local_is_not_propagated() {
+        let program = optimized_tacky_for_source(
+            r#"
+            int read(void);
+            int main(void) {
+                int x = 5;
+                x = read();
+                return x + 3;
+            }
+            "#,
+        );
+        let body = main_body(&program);
+        assert!(
+            body.iter()
+                .any(|inst| matches!(inst, Instruction::Binary { .. })),
+            "`x + 3` should not be folded:\n{}",
+            program
+        );
+        assert!(
+            !body
+                .iter()
+                .any(|inst| matches!(inst, Instruction::Return(Val::Constant(_)))),
+            "{}",
+            program
+        );
+    }
+
+    #[test]
+    fn test_facts_are_dropped_at_labels() {
+        // 循环头是一个汇合点：x 在循环体中被修改，条件不能被折叠
+        let program = optimized_tacky_for_source(
+            r#"
+            int main(void) {
+                int x = 0;
+                while (x < 3)
+                    x = x + 1;
+                return x;
+            }
+            "#,
+        );
+        let body = main_body(&program);
+        assert!(
+            body.iter().any(|inst| matches!(
+                inst,
+                Instruction::Binary {
+                    op: BinaryOperator::LessThan,
+                    ..
+                }
+            )),
+            "{}",
+            program
+        );
+        assert!(
+            body.iter()
+                .any(|inst| matches!(inst, Instruction::Return(Val::Var(_)))),
+            "{}",
+            program
+        );
+    }
+
+    #[test]
+    fn test_dead_stores_are_removed_but_calls_are_kept() {
+        let program = optimized_tacky_for_source(
+            r#"
+            int f(void);
+            int main(void) {
+                int unused = f();
+                int x = 5;
+                return x + 3;
+            }
+            "#,
+        );
+        let body = main_body(&program);
+        // 只剩下对 f 的调用和 `return 8`
+        assert!(
+            matches!(
+                body,
+                [
+                    Instruction::FunCall { .. },
+                    Instruction::Return(Val::Constant(8))
+                ]
+            ),
+            "{}",
+            program
+        );
+    }
+
+    #[test]
+    fn test_static_variables_outlive_the_function() {
+        let program = optimized_tacky_for_source(
+            r#"
+            int f(void);
+            int main(void) {
+                static int n;
+                n = 5;
+                f();
+                return n;
+            }
+            "#,
+        );
+        let body = main_body(&program);
+        // f 可能（递归地）修改 n，所以 `return n` 不能被折叠为 `return 5`；
+        // 即使 n 之后不再被读取，写入它的指令也必须保留
+        assert!(
+            matches!(
+                body,
+                [
+                    Instruction::Copy {
+                        src: Val::Constant(5),
+                        ..
+                    },
+                    Instruction::FunCall { .. },
+                    Instruction::Return(Val::Var(_))
+                ]
+            ),
+            "{}",
+            program
+        );
+    }
+
+    #[test]
+    fn test_constant_loop_condition_becomes_unconditional() {
+        let program = optimized_tacky_for_source(
+            r#"
+            int main(void) {
+                int n = 0;
+                while (1) {
+                    n = n + 1;
+                    if (n == 5) break;
+                }
+                return n;
+            }
+            "#,
+        );
+        let body = main_body(&program);
+        // `while (1)` 的条件跳转被删掉，只剩 `if (n == 5)` 的那一个
+        let conditional_jumps = body
+            .iter()
+            .filter(|inst| matches!(inst, Instruction::JumpIfZero { .. }))
+            .count();
+        assert_eq!(conditional_jumps, 1, "{}", program);
+    }
+
+    #[test]
+    fn test_unreachable_code_after_return_is_removed() {
+        let var = |name: &str| Val::Var(name.to_string());
+        // x = 1; jump_if_zero x, used; return x
+        // dead: x = 2; unused:; x = 3; return x
+        // used:; return 0
+        let mut func = Function {
+            name: "main".to_string(),
+            params: Vec::new(),
+            body: vec![
+                Instruction::Copy {
+                    src: Val::Constant(1),
+                    dst: var("x"),
+                },
+                Instruction::JumpIfZero {
+                    condition: var("x"),
+                    target: "used".to_string(),
+                },
+                Instruction::Return(var("x")),
+                Instruction::Copy {
+                    src: Val::Constant(2),
+                    dst: var("x"),
+                },
+                Instruction::Label("unused".to_string()),
+                Instruction::Copy {
+                    src: Val::Constant(3),
+                    dst: var("x"),
+                },
+                Instruction::Return(var("x")),
+                Instruction::Label("used".to_string()),
+                Instruction::Return(Val::Constant(0)),
+            ],
+            temporaries: Vec::new(),
+            static_variables: HashSet::new(),
+        };
+        eliminate_unreachable_code(&mut func);
+        assert!(
+            matches!(
+                func.body.as_slice(),
+                [
+                    Instruction::Copy {
+                        src: Val::Constant(1),
+                        ..
+                    },
+                    Instruction::JumpIfZero { .. },
+                    Instruction::Return(Val::Var(_)),
+                    Instruction::Label(label),
+                    Instruction::Return(Val::Constant(0)),
+                ] if label == "used"
+            ),
+            "{}",
+            func
+        );
+    }
+
+    #[test]
+    fn test_stores_read_only_by_unreachable_code_are_removed() {
+        let program = optimized_tacky_for_source(
+            r#"
+            int f(void);
+            int main(void) {
+                int a = f();
+                int b = a + 1;
+                return 0;
+                return b;
+            }
+            "#,
+        );
+        // `return b` 不可达，删掉它之后 b 再也没有被读取
+        assert!(
+            matches!(
+                main_body(&program),
+                [
+                    Instruction::FunCall { .. },
+                    Instruction::Return(Val::Constant(0))
+                ]
+            ),
+            "{}",
+            program
+        );
+    }
+
+    #[test]
+    fn test_registered_passes_run_in_order() {
+        use std::sync::Mutex;
+
+        // fn 指针不能捕获状态，用静态变量记录每个 pass 看到的指令数
+        static SEEN: Mutex<Vec<(&str, usize)>> = Mutex::new(Vec::new());
+        fn count_before(func: &mut Function) {
+            SEEN.lock().unwrap().push(("before", func.body.len()));
+        }
+        fn count_after(func: &mut Function) {
+            SEEN.lock().unwrap().push(("after", func.body.len()));
+        }
+
+        // main: tmp.0 = 1 + 2; return tmp.0
+        let mut program = Program {
+            functions: vec![Function {
+                name: "main".to_string(),
+                params: Vec::new(),
+                body: vec![
+                    Instruction::Binary {
+                        op: BinaryOperator::Add,
+                        src1: Val::Constant(1),
+                        src2: Val::Constant(2),
+                        dst: Val::Var("tmp.0".to_string()),
+                    },
+                    Instruction::Return(Val::Var("tmp.0".to_string())),
+                ],
+                temporaries: vec!["tmp.0".to_string()],
+                static_variables: HashSet::new(),
+            }],
+            static_variables: Vec::new(),
+        };
+
+        let mut optimizer = Optimizer::new();
+        optimizer.register(count_before);
+        optimizer.register(propagate_constants);
+        optimizer.register(eliminate_dead_stores);
+        optimizer.register(count_after);
+        optimizer.run(&mut program);
+
+        assert_eq!(*SEEN.lock().unwrap(), [("before", 2), ("after", 1)]);
+        assert!(matches!(
+            main_body(&program),
+            [Instruction::Return(Val::Constant(3))]
+        ));
+    }
+
+    #[test]
+    fn test_folding_at_int_limits_wraps_instead_of_panicking() {
+        assert!(matches!(
+            fold_unary(UnaryOperator::Negate, &Val::Constant(i32::MIN)),
+            Some(Val::Constant(i32::MIN))
+        ));
+
+        // 2147483648 是 long 常量；两次取负再截断回 int，得到 INT_MIN
+        let program = optimized_tacky_for_source("int main(void) { return -(-2147483648); }");
+        assert!(
+            matches!(
+                main_body(&program),
+                [Instruction::Return(Val::Constant(i32::MIN))]
+            ),
+            "{}",
+            program
+        );
+
+        // 加法回绕成 INT_MIN，比较结果按回绕后的值计算
+        let program =
+            optimized_tacky_for_source("int main(void) { return 2147483647 < 2147483647 + 1; }");
+        assert!(
+            matches!(main_body(&program), [Instruction::Return(Val::Constant(0))]),
+            "{}",
+            program
+        );
+    }
+}
@@ -1,16 +1,50 @@
 //! src/common.rs
+
+use std::fmt;
+use std::rc::Rc;
+
 /// 一个简单的计数器，用于在整个编译流程中生成唯一的标识符。
 pub struct UniqueIdGenerator {
     counter: usize,
 }
+impl Default for UniqueIdGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl UniqueIdGenerator {
     pub fn new() -> Self {
         UniqueIdGenerator { counter: 0 }
     }
     /// 获取下一个唯一的数字标识符。
+    #[allow(clippy::should_implement_trait)]
     pub fn next(&mut self) -> usize {
         let id = self.counter;
         self.counter += 1;
         id
     }
 }
+
+/// 源代码中的一行：行号，以及预处理器行标记 `# N "file"` 给出的原始文件名。
+/// 显示为 `10`，或者知道原始文件时的 `10 of foo.c`，正好接在错误信息的 `on line ` 之后。
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SourceLine {
+    pub number: usize,
+    pub file: Option<Rc<str>>,
+}
+
+impl From<usize> for SourceLine {
+    fn from(number: usize) -> Self {
+        SourceLine { number, file: None }
+    }
+}
+
+impl fmt::Display for SourceLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.file {
+            Some(file) => write!(f, "{} of {}", self.number, file),
+            None => write!(f, "{}", self.number),
+        }
+    }
+}
@@ -0,0 +1,331 @@
+//! src/compile_db.rs
+//! 读取 `compile_commands.json` 风格的编译数据库条目。
+//!
+//! 只识别 `directory`、`file`、`arguments` 和 `command` 四个字段，其余字段被忽略。
+//! 为了不引入额外的依赖，这里带了一个只够用的小型 JSON 解析器。
+
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// 编译数据库中的一个条目
+#[derive(Debug, PartialEq)]
+pub struct CompileDbEntry {
+    /// `file` 以及命令行中的相对路径都以此目录为基准
+    pub directory: Option<String>,
+    pub file: String,
+    /// 完整的命令行，第一个元素是编译器本身。
+    /// 条目只给出 `command` 字符串时，按 shell 的规则拆分得到
+    pub arguments: Vec<String>,
+}
+
+/// 解析一个编译数据库条目（一个 JSON 对象）。
+pub fn parse_entry(text: &str) -> Result<CompileDbEntry, String> {
+    let mut reader = JsonReader {
+        chars: text.chars().peekable(),
+    };
+    let value = reader.parse_value()?;
+    reader.skip_whitespace();
+    if reader.chars.peek().is_some() {
+        return Err("Unexpected trailing characters after JSON value".to_string());
+    }
+    let Json::Object(fields) = value else {
+        return Err("Compilation database entry must be a JSON object".to_string());
+    };
+
+    let mut directory = None;
+    let mut file = None;
+    let mut arguments = None;
+    let mut command = None;
+    for (key, value) in fields {
+        match (key.as_str(), value) {
+            ("directory", Json::String(s)) => directory = Some(s),
+            ("file", Json::String(s)) => file = Some(s),
+            ("arguments", Json::Array(items)) => {
+                let args = items
+                    .into_iter()
+                    .map(|item| match item {
+                        Json::String(s) => Ok(s),
+                        _ => Err("'arguments' must only contain strings".to_string()),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                arguments = Some(args);
+            }
+            ("command", Json::String(s)) => command = Some(s),
+            ("directory" | "file" | "arguments" | "command", _) => {
+                return Err(format!("Field '{}' has the wrong type", key));
+            }
+            _ => {}
+        }
+    }
+    // 两者都给出时以 `arguments` 为准
+    let arguments = match (arguments, command) {
+        (Some(arguments), _) => arguments,
+        (None, Some(command)) => split_command(&command)?,
+        (None, None) => return Err("Missing 'arguments' or 'command' field".to_string()),
+    };
+    if arguments.is_empty() {
+        return Err("The command line must at least name the compiler".to_string());
+    }
+    Ok(CompileDbEntry {
+        directory,
+        file: file.ok_or("Missing 'file' field")?,
+        arguments,
+    })
+}
+
+/// 按 POSIX shell 的规则拆分 `command`：参数以空白分隔，单引号内的内容原样保留，
+/// 双引号内和引号外的反斜杠转义下一个字符。
+fn split_command(command: &str) -> Result<Vec<String>, String> {
+    let unterminated = |what: &str| format!("Unterminated {} in 'command'", what);
+    let mut args = Vec::new();
+    // 当前参数；`""` 这样的空引号也会产生一个（空的）参数
+    let mut current: Option<String> = None;
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => args.extend(current.take()),
+            '\'' => {
+                let arg = current.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => arg.push(c),
+                        None => return Err(unterminated("single quote")),
+                    }
+                }
+            }
+            '"' => {
+                let arg = current.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        // 双引号内只有这几个字符可以被转义
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => arg.push(c),
+                            Some(c) => {
+                                arg.push('\\');
+                                arg.push(c);
+                            }
+                            None => return Err(unterminated("double quote")),
+                        },
+                        Some(c) => arg.push(c),
+                        None => return Err(unterminated("double quote")),
+                    }
+                }
+            }
+            '\\' => {
+                let c = chars.next().ok_or_else(|| unterminated("escape"))?;
+                current.get_or_insert_with(String::new).push(c);
+            }
+            c => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    args.extend(current);
+    Ok(args)
+}
+
+/// JSON 值。布尔值和数字只需要被跳过，所以不保留它们的值。
+#[derive(Debug)]
+enum Json {
+    Null,
+    Bool,
+    Number,
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+struct JsonReader<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl JsonReader<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.skip_whitespace();
+        match self.chars.next() {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => Err(format!(
+                "Expected '{}' in JSON, but found '{}'",
+                expected, c
+            )),
+            None => Err(format!(
+                "Expected '{}' in JSON, but found end of input",
+                expected
+            )),
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Json, String> {
+        self.skip_whitespace();
+        match self.chars.peek() {
+            Some('{') => self.parse_object(),
+            Some('[') => self.parse_array(),
+            Some('"') => Ok(Json::String(self.parse_string()?)),
+            Some('t') => self.parse_literal("true", Json::Bool),
+            Some('f') => self.parse_literal("false", Json::Bool),
+            Some('n') => self.parse_literal("null", Json::Null),
+            Some(c) if *c == '-' || c.is_ascii_digit() => {
+                while self
+                    .chars
+                    .next_if(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E'))
+                    .is_some()
+                {}
+                Ok(Json::Number)
+            }
+            Some(c) => Err(format!("Unexpected character '{}' in JSON", c)),
+            None => Err("Unexpected end of JSON input".to_string()),
+        }
+    }
+
+    fn parse_literal(&mut self, word: &str, value: Json) -> Result<Json, String> {
+        for expected in word.chars() {
+            if self.chars.next() != Some(expected) {
+                return Err(format!("Invalid JSON literal, expected '{}'", word));
+            }
+        }
+        Ok(value)
+    }
+
+    fn parse_object(&mut self) -> Result<Json, String> {
+        self.expect('{')?;
+        let mut fields = Vec::new();
+        self.skip_whitespace();
+        if self.chars.next_if_eq(&'}').is_some() {
+            return Ok(Json::Object(fields));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.parse_string()?;
+            self.expect(':')?;
+            fields.push((key, self.parse_value()?));
+            self.skip_whitespace();
+            match self.chars.next() {
+                Some(',') => continue,
+                Some('}') => return Ok(Json::Object(fields)),
+                _ => return Err("Expected ',' or '}' in JSON object".to_string()),
+            }
+        }
+    }
+
+    fn parse_array(&mut self) -> Result<Json, String> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if self.chars.next_if_eq(&']').is_some() {
+            return Ok(Json::Array(items));
+        }
+        loop {
+            items.push(self.parse_value()?);
+            self.skip_whitespace();
+            match self.chars.next() {
+                Some(',') => continue,
+                Some(']') => return Ok(Json::Array(items)),
+                _ => return Err("Expected ',' or ']' in JSON array".to_string()),
+            }
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut s = String::new();
+        loop {
+            match self.chars.next() {
+                Some('"') => return Ok(s),
+                Some('\\') => {
+                    let escaped = match self.chars.next() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => {
+                            let hex: String = self.chars.by_ref().take(4).collect();
+                            u32::from_str_radix(&hex, 16)
+                                .ok()
+                                .and_then(char::from_u32)
+                                .ok_or_else(|| format!("Invalid \\u escape '{}' in JSON", hex))?
+                        }
+                        _ => return Err("Invalid escape sequence in JSON string".to_string()),
+                    };
+                    s.push(escaped);
+                }
+                Some(c) => s.push(c),
+                None => return Err("Unterminated JSON string".to_string()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_entry_ignores_unknown_fields() {
+        let entry = parse_entry(
+            r#"{
+                "directory": "/tmp/build",
+                "arguments": ["cc", "-O1", "-c", "main.c"],
+                "file": "main.c",
+                "output": "main.o",
+                "extra": {"nested": [1, 2.5e3, true, null]}
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            entry,
+            CompileDbEntry {
+                directory: Some("/tmp/build".to_string()),
+                file: "main.c".to_string(),
+                arguments: vec!["cc", "-O1", "-c", "main.c"]
+                    .into_iter()
+                    .map(String::from)
+                    .collect(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_entry_errors() {
+        assert_eq!(
+            parse_entry(r#"{"arguments": ["cc"]}"#).unwrap_err(),
+            "Missing 'file' field"
+        );
+        assert_eq!(
+            parse_entry(r#"{"file": "a.c", "arguments": "cc a.c"}"#).unwrap_err(),
+            "Field 'arguments' has the wrong type"
+        );
+        assert!(parse_entry(r#"{"file": "a.c", "arguments": ["cc""#).is_err());
+        assert_eq!(
+            parse_entry(r#"{"file": "a.c", "arguments": []}"#).unwrap_err(),
+            "The command line must at least name the compiler"
+        );
+        assert_eq!(
+            parse_entry(r#"{"file": "a.c"}"#).unwrap_err(),
+            "Missing 'arguments' or 'command' field"
+        );
+        assert_eq!(
+            parse_entry(r#"{"file": "a.c", "command": "cc 'a.c"}"#).unwrap_err(),
+            "Unterminated single quote in 'command'"
+        );
+    }
+
+    #[test]
+    fn test_command_is_split_like_a_shell() {
+        let entry = parse_entry(
+            r#"{"file": "my file.c", "command": "cc -DNAME=\"a b\" -I 'inc dir'  -c my\\ file.c \"\""}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            entry.arguments,
+            ["cc", "-DNAME=a b", "-I", "inc dir", "-c", "my file.c", ""]
+        );
+    }
+}
@@ -0,0 +1,158 @@
+//! src/diagnostics.rs
+//! 结构化的诊断信息（错误和警告），用于 `--diagnostics=json`。
+//!
+//! 编译器内部的错误仍然是 `String`，位置以 `on line N [of FILE]` 和 `column M`
+//! 的形式写在消息里；这里把它们提取出来，供 IDE 等工具直接使用。
+
+use std::fmt::{self, Write};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub file: Option<String>,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// 从一条编译器消息构造诊断信息。消息中没有给出文件名时使用 `file`，
+    /// 警告消息开头的 `warning: ` 会被去掉，因为严重程度已经单独记录。
+    pub fn from_message(severity: Severity, file: Option<&str>, message: &str) -> Self {
+        let message = message.strip_prefix("warning: ").unwrap_or(message);
+        let mut diagnostic = Diagnostic {
+            severity,
+            file: file.map(String::from),
+            line: number_after(message, "on line "),
+            column: number_after(message, "column "),
+            message: message.to_string(),
+        };
+        // `on line 10 of foo.c`：行号后面跟着由行标记给出的原始文件名
+        if let Some(line) = diagnostic.line {
+            let marker = format!("on line {} of ", line);
+            if let Some(start) = message.find(&marker) {
+                let rest = &message[start + marker.len()..];
+                let end = rest.find([',', ' ']).unwrap_or(rest.len());
+                diagnostic.file = Some(rest[..end].to_string());
+            }
+        }
+        diagnostic
+    }
+}
+
+/// 人类可读的形式：`warning: ...` 或 `error: ...`
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{}: {}", severity, self.message)
+    }
+}
+
+/// 返回 `prefix` 之后紧跟的十进制数（如果有）
+fn number_after(message: &str, prefix: &str) -> Option<usize> {
+    let start = message.find(prefix)? + prefix.len();
+    let digits: String = message[start..]
+        .chars()
+        .take_while(|c| c.is_ascii_digit())
+        .collect();
+    digits.parse().ok()
+}
+
+/// 把诊断信息格式化为一个 JSON 数组，每个元素包含
+/// `severity`、`file`、`line`、`column` 和 `message`，未知的位置为 `null`。
+pub fn to_json(diagnostics: &[Diagnostic]) -> String {
+    let entries: Vec<String> = diagnostics
+        .iter()
+        .map(|d| {
+            let severity = match d.severity {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+            };
+            format!(
+                r#"{{"severity": "{}", "file": {}, "line": {}, "column": {}, "message": {}}}"#,
+                severity,
+                d.file.as_deref().map_or("null".to_string(), json_string),
+                d.line.map_or("null".to_string(), |n| n.to_string()),
+                d.column.map_or("null".to_string(), |n| n.to_string()),
+                json_string(&d.message)
+            )
+        })
+        .collect();
+    format!("[{}]", entries.join(", "))
+}
+
+/// 把字符串编码为带引号的 JSON 字符串
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_location_is_extracted_from_message() {
+        assert_eq!(
+            Diagnostic::from_message(
+                Severity::Error,
+                Some("main.c"),
+                "Expected token Semicolon, but found CloseBrace on line 3, column 14"
+            ),
+            Diagnostic {
+                severity: Severity::Error,
+                file: Some("main.c".to_string()),
+                line: Some(3),
+                column: Some(14),
+                message: "Expected token Semicolon, but found CloseBrace on line 3, column 14"
+                    .to_string(),
+            }
+        );
+        // 行标记给出的文件名优先于调用者提供的文件名
+        let d = Diagnostic::from_message(
+            Severity::Error,
+            Some("main.i"),
+            "Unrecognized character '@' on line 10 of foo.c",
+        );
+        assert_eq!(
+            (d.file.as_deref(), d.line, d.column),
+            (Some("foo.c"), Some(10), None)
+        );
+    }
+
+    #[test]
+    fn test_to_json_escapes_messages() {
+        let warning = Diagnostic::from_message(
+            Severity::Warning,
+            None,
+            "warning: 'x' is used uninitialized in function \"main\"",
+        );
+        assert_eq!(
+            to_json(&[warning]),
+            r#"[{"severity": "warning", "file": null, "line": null, "column": null, "message": "'x' is used uninitialized in function \"main\""}]"#
+        );
+        assert_eq!(to_json(&[]), "[]");
+    }
+}
@@ -0,0 +1,466 @@
+//! src/driver.rs
+//! 编译流水线：从预处理过的源码到汇编代码。
+//!
+//! 命令行驱动（`main.rs`）和其他程序（如 IDE 插件）都通过这里编译。
+//! 这里不调用预处理器和 gcc，也不读写文件：输入是已经预处理过的源码，
+//! 输出是汇编代码。调用者通过 `CompileHooks` 观察编译过程：
+//! 警告和错误一产生就报告，每个阶段完成后都能拿到它的产物，并可以让编译就此停止。
+
+use std::ops::ControlFlow;
+
+use crate::ast::{checked, unchecked};
+use crate::backend::asm_gen::AsmGenerator;
+use crate::backend::emitter::{self, EmitOptions};
+use crate::backend::tacky_gen::TackyGenerator;
+use crate::backend::tacky_opt::Optimizer;
+use crate::common::UniqueIdGenerator;
+use crate::diagnostics::{Diagnostic, Severity};
+use crate::ir::{assembly, tacky};
+use crate::lexer::{Lexer, Token};
+use crate::parser::Parser;
+use crate::semantics::label_resolver::LabelResolver;
+use crate::semantics::lint_checker::LintChecker;
+use crate::semantics::loop_labeler::LoopLabeler;
+use crate::semantics::type_checker::TypeChecker;
+use crate::semantics::uninit_checker::UninitChecker;
+use crate::semantics::validator::Validator;
+
+/// 编译选项，对应命令行中影响编译结果的那部分参数
+#[derive(Debug, Clone)]
+pub struct CompileOptions {
+    /// 优化级别，`1` 时运行 TACKY 优化和寄存器分配
+    pub opt_level: u8,
+    /// 是否启用 `-Wuninitialized`
+    pub warn_uninitialized: bool,
+    /// 是否启用 `-Wshadow`
+    pub warn_shadow: bool,
+    /// 是否运行 `--analyze` 的额外检查（未使用的变量、不可达代码等）
+    pub lint: bool,
+    /// 是否把警告当作错误：语义分析产生了警告时编译失败
+    pub werror: bool,
+    /// 是否识别内建函数（对应 `--no-builtins` 取反）
+    pub builtins: bool,
+    /// `main` 缺少 return 时是否隐式返回 0（对应 `--no-implicit-return` 取反）
+    pub implicit_return: bool,
+    /// 是否记录每个表达式的类型，供 `TypeChecker::dump_types` 使用
+    pub record_types: bool,
+    /// 栈帧大小的上限（字节），超出时编译失败
+    pub max_stack: Option<u32>,
+    /// `opt_level` 为 1 时对 TACKY 运行的优化流水线，可以注册自定义的 pass
+    pub optimizer: Optimizer,
+    pub emit: EmitOptions,
+}
+
+impl Default for CompileOptions {
+    fn default() -> Self {
+        CompileOptions {
+            opt_level: 0,
+            warn_uninitialized: false,
+            warn_shadow: false,
+            lint: false,
+            werror: false,
+            builtins: true,
+            implicit_return: true,
+            record_types: false,
+            max_stack: None,
+            optimizer: Optimizer::with_default_passes(),
+            emit: EmitOptions::default(),
+        }
+    }
+}
+
+/// 编译流程中的各个阶段，按执行顺序排列
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Lex,
+    Parse,
+    /// 标识符解析、goto 标签解析、类型检查、循环标注和警告检查
+    Semantics,
+    Tacky,
+    Codegen,
+    Emit,
+}
+
+/// 一个步骤完成后交给 `CompileHooks::stage_finished` 的产物
+pub enum StageOutput<'a> {
+    Tokens(&'a [Token]),
+    Ast(&'a unchecked::Program),
+    /// 标识符解析完成
+    NamesResolved,
+    /// goto 标签解析完成
+    LabelsResolved,
+    /// 类型检查完成，符号表和记录的类型都在检查器中
+    TypesChecked(&'a TypeChecker),
+    /// 循环标注完成
+    LoopsLabeled,
+    /// 警告检查完成，语义分析结束
+    Analyzed(&'a checked::Program),
+    /// TACKY 生成（以及 `-O1` 下的优化）完成
+    Tacky(&'a tacky::Program),
+    /// 汇编 AST 生成完成，附带每个函数的栈帧大小
+    Assembly {
+        program: &'a assembly::Program,
+        stack_usage: &'a [(String, u32)],
+    },
+}
+
+/// 观察编译过程的回调
+pub trait CompileHooks {
+    /// 每条警告和错误产生时立即调用
+    fn diagnostic(&mut self, diagnostic: Diagnostic);
+
+    /// 一个阶段开始之前调用
+    fn stage_started(&mut self, _stage: Stage) {}
+
+    /// 一个步骤成功完成后调用。返回 `ControlFlow::Break(())` 时编译在这里停止
+    fn stage_finished(&mut self, _output: StageOutput<'_>) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+}
+
+/// 只关心诊断信息的调用者可以直接传入一个闭包
+impl<F: FnMut(Diagnostic)> CompileHooks for F {
+    fn diagnostic(&mut self, diagnostic: Diagnostic) {
+        self(diagnostic)
+    }
+}
+
+/// 编译失败。具体的错误已经通过 `CompileHooks::diagnostic` 报告过了，
+/// 这里只记录失败的阶段和一条汇总信息。
+#[derive(Debug)]
+pub struct CompileError {
+    pub stage: Stage,
+    pub summary: String,
+}
+
+impl std::fmt::Display for CompileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.summary)
+    }
+}
+
+impl std::error::Error for CompileError {}
+
+/// 把一段预处理过的 C 源码编译为汇编代码。
+///
+/// 每条警告和错误产生时都会立即传给 `on_diagnostic`；编译失败时返回 `None`。
+pub fn compile_source(
+    source: &str,
+    options: &CompileOptions,
+    mut on_diagnostic: impl FnMut(Diagnostic),
+) -> Option<String> {
+    compile(source, options, &mut on_diagnostic).ok().flatten()
+}
+
+/// 运行完整的编译流水线。某个 hook 要求停止时返回 `Ok(None)`。
+pub fn compile(
+    source: &str,
+    options: &CompileOptions,
+    hooks: &mut dyn CompileHooks,
+) -> Result<Option<String>, CompileError> {
+    let mut reporter = Reporter {
+        hooks,
+        warning_count: 0,
+    };
+    match run(source, options, &mut reporter)? {
+        ControlFlow::Continue(assembly) => Ok(Some(assembly)),
+        ControlFlow::Break(()) => Ok(None),
+    }
+}
+
+/// 把诊断信息转交给 hooks，同时统计警告数（`werror` 需要）
+struct Reporter<'h> {
+    hooks: &'h mut dyn CompileHooks,
+    warning_count: usize,
+}
+
+impl Reporter<'_> {
+    fn warning(&mut self, message: &str) {
+        self.warning_count += 1;
+        self.hooks
+            .diagnostic(Diagnostic::from_message(Severity::Warning, None, message));
+    }
+
+    /// 报告一个阶段发现的所有错误，返回汇总的 `CompileError`
+    fn errors(&mut self, stage: Stage, errors: Vec<String>) -> CompileError {
+        let count = errors.len();
+        for e in errors {
+            self.hooks
+                .diagnostic(Diagnostic::from_message(Severity::Error, None, &e));
+        }
+        CompileError {
+            stage,
+            summary: format!(
+                "{} error{} generated",
+                count,
+                if count == 1 { "" } else { "s" }
+            ),
+        }
+    }
+
+    fn error(&mut self, stage: Stage, error: String) -> CompileError {
+        self.errors(stage, vec![error])
+    }
+}
+
+/// 在 hook 要求停止时从 `run` 中返回
+macro_rules! finish {
+    ($reporter:expr, $output:expr) => {
+        if $reporter.hooks.stage_finished($output).is_break() {
+            return Ok(ControlFlow::Break(()));
+        }
+    };
+}
+
+fn run(
+    source: &str,
+    options: &CompileOptions,
+    reporter: &mut Reporter,
+) -> Result<ControlFlow<(), String>, CompileError> {
+    let mut id_generator = UniqueIdGenerator::new();
+
+    // 词法、语法分析和后端遇到第一个错误就停止；语义分析会报告所有错误
+    reporter.hooks.stage_started(Stage::Lex);
+    let tokens: Vec<Token> = Lexer::new(source)
+        .collect::<Result<_, _>>()
+        .map_err(|e| reporter.error(Stage::Lex, e))?;
+    finish!(reporter, StageOutput::Tokens(&tokens));
+
+    reporter.hooks.stage_started(Stage::Parse);
+    let c_ast = Parser::new(&tokens)
+        .parse()
+        .map_err(|e| reporter.error(Stage::Parse, e))?;
+    finish!(reporter, StageOutput::Ast(&c_ast));
+
+    reporter.hooks.stage_started(Stage::Semantics);
+    let mut on_warning = |warning: String| reporter.warning(&warning);
+    let mut validator = Validator::new(&mut id_generator);
+    validator.builtins = options.builtins;
+    validator.warn_shadow = options.warn_shadow;
+    validator.on_warning = Some(&mut on_warning);
+    let c_ast = validator.validate_program(c_ast);
+    let c_ast = c_ast.map_err(|errors| reporter.errors(Stage::Semantics, errors))?;
+    finish!(reporter, StageOutput::NamesResolved);
+
+    let c_ast = LabelResolver::new(&mut id_generator)
+        .resolve_program(c_ast)
+        .map_err(|errors| reporter.errors(Stage::Semantics, errors))?;
+    finish!(reporter, StageOutput::LabelsResolved);
+
+    let mut type_checker = TypeChecker::new();
+    type_checker.record_types = options.record_types;
+    // check_program 返回一个新的 AST，其中的隐式类型转换都被显式地表示为 Cast
+    let typed_ast = type_checker
+        .check_program(c_ast)
+        .map_err(|errors| reporter.errors(Stage::Semantics, errors))?;
+    finish!(reporter, StageOutput::TypesChecked(&type_checker));
+
+    let checked_ast = LoopLabeler::new(&mut id_generator)
+        .label_program(typed_ast)
+        .map_err(|errors| reporter.errors(Stage::Semantics, errors))?;
+    finish!(reporter, StageOutput::LoopsLabeled);
+
+    let mut on_warning = |warning: String| reporter.warning(&warning);
+    if options.warn_uninitialized {
+        UninitChecker::new(&mut on_warning).check_program(&checked_ast);
+    }
+    if options.lint {
+        LintChecker::new(&mut on_warning).check_program(&checked_ast);
+    }
+    if options.werror && reporter.warning_count > 0 {
+        return Err(CompileError {
+            stage: Stage::Semantics,
+            summary: format!(
+                "{} warning(s) treated as errors (--werror)",
+                reporter.warning_count
+            ),
+        });
+    }
+    finish!(reporter, StageOutput::Analyzed(&checked_ast));
+
+    reporter.hooks.stage_started(Stage::Tacky);
+    let mut tacky_generator = TackyGenerator::new(
+        &mut id_generator,
+        &mut type_checker.symbols,
+        &type_checker.struct_types,
+    );
+    tacky_generator.implicit_return = options.implicit_return;
+    let mut tacky_ir = tacky_generator
+        .generate_tacky(checked_ast)
+        .map_err(|e| reporter.error(Stage::Tacky, e))?;
+    if options.opt_level >= 1 {
+        options.optimizer.run(&mut tacky_ir);
+    }
+    finish!(reporter, StageOutput::Tacky(&tacky_ir));
+
+    reporter.hooks.stage_started(Stage::Codegen);
+    let mut asm_generator = AsmGenerator::new(
+        &type_checker.symbols,
+        &type_checker.struct_types,
+        options.opt_level,
+    );
+    let asm_ast = asm_generator
+        .generate_assembly(tacky_ir)
+        .map_err(|e| reporter.error(Stage::Codegen, e))?;
+    if let Some(max_stack) = options.max_stack {
+        for (name, bytes) in &asm_generator.stack_usage {
+            if *bytes > max_stack {
+                return Err(reporter.error(
+                    Stage::Codegen,
+                    format!(
+                        "Function '{}' needs a {}-byte stack frame, which exceeds --max-stack={}",
+                        name, bytes, max_stack
+                    ),
+                ));
+            }
+        }
+    }
+    finish!(
+        reporter,
+        StageOutput::Assembly {
+            program: &asm_ast,
+            stack_usage: &asm_generator.stack_usage,
+        }
+    );
+
+    reporter.hooks.stage_started(Stage::Emit);
+    let assembly = emitter::emit_assembly(asm_ast, &options.emit)
+        .map_err(|e| reporter.error(Stage::Emit, e.to_string()))?;
+    Ok(ControlFlow::Continue(assembly))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diagnostics_are_reported_in_order() {
+        let source = "int main(void) {\n    int a;\n    int b;\n    return a + b;\n}\n";
+        let options = CompileOptions {
+            warn_uninitialized: true,
+            ..CompileOptions::default()
+        };
+        let mut seen = Vec::new();
+        let assembly = compile_source(source, &options, |d| seen.push(d));
+        assert!(assembly.is_some());
+        assert_eq!(seen.len(), 2, "{:?}", seen);
+        assert!(seen.iter().all(|d| d.severity == Severity::Warning));
+        assert!(seen[0].message.contains("'a'"), "{:?}", seen);
+        assert!(seen[1].message.contains("'b'"), "{:?}", seen);
+    }
+
+    #[test]
+    fn test_error_is_reported_through_callback() {
+        let mut seen = Vec::new();
+        let assembly = compile_source(
+            "int main(void) { return x; }",
+            &CompileOptions::default(),
+            |d| seen.push(d),
+        );
+        assert!(assembly.is_none());
+        assert_eq!(seen.len(), 1);
+        assert_eq!(seen[0].severity, Severity::Error);
+
+        // 两个无效的 goto 都会被报告
+        let mut seen = Vec::new();
+        let error = compile(
+            "int main(void) { goto a; goto b; return 0; }",
+            &CompileOptions::default(),
+            &mut |d| seen.push(d),
+        )
+        .unwrap_err();
+        assert_eq!(error.stage, Stage::Semantics);
+        assert_eq!(error.summary, "2 errors generated");
+        let messages: Vec<&str> = seen.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "Undefined label 'a' in function 'main'",
+                "Undefined label 'b' in function 'main'"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_custom_passes_run_through_compile_options() {
+        // 把每个 return 的值都改成 42
+        fn return_42(func: &mut tacky::Function) {
+            for inst in &mut func.body {
+                if let tacky::Instruction::Return(val) = inst {
+                    *val = tacky::Val::Constant(42);
+                }
+            }
+        }
+        let mut options = CompileOptions {
+            opt_level: 1,
+            optimizer: Optimizer::new(),
+            ..CompileOptions::default()
+        };
+        options.optimizer.register(return_42);
+        let assembly = compile_source("int main(void) { return 1; }", &options, |_| {}).unwrap();
+        assert!(assembly.contains("$42"), "{}", assembly);
+        assert!(!assembly.contains("$1,"), "{}", assembly);
+    }
+
+    /// 记录 hook 被调用的顺序；`stop_after_types` 时在类型检查之后停止
+    struct Recorder {
+        events: Vec<String>,
+        stop_after_types: bool,
+    }
+
+    impl CompileHooks for Recorder {
+        fn diagnostic(&mut self, diagnostic: Diagnostic) {
+            self.events.push(diagnostic.to_string());
+        }
+
+        fn stage_started(&mut self, stage: Stage) {
+            self.events.push(format!("start {:?}", stage));
+        }
+
+        fn stage_finished(&mut self, output: StageOutput<'_>) -> ControlFlow<()> {
+            match output {
+                StageOutput::NamesResolved => self.events.push("names resolved".to_string()),
+                StageOutput::TypesChecked(_) if self.stop_after_types => {
+                    return ControlFlow::Break(());
+                }
+                _ => {}
+            }
+            ControlFlow::Continue(())
+        }
+    }
+
+    #[test]
+    fn test_warnings_stream_while_passes_run_and_hooks_can_stop() {
+        let options = CompileOptions {
+            warn_shadow: true,
+            werror: true,
+            ..CompileOptions::default()
+        };
+        let source = "int main(void) { int x = 1; { int x = 2; } return x; }";
+        let mut recorder = Recorder {
+            events: Vec::new(),
+            stop_after_types: false,
+        };
+        let error = compile(source, &options, &mut recorder).unwrap_err();
+        // 遮蔽警告在标识符解析结束之前就已经报告；--werror 在语义分析结束时生效
+        assert_eq!(
+            recorder.events,
+            [
+                "start Lex",
+                "start Parse",
+                "start Semantics",
+                "warning: declaration of 'x' shadows a previous local",
+                "names resolved",
+            ]
+        );
+        assert_eq!(error.stage, Stage::Semantics);
+
+        let mut recorder = Recorder {
+            events: Vec::new(),
+            stop_after_types: true,
+        };
+        let result = compile(source, &CompileOptions::default(), &mut recorder);
+        assert!(matches!(result, Ok(None)));
+        assert!(!recorder.events.iter().any(|e| e == "start Tacky"));
+    }
+}
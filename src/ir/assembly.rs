@@ -1,5 +1,8 @@
 // src/ir/assembly.rs
 
+use std::fmt;
+use std::str::FromStr;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Register {
     AX,
@@ -11,6 +14,20 @@ pub enum Register {
     R9,
     R10,
     R11,
+    // 被调用者保存的寄存器，只由寄存器分配器使用
+    BX,
+    R12,
+    R13,
+    R14,
+    R15,
+}
+
+/// 操作数的大小：Byte 为 1 字节，Longword 为 4 字节，Quadword 为 8 字节
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AsmType {
+    Byte,
+    Longword,
+    Quadword,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -24,6 +41,13 @@ pub enum BinaryOperator {
     Add,
     Subtract,
     Multiply,
+    And,
+    Or,
+    Xor,
+    // 移位次数必须是立即数或 %cl
+    Sal,
+    Sar,
+    Shr,
 }
 
 // 【新增】条件码，用于 JmpCC 和 SetCC
@@ -35,60 +59,642 @@ pub enum CondCode {
     GE, // Greater or Equal
     L,  // Less
     LE, // Less or Equal
+    // 无符号比较
+    A,  // Above
+    AE, // Above or Equal
+    B,  // Below
+    BE, // Below or Equal
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Operand {
-    Imm(i32),
+    Imm(i64),
     Reg(Register),
     Pseudo(String),
+    /// 聚合对象（结构体变量）中偏移为给定字节数的位置，在 Pass 2 中被替换为栈地址
+    PseudoMem(String, i32),
     Stack(i32),
+    /// 静态变量中偏移为给定字节数的位置，通过 RIP 相对寻址访问
+    Data(String, i32),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Instruction {
     Mov {
+        asm_type: AsmType,
+        src: Operand,
+        dst: Operand,
+    },
+    /// 将较小的源操作数符号扩展为较大的目标操作数
+    Movsx {
+        src_type: AsmType,
+        dst_type: AsmType,
+        src: Operand,
+        dst: Operand,
+    },
+    /// 将 1 字节的源操作数零扩展为较大的目标操作数
+    MovZeroExtend {
+        src_type: AsmType,
+        dst_type: AsmType,
         src: Operand,
         dst: Operand,
     },
     Unary {
         op: UnaryOperator,
+        asm_type: AsmType,
         operand: Operand,
     },
     Binary {
         op: BinaryOperator,
+        asm_type: AsmType,
         src: Operand,
         dst: Operand,
     },
     // 【新增】比较指令
     Cmp {
+        asm_type: AsmType,
         src1: Operand,
         src2: Operand,
     },
     // 【新增】IDIV 指令现在是独立的
-    Idiv(Operand),
-    Cdq,
+    Idiv(AsmType, Operand),
+    /// 无符号除法，被除数的高位（%edx/%rdx）需要事先清零
+    Div(AsmType, Operand),
+    Cdq(AsmType),
     Ret,
     // 【新增】跳转和标签指令
     Jmp(String),              // 无条件跳转
     JmpCC(CondCode, String),  // 条件跳转
     SetCC(CondCode, Operand), // 条件置位
-    Label(String),            // 标签定义
+    /// 条件传送：条件成立时将 src 复制到 dst（dst 必须是寄存器）
+    CMovCC {
+        cond: CondCode,
+        asm_type: AsmType,
+        src: Operand,
+        dst: Operand,
+    },
+    Label(String), // 标签定义
     AllocateStack {
         bytes: u32,
     }, // 这个从PASS 3移动到这里更合适
     DeallocateStack(u32),
     Push(Operand),
+    /// 在返回前恢复被调用者保存的寄存器
+    Pop(Register),
     Call(String),
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct Function {
     pub name: String,
     pub instructions: Vec<Instruction>,
 }
 
-#[derive(Debug)]
+/// 具有静态存储期的变量，由发射器放入 .data 或 .bss 段
+#[derive(Debug, Clone, PartialEq)]
+pub struct StaticVariable {
+    pub name: String,
+    pub size: u32,
+    pub alignment: u32,
+    pub init: i64,
+    /// 全局变量需要 `.globl`，静态局部变量不需要
+    pub global: bool,
+}
+
+#[derive(Debug, PartialEq)]
 pub struct Program {
     pub functions: Vec<Function>,
+    pub static_variables: Vec<StaticVariable>,
+}
+
+// --- 文本形式 ---
+// 汇编 AST 可以打印为一种稳定的文本 IR，并能原样解析回来，
+// 这样就可以直接用手写的汇编 IR 测试后端的各个 pass。
+//
+// 每个函数以 `function <name>` 开头，之后每行一条指令，例如：
+//     mov l $5 @x.0
+//     binary add q %r10 [-8]
+// 操作数：`$5` 立即数，`%ax` 寄存器，`@x.0` 伪寄存器，`@p.1[4]` 结构体成员，`[-8]` 栈地址，
+// `n.2(%rip)` 和 `p.3+4(%rip)` 静态变量。
+// 静态变量单独占一行：`static <name> <size> <alignment> <init>`。
+
+impl Register {
+    const ALL: [Register; 14] = [
+        Register::AX,
+        Register::CX,
+        Register::DX,
+        Register::DI,
+        Register::SI,
+        Register::R8,
+        Register::R9,
+        Register::R10,
+        Register::R11,
+        Register::BX,
+        Register::R12,
+        Register::R13,
+        Register::R14,
+        Register::R15,
+    ];
+}
+
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Register::AX => "ax",
+            Register::CX => "cx",
+            Register::DX => "dx",
+            Register::DI => "di",
+            Register::SI => "si",
+            Register::R8 => "r8",
+            Register::R9 => "r9",
+            Register::R10 => "r10",
+            Register::R11 => "r11",
+            Register::BX => "bx",
+            Register::R12 => "r12",
+            Register::R13 => "r13",
+            Register::R14 => "r14",
+            Register::R15 => "r15",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl fmt::Display for AsmType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AsmType::Byte => write!(f, "b"),
+            AsmType::Longword => write!(f, "l"),
+            AsmType::Quadword => write!(f, "q"),
+        }
+    }
+}
+
+impl fmt::Display for UnaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnaryOperator::Neg => write!(f, "neg"),
+            UnaryOperator::Not => write!(f, "not"),
+        }
+    }
+}
+
+impl fmt::Display for BinaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            BinaryOperator::Add => "add",
+            BinaryOperator::Subtract => "sub",
+            BinaryOperator::Multiply => "mul",
+            BinaryOperator::And => "and",
+            BinaryOperator::Or => "or",
+            BinaryOperator::Xor => "xor",
+            BinaryOperator::Sal => "sal",
+            BinaryOperator::Sar => "sar",
+            BinaryOperator::Shr => "shr",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl fmt::Display for CondCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            CondCode::E => "e",
+            CondCode::NE => "ne",
+            CondCode::G => "g",
+            CondCode::GE => "ge",
+            CondCode::L => "l",
+            CondCode::LE => "le",
+            CondCode::A => "a",
+            CondCode::AE => "ae",
+            CondCode::B => "b",
+            CondCode::BE => "be",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operand::Imm(value) => write!(f, "${}", value),
+            Operand::Reg(reg) => write!(f, "%{}", reg),
+            Operand::Pseudo(name) => write!(f, "@{}", name),
+            Operand::PseudoMem(name, offset) => write!(f, "@{}[{}]", name, offset),
+            Operand::Stack(offset) => write!(f, "[{}]", offset),
+            Operand::Data(name, 0) => write!(f, "{}(%rip)", name),
+            Operand::Data(name, offset) => write!(f, "{}+{}(%rip)", name, offset),
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Instruction::Mov { asm_type, src, dst } => {
+                write!(f, "mov {} {} {}", asm_type, src, dst)
+            }
+            Instruction::Movsx {
+                src_type,
+                dst_type,
+                src,
+                dst,
+            } => write!(f, "movsx {} {} {} {}", src_type, dst_type, src, dst),
+            Instruction::MovZeroExtend {
+                src_type,
+                dst_type,
+                src,
+                dst,
+            } => write!(f, "movzx {} {} {} {}", src_type, dst_type, src, dst),
+            Instruction::Unary {
+                op,
+                asm_type,
+                operand,
+            } => write!(f, "unary {} {} {}", op, asm_type, operand),
+            Instruction::Binary {
+                op,
+                asm_type,
+                src,
+                dst,
+            } => write!(f, "binary {} {} {} {}", op, asm_type, src, dst),
+            Instruction::Cmp {
+                asm_type,
+                src1,
+                src2,
+            } => write!(f, "cmp {} {} {}", asm_type, src1, src2),
+            Instruction::Idiv(asm_type, operand) => write!(f, "idiv {} {}", asm_type, operand),
+            Instruction::Div(asm_type, operand) => write!(f, "div {} {}", asm_type, operand),
+            Instruction::Cdq(asm_type) => write!(f, "cdq {}", asm_type),
+            Instruction::Ret => write!(f, "ret"),
+            Instruction::Jmp(label) => write!(f, "jmp {}", label),
+            Instruction::JmpCC(cond, label) => write!(f, "jmpcc {} {}", cond, label),
+            Instruction::SetCC(cond, operand) => write!(f, "setcc {} {}", cond, operand),
+            Instruction::CMovCC {
+                cond,
+                asm_type,
+                src,
+                dst,
+            } => write!(f, "cmovcc {} {} {} {}", cond, asm_type, src, dst),
+            Instruction::Label(label) => write!(f, "label {}", label),
+            Instruction::AllocateStack { bytes } => write!(f, "allocate_stack {}", bytes),
+            Instruction::DeallocateStack(bytes) => write!(f, "deallocate_stack {}", bytes),
+            Instruction::Push(operand) => write!(f, "push {}", operand),
+            Instruction::Pop(reg) => write!(f, "pop %{}", reg),
+            Instruction::Call(name) => write!(f, "call {}", name),
+        }
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "function {}", self.name)?;
+        for instruction in &self.instructions {
+            writeln!(f, "    {}", instruction)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for StaticVariable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {} {}",
+            if self.global { "global" } else { "static" },
+            self.name,
+            self.size,
+            self.alignment,
+            self.init
+        )
+    }
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for function in &self.functions {
+            write!(f, "{}", function)?;
+        }
+        for var in &self.static_variables {
+            writeln!(f, "{}", var)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Program {
+    type Err = String;
+
+    /// 解析由 `Display` 打印出的文本形式
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut functions: Vec<Function> = Vec::new();
+        let mut static_variables = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            let result = match tokens.as_slice() {
+                [] => Ok(()),
+                [kind @ ("static" | "global"), name, size, alignment, init] => {
+                    parse_static_variable(name, size, alignment, init, *kind == "global")
+                        .map(|var| static_variables.push(var))
+                }
+                ["function", name] => {
+                    functions.push(Function {
+                        name: name.to_string(),
+                        instructions: Vec::new(),
+                    });
+                    Ok(())
+                }
+                _ => match functions.last_mut() {
+                    Some(function) => parse_instruction(&tokens)
+                        .map(|instruction| function.instructions.push(instruction)),
+                    None => Err("instruction outside of a function".to_string()),
+                },
+            };
+            result.map_err(|e| format!("{} on line {}", e, index + 1))?;
+        }
+        Ok(Program {
+            functions,
+            static_variables,
+        })
+    }
+}
+
+fn parse_instruction(tokens: &[&str]) -> Result<Instruction, String> {
+    Ok(match tokens {
+        ["mov", t, src, dst] => Instruction::Mov {
+            asm_type: parse_asm_type(t)?,
+            src: parse_operand(src)?,
+            dst: parse_operand(dst)?,
+        },
+        ["movsx", src_type, dst_type, src, dst] => Instruction::Movsx {
+            src_type: parse_asm_type(src_type)?,
+            dst_type: parse_asm_type(dst_type)?,
+            src: parse_operand(src)?,
+            dst: parse_operand(dst)?,
+        },
+        ["movzx", src_type, dst_type, src, dst] => Instruction::MovZeroExtend {
+            src_type: parse_asm_type(src_type)?,
+            dst_type: parse_asm_type(dst_type)?,
+            src: parse_operand(src)?,
+            dst: parse_operand(dst)?,
+        },
+        ["unary", op, t, operand] => Instruction::Unary {
+            op: parse_keyword(&[UnaryOperator::Neg, UnaryOperator::Not], op)?,
+            asm_type: parse_asm_type(t)?,
+            operand: parse_operand(operand)?,
+        },
+        ["binary", op, t, src, dst] => Instruction::Binary {
+            op: parse_keyword(
+                &[
+                    BinaryOperator::Add,
+                    BinaryOperator::Subtract,
+                    BinaryOperator::Multiply,
+                    BinaryOperator::And,
+                    BinaryOperator::Or,
+                    BinaryOperator::Xor,
+                    BinaryOperator::Sal,
+                    BinaryOperator::Sar,
+                    BinaryOperator::Shr,
+                ],
+                op,
+            )?,
+            asm_type: parse_asm_type(t)?,
+            src: parse_operand(src)?,
+            dst: parse_operand(dst)?,
+        },
+        ["cmp", t, src1, src2] => Instruction::Cmp {
+            asm_type: parse_asm_type(t)?,
+            src1: parse_operand(src1)?,
+            src2: parse_operand(src2)?,
+        },
+        ["idiv", t, operand] => Instruction::Idiv(parse_asm_type(t)?, parse_operand(operand)?),
+        ["div", t, operand] => Instruction::Div(parse_asm_type(t)?, parse_operand(operand)?),
+        ["cdq", t] => Instruction::Cdq(parse_asm_type(t)?),
+        ["ret"] => Instruction::Ret,
+        ["jmp", label] => Instruction::Jmp(label.to_string()),
+        ["jmpcc", cond, label] => Instruction::JmpCC(parse_cond_code(cond)?, label.to_string()),
+        ["setcc", cond, operand] => {
+            Instruction::SetCC(parse_cond_code(cond)?, parse_operand(operand)?)
+        }
+        ["cmovcc", cond, t, src, dst] => Instruction::CMovCC {
+            cond: parse_cond_code(cond)?,
+            asm_type: parse_asm_type(t)?,
+            src: parse_operand(src)?,
+            dst: parse_operand(dst)?,
+        },
+        ["label", label] => Instruction::Label(label.to_string()),
+        ["allocate_stack", bytes] => Instruction::AllocateStack {
+            bytes: parse_number(bytes)?,
+        },
+        ["deallocate_stack", bytes] => Instruction::DeallocateStack(parse_number(bytes)?),
+        ["push", operand] => Instruction::Push(parse_operand(operand)?),
+        ["pop", operand] => match parse_operand(operand)? {
+            Operand::Reg(reg) => Instruction::Pop(reg),
+            _ => return Err(format!("Invalid pop operand '{}'", operand)),
+        },
+        ["call", name] => Instruction::Call(name.to_string()),
+        _ => return Err(format!("Invalid instruction '{}'", tokens.join(" "))),
+    })
+}
+
+fn parse_operand(token: &str) -> Result<Operand, String> {
+    if let Some(value) = token.strip_prefix('$') {
+        Ok(Operand::Imm(parse_number(value)?))
+    } else if let Some(reg) = token.strip_prefix('%') {
+        Ok(Operand::Reg(parse_keyword(&Register::ALL, reg)?))
+    } else if let Some(name) = token.strip_prefix('@') {
+        match name.strip_suffix(']').and_then(|n| n.split_once('[')) {
+            Some((name, offset)) => Ok(Operand::PseudoMem(name.to_string(), parse_number(offset)?)),
+            None => Ok(Operand::Pseudo(name.to_string())),
+        }
+    } else if let Some(offset) = token.strip_prefix('[').and_then(|t| t.strip_suffix(']')) {
+        Ok(Operand::Stack(parse_number(offset)?))
+    } else if let Some(location) = token.strip_suffix("(%rip)") {
+        match location.split_once('+') {
+            Some((name, offset)) => Ok(Operand::Data(name.to_string(), parse_number(offset)?)),
+            None => Ok(Operand::Data(location.to_string(), 0)),
+        }
+    } else {
+        Err(format!("Invalid operand '{}'", token))
+    }
+}
+
+fn parse_asm_type(token: &str) -> Result<AsmType, String> {
+    parse_keyword(
+        &[AsmType::Byte, AsmType::Longword, AsmType::Quadword],
+        token,
+    )
+}
+
+fn parse_cond_code(token: &str) -> Result<CondCode, String> {
+    parse_keyword(
+        &[
+            CondCode::E,
+            CondCode::NE,
+            CondCode::G,
+            CondCode::GE,
+            CondCode::L,
+            CondCode::LE,
+            CondCode::A,
+            CondCode::AE,
+            CondCode::B,
+            CondCode::BE,
+        ],
+        token,
+    )
+}
+
+/// 在候选值中找到打印形式与 token 相同的那一个
+fn parse_keyword<T: fmt::Display + Copy>(candidates: &[T], token: &str) -> Result<T, String> {
+    candidates
+        .iter()
+        .copied()
+        .find(|candidate| candidate.to_string() == token)
+        .ok_or_else(|| format!("Unknown keyword '{}'", token))
+}
+
+fn parse_static_variable(
+    name: &str,
+    size: &str,
+    alignment: &str,
+    init: &str,
+    global: bool,
+) -> Result<StaticVariable, String> {
+    Ok(StaticVariable {
+        name: name.to_string(),
+        size: parse_number(size)?,
+        alignment: parse_number(alignment)?,
+        init: parse_number(init)?,
+        global,
+    })
+}
+
+fn parse_number<T: FromStr>(token: &str) -> Result<T, String> {
+    token
+        .parse()
+        .map_err(|_| format!("Invalid number '{}'", token))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_round_trip() {
+        let function = Function {
+            name: "f".to_string(),
+            instructions: vec![
+                Instruction::AllocateStack { bytes: 16 },
+                Instruction::Mov {
+                    asm_type: AsmType::Longword,
+                    src: Operand::Imm(-5),
+                    dst: Operand::Pseudo("x.0".to_string()),
+                },
+                Instruction::Mov {
+                    asm_type: AsmType::Longword,
+                    src: Operand::Reg(Register::DI),
+                    dst: Operand::PseudoMem("p.1".to_string(), 4),
+                },
+                Instruction::Movsx {
+                    src_type: AsmType::Longword,
+                    dst_type: AsmType::Quadword,
+                    src: Operand::Stack(-4),
+                    dst: Operand::Reg(Register::R11),
+                },
+                Instruction::MovZeroExtend {
+                    src_type: AsmType::Byte,
+                    dst_type: AsmType::Longword,
+                    src: Operand::Stack(-5),
+                    dst: Operand::Reg(Register::AX),
+                },
+                Instruction::Unary {
+                    op: UnaryOperator::Neg,
+                    asm_type: AsmType::Quadword,
+                    operand: Operand::Stack(-16),
+                },
+                Instruction::Binary {
+                    op: BinaryOperator::Sal,
+                    asm_type: AsmType::Longword,
+                    src: Operand::Reg(Register::CX),
+                    dst: Operand::Stack(-8),
+                },
+                Instruction::Cmp {
+                    asm_type: AsmType::Quadword,
+                    src1: Operand::Imm(3000000000),
+                    src2: Operand::Reg(Register::R10),
+                },
+                Instruction::Cdq(AsmType::Longword),
+                Instruction::Idiv(AsmType::Longword, Operand::Stack(-4)),
+                Instruction::Div(AsmType::Quadword, Operand::Reg(Register::R10)),
+                Instruction::JmpCC(CondCode::NE, ".Lelse.3".to_string()),
+                Instruction::SetCC(CondCode::AE, Operand::Reg(Register::AX)),
+                Instruction::SetCC(CondCode::LE, Operand::Reg(Register::AX)),
+                Instruction::CMovCC {
+                    cond: CondCode::G,
+                    asm_type: AsmType::Longword,
+                    src: Operand::Stack(16),
+                    dst: Operand::Reg(Register::AX),
+                },
+                Instruction::Label(".Lelse.3".to_string()),
+                Instruction::Push(Operand::Imm(7)),
+                Instruction::Pop(Register::R12),
+                Instruction::Mov {
+                    asm_type: AsmType::Longword,
+                    src: Operand::Data("n.2".to_string(), 0),
+                    dst: Operand::Data("p.3".to_string(), 4),
+                },
+                Instruction::Call("putchar".to_string()),
+                Instruction::DeallocateStack(16),
+                Instruction::Jmp(".Lend".to_string()),
+                Instruction::Ret,
+            ],
+        };
+        let program = Program {
+            functions: vec![
+                function,
+                Function {
+                    name: "main".to_string(),
+                    instructions: vec![Instruction::Ret],
+                },
+            ],
+            static_variables: vec![
+                StaticVariable {
+                    name: "n.2".to_string(),
+                    size: 8,
+                    alignment: 8,
+                    init: -1,
+                    global: false,
+                },
+                StaticVariable {
+                    name: "g".to_string(),
+                    size: 4,
+                    alignment: 4,
+                    init: 7,
+                    global: true,
+                },
+            ],
+        };
+
+        let text = program.to_string();
+        assert!(text.contains("    mov l $-5 @x.0\n"), "{}", text);
+        assert!(text.contains("    movzx b l [-5] %ax\n"), "{}", text);
+        assert!(
+            text.contains("    mov l n.2(%rip) p.3+4(%rip)\n"),
+            "{}",
+            text
+        );
+        assert!(text.contains("static n.2 8 8 -1\n"), "{}", text);
+        assert!(text.contains("global g 4 4 7\n"), "{}", text);
+        assert_eq!(text.parse::<Program>(), Ok(program));
+    }
+
+    #[test]
+    fn test_text_parse_errors() {
+        assert_eq!(
+            "ret".parse::<Program>(),
+            Err("instruction outside of a function on line 1".to_string())
+        );
+        assert_eq!(
+            "function f\n    mov l $1 %bp".parse::<Program>(),
+            Err("Unknown keyword 'bp' on line 2".to_string())
+        );
+    }
 }
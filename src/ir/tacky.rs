@@ -2,6 +2,9 @@
 
 //! 定义 TACKY (Three-Address Code, kind of) 中间表示的数据结构。
 
+use std::collections::HashSet;
+use std::fmt;
+
 #[derive(Debug, Clone, Copy)] // Copy is possible since enums are simple
 pub enum UnaryOperator {
     Complement, // ~ (ASDL: Complement)
@@ -16,22 +19,39 @@ pub enum BinaryOperator {
     Multiply,
     Divide,
     Remainder,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
     Equal,        // == (ASDL: Equal) <-- 修改点
     NotEqual,     // != (ASDL: NotEqual) <-- 修改点
     LessThan,     // < (ASDL: LessThan)
     LessOrEqual,  // <= (ASDL: LessOrEqual)
     GreaterThan,  // > (ASDL: GreaterThan)
     GreaterEqual, // >= (ASDL: GreaterOrEqual) <-- 拼写修正
+    // 无符号版本：只有这些运算的结果取决于操作数是否有符号，
+    // 把符号性记在运算符上，常量传播就可以只按位处理值
+    UnsignedDivide,
+    UnsignedRemainder,
+    UnsignedShiftRight,
+    UnsignedLessThan,
+    UnsignedLessOrEqual,
+    UnsignedGreaterThan,
+    UnsignedGreaterEqual,
 }
 // 注意：上面的 BinaryOperator 我也改成了 LessThan/LessOrEqual/GreaterThan，
 // 这样更具描述性，但你用 Less/LessEqual/Greater 也可以，只要保持一致即可。
 // ASDL 使用的是全名，所以我这里也用了全名。
 
 /// TACKY 中的一个值，可以是一个常量或一个临时变量。
-/// 对应 ASDL: val = Constant(int) | Var(identifier)
+/// 对应 ASDL: val = Constant(int) | LongConstant(long) | UIntConstant(uint) | ULongConstant(ulong) | Var(identifier)
 #[derive(Debug, Clone)]
 pub enum Val {
     Constant(i32),
+    LongConstant(i64),
+    UIntConstant(u32),
+    ULongConstant(u64),
     Var(String), // Var 用 String 来存储变量名，如 "tmp0", "tmp1"
 }
 
@@ -40,6 +60,21 @@ pub enum Val {
 #[derive(Debug)]
 pub enum Instruction {
     Return(Val),
+    // int -> long
+    SignExtend {
+        src: Val,
+        dst: Val,
+    },
+    // unsigned int -> long
+    ZeroExtend {
+        src: Val,
+        dst: Val,
+    },
+    // long -> int
+    Truncate {
+        src: Val,
+        dst: Val,
+    },
     Unary {
         op: UnaryOperator,
         src: Val,
@@ -55,6 +90,18 @@ pub enum Instruction {
         src: Val,
         dst: Val,
     },
+    // 写入聚合对象（结构体变量）中偏移为 offset 字节处的成员
+    CopyToOffset {
+        src: Val,
+        dst: String,
+        offset: u32,
+    },
+    // 读取聚合对象中偏移为 offset 字节处的成员
+    CopyFromOffset {
+        src: String,
+        offset: u32,
+        dst: Val,
+    },
     Jump(String),
     JumpIfZero {
         condition: Val,
@@ -78,10 +125,197 @@ pub struct Function {
     pub name: String,
     pub params: Vec<String>,
     pub body: Vec<Instruction>,
+    /// TACKY 生成器为这个函数创建的临时变量，按创建顺序
+    pub temporaries: Vec<String>,
+    /// 程序中具有静态存储期的变量的名字。它们在函数返回后仍然存在，
+    /// 也可能在（递归）调用中被修改，优化时不能当作普通局部变量
+    pub static_variables: HashSet<String>,
+}
+
+impl Function {
+    /// 仍在函数体中被写入的临时变量个数；被优化删掉的临时变量不计入。
+    pub fn temporary_count(&self) -> usize {
+        let mut written = HashSet::new();
+        for inst in &self.body {
+            let dst = match inst {
+                Instruction::SignExtend { dst, .. }
+                | Instruction::ZeroExtend { dst, .. }
+                | Instruction::Truncate { dst, .. }
+                | Instruction::Unary { dst, .. }
+                | Instruction::Binary { dst, .. }
+                | Instruction::Copy { dst, .. }
+                | Instruction::CopyFromOffset { dst, .. }
+                | Instruction::FunCall { dst, .. } => dst,
+                _ => continue,
+            };
+            if let Val::Var(name) = dst {
+                written.insert(name.as_str());
+            }
+        }
+        self.temporaries
+            .iter()
+            .filter(|name| written.contains(name.as_str()))
+            .count()
+    }
+}
+
+/// 具有静态存储期的变量，存放在 .data（有非零初始值时）或 .bss 中。
+#[derive(Debug, Clone, PartialEq)]
+pub struct StaticVariable {
+    pub name: String,
+    /// 占用的字节数：标量为 4 或 8，结构体为其布局的大小
+    pub size: u32,
+    pub alignment: u32,
+    /// 初始值；结构体总是 0
+    pub init: i64,
+    /// 全局变量对其他目标文件可见；静态局部变量不可见
+    pub global: bool,
 }
 
 /// TACKY 程序的根节点。s
 #[derive(Debug)]
 pub struct Program {
     pub functions: Vec<Function>,
+    pub static_variables: Vec<StaticVariable>,
+}
+
+// =================================================================
+// 文本形式的 TACKY，便于阅读 (例如 `--listing`)
+// =================================================================
+
+impl fmt::Display for Val {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Val::Constant(i) => write!(f, "{}", i),
+            Val::LongConstant(i) => write!(f, "{}L", i),
+            Val::UIntConstant(i) => write!(f, "{}u", i),
+            Val::ULongConstant(i) => write!(f, "{}UL", i),
+            Val::Var(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+impl fmt::Display for UnaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self {
+            UnaryOperator::Complement => "~",
+            UnaryOperator::Negate => "-",
+            UnaryOperator::Not => "!",
+        };
+        write!(f, "{}", op)
+    }
+}
+
+impl fmt::Display for BinaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self {
+            BinaryOperator::Add => "+",
+            BinaryOperator::Subtract => "-",
+            BinaryOperator::Multiply => "*",
+            BinaryOperator::Divide => "/",
+            BinaryOperator::Remainder => "%",
+            BinaryOperator::BitAnd => "&",
+            BinaryOperator::BitOr => "|",
+            BinaryOperator::BitXor => "^",
+            BinaryOperator::ShiftLeft => "<<",
+            BinaryOperator::ShiftRight => ">>",
+            BinaryOperator::Equal => "==",
+            BinaryOperator::NotEqual => "!=",
+            BinaryOperator::LessThan => "<",
+            BinaryOperator::LessOrEqual => "<=",
+            BinaryOperator::GreaterThan => ">",
+            BinaryOperator::GreaterEqual => ">=",
+            BinaryOperator::UnsignedDivide => "/u",
+            BinaryOperator::UnsignedRemainder => "%u",
+            BinaryOperator::UnsignedShiftRight => ">>u",
+            BinaryOperator::UnsignedLessThan => "<u",
+            BinaryOperator::UnsignedLessOrEqual => "<=u",
+            BinaryOperator::UnsignedGreaterThan => ">u",
+            BinaryOperator::UnsignedGreaterEqual => ">=u",
+        };
+        write!(f, "{}", op)
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Instruction::Return(val) => write!(f, "return {}", val),
+            Instruction::SignExtend { src, dst } => write!(f, "{} = sign_extend {}", dst, src),
+            Instruction::ZeroExtend { src, dst } => write!(f, "{} = zero_extend {}", dst, src),
+            Instruction::Truncate { src, dst } => write!(f, "{} = truncate {}", dst, src),
+            Instruction::Unary { op, src, dst } => write!(f, "{} = {}{}", dst, op, src),
+            Instruction::Binary {
+                op,
+                src1,
+                src2,
+                dst,
+            } => write!(f, "{} = {} {} {}", dst, src1, op, src2),
+            Instruction::Copy { src, dst } => write!(f, "{} = {}", dst, src),
+            Instruction::CopyToOffset { src, dst, offset } => {
+                write!(f, "{}[+{}] = {}", dst, offset, src)
+            }
+            Instruction::CopyFromOffset { src, offset, dst } => {
+                write!(f, "{} = {}[+{}]", dst, src, offset)
+            }
+            Instruction::Jump(target) => write!(f, "jump {}", target),
+            Instruction::JumpIfZero { condition, target } => {
+                write!(f, "jump_if_zero {}, {}", condition, target)
+            }
+            Instruction::JumpIfNotZero { condition, target } => {
+                write!(f, "jump_if_not_zero {}, {}", condition, target)
+            }
+            Instruction::Label(name) => write!(f, "{}:", name),
+            Instruction::FunCall { name, args, dst } => {
+                let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+                write!(f, "{} = {}({})", dst, name, args.join(", "))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}({}):", self.name, self.params.join(", "))?;
+        for inst in &self.body {
+            // 标签顶格，其余指令缩进
+            match inst {
+                Instruction::Label(_) => writeln!(f, "{}", inst)?,
+                _ => writeln!(f, "    {}", inst)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for StaticVariable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} = {} (size {}, align {})",
+            if self.global { "global" } else { "static" },
+            self.name,
+            self.init,
+            self.size,
+            self.alignment
+        )
+    }
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, func) in self.functions.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", func)?;
+        }
+        if !self.static_variables.is_empty() {
+            writeln!(f)?;
+        }
+        for var in &self.static_variables {
+            writeln!(f, "{}", var)?;
+        }
+        Ok(())
+    }
 }
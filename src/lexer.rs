@@ -1,5 +1,10 @@
 //! src/lexer.rs
 
+use std::ops::Range;
+use std::rc::Rc;
+
+use crate::common::SourceLine;
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum TokenType {
     // ... 保持不变 ...
@@ -11,6 +16,7 @@ pub enum TokenType {
     Minus,        // -
     Tilde,        // ~
     Decrement,    // --
+    Increment,    // ++
     Plus,         // + (【修改】)
     Asterisk,     // * (【修改】)
     Slash,        // / (【修改】)
@@ -29,7 +35,17 @@ pub enum TokenType {
     GreaterEqual, // >=
     Assign,       // =
     Comma,        //,
+    Ampersand,    // &
+    Pipe,         // |
+    Caret,        // ^
+    ShiftLeft,    // <<
+    ShiftRight,   // >>
+    Dot,          // .
     KeywordInt,
+    KeywordLong,
+    KeywordUnsigned,
+    KeywordBool, // _Bool
+    KeywordChar,
     KeywordVoid,
     KeywordReturn,
     KeywordIf,
@@ -40,15 +56,27 @@ pub enum TokenType {
     KeywordFor,
     KeywordBreak,
     KeywordContinue,
+    KeywordGoto,
+    KeywordExtern,
+    KeywordStatic,
+    KeywordStruct,
+    KeywordSizeof,
 
     Identifier(String),
     IntegerConstant(i32),
+    // 超出 int 范围的整数常量会被自动提升为 long
+    LongConstant(i64),
+    // 带 `u`/`U` 后缀，或超出 int 范围但能放进 unsigned int 的十六进制/八进制常量
+    UIntConstant(u32),
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Token {
     pub token_type: TokenType,
-    pub line: usize,
+    // 所在的行；经过预处理的源代码中还记录了原始文件名
+    pub line: SourceLine,
+    // token 第一个字符所在的列（从 1 开始）
+    pub column: usize,
 }
 
 // 1. 定义 Lexer 结构体
@@ -57,6 +85,13 @@ pub struct Lexer<'a> {
     chars: std::iter::Peekable<std::str::Chars<'a>>,
     // 将行号作为结构体的字段
     line: usize,
+    // 下一个待读取字符所在的列
+    column: usize,
+    // 下一个待读取字符的字节偏移，以及最近一个 token 开始处的字节偏移
+    offset: usize,
+    token_start: usize,
+    // 由预处理器行标记 `# N "file"` 给出的原始源文件名
+    file: Option<Rc<str>>,
 }
 
 // 2. 为 Lexer 实现方法
@@ -66,7 +101,51 @@ impl<'a> Lexer<'a> {
         Lexer {
             chars: source.chars().peekable(),
             line: 1,
+            column: 1,
+            offset: 0,
+            token_start: 0,
+            file: None,
+        }
+    }
+
+    /// 当前原始文件中的第 `number` 行，记录在 token 上并用于错误信息
+    fn source_line(&self, number: usize) -> SourceLine {
+        SourceLine {
+            number,
+            file: self.file.clone(),
+        }
+    }
+
+    /// 处理 `gcc -E` 输出的行标记 `# N "file" flags...`（也接受 `#line N "file"`），
+    /// 使之后的行号对应原始源文件。其他预处理器指令被忽略。
+    fn apply_line_marker(&mut self, directive: &str) {
+        let directive = directive.trim_start();
+        let directive = directive.strip_prefix("line").unwrap_or(directive);
+        let mut parts = directive.split_whitespace();
+        let Some(line) = parts.next().and_then(|n| n.parse::<usize>().ok()) else {
+            return;
+        };
+        if let Some(file) = parts
+            .next()
+            .and_then(|f| f.strip_prefix('"'))
+            .and_then(|f| f.strip_suffix('"'))
+        {
+            self.file = Some(Rc::from(file));
+        }
+        // 标记所在行末尾的换行符还会让行号加一
+        self.line = line.saturating_sub(1);
+    }
+
+    /// 消耗一个字符，同时推进列号；遇到换行符时列号回到 1。
+    fn bump(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        self.offset += c.len_utf8();
+        if c == '\n' {
+            self.column = 1;
+        } else {
+            self.column += 1;
         }
+        Some(c)
     }
 
     /// 解析标识符或关键字（现在是方法）。
@@ -75,13 +154,16 @@ impl<'a> Lexer<'a> {
         while let Some(&c) = self.chars.peek() {
             if c.is_alphanumeric() || c == '_' {
                 identifier.push(c);
-                self.chars.next();
+                self.bump();
             } else {
                 break;
             }
         }
         match identifier.as_str() {
             "int" => TokenType::KeywordInt,
+            "long" => TokenType::KeywordLong,
+            "_Bool" => TokenType::KeywordBool,
+            "char" => TokenType::KeywordChar,
             "void" => TokenType::KeywordVoid,
             "return" => TokenType::KeywordReturn,
             "if" => TokenType::KeywordIf,
@@ -91,204 +173,321 @@ impl<'a> Lexer<'a> {
             "while" => TokenType::KeywordWhile,
             "for" => TokenType::KeywordFor,
             "break" => TokenType::KeywordBreak,
+            "goto" => TokenType::KeywordGoto,
+            "extern" => TokenType::KeywordExtern,
+            "static" => TokenType::KeywordStatic,
+            "struct" => TokenType::KeywordStruct,
+            "sizeof" => TokenType::KeywordSizeof,
+            "unsigned" => TokenType::KeywordUnsigned,
             _ => TokenType::Identifier(identifier),
         }
     }
 
     /// 解析整型常量（现在是方法）。
     fn lex_integer_constant(&mut self) -> Result<TokenType, String> {
+        // `0x`/`0X` 前缀表示十六进制
+        let is_hex =
+            self.chars.peek() == Some(&'0') && matches!(self.peek_second(), Some('x') | Some('X'));
         let mut number_str = String::new();
+        if is_hex {
+            number_str.push(self.bump().unwrap());
+            number_str.push(self.bump().unwrap());
+        }
         while let Some(&c) = self.chars.peek() {
-            if c.is_digit(10) {
+            if c.is_ascii_digit() || (is_hex && c.is_ascii_hexdigit()) {
                 number_str.push(c);
-                self.chars.next();
+                self.bump();
             } else {
                 break;
             }
         }
+        // `u`/`U` 后缀表示无符号常量
+        let unsigned_suffix = match self.chars.peek() {
+            Some(&c @ ('u' | 'U')) => self.bump().and(Some(c)),
+            _ => None,
+        };
+        let is_unsigned = unsigned_suffix.is_some();
 
-        if let Some(&next_char) = self.chars.peek() {
-            if next_char.is_alphabetic() {
-                let mut invalid_token = number_str;
-                while let Some(&c) = self.chars.peek() {
-                    if c.is_alphanumeric() || c == '_' {
-                        invalid_token.push(c);
-                        self.chars.next();
-                    } else {
-                        break;
-                    }
+        if let Some(&next_char) = self.chars.peek()
+            && next_char.is_alphabetic()
+        {
+            let mut invalid_token = number_str;
+            invalid_token.extend(unsigned_suffix);
+            while let Some(&c) = self.chars.peek() {
+                if c.is_alphanumeric() || c == '_' {
+                    invalid_token.push(c);
+                    self.bump();
+                } else {
+                    break;
                 }
-                // 现在可以轻松访问 self.line！
+            }
+            // 现在可以轻松访问 self.line！
+            return Err(format!(
+                "Invalid token '{}' on line {}",
+                invalid_token,
+                self.source_line(self.line)
+            ));
+        }
+
+        // 以 0 开头的多位数字是八进制；单独的 `0` 仍是十进制的零
+        let (digits, radix) = if is_hex {
+            if number_str.len() == 2 {
                 return Err(format!(
-                    "Invalid token '{}' on line {}",
-                    invalid_token, self.line
+                    "Hexadecimal constant '{}' has no digits on line {}",
+                    number_str,
+                    self.source_line(self.line)
                 ));
             }
-        }
+            (&number_str[2..], 16)
+        } else if number_str.len() > 1 && number_str.starts_with('0') {
+            if let Some(bad) = number_str.chars().find(|c| !('0'..='7').contains(c)) {
+                return Err(format!(
+                    "Invalid digit '{}' in octal constant '{}' on line {}",
+                    bad,
+                    number_str,
+                    self.source_line(self.line)
+                ));
+            }
+            (&number_str[1..], 8)
+        } else {
+            (number_str.as_str(), 10)
+        };
 
-        match number_str.parse::<i32>() {
-            Ok(num) => Ok(TokenType::IntegerConstant(num)),
-            Err(_) => Err(format!("Failed to parse number: {}", number_str)),
+        // 按照 C 的整数常量类型规则：能放进 int 的是 int，否则是 long；
+        // 十六进制和八进制常量在 int 和 long 之间还可以是 unsigned int。
+        // 带 `u` 后缀的常量目前只能是 unsigned int，放不进的是错误。
+        match i64::from_str_radix(digits, radix) {
+            Ok(num) if is_unsigned => match u32::try_from(num) {
+                Ok(uint_num) => Ok(TokenType::UIntConstant(uint_num)),
+                Err(_) => Err(format!(
+                    "Unsigned constant {}u does not fit in unsigned int on line {}",
+                    number_str,
+                    self.source_line(self.line)
+                )),
+            },
+            Ok(num) => match (i32::try_from(num), u32::try_from(num)) {
+                (Ok(int_num), _) => Ok(TokenType::IntegerConstant(int_num)),
+                (Err(_), Ok(uint_num)) if radix != 10 => Ok(TokenType::UIntConstant(uint_num)),
+                _ => Ok(TokenType::LongConstant(num)),
+            },
+            Err(_) => Err(format!(
+                "Integer constant {} is too large on line {}",
+                number_str,
+                self.source_line(self.line)
+            )),
         }
     }
 
-    /// 核心方法：解析下一个 token。
-    /// 返回 Option<Result<Token, String>>，这是实现 Iterator 的标准模式。
-    // in src/lexer.rs, inside impl<'a> Lexer<'a>
+    /// 查看下一个字符之后的那个字符（不消耗任何字符）。
+    fn peek_second(&self) -> Option<char> {
+        let mut ahead = self.chars.clone();
+        ahead.next();
+        ahead.next()
+    }
 
     /// 核心方法：解析下一个 token。
+    /// 返回 Option<Result<Token, String>>，这是实现 Iterator 的标准模式。
     fn next_token(&mut self) -> Option<Result<Token, String>> {
-        // 首先，跳过所有空白字符和预处理器指令
+        // 首先，跳过所有空白字符、预处理器指令和注释
         loop {
-            match self.chars.peek() {
+            match self.chars.peek().copied() {
                 // 处理换行符
                 Some('\n') => {
                     self.line += 1;
-                    self.chars.next();
+                    self.bump();
                 }
                 // 处理其他空白字符
                 Some(' ') | Some('\t') | Some('\r') => {
-                    self.chars.next();
+                    self.bump();
                 }
                 // *** 新增的逻辑：处理预处理器指令 ***
                 Some('#') => {
                     // 消耗掉 '#'
-                    self.chars.next();
+                    self.bump();
                     // 消耗掉这一行的剩余所有字符，直到换行符或文件结尾
+                    let mut directive = String::new();
+                    while let Some(&c) = self.chars.peek() {
+                        if c == '\n' {
+                            // 遇到换行符，让外层循环来处理它（增加行号）
+                            break;
+                        }
+                        directive.push(c);
+                        self.bump();
+                    }
+                    self.apply_line_marker(&directive);
+                }
+                // 行注释 `// ...`：和预处理器指令一样，跳过到行尾
+                Some('/') if self.peek_second() == Some('/') => {
                     while let Some(c) = self.chars.peek() {
                         if *c == '\n' {
-                            // 遇到换行符，让外层循环来处理它（增加行号）
                             break;
                         }
-                        self.chars.next();
+                        self.bump();
                     }
                 }
-                // 遇到非空白、非'#'的字符，说明是 token 的开始，跳出循环
+                // 块注释 `/* ... */`，可以跨越多行
+                Some('/') if self.peek_second() == Some('*') => {
+                    let start_line = self.line;
+                    self.bump();
+                    self.bump();
+                    loop {
+                        match self.bump() {
+                            Some('*') if self.chars.peek() == Some(&'/') => {
+                                self.bump();
+                                break;
+                            }
+                            Some('\n') => self.line += 1,
+                            Some(_) => {}
+                            None => {
+                                return Some(Err(format!(
+                                    "Unterminated block comment starting on line {}",
+                                    self.source_line(start_line)
+                                )));
+                            }
+                        }
+                    }
+                }
+                // 遇到非空白、非'#'、非注释的字符，说明是 token 的开始，跳出循环
                 _ => break,
             }
         }
 
         // 查看下一个有效字符
         let c = self.chars.peek().cloned()?; // 如果是 None，则表示输入结束
+        let column = self.column;
+        self.token_start = self.offset;
 
         // 根据字符类型分派
         let result = match c {
             '(' => {
-                self.chars.next();
+                self.bump();
                 Ok(TokenType::OpenParen)
             }
             ')' => {
-                self.chars.next();
+                self.bump();
                 Ok(TokenType::CloseParen)
             }
             '{' => {
-                self.chars.next();
+                self.bump();
                 Ok(TokenType::OpenBrace)
             }
             '}' => {
-                self.chars.next();
+                self.bump();
                 Ok(TokenType::CloseBrace)
             }
             ';' => {
-                self.chars.next();
+                self.bump();
                 Ok(TokenType::Semicolon)
             }
+            '^' => {
+                self.bump();
+                Ok(TokenType::Caret)
+            }
             '~' => {
-                self.chars.next();
+                self.bump();
                 Ok(TokenType::Tilde)
             }
+            '.' => {
+                self.bump();
+                Ok(TokenType::Dot)
+            }
             '+' => {
-                self.chars.next();
-                Ok(TokenType::Plus)
+                self.bump();
+                if self.chars.peek() == Some(&'+') {
+                    self.bump();
+                    Ok(TokenType::Increment)
+                } else {
+                    Ok(TokenType::Plus)
+                }
             }
             '*' => {
-                self.chars.next();
+                self.bump();
                 Ok(TokenType::Asterisk)
             }
             '/' => {
-                self.chars.next();
+                self.bump();
                 Ok(TokenType::Slash)
             }
             '%' => {
-                self.chars.next();
+                self.bump();
                 Ok(TokenType::Percent)
             }
             '?' => {
-                self.chars.next();
+                self.bump();
                 Ok(TokenType::QuestionMark)
             }
             ':' => {
-                self.chars.next();
+                self.bump();
                 Ok(TokenType::Colon)
             }
             ',' => {
-                self.chars.next();
+                self.bump();
                 Ok(TokenType::Comma)
             }
             '-' => {
-                self.chars.next();
+                self.bump();
                 if self.chars.peek() == Some(&'-') {
-                    self.chars.next();
+                    self.bump();
                     Ok(TokenType::Decrement)
                 } else {
                     Ok(TokenType::Minus)
                 }
             }
             '&' => {
-                self.chars.next();
+                self.bump();
                 if self.chars.peek() == Some(&'&') {
-                    self.chars.next();
+                    self.bump();
                     Ok(TokenType::And)
                 } else {
-                    Err(format!(
-                        "Unrecognized character '{}' on line {}",
-                        c, self.line
-                    ))
+                    Ok(TokenType::Ampersand)
                 }
             }
             '|' => {
-                self.chars.next();
+                self.bump();
                 if self.chars.peek() == Some(&'|') {
-                    self.chars.next();
+                    self.bump();
                     Ok(TokenType::Or)
                 } else {
-                    Err(format!(
-                        "Unrecognized character '{}' on line {}",
-                        c, self.line
-                    ))
+                    Ok(TokenType::Pipe)
                 }
             }
             '!' => {
-                self.chars.next();
+                self.bump();
                 if self.chars.peek() == Some(&'=') {
-                    self.chars.next();
+                    self.bump();
                     Ok(TokenType::NotEqual)
                 } else {
                     Ok(TokenType::Not)
                 }
             }
             '<' => {
-                self.chars.next();
-                if self.chars.peek() == Some(&'=') {
-                    self.chars.next();
+                self.bump();
+                if self.chars.peek() == Some(&'<') {
+                    self.bump();
+                    Ok(TokenType::ShiftLeft)
+                } else if self.chars.peek() == Some(&'=') {
+                    self.bump();
                     Ok(TokenType::LessEqual)
                 } else {
                     Ok(TokenType::Less)
                 }
             }
             '>' => {
-                self.chars.next();
-                if self.chars.peek() == Some(&'=') {
-                    self.chars.next();
+                self.bump();
+                if self.chars.peek() == Some(&'>') {
+                    self.bump();
+                    Ok(TokenType::ShiftRight)
+                } else if self.chars.peek() == Some(&'=') {
+                    self.bump();
                     Ok(TokenType::GreaterEqual)
                 } else {
                     Ok(TokenType::Greater)
                 }
             }
             '=' => {
-                self.chars.next();
+                self.bump();
                 if self.chars.peek() == Some(&'=') {
-                    self.chars.next();
+                    self.bump();
                     Ok(TokenType::Equal)
                 } else {
                     Ok(TokenType::Assign)
@@ -300,7 +499,8 @@ impl<'a> Lexer<'a> {
 
             _ => Err(format!(
                 "Unrecognized character '{}' on line {}",
-                c, self.line
+                c,
+                self.source_line(self.line)
             )),
         };
 
@@ -308,13 +508,26 @@ impl<'a> Lexer<'a> {
         Some(match result {
             Ok(token_type) => Ok(Token {
                 token_type,
-                line: self.line,
+                line: self.source_line(self.line),
+                column,
             }),
             Err(e) => Err(e),
         })
     }
 }
 
+impl Lexer<'_> {
+    /// 读取全部 token，同时返回每个 token 在源码中的字节范围 `start..end`，
+    /// 供编辑器做语义高亮。
+    pub fn tokens_with_spans(mut self) -> Result<Vec<(Token, Range<usize>)>, String> {
+        let mut tokens = Vec::new();
+        while let Some(token) = self.next_token() {
+            tokens.push((token?, self.token_start..self.offset));
+        }
+        Ok(tokens)
+    }
+}
+
 // 3. 为 Lexer 实现 Iterator trait
 impl<'a> Iterator for Lexer<'a> {
     type Item = Result<Token, String>;
@@ -350,170 +563,210 @@ mod tests {
             // line 2: for (i = 0; i < 10; i = i + 1) {
             Token {
                 token_type: TokenType::KeywordFor,
-                line: 2,
+                line: 2.into(),
+                column: 13,
             },
             Token {
                 token_type: TokenType::OpenParen,
-                line: 2,
+                line: 2.into(),
+                column: 17,
             },
             Token {
                 token_type: TokenType::Identifier("i".to_string()),
-                line: 2,
+                line: 2.into(),
+                column: 18,
             },
             Token {
                 token_type: TokenType::Assign,
-                line: 2,
+                line: 2.into(),
+                column: 20,
             },
             Token {
                 token_type: TokenType::IntegerConstant(0),
-                line: 2,
+                line: 2.into(),
+                column: 22,
             },
             Token {
                 token_type: TokenType::Semicolon,
-                line: 2,
+                line: 2.into(),
+                column: 23,
             },
             Token {
                 token_type: TokenType::Identifier("i".to_string()),
-                line: 2,
+                line: 2.into(),
+                column: 25,
             },
             Token {
                 token_type: TokenType::Less,
-                line: 2,
+                line: 2.into(),
+                column: 27,
             },
             Token {
                 token_type: TokenType::IntegerConstant(10),
-                line: 2,
+                line: 2.into(),
+                column: 29,
             },
             Token {
                 token_type: TokenType::Semicolon,
-                line: 2,
+                line: 2.into(),
+                column: 31,
             },
             Token {
                 token_type: TokenType::Identifier("i".to_string()),
-                line: 2,
+                line: 2.into(),
+                column: 33,
             },
             Token {
                 token_type: TokenType::Assign,
-                line: 2,
+                line: 2.into(),
+                column: 35,
             },
             Token {
                 token_type: TokenType::Identifier("i".to_string()),
-                line: 2,
+                line: 2.into(),
+                column: 37,
             },
             Token {
                 token_type: TokenType::Plus,
-                line: 2,
+                line: 2.into(),
+                column: 39,
             },
             Token {
                 token_type: TokenType::IntegerConstant(1),
-                line: 2,
+                line: 2.into(),
+                column: 41,
             },
             Token {
                 token_type: TokenType::CloseParen,
-                line: 2,
+                line: 2.into(),
+                column: 42,
             },
             Token {
                 token_type: TokenType::OpenBrace,
-                line: 2,
+                line: 2.into(),
+                column: 44,
             },
             // line 3: do {
             Token {
                 token_type: TokenType::KeywordDo,
-                line: 3,
+                line: 3.into(),
+                column: 17,
             },
             Token {
                 token_type: TokenType::OpenBrace,
-                line: 3,
+                line: 3.into(),
+                column: 20,
             },
             // line 4: if (a == 5) {
             Token {
                 token_type: TokenType::KeywordIf,
-                line: 4,
+                line: 4.into(),
+                column: 21,
             },
             Token {
                 token_type: TokenType::OpenParen,
-                line: 4,
+                line: 4.into(),
+                column: 24,
             },
             Token {
                 token_type: TokenType::Identifier("a".to_string()),
-                line: 4,
+                line: 4.into(),
+                column: 25,
             },
             Token {
                 token_type: TokenType::Equal,
-                line: 4,
+                line: 4.into(),
+                column: 27,
             },
             Token {
                 token_type: TokenType::IntegerConstant(5),
-                line: 4,
+                line: 4.into(),
+                column: 30,
             },
             Token {
                 token_type: TokenType::CloseParen,
-                line: 4,
+                line: 4.into(),
+                column: 31,
             },
             Token {
                 token_type: TokenType::OpenBrace,
-                line: 4,
+                line: 4.into(),
+                column: 33,
             },
             // line 5: break;
             Token {
                 token_type: TokenType::KeywordBreak,
-                line: 5,
+                line: 5.into(),
+                column: 25,
             },
             Token {
                 token_type: TokenType::Semicolon,
-                line: 5,
+                line: 5.into(),
+                column: 30,
             },
             // line 6: }
             Token {
                 token_type: TokenType::CloseBrace,
-                line: 6,
+                line: 6.into(),
+                column: 21,
             },
             // line 7: continue;
             Token {
                 token_type: TokenType::KeywordContinue,
-                line: 7,
+                line: 7.into(),
+                column: 21,
             },
             Token {
                 token_type: TokenType::Semicolon,
-                line: 7,
+                line: 7.into(),
+                column: 29,
             },
             // line 8: } while (x > 0);
             Token {
                 token_type: TokenType::CloseBrace,
-                line: 8,
+                line: 8.into(),
+                column: 17,
             },
             Token {
                 token_type: TokenType::KeywordWhile,
-                line: 8,
+                line: 8.into(),
+                column: 19,
             },
             Token {
                 token_type: TokenType::OpenParen,
-                line: 8,
+                line: 8.into(),
+                column: 25,
             },
             Token {
                 token_type: TokenType::Identifier("x".to_string()),
-                line: 8,
+                line: 8.into(),
+                column: 26,
             },
             Token {
                 token_type: TokenType::Greater,
-                line: 8,
+                line: 8.into(),
+                column: 28,
             },
             Token {
                 token_type: TokenType::IntegerConstant(0),
-                line: 8,
+                line: 8.into(),
+                column: 30,
             },
             Token {
                 token_type: TokenType::CloseParen,
-                line: 8,
+                line: 8.into(),
+                column: 31,
             },
             Token {
                 token_type: TokenType::Semicolon,
-                line: 8,
+                line: 8.into(),
+                column: 32,
             },
             // line 9: }
             Token {
                 token_type: TokenType::CloseBrace,
-                line: 9,
+                line: 9.into(),
+                column: 13,
             },
         ];
 
@@ -525,4 +778,187 @@ mod tests {
         // 4. Assert: 比较实际生成的 Tokens 和我们期望的 Tokens。
         assert_eq!(actual_tokens, expected_tokens);
     }
+
+    #[test]
+    fn test_lex_records_token_columns() {
+        let lexer = Lexer::new("int main(void) {\n    return 42;\n}");
+        let tokens: Vec<Token> = lexer.map(|result| result.unwrap()).collect();
+        let ret = tokens
+            .iter()
+            .find(|t| t.token_type == TokenType::KeywordReturn)
+            .unwrap();
+        assert_eq!((ret.line.number, ret.column), (2, 5));
+        // 同一行中后续 token 的列号随之推进
+        assert_eq!(tokens[7].token_type, TokenType::IntegerConstant(42));
+        assert_eq!(tokens[7].column, 12);
+        assert_eq!((tokens[9].line.number, tokens[9].column), (3, 1));
+    }
+
+    #[test]
+    fn test_lex_large_constant_as_long() {
+        // 超出 int 范围的整数常量被识别为 long 常量，其余的仍然是 int 常量
+        let lexer = Lexer::new("3000000000 100");
+        let actual: Vec<TokenType> = lexer.map(|result| result.unwrap().token_type).collect();
+        assert_eq!(
+            actual,
+            vec![
+                TokenType::LongConstant(3000000000),
+                TokenType::IntegerConstant(100)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lex_hex_and_octal_constants() {
+        let lexer = Lexer::new("0x1F 0XfF 0755 0 0x100000000");
+        let actual: Vec<TokenType> = lexer.map(|result| result.unwrap().token_type).collect();
+        assert_eq!(
+            actual,
+            vec![
+                TokenType::IntegerConstant(31),
+                TokenType::IntegerConstant(255),
+                TokenType::IntegerConstant(493),
+                TokenType::IntegerConstant(0),
+                TokenType::LongConstant(0x100000000),
+            ]
+        );
+
+        let lex = |source: &str| Lexer::new(source).collect::<Result<Vec<Token>, String>>();
+        assert_eq!(
+            lex("return 0x;").unwrap_err(),
+            "Hexadecimal constant '0x' has no digits on line 1"
+        );
+        assert_eq!(
+            lex("\nreturn 09;").unwrap_err(),
+            "Invalid digit '9' in octal constant '09' on line 2"
+        );
+    }
+
+    #[test]
+    fn test_lex_unsigned_constants() {
+        let lexer = Lexer::new("1u 7U 4294967295u 0xFFFFFFFF 4294967295 unsigned");
+        let actual: Vec<TokenType> = lexer.map(|result| result.unwrap().token_type).collect();
+        assert_eq!(
+            actual,
+            vec![
+                TokenType::UIntConstant(1),
+                TokenType::UIntConstant(7),
+                TokenType::UIntConstant(u32::MAX),
+                // 十六进制常量可以是 unsigned int，十进制的则直接成为 long
+                TokenType::UIntConstant(u32::MAX),
+                TokenType::LongConstant(4294967295),
+                TokenType::KeywordUnsigned,
+            ]
+        );
+
+        let lex = |source: &str| Lexer::new(source).collect::<Result<Vec<Token>, String>>();
+        assert_eq!(
+            lex("4294967296u").unwrap_err(),
+            "Unsigned constant 4294967296u does not fit in unsigned int on line 1"
+        );
+        assert_eq!(lex("1uu").unwrap_err(), "Invalid token '1uu' on line 1");
+    }
+
+    #[test]
+    fn test_lex_skips_line_comments() {
+        let lexer = Lexer::new("int x = 5; // hi\nreturn x / 2;");
+        let actual: Vec<Token> = lexer.map(|result| result.unwrap()).collect();
+        let types: Vec<&TokenType> = actual.iter().map(|t| &t.token_type).collect();
+        assert_eq!(
+            types,
+            vec![
+                &TokenType::KeywordInt,
+                &TokenType::Identifier("x".to_string()),
+                &TokenType::Assign,
+                &TokenType::IntegerConstant(5),
+                &TokenType::Semicolon,
+                &TokenType::KeywordReturn,
+                &TokenType::Identifier("x".to_string()),
+                &TokenType::Slash,
+                &TokenType::IntegerConstant(2),
+                &TokenType::Semicolon,
+            ]
+        );
+        // 注释后面的 token 仍在第 2 行
+        assert_eq!(actual[5].line.number, 2);
+    }
+
+    #[test]
+    fn test_lex_skips_block_comments() {
+        let lexer = Lexer::new("int /* a */ x;");
+        let types: Vec<TokenType> = lexer.map(|result| result.unwrap().token_type).collect();
+        assert_eq!(
+            types,
+            vec![
+                TokenType::KeywordInt,
+                TokenType::Identifier("x".to_string()),
+                TokenType::Semicolon,
+            ]
+        );
+
+        // 跨三行的注释之后，行号仍然正确；`**/` 也能正确结束注释
+        let lexer = Lexer::new("int\n/* one\n two\n three **/ x;");
+        let tokens: Vec<Token> = lexer.map(|result| result.unwrap()).collect();
+        assert_eq!(tokens[1].token_type, TokenType::Identifier("x".to_string()));
+        assert_eq!(tokens[1].line.number, 4);
+    }
+
+    #[test]
+    fn test_lex_unterminated_block_comment() {
+        let mut lexer = Lexer::new("int x;\n/* never closed\n");
+        assert!(lexer.by_ref().take(3).all(|result| result.is_ok()));
+        assert_eq!(
+            lexer.next(),
+            Some(Err(
+                "Unterminated block comment starting on line 2".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_line_markers_reset_line_and_file() {
+        let source = "# 1 \"foo.c\"\nint x;\n# 10 \"foo.c\" 2\nint @;";
+        let mut lexer = Lexer::new(source);
+        let first = lexer.next().unwrap().unwrap();
+        assert_eq!(first.line.to_string(), "1 of foo.c");
+        assert!(lexer.by_ref().take(3).all(|result| result.is_ok()));
+        assert_eq!(
+            lexer.next(),
+            Some(Err(
+                "Unrecognized character '@' on line 10 of foo.c".to_string()
+            ))
+        );
+
+        // 不是行标记的指令仍然只是被跳过
+        let tokens: Vec<Token> = Lexer::new("#pragma once\nint")
+            .map(|result| result.unwrap())
+            .collect();
+        assert_eq!(tokens[0].line.number, 2);
+    }
+
+    #[test]
+    fn test_tokens_with_spans() {
+        let spans: Vec<(TokenType, std::ops::Range<usize>)> = Lexer::new("int x;")
+            .tokens_with_spans()
+            .unwrap()
+            .into_iter()
+            .map(|(token, span)| (token.token_type, span))
+            .collect();
+        assert_eq!(
+            spans,
+            vec![
+                (TokenType::KeywordInt, 0..3),
+                (TokenType::Identifier("x".to_string()), 4..5),
+                (TokenType::Semicolon, 5..6),
+            ]
+        );
+        // 范围按字节计算，注释和多字节字符也被计入
+        let spans: Vec<_> = Lexer::new("/* é */ return")
+            .tokens_with_spans()
+            .unwrap()
+            .into_iter()
+            .map(|(_, span)| span)
+            .collect();
+        assert_eq!(spans, vec![9..15]);
+    }
 }
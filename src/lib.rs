@@ -2,7 +2,12 @@
 pub mod ast;
 pub mod backend;
 pub mod common;
+pub mod compile_db;
+pub mod diagnostics;
+pub mod driver;
 pub mod ir;
 pub mod lexer;
 pub mod parser;
 pub mod semantics; // Add this line
+#[cfg(test)]
+mod test_support;
@@ -1,36 +1,74 @@
 // src/main.rs
 
-use clap::Parser as ClapParser;
-use my_c_compiler::backend::asm_gen::AsmGenerator;
-use my_c_compiler::backend::emitter;
-use my_c_compiler::backend::tacky_gen::TackyGenerator;
-use my_c_compiler::common::UniqueIdGenerator;
+use clap::{CommandFactory, Parser as ClapParser, ValueHint};
+use my_c_compiler::ast::unchecked;
+use my_c_compiler::backend::cfg::Cfg;
+use my_c_compiler::backend::emitter::{self, EmitOptions};
+use my_c_compiler::backend::tacky_opt::Optimizer;
+use my_c_compiler::compile_db;
+use my_c_compiler::diagnostics::{self, Diagnostic, Severity};
+use my_c_compiler::driver::{self, CompileError, CompileHooks, CompileOptions, Stage, StageOutput};
 use my_c_compiler::lexer::{self, Token};
 use my_c_compiler::parser as CParser;
-use my_c_compiler::semantics::loop_labeler::LoopLabeler;
-use my_c_compiler::semantics::type_checker::TypeChecker;
-use my_c_compiler::semantics::validator::Validator;
+use std::collections::HashMap;
+use std::ffi::OsString;
 use std::fs;
+use std::io::Write;
+use std::ops::ControlFlow;
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Command, Stdio};
+
+/// 打印阶段进度信息；在 `--quiet` 模式下不输出。
+/// 进度信息写到 stderr，stdout 只留给 `--lex`、`--tacky` 等请求的输出，方便重定向。
+macro_rules! progress {
+    ($cli:expr, $($arg:tt)*) => {
+        if !$cli.quiet {
+            eprintln!($($arg)*);
+        }
+    };
+}
 
 /// A C compiler, written in Rust.
-#[derive(ClapParser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[derive(ClapParser, Debug, Clone)]
+#[command(
+    author,
+    version,
+    about,
+    long_about = None,
+    after_help = "Exit status: 0 on success; 2, 3, 4, 5, 6 or 7 when lexing, parsing, semantic \
+                  analysis, TACKY generation, code generation or assembly emission fails; \
+                  1 for any other failure."
+)]
 struct Cli {
+    /// Suppress stage progress messages; only requested dumps are printed
+    #[arg(short, long)]
+    quiet: bool,
+
     /// Stop after lexing and print tokens
     #[arg(long)]
     lex: bool,
 
+    /// Like --lex, but print one token per line with its byte range and line:column
+    /// in the original (unpreprocessed) source
+    #[arg(long)]
+    dump_tokens_with_spans: bool,
+
     /// Stop after parsing and print C AST
     #[arg(long)]
     parse: bool,
 
     #[arg(long)]
     validate: bool,
+    /// Stop after type checking and print each expression with its inferred type
+    #[arg(long)]
+    dump_types: bool,
     /// 【新增】Stop after TACKY IR generation and print TACKY
     #[arg(long)]
     tacky: bool,
+    /// Stop after TACKY generation (and -O1 optimization) and print each function's
+    /// control-flow graph in Graphviz DOT format
+    #[arg(long)]
+    dump_cfg: bool,
 
     /// Stop after assembly generation and print assembly AST
     #[arg(long)]
@@ -38,154 +76,752 @@ struct Cli {
     /// Do not delete the generated .s assembly file
     #[arg(long)]
     keep_asm: bool,
+    /// Do not synthesize `return 0` at the end of functions; report a missing return instead
+    #[arg(long)]
+    no_implicit_return: bool,
+    /// Treat compiler builtins such as `__builtin_expect` as ordinary function calls
+    #[arg(long)]
+    no_builtins: bool,
+    /// Enable a warning: `-Wuninitialized` or `-Wshadow`
+    #[arg(short = 'W', value_name = "WARNING", value_parser = ["uninitialized", "shadow"])]
+    warnings: Vec<String>,
+    /// Report every warning (unused variables, uninitialized reads, shadowing, unreachable
+    /// code, assignments used as conditions, missing returns) and stop before code generation
+    #[arg(long, conflicts_with_all = ["compile_only", "assert_exit", "eval"])]
+    analyze: bool,
+    /// Treat warnings as errors: fail after semantic analysis if any warning was reported
+    #[arg(long)]
+    werror: bool,
+    /// Diagnostic output format; `json` prints all warnings and errors to stderr as one
+    /// JSON array (implies `--quiet`)
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        value_parser = ["human", "json"],
+        default_value = "human",
+        conflicts_with = "eval"
+    )]
+    diagnostics: String,
+    /// Optimization level (0 or 1)
+    #[arg(short = 'O', value_name = "LEVEL", default_value_t = 0)]
+    opt_level: u8,
+    /// Fail if any function's stack frame exceeds this many bytes
+    #[arg(long, value_name = "BYTES")]
+    max_stack: Option<u32>,
+    /// Emit CFI directives so debuggers can unwind generated functions
+    #[arg(long)]
+    emit_cfi: bool,
+    /// Start every function with `endbr64` so it can be linked into CET-enabled binaries
+    #[arg(long)]
+    cet: bool,
+    /// Write a .lst file that shows each function's source lines next to its TACKY IR and assembly
+    #[arg(long)]
+    listing: bool,
+    /// Print the stack frame size of each function
+    #[arg(long)]
+    print_stack_usage: bool,
+    /// Print how many TACKY temporaries each function uses
+    #[arg(long)]
+    print_temps: bool,
+    /// Pipe the assembly directly into gcc instead of writing a .s file (also applies to -c)
+    #[arg(long, conflicts_with = "keep_asm")]
+    pipe_asm: bool,
+    /// Print the final assembly to stdout and stop, without writing a .s file or running gcc
+    #[arg(
+        long,
+        visible_alias = "emit-asm",
+        conflicts_with_all = ["keep_asm", "pipe_asm", "compile_only", "listing"]
+    )]
+    asm_stdout: bool,
     /// Only compile and assemble, do not link. Produces a .o object file.
     #[arg(short = 'c')]
     compile_only: bool,
-    /// The C source file to compile
-    input_file: PathBuf,
+    /// Write the executable to FILE instead of next to the source file; with -c, FILE names the object file
+    #[arg(
+        short = 'o',
+        long,
+        value_name = "FILE",
+        value_hint = ValueHint::FilePath,
+        conflicts_with = "eval"
+    )]
+    output: Option<PathBuf>,
+    /// Run the produced executable and check that it exits with this code
+    #[arg(long, value_name = "N", conflicts_with = "compile_only")]
+    assert_exit: Option<i32>,
+    /// Parse a bare expression given on the command line and print its AST (for testing)
+    #[arg(long, value_name = "EXPR", hide = true)]
+    parse_only_expr: Option<String>,
+    /// Compile and run a snippet of statements; the value of a trailing expression becomes the exit code
+    #[arg(long, value_name = "SNIPPET", conflicts_with = "compile_only")]
+    eval: Option<String>,
+    /// Compile the input file into a temporary directory, run it, and exit with its exit code.
+    /// If compilation fails, exit with the same per-stage code as a normal compilation
+    #[arg(
+        long,
+        conflicts_with_all = ["compile_only", "output", "eval", "assert_exit", "asm_stdout", "analyze"]
+    )]
+    run: bool,
+    /// Compile according to a compile_commands.json-style entry (a JSON object with `file` and `arguments`)
+    #[arg(long, value_name = "JSON_FILE", conflicts_with_all = ["eval", "input_files"])]
+    compile_db_entry: Option<PathBuf>,
+    /// When one of several input files fails to compile, keep compiling the others
+    #[arg(long)]
+    keep_going: bool,
+    /// The C source files to compile; each one is compiled on its own
+    #[arg(required_unless_present_any = ["parse_only_expr", "eval", "compile_db_entry"])]
+    input_files: Vec<PathBuf>,
+    /// 当前正在编译的文件，由 `main` 依次设置为 `input_files` 中的每一项
+    #[arg(skip)]
+    input_file: Option<PathBuf>,
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let cli = Cli::parse();
-    if let Err(e) = run_pipeline(&cli) {
-        eprintln!("\nCompilation failed: {}", e);
+    let mut cli = Cli::parse();
+    if let Some(entry_path) = &cli.compile_db_entry {
+        cli = match cli_from_compile_db_entry(entry_path) {
+            Ok(entry_cli) => entry_cli,
+            Err(e) => {
+                eprintln!("\nInvalid compilation database entry: {}", e);
+                std::process::exit(1);
+            }
+        };
+    }
+    if let Some(expr) = &cli.parse_only_expr {
+        if let Err(e) = parse_only_expression(expr) {
+            eprintln!("\nParsing failed: {}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if let Some(snippet) = &cli.eval {
+        match run_eval(&cli, snippet) {
+            Ok(code) => std::process::exit(code),
+            Err(e) => {
+                eprintln!("\nEvaluation failed: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+    if cli.input_files.len() > 1 && cli.output.is_some() {
+        eprintln!("\nCannot use -o with multiple input files");
         std::process::exit(1);
     }
+    if cli.run {
+        if cli.input_files.len() != 1 {
+            eprintln!("\n--run takes exactly one input file");
+            std::process::exit(1);
+        }
+        cli.input_file = Some(cli.input_files[0].clone());
+        match run_input(&cli) {
+            Ok(code) => std::process::exit(code),
+            Err(e) => {
+                eprintln!("\nRun failed: {}", e);
+                std::process::exit(failure_exit_code(e.as_ref()));
+            }
+        }
+    }
+    let json = cli.diagnostics == "json";
+    if json {
+        // stderr 上只输出 JSON 数组，方便工具直接解析
+        cli.quiet = true;
+    }
+    // 每个文件单独编译；默认在第一个失败的文件处停止，--keep-going 时继续编译其余文件
+    let mut collected = Vec::new();
+    // 有文件失败时，以第一个失败的退出码退出
+    let mut failure = None;
+    for input in &cli.input_files {
+        let mut file_cli = cli.clone();
+        file_cli.input_file = Some(input.clone());
+        if let Err(code) = compile_input(&file_cli, json.then_some(&mut collected)) {
+            failure.get_or_insert(code);
+            if !cli.keep_going {
+                break;
+            }
+        }
+    }
+    if json {
+        eprintln!("{}", diagnostics::to_json(&collected));
+    }
+    if let Some(code) = failure {
+        std::process::exit(code);
+    }
     Ok(())
 }
 
-fn run_pipeline(cli: &Cli) -> Result<(), Box<dyn std::error::Error>> {
-    let mut id_generator = UniqueIdGenerator::new();
+/// 编译 `cli.input_file`，并按需检查可执行文件的退出码；失败时返回进程的退出码。
+/// `collected` 为 `Some` 时（`--diagnostics=json`）诊断信息被收集起来，否则直接输出。
+fn compile_input(cli: &Cli, collected: Option<&mut Vec<Diagnostic>>) -> Result<(), i32> {
+    let file = cli.input_file.as_ref().map(|p| p.display().to_string());
+    match collected {
+        Some(collected) => {
+            let start = collected.len();
+            let result = run_pipeline(cli, &mut |d| collected.push(d));
+            // 编译错误已经逐条报告过了，不再重复汇总信息
+            if let Err(e) = &result
+                && !collected[start..]
+                    .iter()
+                    .any(|d| d.severity == Severity::Error)
+            {
+                collected.push(Diagnostic::from_message(
+                    Severity::Error,
+                    file.as_deref(),
+                    &e.to_string(),
+                ));
+            }
+            for d in &mut collected[start..] {
+                if d.file.is_none() {
+                    d.file = file.clone();
+                }
+            }
+            if let Err(e) = result {
+                return Err(failure_exit_code(e.as_ref()));
+            }
+        }
+        None => {
+            if let Err(e) = run_pipeline(cli, &mut print_diagnostic) {
+                if cli.input_files.len() > 1 {
+                    eprintln!(
+                        "\nCompilation of {} failed: {}",
+                        file.unwrap_or_default(),
+                        e
+                    );
+                } else {
+                    eprintln!("\nCompilation failed: {}", e);
+                }
+                return Err(failure_exit_code(e.as_ref()));
+            }
+        }
+    }
+    if let Some(expected) = cli.assert_exit
+        && let Err(e) = assert_exit_code(cli, expected)
+    {
+        eprintln!("\nExit code assertion failed: {}", e);
+        return Err(1);
+    }
+    Ok(())
+}
+
+/// 编译失败时的退出码：按失败的阶段区分，测试脚本不必解析输出就能知道错误出在哪里；
+/// 预处理、读写文件、调用 gcc 等其他失败统一为 1。
+fn failure_exit_code(error: &(dyn std::error::Error + 'static)) -> i32 {
+    match error.downcast_ref::<CompileError>().map(|e| e.stage) {
+        Some(Stage::Lex) => 2,
+        Some(Stage::Parse) => 3,
+        Some(Stage::Semantics) => 4,
+        Some(Stage::Tacky) => 5,
+        Some(Stage::Codegen) => 6,
+        Some(Stage::Emit) => 7,
+        None => 1,
+    }
+}
+
+/// gcc 中值作为下一个参数给出的常见选项；跳过它们时连同值一起跳过
+const SEPARATE_VALUE_OPTIONS: [&str; 12] = [
+    "-I",
+    "-D",
+    "-U",
+    "-include",
+    "-imacros",
+    "-isystem",
+    "-iquote",
+    "-idirafter",
+    "-MF",
+    "-MT",
+    "-MQ",
+    "-x",
+];
+
+/// 根据编译数据库条目构造命令行。条目中的命令行是为别的编译器写的，
+/// 只保留本编译器认识并且接受的选项，其余的（`-I`、`-D`、`-Wall` 之类）和
+/// 源文件本身都被跳过；源文件取自 `file` 字段。`file` 和 `-o` 等路径中的
+/// 相对路径都以 `directory` 为基准。
+fn cli_from_compile_db_entry(path: &Path) -> Result<Cli, Box<dyn std::error::Error>> {
+    let entry = compile_db::parse_entry(&fs::read_to_string(path)?)?;
+    let resolve = |path: &str| match &entry.directory {
+        Some(directory) => Path::new(directory).join(path),
+        None => PathBuf::from(path),
+    };
+
+    let command = Cli::command();
+    let compiler = OsString::from(&entry.arguments[0]);
+    let mut args = vec![compiler.clone()];
+    let mut rest = entry.arguments[1..].iter();
+    while let Some(arg) = rest.next() {
+        // 不以 `-` 开头的是源文件，或者被跳过的选项的值
+        let Some(option) = arg.strip_prefix('-').filter(|option| !option.is_empty()) else {
+            continue;
+        };
+        // `--name[=value]` 或 `-Xvalue`
+        let (known, attached) = match option.strip_prefix('-') {
+            Some(long) => {
+                let (name, value) = long
+                    .split_once('=')
+                    .map_or((long, None), |(name, value)| (name, Some(value)));
+                let known = command.get_arguments().find(|a| a.get_long() == Some(name));
+                (known, value)
+            }
+            None => {
+                let mut chars = option.chars();
+                let short = chars.next();
+                let known = command.get_arguments().find(|a| a.get_short() == short);
+                (
+                    known,
+                    Some(chars.as_str()).filter(|value| !value.is_empty()),
+                )
+            }
+        };
+        let Some(known) = known else {
+            if SEPARATE_VALUE_OPTIONS.contains(&arg.as_str()) {
+                rest.next();
+            }
+            continue;
+        };
+
+        let flag = match known.get_long() {
+            Some(long) => format!("--{}", long),
+            None => format!("-{}", known.get_short().unwrap_or_default()),
+        };
+        let mut option_args = vec![OsString::from(flag)];
+        if known.get_action().takes_values() {
+            let Some(value) = attached.or_else(|| rest.next().map(String::as_str)) else {
+                continue;
+            };
+            if known.get_value_hint() == ValueHint::FilePath {
+                option_args.push(resolve(value).into_os_string());
+            } else {
+                option_args.push(OsString::from(value));
+            }
+        } else if attached.is_some() {
+            // 例如 `-cfoo`：本编译器没有这样的选项
+            continue;
+        }
+
+        // 选项名相同但值不被接受（例如 `-Wall`、`-Os`）时也跳过
+        let probe = [compiler.clone()]
+            .into_iter()
+            .chain(option_args.iter().cloned())
+            .chain([OsString::from(&entry.file)]);
+        if Cli::try_parse_from(probe).is_ok() {
+            args.extend(option_args);
+        }
+    }
+    args.push(resolve(&entry.file).into_os_string());
+    Ok(Cli::try_parse_from(args)?)
+}
+
+/// 把 `--eval` 的代码片段包装成 `main` 函数。
+/// 片段中最后一个 `;` 或 `}` 之后的部分被视为表达式，作为 main 的返回值。
+fn wrap_eval_snippet(snippet: &str) -> String {
+    let split = snippet.rfind([';', '}']).map_or(0, |i| i + 1);
+    let (statements, tail) = snippet.split_at(split);
+    let tail = tail.trim();
+    if tail.is_empty() {
+        format!("int main(void) {{\n{}\n}}\n", statements)
+    } else {
+        format!("int main(void) {{\n{}\nreturn {};\n}}\n", statements, tail)
+    }
+}
+
+/// 警告和错误总是输出到 stderr，不受 `--quiet` 影响
+fn print_diagnostic(diagnostic: Diagnostic) {
+    eprintln!("{}", diagnostic);
+}
+
+/// 在临时目录中编译并运行 `--eval` 片段，返回程序的退出码。
+fn run_eval(cli: &Cli, snippet: &str) -> Result<i32, Box<dyn std::error::Error>> {
+    let work_dir = std::env::temp_dir().join(format!("my_c_compiler_eval_{}", std::process::id()));
+    fs::create_dir_all(&work_dir)?;
+    let source_path = work_dir.join("eval.c");
+    fs::write(&source_path, wrap_eval_snippet(snippet))?;
+
+    let mut eval_cli = cli.clone();
+    eval_cli.input_file = Some(source_path);
+    let result = run_pipeline(&eval_cli, &mut print_diagnostic)
+        .and_then(|_| run_executable(&work_dir.join("eval")));
+    fs::remove_dir_all(&work_dir)?;
+    result
+}
+
+/// `--run`：把 `cli.input_file` 编译到临时目录中并运行，返回程序的退出码。
+/// 无论编译和运行是否成功，临时目录都会被删除。
+fn run_input(cli: &Cli) -> Result<i32, Box<dyn std::error::Error>> {
+    let work_dir = std::env::temp_dir().join(format!("my_c_compiler_run_{}", std::process::id()));
+    fs::create_dir_all(&work_dir)?;
+    let exe_path = work_dir.join("a.out");
+
+    let mut run_cli = cli.clone();
+    run_cli.output = Some(exe_path.clone());
+    let result =
+        run_pipeline(&run_cli, &mut print_diagnostic).and_then(|_| run_executable(&exe_path));
+    fs::remove_dir_all(&work_dir)?;
+    result
+}
+
+/// 运行可执行文件，返回它的退出码。
+fn run_executable(exe_path: &Path) -> Result<i32, Box<dyn std::error::Error>> {
+    let status = Command::new(exe_path).status()?;
+    status
+        .code()
+        .ok_or_else(|| "program was terminated by a signal".into())
+}
+
+/// 词法分析并解析一个独立的表达式，打印它的 AST。
+fn parse_only_expression(expr: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let tokens: Vec<Token> = lexer::Lexer::new(expr).collect::<Result<_, _>>()?;
+    let expr_ast = CParser::Parser::new(&tokens).parse_standalone_expression()?;
+    println!("{:#?}", expr_ast);
+    Ok(())
+}
+
+/// 运行编译出的可执行文件，检查它的退出码是否等于 `expected`。
+fn assert_exit_code(cli: &Cli, expected: i32) -> Result<(), Box<dyn std::error::Error>> {
+    let exe_path = output_path(cli)?;
+
+    progress!(cli, "\n9. Running {}...", exe_path.display());
+    let status = Command::new(&exe_path).status()?;
+    match status.code() {
+        Some(code) if code == expected => {
+            progress!(cli, "   ✓ Program exited with expected code {}.", code);
+            Ok(())
+        }
+        Some(code) => Err(format!("expected exit code {}, but got {}", expected, code).into()),
+        None => Err(format!(
+            "expected exit code {}, but the program was terminated by a signal",
+            expected
+        )
+        .into()),
+    }
+}
+
+/// 最终产物的路径：`-o` 指定时直接使用，否则放在源文件旁边，
+/// 可执行文件去掉扩展名，`-c` 的目标文件使用 `.o` 扩展名。
+fn output_path(cli: &Cli) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    if let Some(output) = &cli.output {
+        return Ok(output.clone());
+    }
+    let input_path = cli.input_file.as_ref().ok_or("No input file")?;
+    let file_stem = input_path.file_stem().ok_or("Invalid input file name")?;
+    let parent_dir = input_path.parent().unwrap_or_else(|| Path::new("."));
+    let path = parent_dir.join(file_stem);
+    Ok(if cli.compile_only {
+        path.with_extension("o")
+    } else {
+        path
+    })
+}
+
+/// 命令行的编译选项中交给 driver 的那部分
+fn compile_options(cli: &Cli) -> CompileOptions {
+    let warning_enabled = |name: &str| cli.analyze || cli.warnings.iter().any(|w| w == name);
+    CompileOptions {
+        opt_level: cli.opt_level,
+        warn_uninitialized: warning_enabled("uninitialized"),
+        warn_shadow: warning_enabled("shadow"),
+        lint: cli.analyze,
+        werror: cli.werror,
+        builtins: !cli.no_builtins,
+        implicit_return: !cli.no_implicit_return,
+        record_types: cli.dump_types,
+        max_stack: cli.max_stack,
+        optimizer: Optimizer::with_default_passes(),
+        emit: emit_options(cli),
+    }
+}
+
+fn emit_options(cli: &Cli) -> EmitOptions {
+    EmitOptions {
+        emit_cfi: cli.emit_cfi,
+        cet: cli.cet,
+    }
+}
+
+/// 命令行的编译回调：打印阶段进度，并处理 `--lex`、`--tacky` 等
+/// 在某个阶段之后输出中间结果并停止的选项。
+struct CliHooks<'a> {
+    cli: &'a Cli,
+    /// 未经预处理的源码，`--dump-tokens-with-spans` 直接对它分词
+    original_source: &'a str,
+    on_diagnostic: &'a mut dyn FnMut(Diagnostic),
+    /// `--listing` 按函数收集的内容
+    listing: Option<Listing>,
+    /// 回调中发生的错误；发生错误时编译随即停止
+    error: Option<Box<dyn std::error::Error>>,
+}
+
+impl CliHooks<'_> {
+    fn halt(&self, flag: &str) -> ControlFlow<()> {
+        progress!(self.cli, "\nHalting as requested by {}.", flag);
+        ControlFlow::Break(())
+    }
+
+    /// 直接对原始源文件分词，这样字节范围对应编辑器中打开的文件
+    fn dump_tokens_with_spans(&self) -> Result<(), String> {
+        println!("--- Tokens With Spans ---");
+        for (token, span) in lexer::Lexer::new(self.original_source).tokens_with_spans()? {
+            println!(
+                "{:?} {}:{} {}:{}",
+                token.token_type, span.start, span.end, token.line.number, token.column
+            );
+        }
+        println!("-------------------------");
+        Ok(())
+    }
+}
 
-    // --- STAGE 1 & 2: PREPROCESSING and LEXING ---
-    println!("1. Preprocessing {}...", cli.input_file.display());
-    let input_path = &cli.input_file;
+impl CompileHooks for CliHooks<'_> {
+    fn diagnostic(&mut self, diagnostic: Diagnostic) {
+        (self.on_diagnostic)(diagnostic);
+    }
+
+    fn stage_started(&mut self, stage: Stage) {
+        let cli = self.cli;
+        match stage {
+            Stage::Lex => progress!(cli, "\n2. Lexing source code..."),
+            Stage::Parse => progress!(
+                cli,
+                "\n3. Parsing tokens into C Abstract Syntax Tree (AST)..."
+            ),
+            Stage::Semantics => progress!(cli, "\n4. Performing semantic analysis..."),
+            Stage::Tacky => progress!(
+                cli,
+                "\n5. Generating TACKY Intermediate Representation (IR)..."
+            ),
+            Stage::Codegen => progress!(cli, "\n6. Generating Assembly AST from TACKY IR..."),
+            Stage::Emit => progress!(cli, "\n7. Emitting assembly code from Assembly AST..."),
+        }
+    }
+
+    fn stage_finished(&mut self, output: StageOutput<'_>) -> ControlFlow<()> {
+        let cli = self.cli;
+        match output {
+            StageOutput::Tokens(tokens) => {
+                progress!(
+                    cli,
+                    "   ✓ Lexing successful, found {} tokens.",
+                    tokens.len()
+                );
+                if cli.dump_tokens_with_spans {
+                    if let Err(e) = self.dump_tokens_with_spans() {
+                        self.error = Some(e.into());
+                        return ControlFlow::Break(());
+                    }
+                    return self.halt("--dump-tokens-with-spans");
+                }
+                if cli.lex {
+                    println!(
+                        "--- Generated Tokens ---\n{:#?}\n------------------------",
+                        tokens
+                    );
+                    return self.halt("--lex");
+                }
+            }
+            StageOutput::Ast(c_ast) => {
+                progress!(cli, "   ✓ Parsing successful.");
+                if let Some(listing) = &mut self.listing {
+                    listing.record_start_lines(c_ast, cli.input_file.as_deref());
+                }
+                if cli.parse {
+                    println!(
+                        "--- Generated C AST ---\n{:#?}\n---------------------",
+                        c_ast
+                    );
+                    return self.halt("--parse");
+                }
+            }
+            StageOutput::NamesResolved => {
+                progress!(cli, "   - Pass 1: Identifier resolution complete.");
+            }
+            StageOutput::LabelsResolved => {
+                progress!(cli, "   - Pass 2: Label resolution complete.");
+            }
+            StageOutput::TypesChecked(type_checker) => {
+                progress!(cli, "   - Pass 3: Type checking complete.");
+                if cli.dump_types {
+                    println!(
+                        "--- Expression Types ---\n{}\n------------------------",
+                        type_checker.dump_types()
+                    );
+                    return self.halt("--dump-types");
+                }
+            }
+            StageOutput::LoopsLabeled => {
+                progress!(cli, "   - Pass 4: Loop labeling complete.");
+            }
+            StageOutput::Analyzed(checked_ast) => {
+                progress!(cli, "   ✓ Semantic analysis successful.");
+                if cli.analyze {
+                    return self.halt("--analyze");
+                }
+                if cli.validate {
+                    println!(
+                        "--- Final Checked AST ---\n{:#?}\n---------------------",
+                        checked_ast
+                    );
+                    return self.halt("--validate");
+                }
+            }
+            StageOutput::Tacky(tacky_ir) => {
+                progress!(cli, "   ✓ TACKY IR generation successful.");
+                if cli.opt_level >= 1 {
+                    progress!(
+                        cli,
+                        "   ✓ TACKY optimization (-O{}) complete.",
+                        cli.opt_level
+                    );
+                }
+                if cli.print_temps {
+                    println!("--- Temporaries ---");
+                    println!("{:<24} {:>10}", "function", "temps");
+                    for func in &tacky_ir.functions {
+                        println!("{:<24} {:>10}", func.name, func.temporary_count());
+                    }
+                    println!("-------------------");
+                }
+                if cli.tacky {
+                    println!(
+                        "--- Generated TACKY IR ---\n{:#?}\n------------------------",
+                        tacky_ir
+                    );
+                    return self.halt("--tacky");
+                }
+                if cli.dump_cfg {
+                    for func in &tacky_ir.functions {
+                        print!("{}", Cfg::build(func).to_dot());
+                    }
+                    return self.halt("--dump-cfg");
+                }
+                if let Some(listing) = &mut self.listing {
+                    listing.tacky = tacky_ir
+                        .functions
+                        .iter()
+                        .map(|func| (func.name.clone(), func.to_string()))
+                        .collect();
+                }
+            }
+            StageOutput::Assembly {
+                program,
+                stack_usage,
+            } => {
+                progress!(cli, "   ✓ Assembly AST generation successful.");
+                if let Some(listing) = &mut self.listing {
+                    match emitter::emit_functions(program, &emit_options(cli)) {
+                        Ok(functions) => listing.assembly = functions.into_iter().collect(),
+                        Err(e) => {
+                            self.error = Some(e.into());
+                            return ControlFlow::Break(());
+                        }
+                    }
+                }
+                if cli.print_stack_usage {
+                    println!("--- Stack Usage ---");
+                    println!("{:<24} {:>10}", "function", "bytes");
+                    for (name, bytes) in stack_usage {
+                        println!("{:<24} {:>10}", name, bytes);
+                    }
+                    println!("-------------------");
+                }
+                if cli.codegen {
+                    println!(
+                        "--- Generated Assembly AST ---\n{:#?}\n--------------------------",
+                        program
+                    );
+                    return self.halt("--codegen");
+                }
+            }
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+/// 运行完整的编译流程：预处理，交给 driver 编译为汇编，再由 gcc 汇编和链接。
+/// 警告和错误一产生就交给 `on_diagnostic`，由调用者决定直接输出还是收集起来
+/// （`--diagnostics=json`）。
+fn run_pipeline(
+    cli: &Cli,
+    on_diagnostic: &mut dyn FnMut(Diagnostic),
+) -> Result<(), Box<dyn std::error::Error>> {
+    // --- STAGE 1: PREPROCESSING ---
+    let input_path = cli.input_file.as_ref().ok_or("No input file")?;
+    progress!(cli, "1. Preprocessing {}...", input_path.display());
     if !input_path.exists() {
         return Err(format!("Input file not found: {}", input_path.display()).into());
     }
+    // 在预处理之前检查编码，这样报告的字节位置对应用户自己的源文件
+    let original_source = read_utf8_source(input_path)?;
     let file_stem = input_path.file_stem().ok_or("Invalid input file name")?;
     let parent_dir = input_path.parent().unwrap_or_else(|| Path::new("."));
     let preprocessed_path = parent_dir.join(file_stem).with_extension("i");
     preprocess(input_path, &preprocessed_path)?;
-    let source_code = fs::read_to_string(&preprocessed_path)?;
-
-    println!("\n2. Lexing source code...");
-    let tokens: Vec<Token> = lexer::Lexer::new(&source_code).collect::<Result<_, _>>()?;
-    println!("   ✓ Lexing successful, found {} tokens.", tokens.len());
-    if cli.lex {
-        println!(
-            "--- Generated Tokens ---\n{:#?}\n------------------------",
-            tokens
-        );
-        println!("\nHalting as requested by --lex.");
-        fs::remove_file(&preprocessed_path)?;
-        return Ok(());
-    }
-
-    // --- STAGE 3: PARSING ---
-    println!("\n3. Parsing tokens into C Abstract Syntax Tree (AST)...");
-    let c_ast = CParser::Parser::new(&tokens).parse()?;
-    println!("   ✓ Parsing successful.");
-    if cli.parse {
-        println!(
-            "--- Generated C AST ---\n{:#?}\n---------------------",
-            c_ast
-        );
-        println!("\nHalting as requested by --parse.");
-        fs::remove_file(&preprocessed_path)?;
-        return Ok(());
-    }
+    // 预处理的结果读入内存后就不再需要；先删除它，之后任何一步出错都不会把它留在源文件旁边
+    let source_code = read_utf8_source(&preprocessed_path);
+    fs::remove_file(&preprocessed_path)?;
+    let source_code = source_code?;
 
-    // --- STAGE 4: SEMANTIC ANALYSIS ---
-    println!("\n4. Performing semantic analysis...");
-
-    // --- Pass 1: Identifier Resolution ---
-    let mut validator = Validator::new(&mut id_generator);
-    // validate_program 接受 unchecked AST 并返回一个新的、名字被解析过的 unchecked AST。
-    let name_resolved_ast = validator.validate_program(c_ast)?;
-    println!("   - Pass 1: Identifier resolution complete.");
-    // --- Pass 2: Type Checking ---
-    let mut type_checker = TypeChecker::new();
-    // check_program 接收一个引用，它不修改 AST，但会返回 Result 来报告错误。
-    // 我们必须处理这个 Result！使用 `?` 可以让程序在出错时提前返回。
-    type_checker.check_program(&name_resolved_ast)?;
-    println!("   - Pass 2: Type checking complete.");
-    // 此时，type_checker.symbols 中包含了所有标识符的类型信息，
-    // 未来可以传递给代码生成器。
-    // --- Pass 3: Loop Labeling ---
-    let mut labeler = LoopLabeler::new(&mut id_generator);
-    // label_program 接收 name_resolved_ast 并将其转换为最终的 checked_ast。
-    let checked_ast = labeler.label_program(name_resolved_ast)?;
-    println!("   - Pass 3: Loop labeling complete.");
-    // --- Semantic Analysis Succeeded ---
-    println!("   ✓ Semantic analysis successful.");
-
-    if cli.validate {
-        println!(
-            "--- Final Checked AST ---\n{:#?}\n---------------------",
-            checked_ast
-        );
-        println!("\nHalting as requested by --validate.");
-        fs::remove_file(&preprocessed_path)?;
+    // --- STAGE 2 - 7: COMPILING TO ASSEMBLY ---
+    let mut hooks = CliHooks {
+        cli,
+        original_source: &original_source,
+        on_diagnostic,
+        listing: cli.listing.then(Listing::default),
+        error: None,
+    };
+    let Some(assembly_code) = driver::compile(&source_code, &compile_options(cli), &mut hooks)?
+    else {
+        // 某个 --lex、--tacky 之类的选项要求在中途停止
+        return hooks.error.map_or(Ok(()), Err);
+    };
+    if cli.asm_stdout {
+        print!("{}", assembly_code);
+        progress!(cli, "\nHalting as requested by --asm-stdout.");
         return Ok(());
     }
-    // // --- STAGE 5 & 6 & 7: CODE GENERATION ---
-    println!("\n5. Generating TACKY Intermediate Representation (IR)...");
-    let mut tacky_generator = TackyGenerator::new(&mut id_generator);
-    let tacky_ir = tacky_generator.generate_tacky(checked_ast)?;
-    println!("   ✓ TACKY IR generation successful.");
-    if cli.tacky {
-        println!(
-            "--- Generated TACKY IR ---\n{:#?}\n------------------------",
-            tacky_ir
+    let assembly_path = parent_dir.join(file_stem).with_extension("s");
+    if cli.pipe_asm {
+        // 汇编代码只保存在内存中，稍后通过管道直接交给 gcc
+        progress!(cli, "   ✓ Assembly code emission complete (in memory).");
+    } else {
+        fs::write(&assembly_path, &assembly_code)?;
+        progress!(
+            cli,
+            "   ✓ Assembly code emission complete: {}",
+            assembly_path.display()
         );
-        println!("\nHalting as requested by --tacky.");
-        fs::remove_file(&preprocessed_path)?;
-        return Ok(());
     }
 
-    println!("\n6. Generating Assembly AST from TACKY IR...");
-    let mut asm_generator = AsmGenerator::new();
-    let asm_ast = asm_generator.generate_assembly(tacky_ir)?;
-    println!("   ✓ Assembly AST generation successful.");
-    if cli.codegen {
-        println!(
-            "--- Generated Assembly AST ---\n{:#?}\n--------------------------",
-            asm_ast
-        );
-        println!("\nHalting as requested by --codegen.");
-        fs::remove_file(&preprocessed_path)?;
-        return Ok(());
+    if let Some(listing) = &hooks.listing {
+        let listing_path = parent_dir.join(file_stem).with_extension("lst");
+        fs::write(&listing_path, listing.render(&original_source))?;
+        progress!(cli, "   ✓ Listing written: {}", listing_path.display());
     }
 
-    println!("\n7. Emitting assembly code from Assembly AST...");
-    let assembly_code = emitter::emit_assembly(asm_ast)?;
-    let assembly_path = parent_dir.join(file_stem).with_extension("s");
-    fs::write(&assembly_path, &assembly_code)?;
-    println!(
-        "   ✓ Assembly code emission complete: {}",
-        assembly_path.display()
-    );
-
     // --- STAGE 8: ASSEMBLE or LINK ---
+    let output_path = output_path(cli)?;
     if cli.compile_only {
-        println!("\n8. Assembling to object file (-c flag detected)...");
-        let output_path = parent_dir.join(file_stem).with_extension("o");
-        assemble_to_object(&assembly_path, &output_path)?;
-        println!("   ✓ Assembling complete: {}", output_path.display());
+        progress!(cli, "\n8. Assembling to object file (-c flag detected)...");
+        if cli.pipe_asm {
+            assemble_from_stdin(&assembly_code, &output_path, true)?;
+        } else {
+            assemble_to_object(&assembly_path, &output_path)?;
+        }
+        progress!(cli, "   ✓ Assembling complete: {}", output_path.display());
     } else {
-        println!("\n8. Assembling and linking...");
-        let output_path = parent_dir.join(file_stem);
-        link_to_executable(&assembly_path, &output_path)?;
-        println!(
+        progress!(cli, "\n8. Assembling and linking...");
+        if cli.pipe_asm {
+            assemble_from_stdin(&assembly_code, &output_path, false)?;
+        } else {
+            link_to_executable(&assembly_path, &output_path)?;
+        }
+        progress!(
+            cli,
             "   ✓ Assembling and linking complete: {}",
             output_path.display()
         );
     }
 
     // --- Cleanup ---
-    fs::remove_file(&preprocessed_path)?;
-    if !cli.keep_asm {
+    if cli.pipe_asm {
+        // 没有写出 .s 文件，无需清理
+    } else if !cli.keep_asm {
         if let Err(e) = fs::remove_file(&assembly_path) {
             eprintln!(
                 "Warning: could not remove temporary assembly file '{}': {}",
@@ -194,27 +830,129 @@ fn run_pipeline(cli: &Cli) -> Result<(), Box<dyn std::error::Error>> {
             );
         }
     } else {
-        println!(
+        progress!(
+            cli,
             "   ℹ️ Assembly file kept as requested by --keep-asm: {}",
             assembly_path.display()
         );
     }
 
     if cli.compile_only {
-        println!(
+        progress!(
+            cli,
             "\n✅ Success! Object file created at: {}",
-            parent_dir.join(file_stem).with_extension("o").display()
+            output_path.display()
         );
     } else {
-        println!(
+        progress!(
+            cli,
             "\n✅ Success! Executable created at: {}",
-            parent_dir.join(file_stem).display()
+            output_path.display()
         );
     }
 
     Ok(())
 }
 
+/// 读取一个源文件，遇到非法 UTF-8 时报告出错的字节位置和行号。
+fn read_utf8_source(path: &Path) -> Result<String, Box<dyn std::error::Error>> {
+    let bytes = fs::read(path)?;
+    String::from_utf8(bytes).map_err(|e| {
+        let offset = e.utf8_error().valid_up_to();
+        let bytes = e.as_bytes();
+        let line = bytes[..offset].iter().filter(|&&b| b == b'\n').count() + 1;
+        format!(
+            "{} is not valid UTF-8 at byte {} (line {}, byte 0x{:02x})",
+            path.display(),
+            offset,
+            line,
+            bytes[offset]
+        )
+        .into()
+    })
+}
+
+/// `--listing` 的内容：每个函数的源代码，后面紧跟它生成的 TACKY 和汇编代码，
+/// 便于对照每一步的降级结果。各阶段的产物在被后续阶段消耗之前按函数保存在这里。
+#[derive(Default)]
+struct Listing {
+    /// 每个函数定义在原始源文件中的起始行；定义在头文件中的函数不在其中
+    start_lines: HashMap<String, usize>,
+    /// 按定义顺序排列的 `(函数名, TACKY)`
+    tacky: Vec<(String, String)>,
+    /// 每个函数最终发射的汇编代码
+    assembly: HashMap<String, String>,
+}
+
+impl Listing {
+    /// 记录 `input` 中每个函数定义的起始行。预处理后的行号已经由行标记
+    /// 还原为原始文件中的行号，来自其他文件的函数被跳过。
+    fn record_start_lines(&mut self, program: &unchecked::Program, input: Option<&Path>) {
+        let input = input.map(|path| path.to_string_lossy());
+        for decl in &program.declarations {
+            if let unchecked::Declaration::Function {
+                name,
+                body: Some(_),
+                line,
+                ..
+            } = decl
+                && (line.file.is_none() || line.file.as_deref() == input.as_deref())
+            {
+                self.start_lines.insert(name.clone(), line.number);
+            }
+        }
+    }
+
+    /// 每个函数的源代码从它的起始行一直延伸到下一个函数开始之前；
+    /// 第一个函数之前的行单独列在最前面。
+    fn render(&self, source: &str) -> String {
+        let lines: Vec<&str> = source.lines().collect();
+        let mut starts: Vec<usize> = self.start_lines.values().copied().collect();
+        starts.sort_unstable();
+        let numbered = |out: &mut String, first: usize, end: usize| {
+            let mut end = end.min(lines.len());
+            while end > first && lines[end - 1].trim().is_empty() {
+                end -= 1;
+            }
+            for (i, line) in lines.iter().enumerate().take(end).skip(first) {
+                out.push_str(&format!("{:>4} | {}\n", i + 1, line));
+            }
+        };
+
+        let mut out = String::new();
+        let first_start = starts.first().map_or(lines.len(), |start| {
+            start.saturating_sub(1).min(lines.len())
+        });
+        if lines[..first_start]
+            .iter()
+            .any(|line| !line.trim().is_empty())
+        {
+            out.push_str("=== File scope ===\n");
+            numbered(&mut out, 0, first_start);
+            out.push('\n');
+        }
+        for (name, tacky) in &self.tacky {
+            out.push_str(&format!("=== {} ===\n", name));
+            match self.start_lines.get(name) {
+                Some(&start) => {
+                    let end = starts
+                        .iter()
+                        .find(|&&next| next > start)
+                        .map_or(lines.len(), |next| next - 1);
+                    numbered(&mut out, start.saturating_sub(1), end);
+                }
+                None => out.push_str("     (defined in an included file)\n"),
+            }
+            out.push_str("--- TACKY ---\n");
+            out.push_str(tacky);
+            out.push_str("--- Assembly ---\n");
+            out.push_str(self.assembly.get(name).map_or("", String::as_str));
+            out.push('\n');
+        }
+        out
+    }
+}
+
 fn run_command(command: &mut Command) -> Result<(), Box<dyn std::error::Error>> {
     let status = command.status()?;
     if !status.success() {
@@ -243,6 +981,40 @@ fn link_to_executable(input: &Path, output: &Path) -> Result<(), Box<dyn std::er
     )
 }
 
+/// 通过标准输入把汇编代码交给 gcc，不在磁盘上留下 .s 文件。
+/// `compile_only` 为 true 时只汇编为目标文件，否则同时链接为可执行文件。
+fn assemble_from_stdin(
+    assembly_code: &str,
+    output: &Path,
+    compile_only: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut command = Command::new("gcc");
+    if compile_only {
+        command.arg("-c");
+    } else {
+        command.arg("-no-pie");
+    }
+    command
+        .arg("-x")
+        .arg("assembler")
+        .arg("-")
+        .arg("-o")
+        .arg(output)
+        .stdin(Stdio::piped());
+
+    let mut child = command.spawn()?;
+    child
+        .stdin
+        .take()
+        .ok_or("Failed to open gcc stdin")?
+        .write_all(assembly_code.as_bytes())?;
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(format!("Command `{:?}` failed with status: {}", command, status).into());
+    }
+    Ok(())
+}
+
 fn assemble_to_object(input: &Path, output: &Path) -> Result<(), Box<dyn std::error::Error>> {
     run_command(
         Command::new("gcc")
@@ -3,12 +3,19 @@
 // 从我们的 lexer 模块中导入 Token 和 TokenType
 use crate::{
     ast::unchecked::*,
+    common::SourceLine,
     lexer::{Token, TokenType},
 };
 
+/// 内建的 typedef 名字。与关键字不同，它们和变量、函数共用一个命名空间，
+/// 在被同名的普通标识符遮蔽的作用域中就不再表示类型。
+const BUILTIN_TYPEDEFS: [(&str, CType); 1] = [("bool", CType::Bool)];
+
 pub struct Parser<'a> {
     tokens: &'a [Token],
     position: usize,
+    // 每层作用域中声明的、与内建 typedef 同名的普通标识符
+    shadowed_typedefs: Vec<Vec<String>>,
 }
 
 impl<'a> Parser<'a> {
@@ -17,6 +24,7 @@ impl<'a> Parser<'a> {
         Parser {
             tokens,
             position: 0,
+            shadowed_typedefs: vec![Vec::new()],
         }
     }
 
@@ -32,65 +40,352 @@ impl<'a> Parser<'a> {
         let mut declarations = Vec::new();
         // 循环解析顶层声明，直到 token 流结束
         while self.peek().is_some() {
-            declarations.push(self.parse_declaration()?);
+            declarations.extend(self.parse_declaration()?);
         }
         Ok(Program { declarations })
     }
 
-    /// 解析一个声明（函数或变量）。
-    /// <declaration> ::= "int" <identifier> ( "(" ... | "=" ... | ";" )
-    fn parse_declaration(&mut self) -> Result<Declaration, String> {
-        self.expect_token(TokenType::KeywordInt)?;
+    /// 解析一个独立的表达式（不需要外层函数），用于单独测试表达式解析。
+    /// 整个 token 流必须恰好是一个表达式。
+    pub fn parse_standalone_expression(&mut self) -> Result<Expression, String> {
+        let expr = self.parse_expression(0)?;
+        if let Some(token) = self.peek() {
+            return Err(format!(
+                "Unexpected token {:?} after expression on line {}",
+                token.token_type, token.line
+            ));
+        }
+        Ok(expr)
+    }
+
+    /// 解析一个声明（函数或变量）。变量声明可以有多个声明符，每个声明符产生一个
+    /// `Declaration::Variable`，其他声明只产生一个 `Declaration`。
+    /// <declaration> ::= ["extern" | "static"] <type-specifier> <identifier> ( "(" ... | "=" ... | "," ... | ";" )
+    fn parse_declaration(&mut self) -> Result<Vec<Declaration>, String> {
+        let line = self
+            .peek()
+            .map_or_else(SourceLine::default, |t| t.line.clone());
+        // 函数默认就具有外部链接，`extern` 对函数声明没有额外含义
+        let is_extern = self
+            .peek()
+            .is_some_and(|t| t.token_type == TokenType::KeywordExtern);
+        let storage_class = self
+            .peek()
+            .is_some_and(|t| t.token_type == TokenType::KeywordStatic)
+            .then_some(StorageClass::Static);
+        if is_extern || storage_class.is_some() {
+            self.consume();
+        }
+        let var_type = self.parse_type_specifier()?;
+
+        // `struct Tag { ... };` 声明的是一个结构体类型，而不是变量
+        if let CType::Struct(tag) = &var_type
+            && !is_extern
+            && storage_class.is_none()
+            && self
+                .peek()
+                .is_some_and(|t| t.token_type == TokenType::OpenBrace)
+        {
+            return Ok(vec![self.parse_struct_declaration(tag.clone())?]);
+        }
+
         let name = self.expect_identifier()?;
+        self.declare_name(&name);
 
         // 通过预读下一个 token 来区分是变量还是函数
         if self
             .peek()
-            .map_or(false, |t| t.token_type == TokenType::OpenParen)
+            .is_some_and(|t| t.token_type == TokenType::OpenParen)
         {
             // 下一个是 '(', 这是一个函数声明
-            self.parse_function_declaration(name)
+            // 结构体按值返回需要额外的调用约定，目前不支持
+            if matches!(var_type, CType::Struct(_)) {
+                return Err(format!(
+                    "Function '{}' cannot return a struct on line {}",
+                    name, line
+                ));
+            }
+            // 具有内部链接的函数需要区分符号的可见性，目前不支持
+            if storage_class.is_some() {
+                return Err(format!(
+                    "'static' function '{}' is not supported on line {}",
+                    name, line
+                ));
+            }
+            Ok(vec![self.parse_function_declaration(name, var_type, line)?])
+        } else if is_extern {
+            Err(format!(
+                "'extern' variable '{}' is not supported on line {}",
+                name, line
+            ))
         } else {
             // 否则，这是一个变量声明
-            self.parse_variable_declaration(name)
+            self.parse_variable_declaration(name, var_type, storage_class)
+        }
+    }
+
+    /// 解析结构体声明的成员列表 (已经消费了 "struct" 和标签)。
+    /// <struct-declaration> ::= "{" <member-declaration> { <member-declaration> } "}" ";"
+    /// <member-declaration> ::= <type-specifier> <identifier> ";"
+    fn parse_struct_declaration(&mut self, tag: String) -> Result<Declaration, String> {
+        self.expect_token(TokenType::OpenBrace)?;
+        let mut members = Vec::new();
+        while self
+            .peek()
+            .is_some_and(|t| t.token_type != TokenType::CloseBrace)
+        {
+            let line = self
+                .peek()
+                .map_or_else(SourceLine::default, |t| t.line.clone());
+            let member_type = self.parse_type_specifier()?;
+            if matches!(member_type, CType::Struct(_)) {
+                return Err(format!(
+                    "Nested struct members are not supported on line {}",
+                    line
+                ));
+            }
+            let name = self.expect_identifier()?;
+            self.expect_token(TokenType::Semicolon)?;
+            members.push(MemberDeclaration { name, member_type });
+        }
+        let close = self.expect_token(TokenType::CloseBrace)?;
+        if members.is_empty() {
+            return Err(format!(
+                "struct '{}' must have at least one member on line {}",
+                tag, close.line
+            ));
+        }
+        self.expect_token(TokenType::Semicolon)?;
+        Ok(Declaration::Struct { tag, members })
+    }
+
+    /// 解析一个类型说明符。
+    /// <type-specifier> ::= "int" | "long" | "long" "long" | "unsigned" ["int" | "long" ["long"]] | "_Bool" | "char"
+    ///                    | "struct" <identifier> | <typedef-name>
+    fn parse_type_specifier(&mut self) -> Result<CType, String> {
+        if let Some(typedef_type) = self.peek().and_then(|t| self.typedef_type(t)) {
+            self.consume();
+            return Ok(typedef_type);
         }
+        match self.peek().map(|t| &t.token_type) {
+            Some(TokenType::KeywordStruct) => {
+                self.consume();
+                Ok(CType::Struct(self.expect_identifier()?))
+            }
+            Some(TokenType::KeywordInt) => {
+                self.consume();
+                Ok(CType::Int)
+            }
+            Some(TokenType::KeywordBool) => {
+                self.consume();
+                Ok(CType::Bool)
+            }
+            Some(TokenType::KeywordChar) => {
+                self.consume();
+                Ok(CType::Char)
+            }
+            // 在 x86-64 System V 上 long 和 long long 都是 64 位
+            Some(TokenType::KeywordLong) => {
+                let line = self
+                    .consume()
+                    .map_or_else(SourceLine::default, |t| t.line.clone());
+                let is_long = |t: &Token| t.token_type == TokenType::KeywordLong;
+                if self.peek().is_some_and(is_long) {
+                    self.consume();
+                    if self.peek().is_some_and(is_long) {
+                        return Err(format!("'long long long' is too long on line {}", line));
+                    }
+                }
+                Ok(CType::Long)
+            }
+            Some(TokenType::KeywordUnsigned) => {
+                let line = self
+                    .consume()
+                    .map_or_else(SourceLine::default, |t| t.line.clone());
+                match self.peek().map(|t| &t.token_type) {
+                    Some(TokenType::KeywordInt) => {
+                        self.consume();
+                    }
+                    // `unsigned long` 和 `unsigned long long` 都是 64 位
+                    Some(TokenType::KeywordLong) => {
+                        self.consume();
+                        let is_long = |t: &Token| t.token_type == TokenType::KeywordLong;
+                        if self.peek().is_some_and(is_long) {
+                            self.consume();
+                            if self.peek().is_some_and(is_long) {
+                                return Err(format!(
+                                    "'long long long' is too long on line {}",
+                                    line
+                                ));
+                            }
+                        }
+                        return Ok(CType::ULong);
+                    }
+                    _ => {}
+                }
+                Ok(CType::UInt)
+            }
+            Some(other) => Err(format!("Expected a type specifier, but found {:?}", other)),
+            None => Err("Expected a type specifier, but found end of input.".to_string()),
+        }
+    }
+
+    /// 判断当前 token 是否是一个类型说明符（即是否是一个声明的开始）。
+    fn peek_is_type_specifier(&self) -> bool {
+        self.peek().is_some_and(|t| self.is_type_specifier(t))
     }
 
-    /// 解析一个函数声明 (已经消费了 "int" 和 identifier)。
+    /// 判断一个 token 是否是类型说明符的开始。
+    fn is_type_specifier(&self, token: &Token) -> bool {
+        matches!(
+            token.token_type,
+            TokenType::KeywordInt
+                | TokenType::KeywordLong
+                | TokenType::KeywordUnsigned
+                | TokenType::KeywordBool
+                | TokenType::KeywordChar
+                | TokenType::KeywordStruct
+        ) || self.typedef_type(token).is_some()
+    }
+
+    /// 如果 token 是当前作用域中可见的内建 typedef 名字，返回它表示的类型。
+    fn typedef_type(&self, token: &Token) -> Option<CType> {
+        let TokenType::Identifier(name) = &token.token_type else {
+            return None;
+        };
+        if self.shadowed_typedefs.iter().flatten().any(|n| n == name) {
+            return None;
+        }
+        BUILTIN_TYPEDEFS
+            .iter()
+            .find(|(typedef_name, _)| typedef_name == name)
+            .map(|(_, ctype)| ctype.clone())
+    }
+
+    /// 在当前作用域中声明一个普通标识符；与内建 typedef 同名时遮蔽它。
+    fn declare_name(&mut self, name: &str) {
+        if BUILTIN_TYPEDEFS
+            .iter()
+            .any(|(typedef_name, _)| *typedef_name == name)
+        {
+            self.shadowed_typedefs
+                .last_mut()
+                .expect("file scope is never popped")
+                .push(name.to_string());
+        }
+    }
+
+    /// 解析一个函数声明 (已经消费了返回类型和 identifier)。
     /// <function-declaration> ::= "(" <param-list> ")" ( <block> | ";" )
-    fn parse_function_declaration(&mut self, name: String) -> Result<Declaration, String> {
+    fn parse_function_declaration(
+        &mut self,
+        name: String,
+        return_type: CType,
+        line: SourceLine,
+    ) -> Result<Declaration, String> {
         self.expect_token(TokenType::OpenParen)?;
-        let params = self.parse_param_list()?;
+        // 参数与函数体共用一个作用域
+        self.shadowed_typedefs.push(Vec::new());
+        let (param_types, params): (Vec<CType>, Vec<String>) =
+            self.parse_param_list()?.into_iter().unzip();
         self.expect_token(TokenType::CloseParen)?;
 
         // 函数声明后面可以是函数体 '{...}' 或一个分号 ';' (函数原型)
         let body = if self
             .peek()
-            .map_or(false, |t| t.token_type == TokenType::OpenBrace)
+            .is_some_and(|t| t.token_type == TokenType::OpenBrace)
         {
+            // 只有原型可以省略参数名，函数定义需要用名字访问参数
+            if params.iter().any(String::is_empty) {
+                return Err(format!(
+                    "Parameter name omitted in definition of function '{}' on line {}",
+                    name, line
+                ));
+            }
             Some(self.parse_block()?)
         } else {
             self.expect_token(TokenType::Semicolon)?;
             None
         };
+        self.shadowed_typedefs.pop();
 
-        Ok(Declaration::Function { name, params, body })
+        Ok(Declaration::Function {
+            name,
+            params,
+            param_types,
+            return_type,
+            body,
+            line,
+        })
+    }
+
+    /// 解析一个变量声明 (已经消费了类型说明符和第一个 identifier)。
+    /// 逗号分隔的每个声明符共享同一个类型和存储类，按出现顺序返回。
+    /// <variable-declaration> ::= [ "=" <initializer> ] { "," <identifier> [ "=" <initializer> ] } ";"
+    fn parse_variable_declaration(
+        &mut self,
+        mut name: String,
+        var_type: CType,
+        storage_class: Option<StorageClass>,
+    ) -> Result<Vec<Declaration>, String> {
+        let mut declarations = Vec::new();
+        loop {
+            let init = if self
+                .peek()
+                .is_some_and(|t| t.token_type == TokenType::Assign)
+            {
+                self.consume(); // 消费 '='
+                Some(self.parse_initializer()?)
+            } else {
+                None
+            };
+            declarations.push(Declaration::Variable {
+                name,
+                init,
+                var_type: var_type.clone(),
+                storage_class,
+            });
+            if !self
+                .peek()
+                .is_some_and(|t| t.token_type == TokenType::Comma)
+            {
+                break;
+            }
+            self.consume(); // 消费 ','
+            name = self.expect_identifier()?;
+            self.declare_name(&name);
+        }
+        self.expect_token(TokenType::Semicolon)?;
+        Ok(declarations)
     }
 
-    /// 解析一个变量声明 (已经消费了 "int" 和 identifier)。
-    /// <variable-declaration> ::= [ "=" <expression> ] ";"
-    fn parse_variable_declaration(&mut self, name: String) -> Result<Declaration, String> {
-        let init = if self
+    /// 解析标量的初始化器。标量也可以用只含一个元素的花括号列表初始化。
+    /// <initializer> ::= <expression> | "{" <expression> [","] "}"
+    fn parse_initializer(&mut self) -> Result<Expression, String> {
+        if !self
             .peek()
-            .map_or(false, |t| t.token_type == TokenType::Assign)
+            .is_some_and(|t| t.token_type == TokenType::OpenBrace)
         {
-            self.consume(); // 消费 '='
-            Some(self.parse_expression(0)?)
-        } else {
-            None
-        };
-        self.expect_token(TokenType::Semicolon)?;
-        Ok(Declaration::Variable { name, init })
+            return self.parse_assignment_expression();
+        }
+        self.consume(); // 消费 '{'
+        let init = self.parse_assignment_expression()?;
+        if self
+            .peek()
+            .is_some_and(|t| t.token_type == TokenType::Comma)
+        {
+            self.consume(); // 允许结尾的逗号
+            if let Some(token) = self.peek()
+                && token.token_type != TokenType::CloseBrace
+            {
+                return Err(format!(
+                    "too many initializers for scalar on line {}",
+                    token.line
+                ));
+            }
+        }
+        self.expect_token(TokenType::CloseBrace)?;
+        Ok(init)
     }
 
     // ===================================================================
@@ -103,35 +398,46 @@ impl<'a> Parser<'a> {
     /// <block> ::= "{" {<block-item>} "}"
     fn parse_block(&mut self) -> Result<Block, String> {
         self.expect_token(TokenType::OpenBrace)?;
+        self.shadowed_typedefs.push(Vec::new());
         let mut items = Vec::new();
         while self
             .peek()
-            .map_or(false, |t| t.token_type != TokenType::CloseBrace)
+            .is_some_and(|t| t.token_type != TokenType::CloseBrace)
         {
-            items.push(self.parse_block_item()?);
+            items.extend(self.parse_block_item()?);
         }
         self.expect_token(TokenType::CloseBrace)?;
+        self.shadowed_typedefs.pop();
         Ok(Block { blocks: items })
     }
 
-    /// 解析代码块中的一项（可以是声明或语句）。
+    /// 解析代码块中的一项（可以是声明或语句）。有多个声明符的声明产生多项。
     /// <block-item> ::= <statement> | <declaration>
-    fn parse_block_item(&mut self) -> Result<BlockItem, String> {
-        if self
-            .peek()
-            .map_or(false, |t| t.token_type == TokenType::KeywordInt)
+    fn parse_block_item(&mut self) -> Result<Vec<BlockItem>, String> {
+        if self.peek_is_type_specifier()
+            || self.peek().is_some_and(|t| {
+                matches!(
+                    t.token_type,
+                    TokenType::KeywordExtern | TokenType::KeywordStatic
+                )
+            })
         {
-            // 'int' 关键字开头，必定是声明
-            self.parse_declaration().map(BlockItem::D)
+            // 类型说明符或存储类说明符开头，必定是声明
+            Ok(self
+                .parse_declaration()?
+                .into_iter()
+                .map(BlockItem::D)
+                .collect())
         } else {
             // 否则，是语句
-            self.parse_statement().map(BlockItem::S)
+            Ok(vec![BlockItem::S(self.parse_statement()?)])
         }
     }
 
     /// 解析一个语句。
     /// <statement> ::= <if-stmt> | <for-stmt> | <while-stmt> | <do-while-stmt>
     ///               | <return-stmt> | <break-stmt> | <continue-stmt>
+    ///               | "goto" <identifier> ";" | <identifier> ":" <statement>
     ///               | <block> | [<expression>] ";"
     fn parse_statement(&mut self) -> Result<Statement, String> {
         if let Some(token) = self.peek() {
@@ -156,6 +462,24 @@ impl<'a> Parser<'a> {
                     self.expect_token(TokenType::Semicolon)?;
                     Ok(Statement::Continue)
                 }
+                TokenType::KeywordGoto => {
+                    self.consume(); // 消费 "goto"
+                    let label = self.expect_identifier()?;
+                    self.expect_token(TokenType::Semicolon)?;
+                    Ok(Statement::Goto(label))
+                }
+                // 标识符后面紧跟 `:` 是标签，而不是表达式的开头
+                TokenType::Identifier(_)
+                    if self
+                        .tokens
+                        .get(self.position + 1)
+                        .is_some_and(|t| t.token_type == TokenType::Colon) =>
+                {
+                    let name = self.expect_identifier()?;
+                    self.consume(); // 消费 ":"
+                    let stmt = Box::new(self.parse_statement()?);
+                    Ok(Statement::Labeled { name, stmt })
+                }
                 TokenType::OpenBrace => self.parse_block().map(Statement::Compound),
                 TokenType::Semicolon => {
                     self.consume(); // 消费 ";"
@@ -184,7 +508,7 @@ impl<'a> Parser<'a> {
 
         let else_stat = if self
             .peek()
-            .map_or(false, |t| t.token_type == TokenType::KeywordElse)
+            .is_some_and(|t| t.token_type == TokenType::KeywordElse)
         {
             self.consume(); // 消费 "else"
             Some(Box::new(self.parse_statement()?))
@@ -204,14 +528,23 @@ impl<'a> Parser<'a> {
     fn parse_for_statement(&mut self) -> Result<Statement, String> {
         self.expect_token(TokenType::KeywordFor)?;
         self.expect_token(TokenType::OpenParen)?;
+        // 初始化部分声明的变量只在 for 语句内可见
+        self.shadowed_typedefs.push(Vec::new());
 
         // 解析初始化部分
-        let init = if self
-            .peek()
-            .map_or(false, |t| t.token_type == TokenType::KeywordInt)
-        {
+        let init = if self.peek_is_type_specifier() {
             // for (int i = 0; ... )
-            let decl = self.parse_declaration()?;
+            let line = self
+                .peek()
+                .map_or_else(SourceLine::default, |t| t.line.clone());
+            let mut decls = self.parse_declaration()?;
+            if decls.len() > 1 {
+                return Err(format!(
+                    "Multiple declarators in a for loop initializer are not supported on line {}",
+                    line
+                ));
+            }
+            let decl = decls.pop().unwrap();
             // for 循环的初始化器中不允许函数声明
             if let Declaration::Function { .. } = &decl {
                 return Err(
@@ -221,7 +554,7 @@ impl<'a> Parser<'a> {
             Some(Box::new(BlockItem::D(decl)))
         } else if self
             .peek()
-            .map_or(false, |t| t.token_type == TokenType::Semicolon)
+            .is_some_and(|t| t.token_type == TokenType::Semicolon)
         {
             // for ( ; ... )
             self.consume(); // 消费 ";"
@@ -236,7 +569,7 @@ impl<'a> Parser<'a> {
         // 解析条件部分
         let condition = if self
             .peek()
-            .map_or(false, |t| t.token_type == TokenType::Semicolon)
+            .is_some_and(|t| t.token_type == TokenType::Semicolon)
         {
             None // for (...; ; ...)
         } else {
@@ -247,7 +580,7 @@ impl<'a> Parser<'a> {
         // 解析迭代表达式部分
         let post = if self
             .peek()
-            .map_or(false, |t| t.token_type == TokenType::CloseParen)
+            .is_some_and(|t| t.token_type == TokenType::CloseParen)
         {
             None // for (...; ...; )
         } else {
@@ -257,6 +590,7 @@ impl<'a> Parser<'a> {
 
         // 解析循环体
         let body = Box::new(self.parse_statement()?);
+        self.shadowed_typedefs.pop();
 
         Ok(Statement::For {
             init,
@@ -299,6 +633,7 @@ impl<'a> Parser<'a> {
 
     /// 使用“优先级爬升法”解析表达式。
     /// <expression> ::= <factor> { <binop> <expression> } | <assignment> | <conditional>
+    ///                | <expression> "," <expression>
     fn parse_expression(&mut self, min_precedence: u8) -> Result<Expression, String> {
         let mut left = self.parse_factor()?;
 
@@ -341,6 +676,11 @@ impl<'a> Parser<'a> {
                     left: Box::new(left),
                     right: Box::new(right),
                 };
+            } else if next_token.token_type == TokenType::Comma {
+                left = Expression::Comma {
+                    left: Box::new(left),
+                    right: Box::new(right),
+                };
             } else {
                 let op = self.token_to_binary_operator(&next_token.token_type)?;
                 left = Expression::Binary {
@@ -354,6 +694,12 @@ impl<'a> Parser<'a> {
         Ok(left)
     }
 
+    /// 解析一个不含（顶层）逗号运算符的表达式，即 C 语法中的 assignment-expression。
+    /// 函数实参和初始化器中的逗号是分隔符，不能被当作运算符吃掉。
+    fn parse_assignment_expression(&mut self) -> Result<Expression, String> {
+        self.parse_expression(Self::get_precedence(&TokenType::Assign))
+    }
+
     // ===================================================================
     //  4. 原子项与辅助解析 (Factors & Parsing Helpers)
     // ===================================================================
@@ -361,24 +707,35 @@ impl<'a> Parser<'a> {
     // ===================================================================
 
     /// 解析一个“因子”，即表达式中的最小单元。
-    /// <factor> ::= <int> | <identifier> [ "(" <arg-list> ")" ] | <unop> <factor> | "(" <expression> ")"
+    /// <factor> ::= <unop> <factor> | "sizeof" <factor> | "sizeof" "(" <type-specifier> ")"
+    ///            | <postfix-exp>
+    /// <primary-exp> ::= <int> | <identifier> [ "(" <arg-list> ")" ] | "(" <expression> ")"
     fn parse_factor(&mut self) -> Result<Expression, String> {
         let next_token = self
             .peek()
             .cloned()
             .ok_or_else(|| "Unexpected end of input, expected a factor.".to_string())?;
 
-        match &next_token.token_type {
+        let primary = match &next_token.token_type {
             TokenType::IntegerConstant(val) => {
                 self.consume();
                 Ok(Expression::Constant(*val))
             }
+            TokenType::LongConstant(val) => {
+                self.consume();
+                Ok(Expression::LongConstant(*val))
+            }
+            TokenType::UIntConstant(val) => {
+                self.consume();
+                Ok(Expression::UIntConstant(*val))
+            }
             TokenType::Identifier(name) => {
+                let line = next_token.line.clone();
                 // 需要预读一个 token 来判断是变量还是函数调用
                 if self
                     .tokens
                     .get(self.position + 1)
-                    .map_or(false, |t| t.token_type == TokenType::OpenParen)
+                    .is_some_and(|t| t.token_type == TokenType::OpenParen)
                 {
                     // 是函数调用
                     self.consume(); // 消费 identifier
@@ -388,23 +745,57 @@ impl<'a> Parser<'a> {
                     Ok(Expression::FunctionCall {
                         name: name.clone(),
                         args,
+                        line,
                     })
                 } else {
                     // 是变量
                     self.consume();
-                    Ok(Expression::Var(name.clone()))
+                    Ok(Expression::Var {
+                        name: name.clone(),
+                        line,
+                    })
                 }
             }
             // 一元运算符
             TokenType::Minus | TokenType::Tilde | TokenType::Not => {
                 self.consume();
                 let operator = self.token_to_unary_operator(&next_token.token_type)?;
-                // 一元运算符有最高优先级，因此直接递归解析其后的因子
+                // 一元运算符的优先级仅次于后缀运算符，因此直接递归解析其后的因子
                 let expression = self.parse_factor()?;
-                Ok(Expression::Unary {
+                return Ok(Expression::Unary {
                     operator,
                     expression: Box::new(expression),
-                })
+                });
+            }
+            // `sizeof(type)` 与 `sizeof(x)` 都以 '(' 开头，
+            // 需要再预读一个 token：是类型说明符时按类型解析，否则是括号表达式
+            TokenType::KeywordSizeof => {
+                self.consume(); // 消费 "sizeof"
+                let is_type = self
+                    .peek()
+                    .is_some_and(|t| t.token_type == TokenType::OpenParen)
+                    && self
+                        .tokens
+                        .get(self.position + 1)
+                        .is_some_and(|t| self.is_type_specifier(t));
+                let operand = if is_type {
+                    self.consume(); // 消费 '('
+                    let target_type = self.parse_type_specifier()?;
+                    self.expect_token(TokenType::CloseParen)?;
+                    SizeOfOperand::Type(target_type)
+                } else {
+                    SizeOfOperand::Expression(Box::new(self.parse_factor()?))
+                };
+                return Ok(Expression::SizeOf(operand));
+            }
+            // 前缀自增/自减
+            TokenType::Increment | TokenType::Decrement => {
+                self.consume();
+                let expression = self.parse_factor()?;
+                return Ok(Expression::Prefix {
+                    operator: Self::token_to_inc_dec_operator(&next_token.token_type),
+                    expression: Box::new(expression),
+                });
             }
             // 括号表达式
             TokenType::OpenParen => {
@@ -417,20 +808,55 @@ impl<'a> Parser<'a> {
                 "Unexpected token {:?}, expected a factor.",
                 next_token.token_type
             )),
+        }?;
+        self.parse_postfix(primary)
+    }
+
+    /// 解析跟在基本表达式后面的后缀运算符。
+    /// <postfix-exp> ::= <primary-exp> { "." <identifier> | "++" | "--" }
+    fn parse_postfix(&mut self, mut expr: Expression) -> Result<Expression, String> {
+        loop {
+            match self.peek().map(|t| &t.token_type) {
+                Some(TokenType::Dot) => {
+                    self.consume(); // 消费 '.'
+                    let member = self.expect_identifier()?;
+                    expr = Expression::Member {
+                        base: Box::new(expr),
+                        member,
+                    };
+                }
+                Some(token_type @ (TokenType::Increment | TokenType::Decrement)) => {
+                    let operator = Self::token_to_inc_dec_operator(token_type);
+                    self.consume();
+                    expr = Expression::Postfix {
+                        operator,
+                        expression: Box::new(expr),
+                    };
+                }
+                _ => return Ok(expr),
+            }
+        }
+    }
+
+    fn token_to_inc_dec_operator(token_type: &TokenType) -> IncDecOperator {
+        match token_type {
+            TokenType::Increment => IncDecOperator::Increment,
+            _ => IncDecOperator::Decrement,
         }
     }
 
     /// 解析函数参数列表 (声明时使用)。
-    /// <param-list> ::= "void" | [ "int" <identifier> { "," "int" <identifier> } ]
-    fn parse_param_list(&mut self) -> Result<Vec<String>, String> {
+    /// <param-list> ::= "void" | [ <param> { "," <param> } ]
+    /// <param> ::= <type-specifier> [ <identifier> ]
+    fn parse_param_list(&mut self) -> Result<Vec<(CType, String)>, String> {
         if self
             .peek()
-            .map_or(false, |t| t.token_type == TokenType::KeywordVoid)
+            .is_some_and(|t| t.token_type == TokenType::KeywordVoid)
         {
             self.consume(); // 消费 "void"
             if self
                 .peek()
-                .map_or(true, |t| t.token_type != TokenType::CloseParen)
+                .is_none_or(|t| t.token_type != TokenType::CloseParen)
             {
                 return Err("Expected ')' after 'void' in parameter list.".to_string());
             }
@@ -439,48 +865,68 @@ impl<'a> Parser<'a> {
 
         if self
             .peek()
-            .map_or(false, |t| t.token_type == TokenType::CloseParen)
+            .is_some_and(|t| t.token_type == TokenType::CloseParen)
         {
             return Ok(Vec::new()); // 空参数列表
         }
 
-        let mut params = Vec::new();
-        // 第一个参数
-        self.expect_token(TokenType::KeywordInt)?;
-        params.push(self.expect_identifier()?);
-        // 后续参数
+        let mut params = vec![self.parse_param()?];
         while self
             .peek()
-            .map_or(false, |t| t.token_type == TokenType::Comma)
+            .is_some_and(|t| t.token_type == TokenType::Comma)
         {
             self.consume(); // 消费 ','
-            self.expect_token(TokenType::KeywordInt)?;
-            params.push(self.expect_identifier()?);
+            params.push(self.parse_param()?);
         }
 
         Ok(params)
     }
 
+    /// 解析一个参数。原型中的参数可以省略名字，此时名字为空字符串。
+    /// 结构体参数的传递方式不同于标量，目前不支持。
+    fn parse_param(&mut self) -> Result<(CType, String), String> {
+        let line = self
+            .peek()
+            .map_or_else(SourceLine::default, |t| t.line.clone());
+        let param_type = self.parse_type_specifier()?;
+        if matches!(param_type, CType::Struct(_)) {
+            return Err(format!(
+                "Struct parameters are not supported on line {}",
+                line
+            ));
+        }
+        let name = if self
+            .peek()
+            .is_some_and(|t| matches!(t.token_type, TokenType::Identifier(_)))
+        {
+            self.expect_identifier()?
+        } else {
+            String::new()
+        };
+        self.declare_name(&name);
+        Ok((param_type, name))
+    }
+
     /// 解析函数实参列表 (调用时使用)。
     /// <argument-list> ::= [ <expression> { "," <expression> } ]
     fn parse_argument_list(&mut self) -> Result<Vec<Expression>, String> {
         if self
             .peek()
-            .map_or(false, |t| t.token_type == TokenType::CloseParen)
+            .is_some_and(|t| t.token_type == TokenType::CloseParen)
         {
             return Ok(Vec::new()); // 空参数列表
         }
 
         let mut args = Vec::new();
         // 第一个参数
-        args.push(self.parse_expression(0)?);
+        args.push(self.parse_assignment_expression()?);
         // 后续参数
         while self
             .peek()
-            .map_or(false, |t| t.token_type == TokenType::Comma)
+            .is_some_and(|t| t.token_type == TokenType::Comma)
         {
             self.consume(); // 消费 ','
-            args.push(self.parse_expression(0)?);
+            args.push(self.parse_assignment_expression()?);
         }
 
         Ok(args)
@@ -509,8 +955,8 @@ impl<'a> Parser<'a> {
         match self.peek() {
             Some(token) if token.token_type == expected_type => Ok(self.consume().unwrap()),
             Some(token) => Err(format!(
-                "Expected token {:?}, but found {:?} on line {}",
-                expected_type, token.token_type, token.line
+                "Expected token {:?}, but found {:?} on line {}, column {}",
+                expected_type, token.token_type, token.line, token.column
             )),
             None => Err(format!(
                 "Expected token {:?}, but found end of input.",
@@ -535,15 +981,20 @@ impl<'a> Parser<'a> {
     /// 获取一个二元运算符的优先级。
     fn get_precedence(token_type: &TokenType) -> u8 {
         match token_type {
-            TokenType::Assign => 1,       // 右结合
+            TokenType::Comma => 1,        // 最低优先级，左结合
+            TokenType::Assign => 2,       // 右结合
             TokenType::QuestionMark => 3, // 右结合 (三元)
             TokenType::Or => 5,
             TokenType::And => 10,
+            TokenType::Pipe => 15,
+            TokenType::Caret => 20,
+            TokenType::Ampersand => 25,
             TokenType::Equal | TokenType::NotEqual => 30,
             TokenType::Less
             | TokenType::LessEqual
             | TokenType::Greater
             | TokenType::GreaterEqual => 35,
+            TokenType::ShiftLeft | TokenType::ShiftRight => 40,
             TokenType::Plus | TokenType::Minus => 45,
             TokenType::Asterisk | TokenType::Slash | TokenType::Percent => 50,
             _ => 0, // 0 表示不是二元运算符或不参与优先级比较
@@ -558,6 +1009,11 @@ impl<'a> Parser<'a> {
             TokenType::Asterisk => Ok(BinaryOperator::Multiply),
             TokenType::Slash => Ok(BinaryOperator::Divide),
             TokenType::Percent => Ok(BinaryOperator::Remainder),
+            TokenType::Ampersand => Ok(BinaryOperator::BitAnd),
+            TokenType::Pipe => Ok(BinaryOperator::BitOr),
+            TokenType::Caret => Ok(BinaryOperator::BitXor),
+            TokenType::ShiftLeft => Ok(BinaryOperator::ShiftLeft),
+            TokenType::ShiftRight => Ok(BinaryOperator::ShiftRight),
             TokenType::And => Ok(BinaryOperator::And),
             TokenType::Or => Ok(BinaryOperator::Or),
             TokenType::Equal => Ok(BinaryOperator::Equal),
@@ -825,6 +1281,7 @@ mod tests {
             name,
             params,
             body: None,
+            ..
         } = &program.declarations[0]
         {
             assert_eq!(name, "add");
@@ -867,4 +1324,443 @@ mod tests {
 
         println!("\n--- Function Declaration and Call Test Passed! ---");
     }
+
+    #[test]
+    fn test_standalone_expression_precedence() {
+        let tokens: Vec<Token> = Lexer::new("1 + 2 * 3").collect::<Result<_, _>>().unwrap();
+        let expr = Parser::new(&tokens)
+            .parse_standalone_expression()
+            .expect("Parsing failed");
+
+        // `*` 比 `+` 绑定得更紧：1 + (2 * 3)
+        assert_eq!(
+            expr,
+            Expression::Binary {
+                operator: BinaryOperator::Add,
+                left: Box::new(Expression::Constant(1)),
+                right: Box::new(Expression::Binary {
+                    operator: BinaryOperator::Multiply,
+                    left: Box::new(Expression::Constant(2)),
+                    right: Box::new(Expression::Constant(3)),
+                }),
+            }
+        );
+
+        // 表达式后面多余的 token 是错误
+        let tokens: Vec<Token> = Lexer::new("1 + 2 )").collect::<Result<_, _>>().unwrap();
+        assert!(Parser::new(&tokens).parse_standalone_expression().is_err());
+    }
+
+    #[test]
+    fn test_long_long_is_long() {
+        let parse_source = |source: &str| {
+            let tokens: Vec<Token> = Lexer::new(source).collect::<Result<_, _>>().unwrap();
+            Parser::new(&tokens).parse()
+        };
+
+        for (source, expected) in [
+            ("int main(void) { long x = 5; return x; }", CType::Long),
+            ("int main(void) { long long x = 5; return x; }", CType::Long),
+            (
+                "int main(void) { unsigned long x = 5; return x; }",
+                CType::ULong,
+            ),
+            (
+                "int main(void) { unsigned long long x = 5; return x; }",
+                CType::ULong,
+            ),
+        ] {
+            let program = parse_source(source).expect("Parsing failed");
+            let Declaration::Function {
+                body: Some(body), ..
+            } = &program.declarations[0]
+            else {
+                panic!("Expected a function definition for 'main'.");
+            };
+            assert!(matches!(
+                &body.blocks[0],
+                BlockItem::D(Declaration::Variable { var_type, .. }) if *var_type == expected
+            ));
+        }
+
+        let err = parse_source("int main(void) {\n long long long x; return 0; }").unwrap_err();
+        assert_eq!(err, "'long long long' is too long on line 2");
+        let err = parse_source("int main(void) {\n unsigned long long long x; }").unwrap_err();
+        assert_eq!(err, "'long long long' is too long on line 2");
+
+        // 行标记之后的错误指向原始文件中的行
+        let err =
+            parse_source("# 10 \"foo.c\"\nint main(void) {\n long long long x; }").unwrap_err();
+        assert_eq!(err, "'long long long' is too long on line 11 of foo.c");
+    }
+
+    #[test]
+    fn test_bool_is_a_builtin_typedef_name() {
+        let parse_source = |source: &str| {
+            let tokens: Vec<Token> = Lexer::new(source).collect::<Result<_, _>>().unwrap();
+            Parser::new(&tokens).parse()
+        };
+
+        // 参数 `bool` 遮蔽了类型名，函数体中的 `bool = 1;` 是赋值；离开函数后又是类型名
+        let program = parse_source(
+            "int f(int bool) { bool = 1; return bool; }\nbool g(bool b) { return b; }",
+        )
+        .expect("Parsing failed");
+        let Declaration::Function {
+            body: Some(body), ..
+        } = &program.declarations[0]
+        else {
+            panic!("Expected a function definition for 'f'.");
+        };
+        assert!(matches!(
+            &body.blocks[0],
+            BlockItem::S(Statement::Expression(Expression::Assign { .. }))
+        ));
+        let Declaration::Function {
+            return_type,
+            param_types,
+            ..
+        } = &program.declarations[1]
+        else {
+            panic!("Expected a function declaration for 'g'.");
+        };
+        assert_eq!(return_type, &CType::Bool);
+        assert_eq!(param_types, &[CType::Bool]);
+
+        // 内层块中的变量只在块内遮蔽类型名
+        let program = parse_source(
+            "int main(void) { { int bool = 2; bool = bool + 1; } bool b = 3; return b; }",
+        )
+        .expect("Parsing failed");
+        let Declaration::Function {
+            body: Some(body), ..
+        } = &program.declarations[0]
+        else {
+            panic!("Expected a function definition for 'main'.");
+        };
+        assert!(matches!(
+            &body.blocks[1],
+            BlockItem::D(Declaration::Variable {
+                var_type: CType::Bool,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_typed_parameter_list() {
+        let parse_source = |source: &str| {
+            let tokens: Vec<Token> = Lexer::new(source).collect::<Result<_, _>>().unwrap();
+            Parser::new(&tokens).parse()
+        };
+
+        let program = parse_source("int f(long a, _Bool b, int c);").expect("Parsing failed");
+        let Declaration::Function {
+            params,
+            param_types,
+            ..
+        } = &program.declarations[0]
+        else {
+            panic!("Expected a function declaration for 'f'.");
+        };
+        assert_eq!(params, &["a", "b", "c"]);
+        assert_eq!(param_types, &[CType::Long, CType::Bool, CType::Int]);
+
+        let err = parse_source("struct P { int x; };\nint f(struct P p);").unwrap_err();
+        assert_eq!(err, "Struct parameters are not supported on line 2");
+    }
+
+    #[test]
+    fn test_bitwise_operator_precedence() {
+        // C 的优先级：== 高于 &，& 高于 ^，^ 高于 |，| 高于 &&
+        let tokens: Vec<Token> = Lexer::new("1 | 2 ^ 3 & 4 == 5 && 6")
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let expr = Parser::new(&tokens)
+            .parse_standalone_expression()
+            .expect("Parsing failed");
+
+        let bin = |operator, left, right| Expression::Binary {
+            operator,
+            left: Box::new(left),
+            right: Box::new(right),
+        };
+        let c = Expression::Constant;
+        assert_eq!(
+            expr,
+            bin(
+                BinaryOperator::And,
+                bin(
+                    BinaryOperator::BitOr,
+                    c(1),
+                    bin(
+                        BinaryOperator::BitXor,
+                        c(2),
+                        bin(
+                            BinaryOperator::BitAnd,
+                            c(3),
+                            bin(BinaryOperator::Equal, c(4), c(5))
+                        )
+                    )
+                ),
+                c(6)
+            )
+        );
+    }
+
+    #[test]
+    fn test_comma_operator_is_lowest_precedence_but_not_in_arguments() {
+        let parse = |source: &str| {
+            let tokens: Vec<Token> = Lexer::new(source).collect::<Result<_, _>>().unwrap();
+            Parser::new(&tokens)
+                .parse_standalone_expression()
+                .expect("Parsing failed")
+        };
+        let comma = |left, right| Expression::Comma {
+            left: Box::new(left),
+            right: Box::new(right),
+        };
+        let var = |name: &str| Expression::Var {
+            name: name.to_string(),
+            line: 1.into(),
+        };
+
+        // 左结合，并且比赋值绑定得更松：(a = 1), b
+        assert_eq!(
+            parse("a = 1, b, 3"),
+            comma(
+                comma(
+                    Expression::Assign {
+                        left: Box::new(var("a")),
+                        right: Box::new(Expression::Constant(1)),
+                    },
+                    var("b")
+                ),
+                Expression::Constant(3)
+            )
+        );
+        // 实参列表中的逗号是分隔符，括号内的逗号才是运算符
+        assert_eq!(
+            parse("f(a, (b, c))"),
+            Expression::FunctionCall {
+                name: "f".to_string(),
+                args: vec![var("a"), comma(var("b"), var("c"))],
+                line: 1.into(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_sizeof_type_and_expression() {
+        let parse = |source: &str| {
+            let tokens: Vec<Token> = Lexer::new(source).collect::<Result<_, _>>().unwrap();
+            Parser::new(&tokens)
+                .parse_standalone_expression()
+                .expect("Parsing failed")
+        };
+        let var = |name: &str| {
+            Box::new(Expression::Var {
+                name: name.to_string(),
+                line: 1.into(),
+            })
+        };
+
+        assert_eq!(
+            parse("sizeof(int)"),
+            Expression::SizeOf(SizeOfOperand::Type(CType::Int))
+        );
+        assert_eq!(
+            parse("sizeof(long)"),
+            Expression::SizeOf(SizeOfOperand::Type(CType::Long))
+        );
+        // 括号里不是类型时，是一个括号表达式
+        assert_eq!(
+            parse("sizeof(x)"),
+            Expression::SizeOf(SizeOfOperand::Expression(var("x")))
+        );
+        // sizeof 和其他一元运算符一样，比二元运算符绑定得更紧
+        assert_eq!(
+            parse("sizeof x + 1"),
+            Expression::Binary {
+                operator: BinaryOperator::Add,
+                left: Box::new(Expression::SizeOf(SizeOfOperand::Expression(var("x")))),
+                right: Box::new(Expression::Constant(1)),
+            }
+        );
+    }
+
+    #[test]
+    fn test_dangling_else_binds_to_inner_if() {
+        let source = "int main(void) { if (1) if (0) return 1; else return 2; return 3; }";
+        let tokens: Vec<Token> = Lexer::new(source).collect::<Result<_, _>>().unwrap();
+        let program = Parser::new(&tokens).parse().expect("Parsing failed");
+
+        let Declaration::Function {
+            body: Some(body), ..
+        } = &program.declarations[0]
+        else {
+            panic!("Expected a function definition for 'main'.");
+        };
+        // 外层 if 没有 else，else 属于内层 if
+        let BlockItem::S(Statement::If {
+            then_stat,
+            else_stat: None,
+            ..
+        }) = &body.blocks[0]
+        else {
+            panic!(
+                "Expected an outer if without else, got {:?}",
+                body.blocks[0]
+            );
+        };
+        assert!(matches!(
+            then_stat.as_ref(),
+            Statement::If {
+                else_stat: Some(_),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_extern_declarations() {
+        // extern 函数声明等同于普通原型
+        let tokens: Vec<Token> = Lexer::new("extern int putchar(int c);")
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let program = Parser::new(&tokens).parse().expect("Parsing failed");
+        assert!(matches!(
+            &program.declarations[0],
+            Declaration::Function { name, body: None, .. } if name == "putchar"
+        ));
+
+        // extern 变量需要全局变量支持，目前报错
+        let tokens: Vec<Token> = Lexer::new("int main(void) { extern int x; return x; }")
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let err = Parser::new(&tokens).parse().unwrap_err();
+        assert_eq!(err, "'extern' variable 'x' is not supported on line 1");
+    }
+
+    #[test]
+    fn test_comma_separated_declarators() {
+        let parse_source = |source: &str| {
+            let tokens: Vec<Token> = Lexer::new(source).collect::<Result<_, _>>().unwrap();
+            Parser::new(&tokens).parse()
+        };
+        // 每个声明符各自成为一个声明，类型和初始化器互不影响
+        let program =
+            parse_source("long a = 1, b, c = a; int main(void) { int x, y = 2; return y; }")
+                .expect("Parsing failed");
+        let globals: Vec<(&str, bool)> = program.declarations[..3]
+            .iter()
+            .map(|decl| match decl {
+                Declaration::Variable {
+                    name,
+                    init,
+                    var_type: CType::Long,
+                    ..
+                } => (name.as_str(), init.is_some()),
+                other => panic!("Expected a long variable, got {:?}", other),
+            })
+            .collect();
+        assert_eq!(globals, [("a", true), ("b", false), ("c", true)]);
+        let Declaration::Function {
+            body: Some(body), ..
+        } = &program.declarations[3]
+        else {
+            panic!("Expected a function definition for 'main'.");
+        };
+        assert_eq!(body.blocks.len(), 3);
+
+        assert_eq!(
+            parse_source("int a, ;").unwrap_err(),
+            "Expected an identifier, but found Semicolon"
+        );
+    }
+
+    #[test]
+    fn test_prototype_with_unnamed_params() {
+        let parse_source = |source: &str| {
+            let tokens: Vec<Token> = Lexer::new(source).collect::<Result<_, _>>().unwrap();
+            Parser::new(&tokens).parse()
+        };
+        let program = parse_source("int f(int, long b); int main(void) { return f(1, 2); }")
+            .expect("Parsing failed");
+        assert!(matches!(
+            &program.declarations[0],
+            Declaration::Function { params, param_types, body: None, .. }
+                if params == &["", "b"] && param_types == &[CType::Int, CType::Long]
+        ));
+
+        // 函数定义必须给出参数名
+        assert_eq!(
+            parse_source("int f(int) { return 0; }").unwrap_err(),
+            "Parameter name omitted in definition of function 'f' on line 1"
+        );
+    }
+
+    #[test]
+    fn test_braced_scalar_initializer() {
+        let parse_source = |source: &str| {
+            let tokens: Vec<Token> = Lexer::new(source).collect::<Result<_, _>>().unwrap();
+            Parser::new(&tokens).parse()
+        };
+
+        let program = parse_source("int main(void) { int x = {5}; int y = {6,}; return x; }")
+            .expect("Parsing failed");
+        let Declaration::Function {
+            body: Some(body), ..
+        } = &program.declarations[0]
+        else {
+            panic!("Expected a function definition for 'main'.");
+        };
+        assert!(matches!(
+            &body.blocks[0],
+            BlockItem::D(Declaration::Variable {
+                init: Some(Expression::Constant(5)),
+                ..
+            })
+        ));
+        assert!(matches!(
+            &body.blocks[1],
+            BlockItem::D(Declaration::Variable {
+                init: Some(Expression::Constant(6)),
+                ..
+            })
+        ));
+
+        let err = parse_source("int main(void) { int x = {1, 2}; return x; }").unwrap_err();
+        assert_eq!(err, "too many initializers for scalar on line 1");
+    }
+
+    #[test]
+    fn test_struct_declaration_and_member_access() {
+        let tokens: Vec<Token> =
+            Lexer::new("struct P { int x; _Bool b; }; int main(void) { struct P p; return p.x; }")
+                .collect::<Result<_, _>>()
+                .unwrap();
+        let program = Parser::new(&tokens).parse().expect("Parsing failed");
+        let Declaration::Struct { tag, members } = &program.declarations[0] else {
+            panic!("Expected a struct declaration.");
+        };
+        assert_eq!(tag, "P");
+        assert_eq!(members.len(), 2);
+        assert_eq!(members[1].member_type, CType::Bool);
+
+        let Declaration::Function {
+            body: Some(body), ..
+        } = &program.declarations[1]
+        else {
+            panic!("Expected a function definition for 'main'.");
+        };
+        assert!(matches!(
+            &body.blocks[0],
+            BlockItem::D(Declaration::Variable { var_type: CType::Struct(t), .. }) if t == "P"
+        ));
+        assert!(matches!(
+            &body.blocks[1],
+            BlockItem::S(Statement::Return(Expression::Member { member, .. })) if member == "x"
+        ));
+    }
 }
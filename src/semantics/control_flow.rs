@@ -0,0 +1,84 @@
+// src/semantics/control_flow.rs
+
+//! 基于语法结构的控制流分析，供 `--analyze` 的缺少 return 警告和
+//! `--no-implicit-return` 的缺少 return 错误共同使用。
+
+use crate::ast::checked::{Block, BlockItem, Expression, LoopId, Statement, Visitor};
+
+/// 执行到块的末尾之前是否一定已经返回（或者永远不会执行到末尾）
+pub fn block_always_returns(block: &Block) -> bool {
+    let last = block
+        .blocks
+        .iter()
+        .rev()
+        .find(|item| !matches!(item, BlockItem::S(Statement::Empty)));
+    matches!(last, Some(BlockItem::S(stmt)) if always_returns(stmt))
+}
+
+fn always_returns(stmt: &Statement) -> bool {
+    match stmt {
+        // goto 会跳到函数中的其他位置，不会从这里落到末尾
+        Statement::Return(_) | Statement::Goto(_) => true,
+        Statement::Compound(block) => block_always_returns(block),
+        Statement::If {
+            then_stat,
+            else_stat: Some(else_stat),
+            ..
+        } => always_returns(then_stat) && always_returns(else_stat),
+        Statement::Labeled { stmt, .. } => always_returns(stmt),
+        // 没有 break 的死循环永远不会结束
+        Statement::For {
+            condition: None,
+            body,
+            id,
+            ..
+        } => !breaks_out_of(body, *id),
+        Statement::While {
+            condition,
+            body,
+            id,
+            ..
+        }
+        | Statement::DoWhile {
+            body,
+            condition,
+            id,
+            ..
+        } if is_nonzero_constant(condition) => !breaks_out_of(body, *id),
+        _ => false,
+    }
+}
+
+fn is_nonzero_constant(expr: &Expression) -> bool {
+    match expr {
+        Expression::Constant(value) => *value != 0,
+        Expression::LongConstant(value) => *value != 0,
+        Expression::UIntConstant(value) => *value != 0,
+        Expression::ULongConstant(value) => *value != 0,
+        _ => false,
+    }
+}
+
+/// 语句中是否有跳出编号为 `id` 的循环的 break
+fn breaks_out_of(stmt: &Statement, id: LoopId) -> bool {
+    let mut finder = BreakFinder { id, found: false };
+    finder.visit_statement(stmt);
+    finder.found
+}
+
+struct BreakFinder {
+    id: LoopId,
+    found: bool,
+}
+
+impl Visitor for BreakFinder {
+    fn visit_statement(&mut self, stmt: &Statement) {
+        match stmt {
+            Statement::Break { target_id } => self.found |= *target_id == self.id,
+            _ => self.walk_statement(stmt),
+        }
+    }
+
+    // 表达式中不会有 break
+    fn visit_expression(&mut self, _expr: &Expression) {}
+}
@@ -0,0 +1,208 @@
+// src/semantics/label_resolver.rs
+
+use crate::ast::unchecked::{BlockItem, Declaration, Program, Statement};
+use crate::common::UniqueIdGenerator;
+use std::collections::HashMap;
+
+/// 解析 `goto` 的目标标签。
+///
+/// 标签的作用域是整个函数，所以先收集函数内的全部标签定义（向前跳转也能找到目标），
+/// 再检查每个 `goto`。标签被改写为 `name.N` 形式的唯一名字，
+/// 这样不同函数中的同名标签在汇编中不会冲突。
+pub struct LabelResolver<'a> {
+    id_generator: &'a mut UniqueIdGenerator,
+    // 当前函数中：原标签名 -> 唯一标签名
+    labels: HashMap<String, String>,
+    // 当前正在处理的函数名，用于错误信息
+    function: String,
+    // 已经发现的错误。出错的标签或 goto 保持原样，解析继续进行
+    errors: Vec<String>,
+}
+
+impl<'a> LabelResolver<'a> {
+    pub fn new(id_generator: &'a mut UniqueIdGenerator) -> Self {
+        LabelResolver {
+            id_generator,
+            labels: HashMap::new(),
+            function: String::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// 出错时返回发现的所有错误
+    pub fn resolve_program(&mut self, mut prog: Program) -> Result<Program, Vec<String>> {
+        for decl in &mut prog.declarations {
+            if let Declaration::Function {
+                name,
+                body: Some(body),
+                ..
+            } = decl
+            {
+                self.function = name.clone();
+                self.labels.clear();
+                for item in &mut body.blocks {
+                    if let BlockItem::S(stmt) = item {
+                        self.collect_labels(stmt);
+                    }
+                }
+                for item in &mut body.blocks {
+                    if let BlockItem::S(stmt) = item {
+                        self.resolve_gotos(stmt);
+                    }
+                }
+            }
+        }
+        if !self.errors.is_empty() {
+            return Err(std::mem::take(&mut self.errors));
+        }
+        Ok(prog)
+    }
+
+    /// 第一遍：记录并改写所有标签定义，同时检查重复
+    fn collect_labels(&mut self, stmt: &mut Statement) {
+        if let Statement::Labeled { name, .. } = stmt {
+            if self.labels.contains_key(name.as_str()) {
+                self.errors.push(format!(
+                    "Duplicate label '{}' in function '{}'",
+                    name, self.function
+                ));
+            } else {
+                let unique_name = format!("{}.{}", name, self.id_generator.next());
+                self.labels.insert(name.clone(), unique_name.clone());
+                *name = unique_name;
+            }
+        }
+        for child in sub_statements(stmt) {
+            self.collect_labels(child);
+        }
+    }
+
+    /// 第二遍：把每个 `goto` 指向改写后的标签
+    fn resolve_gotos(&mut self, stmt: &mut Statement) {
+        if let Statement::Goto(label) = stmt {
+            match self.labels.get(label.as_str()) {
+                Some(unique_name) => *label = unique_name.clone(),
+                None => self.errors.push(format!(
+                    "Undefined label '{}' in function '{}'",
+                    label, self.function
+                )),
+            }
+        }
+        for child in sub_statements(stmt) {
+            self.resolve_gotos(child);
+        }
+    }
+}
+
+/// 返回一个语句直接包含的所有子语句
+fn sub_statements(stmt: &mut Statement) -> Vec<&mut Statement> {
+    match stmt {
+        Statement::If {
+            then_stat,
+            else_stat,
+            ..
+        } => {
+            let mut children = vec![then_stat.as_mut()];
+            children.extend(else_stat.as_deref_mut());
+            children
+        }
+        Statement::Compound(block) => block
+            .blocks
+            .iter_mut()
+            .filter_map(|item| match item {
+                BlockItem::S(stmt) => Some(stmt),
+                BlockItem::D(_) => None,
+            })
+            .collect(),
+        Statement::While { body, .. } | Statement::DoWhile { body, .. } => vec![body.as_mut()],
+        Statement::For { body, .. } => vec![body.as_mut()],
+        Statement::Labeled { stmt, .. } => vec![stmt.as_mut()],
+        Statement::Return(_)
+        | Statement::Expression(_)
+        | Statement::Empty
+        | Statement::Break
+        | Statement::Continue
+        | Statement::Goto(_) => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::{Lexer, Token};
+    use crate::parser::Parser;
+
+    fn resolve(source: &str) -> Result<Program, String> {
+        let tokens: Vec<Token> = Lexer::new(source).collect::<Result<_, _>>().unwrap();
+        let ast = Parser::new(&tokens).parse().unwrap();
+        LabelResolver::new(&mut UniqueIdGenerator::new())
+            .resolve_program(ast)
+            .map_err(|errors| errors.join("\n"))
+    }
+
+    #[test]
+    fn test_goto_targets_renamed_label() {
+        let prog = resolve(
+            "int main(void) { goto end; { end: return 1; } }
+             int f(void) { end: return 2; }",
+        )
+        .unwrap();
+        let Declaration::Function {
+            body: Some(body), ..
+        } = &prog.declarations[0]
+        else {
+            panic!("Expected main function");
+        };
+        // 标签在内层块中，向前的 goto 仍然能找到它
+        assert_eq!(
+            body.blocks[0],
+            BlockItem::S(Statement::Goto("end.0".into()))
+        );
+        let BlockItem::S(Statement::Compound(inner)) = &body.blocks[1] else {
+            panic!("Expected a compound statement");
+        };
+        assert!(matches!(
+            &inner.blocks[0],
+            BlockItem::S(Statement::Labeled { name, .. }) if name == "end.0"
+        ));
+        // 另一个函数中的同名标签得到不同的名字
+        let Declaration::Function {
+            body: Some(body), ..
+        } = &prog.declarations[1]
+        else {
+            panic!("Expected function f");
+        };
+        assert!(matches!(
+            &body.blocks[0],
+            BlockItem::S(Statement::Labeled { name, .. }) if name == "end.1"
+        ));
+    }
+
+    #[test]
+    fn test_label_errors() {
+        assert_eq!(
+            resolve("int main(void) { goto missing; return 0; }").unwrap_err(),
+            "Undefined label 'missing' in function 'main'"
+        );
+        // 标签属于函数，不能跳到另一个函数中
+        assert_eq!(
+            resolve("int f(void) { out: return 0; } int main(void) { goto out; }").unwrap_err(),
+            "Undefined label 'out' in function 'main'"
+        );
+        assert_eq!(
+            resolve("int main(void) { a: ; if (1) { a: return 0; } }").unwrap_err(),
+            "Duplicate label 'a' in function 'main'"
+        );
+        // 一次报告所有函数中的全部错误
+        assert_eq!(
+            resolve(
+                "int f(void) { goto a; goto b; return 0; }
+                 int main(void) { x: x: return 0; }"
+            )
+            .unwrap_err(),
+            "Undefined label 'a' in function 'f'\n\
+             Undefined label 'b' in function 'f'\n\
+             Duplicate label 'x' in function 'main'"
+        );
+    }
+}
@@ -0,0 +1,228 @@
+// src/semantics/lint_checker.rs
+
+use std::collections::HashSet;
+
+use crate::ast::checked::{Block, BlockItem, Declaration, Expression, Program, Statement, Visitor};
+use crate::semantics::control_flow::block_always_returns;
+
+/// `--analyze` 额外运行的几项检查。它们只看语法结构，不跟踪控制流：
+///
+/// - 声明之后从未被读取的局部变量；
+/// - 同一块中 `return`、`break`、`continue`、`goto` 之后的语句（带标签的语句除外）；
+/// - 直接用赋值表达式作 if 或循环的条件，通常是把 `==` 写成了 `=`；
+/// - `main` 以外、执行到函数末尾时可能没有 return 的函数。
+pub struct LintChecker<'a> {
+    // 当前正在检查的函数名
+    function: String,
+    // 当前函数中声明的局部变量（Validator 改写后的唯一名字），按声明顺序
+    locals: Vec<String>,
+    // 当前函数中被读取过的变量
+    reads: HashSet<String>,
+    // 每发现一处问题就立即报告
+    on_warning: &'a mut dyn FnMut(String),
+}
+
+impl<'a> LintChecker<'a> {
+    pub fn new(on_warning: &'a mut dyn FnMut(String)) -> Self {
+        LintChecker {
+            function: String::new(),
+            locals: Vec::new(),
+            reads: HashSet::new(),
+            on_warning,
+        }
+    }
+
+    /// 检查整个程序，每条警告产生时立即交给 `on_warning`
+    pub fn check_program(mut self, prog: &Program) {
+        for decl in &prog.declarations {
+            if let Declaration::Function {
+                name,
+                body: Some(body),
+                ..
+            } = decl
+            {
+                self.function = name.clone();
+                self.visit_block(body);
+                for local in std::mem::take(&mut self.locals) {
+                    if !self.reads.contains(&local) {
+                        let name = local.split('.').next().unwrap_or(&local).to_string();
+                        self.warn(format!("unused variable '{}'", name));
+                    }
+                }
+                self.reads.clear();
+                // main 末尾隐式返回 0
+                if name != "main" && !block_always_returns(body) {
+                    self.warn("control reaches the end without a return".to_string());
+                }
+            }
+        }
+    }
+
+    fn warn(&mut self, message: String) {
+        (self.on_warning)(format!(
+            "warning: {} in function '{}'",
+            message, self.function
+        ));
+    }
+
+    /// 记录表达式中读取的变量
+    fn scan(&mut self, expr: &Expression) {
+        ReadCollector {
+            reads: &mut self.reads,
+        }
+        .visit_expression(expr);
+    }
+}
+
+impl Visitor for LintChecker<'_> {
+    fn visit_block(&mut self, block: &Block) {
+        // 上一条语句无条件跳走时，记录它的关键字；每段死代码只报告一次
+        let mut jumped_by = None;
+        for item in &block.blocks {
+            match item {
+                BlockItem::D(decl) => self.visit_declaration(decl),
+                BlockItem::S(Statement::Empty) => {}
+                BlockItem::S(stmt) => {
+                    // 带标签的语句可以通过 goto 到达
+                    if let Some(keyword) = jumped_by.take()
+                        && !matches!(stmt, Statement::Labeled { .. })
+                    {
+                        self.warn(format!("unreachable code after '{}'", keyword));
+                    }
+                    self.visit_statement(stmt);
+                    jumped_by = match stmt {
+                        Statement::Return(_) => Some("return"),
+                        Statement::Break { .. } => Some("break"),
+                        Statement::Continue { .. } => Some("continue"),
+                        Statement::Goto(_) => Some("goto"),
+                        _ => None,
+                    };
+                }
+            }
+        }
+    }
+
+    fn visit_declaration(&mut self, decl: &Declaration) {
+        if let Declaration::Variable { name, .. } = decl {
+            self.locals.push(name.clone());
+        }
+        self.walk_declaration(decl);
+    }
+
+    fn visit_statement(&mut self, stmt: &Statement) {
+        let condition = match stmt {
+            Statement::If { condition, .. }
+            | Statement::While { condition, .. }
+            | Statement::DoWhile { condition, .. }
+            | Statement::For {
+                condition: Some(condition),
+                ..
+            } => Some(condition),
+            _ => None,
+        };
+        if let Some(Expression::Assign { .. }) = condition {
+            self.warn("assignment used as a condition".to_string());
+        }
+        self.walk_statement(stmt);
+    }
+
+    fn visit_expression(&mut self, expr: &Expression) {
+        self.scan(expr);
+    }
+}
+
+struct ReadCollector<'a> {
+    reads: &'a mut HashSet<String>,
+}
+
+impl Visitor for ReadCollector<'_> {
+    fn visit_expression(&mut self, expr: &Expression) {
+        match expr {
+            Expression::Var { name, .. } => {
+                self.reads.insert(name.clone());
+            }
+            // 只被赋值的变量不算被使用
+            Expression::Assign { left, right }
+                if matches!(left.as_ref(), Expression::Var { .. }) =>
+            {
+                self.visit_expression(right);
+            }
+            _ => self.walk_expression(expr),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::check_source;
+
+    fn warnings_of(source: &str) -> Vec<String> {
+        let ast = check_source(source).ast;
+        let mut warnings = Vec::new();
+        LintChecker::new(&mut |w| warnings.push(w)).check_program(&ast);
+        warnings
+    }
+
+    #[test]
+    fn test_reports_each_category() {
+        let warnings = warnings_of(
+            r#"
+            int f(int a) {
+                int unused;
+                int set_only;
+                set_only = 3;
+                if (a = 2) return a;
+            }
+            int main(void) {
+                return f(1);
+                f(2);
+            }
+            "#,
+        );
+        assert_eq!(
+            warnings,
+            vec![
+                "warning: assignment used as a condition in function 'f'",
+                "warning: unused variable 'unused' in function 'f'",
+                "warning: unused variable 'set_only' in function 'f'",
+                "warning: control reaches the end without a return in function 'f'",
+                "warning: unreachable code after 'return' in function 'main'",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_clean_code_does_not_warn() {
+        assert!(
+            warnings_of(
+                r#"
+                int sign(int x) {
+                    if (x < 0) return -1;
+                    else if (x > 0) return 1;
+                    else return 0;
+                }
+                int spin(void) {
+                    int i = 0;
+                    for (;;) {
+                        i = i + 1;
+                        if (i == 3) return i;
+                    }
+                }
+                int main(void) {
+                    int n = 0;
+                    goto done;
+                done:
+                    return sign(n) + spin();
+                }
+                "#
+            )
+            .is_empty()
+        );
+        // 循环中的 break 让函数可以执行到末尾
+        assert_eq!(
+            warnings_of("int f(void) { while (1) { break; } } int main(void) { return f(); }"),
+            vec!["warning: control reaches the end without a return in function 'f'"]
+        );
+    }
+}
@@ -8,6 +8,8 @@ pub struct LoopLabeler<'a> {
     id_generator: &'a mut UniqueIdGenerator,
     // 一个栈，保存当前嵌套的循环的 ID
     loop_id_stack: Vec<checked::LoopId>,
+    // 已经发现的错误。出错的语句会被跳过，标记继续进行
+    errors: Vec<String>,
 }
 
 impl<'a> LoopLabeler<'a> {
@@ -15,49 +17,77 @@ impl<'a> LoopLabeler<'a> {
         LoopLabeler {
             id_generator,
             loop_id_stack: Vec::new(),
+            errors: Vec::new(),
         }
     }
 
-    // 接收 unchecked::Program，返回 checked::Program
-    pub fn label_program(&mut self, prog: unchecked::Program) -> Result<checked::Program, String> {
+    // 接收 unchecked::Program，返回 checked::Program；出错时返回发现的所有错误
+    pub fn label_program(
+        &mut self,
+        prog: unchecked::Program,
+    ) -> Result<checked::Program, Vec<String>> {
         let mut decls = Vec::new();
         // 遍历所有顶层声明
         for decl in prog.declarations {
-            decls.push(self.label_declaration(decl)?);
+            decls.push(self.label_declaration(decl));
+        }
+        if !self.errors.is_empty() {
+            return Err(std::mem::take(&mut self.errors));
         }
         Ok(checked::Program {
             declarations: decls,
         })
     }
     // --- 【修改】处理声明 ---
-    fn label_declaration(
-        &mut self,
-        decl: unchecked::Declaration,
-    ) -> Result<checked::Declaration, String> {
+    fn label_declaration(&mut self, decl: unchecked::Declaration) -> checked::Declaration {
         match decl {
-            unchecked::Declaration::Function { name, params, body } => {
+            unchecked::Declaration::Function {
+                name, params, body, ..
+            } => {
                 // 函数体可能不存在（函数原型），所以是 Option<Block>
-                let checked_body = body.map(|b| self.label_block(b)).transpose()?;
-                Ok(checked::Declaration::Function {
+                let checked_body = body.map(|b| self.label_block(b));
+                checked::Declaration::Function {
                     name,
                     params, // 参数列表是 String，不包含需要标记的语句
                     body: checked_body,
-                })
+                }
             }
             // 变量声明不包含语句，直接转换
-            unchecked::Declaration::Variable { name, init } => {
+            unchecked::Declaration::Variable {
+                name,
+                init,
+                var_type,
+                storage_class,
+            } => {
                 // 全局/局部变量的 init 是 Expression，不包含语句，直接移动
-                Ok(checked::Declaration::Variable { name, init })
+                checked::Declaration::Variable {
+                    name,
+                    init,
+                    var_type,
+                    storage_class,
+                }
+            }
+            unchecked::Declaration::Struct { tag, members } => {
+                checked::Declaration::Struct { tag, members }
             }
         }
     }
 
-    fn label_block(&mut self, block: unchecked::Block) -> Result<checked::Block, String> {
+    // 某一项出错时记录错误并跳过它，继续标记后面的项，这样一次编译能报告所有错误
+    fn label_block(&mut self, block: unchecked::Block) -> checked::Block {
         let mut items = Vec::new();
         for item in block.blocks {
-            items.push(self.label_block_item(item)?);
+            let depth = self.loop_id_stack.len();
+            match self.label_block_item(item) {
+                Ok(item) => items.push(item),
+                Err(e) => {
+                    // 出错的语句可能没来得及弹出它压入的循环 ID
+                    self.loop_id_stack.truncate(depth);
+                    self.errors.push(e);
+                }
+            }
         }
-        Ok(checked::Block { blocks: items })
+        checked::Block { blocks: items }
     }
     fn label_block_item(
         &mut self,
@@ -67,7 +97,7 @@ impl<'a> LoopLabeler<'a> {
             unchecked::BlockItem::S(stmt) => Ok(checked::BlockItem::S(self.label_statement(stmt)?)),
             // 当块内有声明时，也需要递归地转换它
             unchecked::BlockItem::D(decl) => {
-                Ok(checked::BlockItem::D(self.label_declaration(decl)?))
+                Ok(checked::BlockItem::D(self.label_declaration(decl)))
             }
         }
     }
@@ -148,8 +178,13 @@ impl<'a> LoopLabeler<'a> {
             unchecked::Statement::Return(e) => Ok(checked::Statement::Return(e)),
             unchecked::Statement::Expression(e) => Ok(checked::Statement::Expression(e)),
             unchecked::Statement::Empty => Ok(checked::Statement::Empty),
+            unchecked::Statement::Goto(label) => Ok(checked::Statement::Goto(label)),
+            unchecked::Statement::Labeled { name, stmt } => Ok(checked::Statement::Labeled {
+                name,
+                stmt: Box::new(self.label_statement(*stmt)?),
+            }),
             unchecked::Statement::Compound(b) => {
-                Ok(checked::Statement::Compound(self.label_block(b)?))
+                Ok(checked::Statement::Compound(self.label_block(b)))
             }
             unchecked::Statement::If {
                 condition,
@@ -196,6 +231,9 @@ mod tests {
             declarations: vec![Declaration::Function {
                 name: "main".to_string(),
                 params: Vec::new(),
+                param_types: Vec::new(),
+                return_type: CType::Int,
+                line: 1.into(),
                 body: Some(Block {
                     blocks: vec![
                         BlockItem::S(Statement::While {
@@ -317,6 +355,9 @@ mod tests {
             declarations: vec![Declaration::Function {
                 name: "main".to_string(),
                 params: Vec::new(),
+                param_types: Vec::new(),
+                return_type: CType::Int,
+                line: 1.into(),
                 body: Some(Block {
                     blocks: vec![BlockItem::S(Statement::Break)],
                 }),
@@ -328,6 +369,72 @@ mod tests {
 
         let result = labeler.label_program(unchecked_ast);
         assert!(result.is_err());
-        assert_eq!(result.unwrap_err(), "'break' statement not in a loop");
+        assert_eq!(result.unwrap_err(), vec!["'break' statement not in a loop"]);
+    }
+
+    #[test]
+    fn test_break_in_nested_block_targets_enclosing_loop() {
+        // while (1) { { if (c) break; } }
+        // break 位于两层块之内，仍然必须指向 while 循环
+        let unchecked_ast = Program {
+            declarations: vec![Declaration::Function {
+                name: "main".to_string(),
+                params: Vec::new(),
+                param_types: Vec::new(),
+                return_type: CType::Int,
+                line: 1.into(),
+                body: Some(Block {
+                    blocks: vec![BlockItem::S(Statement::While {
+                        condition: Expression::Constant(1),
+                        body: Box::new(Statement::Compound(Block {
+                            blocks: vec![BlockItem::S(Statement::Compound(Block {
+                                blocks: vec![BlockItem::S(Statement::If {
+                                    condition: Expression::Var {
+                                        name: "c".to_string(),
+                                        line: 1.into(),
+                                    },
+                                    then_stat: Box::new(Statement::Break),
+                                    else_stat: None,
+                                })],
+                            }))],
+                        })),
+                    })],
+                }),
+            }],
+        };
+
+        let mut id_gen = UniqueIdGenerator::new();
+        let mut labeler = LoopLabeler::new(&mut id_gen);
+        let checked_ast = labeler
+            .label_program(unchecked_ast)
+            .expect("Labeling should succeed");
+
+        let checked::Declaration::Function {
+            body: Some(main_body),
+            ..
+        } = &checked_ast.declarations[0]
+        else {
+            panic!("Expected main function");
+        };
+        let checked::BlockItem::S(checked::Statement::While { id, body, .. }) =
+            &main_body.blocks[0]
+        else {
+            panic!("Expected a While loop");
+        };
+        let checked::Statement::Compound(outer) = &**body else {
+            panic!("While body should be a compound statement");
+        };
+        let checked::BlockItem::S(checked::Statement::Compound(inner)) = &outer.blocks[0] else {
+            panic!("Expected a nested block");
+        };
+        let checked::BlockItem::S(checked::Statement::If { then_stat, .. }) = &inner.blocks[0]
+        else {
+            panic!("Expected If statement");
+        };
+        assert_eq!(
+            **then_stat,
+            checked::Statement::Break { target_id: *id },
+            "break two blocks deep should target the while loop"
+        );
     }
 }
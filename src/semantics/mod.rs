@@ -1,3 +1,7 @@
+pub mod control_flow;
+pub mod label_resolver;
+pub mod lint_checker;
 pub mod loop_labeler;
 pub mod type_checker;
+pub mod uninit_checker;
 pub mod validator; // <-- 新增
@@ -1,69 +1,141 @@
 // src/semantics/type_checker.rs
 
 use crate::ast::unchecked::*;
+use crate::backend::tacky_gen::convert_binaryop;
+use crate::backend::tacky_opt::fold_binary;
+use crate::ir::tacky::Val;
 use std::collections::HashMap;
 
-/// 表示 C 语言中的基本类型
-#[derive(Debug, Clone, PartialEq)]
-pub enum CType {
-    Int,
-    // 在这个阶段，我们只关心函数参数的数量
-    Function { param_count: usize },
-}
-
 /// 符号表中存储的关于一个标识符的信息
 #[derive(Debug, Clone)]
 pub struct Symbol {
     /// 标识符的类型
     pub c_type: CType,
-    /// 如果是函数，它是否已经被定义 (有函数体)
+    /// 如果是函数，它是否已经被定义 (有函数体)；
+    /// 如果是全局变量，是否已经有一个带初始化器的声明
     pub defined: bool,
+    /// 具有静态存储期的变量的初始值（没有初始化器时为 0）；其他标识符为 None
+    pub static_init: Option<i64>,
+}
+
+/// 结构体中一个成员的布局
+#[derive(Debug, Clone)]
+pub struct MemberLayout {
+    pub name: String,
+    pub member_type: CType,
+    /// 相对于结构体起始位置的字节偏移
+    pub offset: u32,
+}
+
+/// 结构体的内存布局：成员按声明顺序排列，每个成员按自身大小对齐
+#[derive(Debug, Clone)]
+pub struct StructLayout {
+    pub members: Vec<MemberLayout>,
+    pub size: u32,
+    pub alignment: u32,
+}
+
+impl StructLayout {
+    /// 按名字查找成员
+    pub fn member(&self, name: &str) -> Option<&MemberLayout> {
+        self.members.iter().find(|m| m.name == name)
+    }
 }
 
 /// 类型检查器，它会构建并持有一个符号表
+#[derive(Debug)]
 pub struct TypeChecker {
     /// 符号表，将标识符名称映射到其类型和定义状态
     /// 注意：这里的 key 是在标识符解析后可能被重命名的名字
     pub symbols: HashMap<String, Symbol>,
+    /// 结构体类型表，将（唯一的）结构体标签映射到其布局
+    pub struct_types: HashMap<String, StructLayout>,
+    /// 正在检查的函数的返回类型，return 语句的值会被转换为它
+    current_return_type: CType,
+    /// 已经发现的错误。出错的声明或语句会被跳过，检查继续进行
+    errors: Vec<String>,
+    /// 为 `--dump-types` 记录每个表达式检查出的类型
+    pub record_types: bool,
+    /// 当前顶层表达式中每个子表达式检查出的类型，按后序排列。
+    /// 类型转换的类型就是目标类型，不需要记录
+    expression_types: Vec<CType>,
+    /// 标注了类型的顶层表达式，每个一行
+    typed_expressions: Vec<String>,
 }
 // 在 TypeChecker 定义之后
 
+impl Default for TypeChecker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl TypeChecker {
     /// 创建一个新的、空的 TypeChecker
     pub fn new() -> Self {
         TypeChecker {
             symbols: HashMap::new(),
+            struct_types: HashMap::new(),
+            current_return_type: CType::Int,
+            errors: Vec::new(),
+            record_types: false,
+            expression_types: Vec::new(),
+            typed_expressions: Vec::new(),
         }
     }
 
     /// 类型检查的主入口。
-    /// 它不返回新的 AST，如果成功，它会填充自身的符号表。
-    /// 如果失败，它返回一个错误字符串。
-    pub fn check_program(&mut self, prog: &Program) -> Result<(), String> {
+    /// 它返回一个新的 AST，其中所有的隐式类型转换都被显式地表示为 `Expression::Cast`。
+    /// 同时它会填充自身的符号表。如果失败，它返回发现的所有错误。
+    pub fn check_program(&mut self, prog: Program) -> Result<Program, Vec<String>> {
         // 遍历所有顶层声明，填充符号表并进行检查
-        for decl in &prog.declarations {
-            self.check_declaration(decl)?;
+        let mut checked_decls = Vec::new();
+        for decl in prog.declarations {
+            match self.check_declaration(decl, true) {
+                Ok(decl) => checked_decls.push(decl),
+                Err(e) => self.errors.push(e),
+            }
         }
 
-        // 成功，没有错误
-        Ok(())
+        if !self.errors.is_empty() {
+            return Err(std::mem::take(&mut self.errors));
+        }
+        Ok(Program {
+            declarations: checked_decls,
+        })
     }
 
-    /// 检查一个声明（函数或变量）
-    fn check_declaration(&mut self, decl: &Declaration) -> Result<(), String> {
+    /// 检查一个声明（函数或变量），`is_global` 表示它位于文件作用域
+    fn check_declaration(
+        &mut self,
+        decl: Declaration,
+        is_global: bool,
+    ) -> Result<Declaration, String> {
         match decl {
-            Declaration::Function { name, params, body } => {
-                let param_count = params.len();
+            Declaration::Function {
+                name,
+                params,
+                param_types,
+                return_type,
+                body,
+                line,
+            } => {
                 let has_body = body.is_some();
-                let fun_type = CType::Function { param_count };
+                let fun_type = CType::Function {
+                    param_types: param_types.clone(),
+                    return_type: Box::new(return_type.clone()),
+                };
 
                 let mut already_defined = false;
 
                 // 检查符号表中是否已存在该函数
-                if let Some(old_symbol) = self.symbols.get(name) {
-                    // 1. 检查类型是否兼容
+                if let Some(old_symbol) = self.symbols.get(&name) {
+                    // 1. 检查类型是否兼容：参数个数和每个参数的类型都必须一致
                     if old_symbol.c_type != fun_type {
-                        return Err(format!("Incompatible declaration for function '{}'", name));
+                        return Err(format!(
+                            "Incompatible declaration for function '{}': previously declared as {}, now {}",
+                            name, old_symbol.c_type, fun_type
+                        ));
                     }
                     already_defined = old_symbol.defined;
                 }
@@ -77,121 +149,308 @@ impl TypeChecker {
                 let new_symbol = Symbol {
                     c_type: fun_type,
                     defined: already_defined || has_body,
+                    static_init: None,
                 };
                 self.symbols.insert(name.clone(), new_symbol);
 
                 // 4. 如果有函数体，深入检查
-                if let Some(block) = body {
-                    // 为函数体创建一个新的 "作用域"
-                    // 在这个简化的类型检查器中，我们不处理作用域，
-                    // 因为所有变量都已经是唯一名称了。
-                    // 我们只需临时将参数添加到符号表中。
-                    for param_name in params {
-                        self.symbols.insert(
-                            param_name.clone(),
-                            Symbol {
-                                c_type: CType::Int,
-                                defined: true, // 参数总被视为已定义
-                            },
-                        );
+                let checked_body = match body {
+                    Some(block) => {
+                        // 在这个简化的类型检查器中，我们不处理作用域，
+                        // 因为所有变量都已经是唯一名称了。
+                        // 参数也会留在符号表中，后端需要用它们来确定操作数的大小。
+                        for (param_name, param_type) in params.iter().zip(&param_types) {
+                            self.symbols.insert(
+                                param_name.clone(),
+                                Symbol {
+                                    c_type: param_type.clone(),
+                                    defined: true, // 参数总被视为已定义
+                                    static_init: None,
+                                },
+                            );
+                        }
+                        self.current_return_type = return_type.clone();
+                        Some(self.check_block(block))
                     }
+                    None => None,
+                };
 
-                    self.check_block(block)?;
-
-                    // 检查完函数体后，移除参数，防止它们污染全局符号表
-                    for param_name in params {
-                        self.symbols.remove(param_name);
+                Ok(Declaration::Function {
+                    name,
+                    params,
+                    param_types,
+                    return_type,
+                    body: checked_body,
+                    line,
+                })
+            }
+            Declaration::Variable {
+                name,
+                init,
+                var_type,
+                storage_class,
+            } => {
+                if matches!(var_type, CType::Struct(_)) && init.is_some() {
+                    return Err(format!(
+                        "Initializer for struct variable '{}' is not supported",
+                        name
+                    ));
+                }
+                // 全局变量可以重复声明：类型必须一致，且最多只有一个声明带初始化器
+                let mut previous_init = None;
+                if is_global && let Some(old_symbol) = self.symbols.get(&name) {
+                    if old_symbol.c_type != var_type {
+                        return Err(format!(
+                            "Incompatible declaration for '{}': previously declared as {}, now {}",
+                            name, old_symbol.c_type, var_type
+                        ));
+                    }
+                    if old_symbol.defined {
+                        if init.is_some() {
+                            return Err(format!("Variable '{}' is defined more than once", name));
+                        }
+                        previous_init = old_symbol.static_init;
                     }
                 }
-            }
-            Declaration::Variable { name, init } => {
-                // 标识符解析后，变量名已经是唯一的，所以我们直接添加
+                // 标识符解析后，局部变量名已经是唯一的，所以我们直接添加
                 self.symbols.insert(
                     name.clone(),
                     Symbol {
-                        c_type: CType::Int,
-                        defined: true,
+                        c_type: var_type.clone(),
+                        defined: !is_global || init.is_some() || previous_init.is_some(),
+                        static_init: None,
                     },
                 );
 
-                // 检查初始化表达式
-                if let Some(init_expr) = init {
-                    self.check_expression(init_expr)?;
+                // 检查初始化表达式，并把它转换为变量的类型
+                let checked_init = match init {
+                    Some(init_expr) => {
+                        let (expr, expr_type) = self.check_full_expression(init_expr)?;
+                        let expr = convert_to(expr, &expr_type, &var_type);
+                        self.record_typed_expression(&format!("{} {} = ", var_type, name), &expr);
+                        Some(expr)
+                    }
+                    None => None,
+                };
+
+                // 全局变量和静态变量只在程序启动时初始化一次，初始值必须在编译期确定
+                if is_global || storage_class == Some(StorageClass::Static) {
+                    let value = match &checked_init {
+                        Some(expr) => {
+                            self.constant_value(expr).map(|(v, _)| v).ok_or_else(|| {
+                                format!(
+                                    "Initializer for {} variable '{}' is not a constant",
+                                    if is_global { "global" } else { "static" },
+                                    name
+                                )
+                            })?
+                        }
+                        // 暂定定义沿用之前的声明给出的初始值
+                        None => previous_init.unwrap_or(0),
+                    };
+                    self.symbols.get_mut(&name).unwrap().static_init = Some(value);
                 }
+
+                Ok(Declaration::Variable {
+                    name,
+                    init: checked_init,
+                    var_type,
+                    storage_class,
+                })
+            }
+            Declaration::Struct { tag, members } => {
+                let mut layout = StructLayout {
+                    members: Vec::new(),
+                    size: 0,
+                    alignment: 1,
+                };
+                for member in &members {
+                    let size = scalar_size(&member.member_type);
+                    let offset = layout.size.next_multiple_of(size);
+                    layout.members.push(MemberLayout {
+                        name: member.name.clone(),
+                        member_type: member.member_type.clone(),
+                        offset,
+                    });
+                    layout.size = offset + size;
+                    layout.alignment = layout.alignment.max(size);
+                }
+                // 结构体的大小是其对齐的整数倍
+                layout.size = layout.size.next_multiple_of(layout.alignment);
+                self.struct_types.insert(tag.clone(), layout);
+                Ok(Declaration::Struct { tag, members })
             }
         }
-        Ok(())
     }
 
-    /// 检查一个块
-    fn check_block(&mut self, block: &Block) -> Result<(), String> {
-        for item in &block.blocks {
-            self.check_block_item(item)?;
+    /// 检查一个块。某一项出错时记录错误并跳过它，继续检查后面的项，
+    /// 这样一次编译就能报告所有错误。
+    fn check_block(&mut self, block: Block) -> Block {
+        let mut items = Vec::new();
+        for item in block.blocks {
+            match self.check_block_item(item) {
+                Ok(item) => items.push(item),
+                Err(e) => self.errors.push(e),
+            }
         }
-        Ok(())
+        Block { blocks: items }
     }
 
     /// 检查块中的一项
-    fn check_block_item(&mut self, item: &BlockItem) -> Result<(), String> {
+    fn check_block_item(&mut self, item: BlockItem) -> Result<BlockItem, String> {
         match item {
-            BlockItem::S(stmt) => self.check_statement(stmt),
-            BlockItem::D(decl) => self.check_declaration(decl),
+            BlockItem::S(stmt) => Ok(BlockItem::S(self.check_statement(stmt)?)),
+            BlockItem::D(decl) => Ok(BlockItem::D(self.check_declaration(decl, false)?)),
         }
     }
 
     /// 检查一个语句
-    fn check_statement(&mut self, stmt: &Statement) -> Result<(), String> {
+    fn check_statement(&mut self, stmt: Statement) -> Result<Statement, String> {
         match stmt {
-            Statement::Return(expr) | Statement::Expression(expr) => self.check_expression(expr),
+            Statement::Return(expr) => {
+                // 返回值需要转换为函数的返回类型
+                let (expr, expr_type) = self.check_full_expression(expr)?;
+                let expr = convert_to(expr, &expr_type, &self.current_return_type);
+                self.record_typed_expression("return ", &expr);
+                Ok(Statement::Return(expr))
+            }
+            Statement::Expression(expr) => {
+                let expr = self.check_full_expression(expr)?.0;
+                self.record_typed_expression("", &expr);
+                Ok(Statement::Expression(expr))
+            }
             Statement::If {
                 condition,
                 then_stat,
                 else_stat,
             } => {
-                self.check_expression(condition)?;
-                self.check_statement(then_stat)?;
-                if let Some(else_s) = else_stat {
-                    self.check_statement(else_s)?;
-                }
-                Ok(())
+                let condition = self.check_condition(condition)?;
+                let then_stat = Box::new(self.check_statement(*then_stat)?);
+                let else_stat = match else_stat {
+                    Some(else_s) => Some(Box::new(self.check_statement(*else_s)?)),
+                    None => None,
+                };
+                Ok(Statement::If {
+                    condition,
+                    then_stat,
+                    else_stat,
+                })
             }
-            Statement::Compound(block) => self.check_block(block),
+            Statement::Compound(block) => Ok(Statement::Compound(self.check_block(block))),
             Statement::For {
                 init,
                 condition,
                 post,
                 body,
             } => {
-                if let Some(init_item) = init {
-                    self.check_block_item(init_item)?;
-                }
-                if let Some(cond_expr) = condition {
-                    self.check_expression(cond_expr)?;
-                }
-                if let Some(post_expr) = post {
-                    self.check_expression(post_expr)?;
-                }
-                self.check_statement(body)
+                let init = match init {
+                    Some(init_item) => Some(Box::new(self.check_block_item(*init_item)?)),
+                    None => None,
+                };
+                let condition = match condition {
+                    Some(cond_expr) => Some(self.check_condition(cond_expr)?),
+                    None => None,
+                };
+                let post = match post {
+                    Some(post_expr) => Some(self.check_condition(post_expr)?),
+                    None => None,
+                };
+                let body = Box::new(self.check_statement(*body)?);
+                Ok(Statement::For {
+                    init,
+                    condition,
+                    post,
+                    body,
+                })
             }
             Statement::While { condition, body } => {
-                self.check_expression(condition)?;
-                self.check_statement(body)
+                let condition = self.check_condition(condition)?;
+                let body = Box::new(self.check_statement(*body)?);
+                Ok(Statement::While { condition, body })
             }
             Statement::DoWhile { body, condition } => {
-                self.check_statement(body)?;
-                self.check_expression(condition)
+                let body = Box::new(self.check_statement(*body)?);
+                let condition = self.check_condition(condition)?;
+                Ok(Statement::DoWhile { body, condition })
+            }
+            Statement::Labeled { name, stmt } => Ok(Statement::Labeled {
+                name,
+                stmt: Box::new(self.check_statement(*stmt)?),
+            }),
+            // Empty, Break, Continue, Goto 不需要类型检查
+            Statement::Empty | Statement::Break | Statement::Continue | Statement::Goto(_) => {
+                Ok(stmt)
             }
-            // Empty, Break, Continue 不需要类型检查
-            Statement::Empty | Statement::Break | Statement::Continue => Ok(()),
         }
     }
 
-    /// 检查一个表达式
-    fn check_expression(&mut self, expr: &Expression) -> Result<(), String> {
+    /// 检查一个值被丢弃或只用于判断真假的顶层表达式（条件、for 的第三部分）
+    fn check_condition(&mut self, expr: Expression) -> Result<Expression, String> {
+        let expr = self.check_full_expression(expr)?.0;
+        self.record_typed_expression("", &expr);
+        Ok(expr)
+    }
+
+    /// 检查一个顶层表达式。之前出错的表达式可能留下了一部分类型记录，先清空
+    fn check_full_expression(&mut self, expr: Expression) -> Result<(Expression, CType), String> {
+        self.expression_types.clear();
+        self.check_expression(expr)
+    }
+
+    /// 检查一个作为值使用的表达式，返回插入了显式类型转换的新表达式及其类型。
+    /// 结构体只能作为成员访问的对象出现，不能直接作为值使用。
+    fn check_expression(&mut self, expr: Expression) -> Result<(Expression, CType), String> {
+        let (expr, expr_type) = self.check_any_expression(expr)?;
+        if let CType::Struct(_) = expr_type {
+            return Err(format!(
+                "A value of type '{}' cannot be used here",
+                expr_type
+            ));
+        }
+        Ok((expr, expr_type))
+    }
+
+    /// 检查任意表达式（包括结构体类型的表达式），并按后序记录它的类型
+    fn check_any_expression(&mut self, expr: Expression) -> Result<(Expression, CType), String> {
+        let is_cast = matches!(expr, Expression::Cast { .. });
+        let (expr, expr_type) = self.check_expression_node(expr)?;
+        if !is_cast {
+            self.expression_types.push(expr_type.clone());
+        }
+        Ok((expr, expr_type))
+    }
+
+    fn check_expression_node(&mut self, expr: Expression) -> Result<(Expression, CType), String> {
         match expr {
-            Expression::Constant(_) => Ok(()), // 常量总是合法的
-            Expression::Var(name) => {
-                let symbol = self.symbols.get(name).ok_or_else(|| {
+            Expression::Member { base, member } => {
+                let (base, base_type) = self.check_any_expression(*base)?;
+                let CType::Struct(tag) = &base_type else {
+                    return Err(format!(
+                        "Member access '.{}' on non-struct type '{}'",
+                        member, base_type
+                    ));
+                };
+                let member_type = self
+                    .struct_types
+                    .get(tag)
+                    .and_then(|layout| layout.member(&member))
+                    .map(|m| m.member_type.clone())
+                    .ok_or_else(|| format!("'{}' has no member named '{}'", base_type, member))?;
+                Ok((
+                    Expression::Member {
+                        base: Box::new(base),
+                        member,
+                    },
+                    member_type,
+                ))
+            }
+            // 常量总是合法的，类型由字面量本身决定
+            Expression::Constant(_) => Ok((expr, CType::Int)),
+            Expression::LongConstant(_) => Ok((expr, CType::Long)),
+            Expression::UIntConstant(_) => Ok((expr, CType::UInt)),
+            Expression::ULongConstant(_) => Ok((expr, CType::ULong)),
+            Expression::Var { name, line } => {
+                let symbol = self.symbols.get(&name).ok_or_else(|| {
                     format!(
                         "Internal error: undeclared identifier '{}' after validation pass",
                         name
@@ -202,10 +461,11 @@ impl TypeChecker {
                 if matches!(symbol.c_type, CType::Function { .. }) {
                     return Err(format!("Function '{}' used as a variable", name));
                 }
-                Ok(())
+                let var_type = symbol.c_type.clone();
+                Ok((Expression::Var { name, line }, var_type))
             }
-            Expression::FunctionCall { name, args } => {
-                let symbol = self.symbols.get(name).ok_or_else(|| {
+            Expression::FunctionCall { name, args, line } => {
+                let symbol = self.symbols.get(&name).ok_or_else(|| {
                     format!(
                         "Internal error: undeclared identifier '{}' after validation pass",
                         name
@@ -213,49 +473,674 @@ impl TypeChecker {
                 })?;
 
                 // 检查变量是否被用作函数
-                match symbol.c_type {
-                    CType::Int => Err(format!("Variable '{}' used as a function", name)),
-                    CType::Function { param_count } => {
+                match &symbol.c_type {
+                    CType::Function {
+                        param_types,
+                        return_type,
+                    } => {
+                        let (param_types, return_type) =
+                            (param_types.clone(), *return_type.clone());
                         // 检查参数数量
-                        if args.len() != param_count {
+                        if args.len() != param_types.len() {
                             return Err(format!(
                                 "Function '{}' called with {} arguments, but expects {}",
                                 name,
                                 args.len(),
-                                param_count
+                                param_types.len()
                             ));
                         }
-                        // 递归检查每个参数表达式
-                        for arg in args {
-                            self.check_expression(arg)?;
+                        // 递归检查每个参数表达式，并把它们转换为对应的参数类型
+                        let mut checked_args = Vec::new();
+                        for (arg, param_type) in args.into_iter().zip(&param_types) {
+                            let (arg, arg_type) = self.check_expression(arg)?;
+                            checked_args.push(convert_to(arg, &arg_type, param_type));
                         }
-                        Ok(())
+                        Ok((
+                            Expression::FunctionCall {
+                                name,
+                                args: checked_args,
+                                line,
+                            },
+                            return_type,
+                        ))
                     }
+                    _ => Err(format!("Variable '{}' used as a function", name)),
                 }
             }
             Expression::Assign { left, right } => {
-                // 对于赋值，左右两边都需要是 int (在这个阶段)
                 // 标识符解析器已经确保了左边是 l-value (Var)
-                self.check_expression(left)?;
-                self.check_expression(right)?;
-                Ok(())
+                // 右边的值会被转换为左边的类型
+                let (left, left_type) = self.check_expression(*left)?;
+                let (right, right_type) = self.check_expression(*right)?;
+                let right = convert_to(right, &right_type, &left_type);
+                Ok((
+                    Expression::Assign {
+                        left: Box::new(left),
+                        right: Box::new(right),
+                    },
+                    left_type,
+                ))
             }
-            Expression::Unary { expression, .. } => self.check_expression(expression),
-            Expression::Binary { left, right, .. } => {
-                self.check_expression(left)?;
-                self.check_expression(right)?;
-                Ok(())
+            // 自增/自减的值与操作数同类型
+            Expression::Prefix {
+                operator,
+                expression,
+            } => {
+                let (inner, inner_type) = self.check_expression(*expression)?;
+                Ok((
+                    Expression::Prefix {
+                        operator,
+                        expression: Box::new(inner),
+                    },
+                    inner_type,
+                ))
+            }
+            Expression::Postfix {
+                operator,
+                expression,
+            } => {
+                let (inner, inner_type) = self.check_expression(*expression)?;
+                Ok((
+                    Expression::Postfix {
+                        operator,
+                        expression: Box::new(inner),
+                    },
+                    inner_type,
+                ))
+            }
+            Expression::Unary {
+                operator,
+                expression,
+            } => {
+                let (inner, inner_type) = self.check_expression(*expression)?;
+                match operator {
+                    // 逻辑非的结果总是 int
+                    UnaryOperator::Not => Ok((
+                        Expression::Unary {
+                            operator,
+                            expression: Box::new(inner),
+                        },
+                        CType::Int,
+                    )),
+                    // 其他一元运算符先对操作数做整数提升
+                    UnaryOperator::Negate | UnaryOperator::Complement => {
+                        let promoted_type = promote(&inner_type);
+                        let inner = convert_to(inner, &inner_type, &promoted_type);
+                        Ok((
+                            Expression::Unary {
+                                operator,
+                                expression: Box::new(inner),
+                            },
+                            promoted_type,
+                        ))
+                    }
+                }
+            }
+            Expression::Binary {
+                operator,
+                left,
+                right,
+            } => {
+                let (left, left_type) = self.check_expression(*left)?;
+                let (right, right_type) = self.check_expression(*right)?;
+
+                // 逻辑运算符的两边各自独立求值，不需要转换为公共类型
+                if matches!(operator, BinaryOperator::And | BinaryOperator::Or) {
+                    return Ok((
+                        Expression::Binary {
+                            operator,
+                            left: Box::new(left),
+                            right: Box::new(right),
+                        },
+                        CType::Int,
+                    ));
+                }
+
+                // 移位运算的两个操作数各自做整数提升，结果是左操作数提升后的类型
+                if matches!(
+                    operator,
+                    BinaryOperator::ShiftLeft | BinaryOperator::ShiftRight
+                ) {
+                    let left_promoted = promote(&left_type);
+                    let right_promoted = promote(&right_type);
+                    return Ok((
+                        Expression::Binary {
+                            operator,
+                            left: Box::new(convert_to(left, &left_type, &left_promoted)),
+                            right: Box::new(convert_to(right, &right_type, &right_promoted)),
+                        },
+                        left_promoted,
+                    ));
+                }
+
+                // 其他运算符：两边都转换为公共类型 (usual arithmetic conversions)
+                let common_type = get_common_type(&left_type, &right_type);
+                let left = convert_to(left, &left_type, &common_type);
+                let right = convert_to(right, &right_type, &common_type);
+                let result_type = match operator {
+                    BinaryOperator::Add
+                    | BinaryOperator::Subtract
+                    | BinaryOperator::Multiply
+                    | BinaryOperator::Divide
+                    | BinaryOperator::Remainder
+                    | BinaryOperator::BitAnd
+                    | BinaryOperator::BitOr
+                    | BinaryOperator::BitXor => common_type,
+                    // 关系运算符的结果总是 int
+                    _ => CType::Int,
+                };
+                Ok((
+                    Expression::Binary {
+                        operator,
+                        left: Box::new(left),
+                        right: Box::new(right),
+                    },
+                    result_type,
+                ))
             }
             Expression::Conditional {
                 condition,
                 left,
                 right,
             } => {
-                self.check_expression(condition)?;
-                self.check_expression(left)?;
-                self.check_expression(right)?;
-                Ok(())
+                let condition = self.check_expression(*condition)?.0;
+                let (left, left_type) = self.check_expression(*left)?;
+                let (right, right_type) = self.check_expression(*right)?;
+                // 两个分支转换为公共类型
+                let common_type = get_common_type(&left_type, &right_type);
+                let left = convert_to(left, &left_type, &common_type);
+                let right = convert_to(right, &right_type, &common_type);
+                Ok((
+                    Expression::Conditional {
+                        condition: Box::new(condition),
+                        left: Box::new(left),
+                        right: Box::new(right),
+                    },
+                    common_type,
+                ))
+            }
+            // 左侧的值被丢弃，整个表达式的类型就是右侧的类型
+            Expression::Comma { left, right } => {
+                let left = self.check_expression(*left)?.0;
+                let (right, right_type) = self.check_expression(*right)?;
+                Ok((
+                    Expression::Comma {
+                        left: Box::new(left),
+                        right: Box::new(right),
+                    },
+                    right_type,
+                ))
             }
+            Expression::Cast {
+                target_type,
+                expression,
+            } => {
+                let inner = self.check_expression(*expression)?.0;
+                Ok((
+                    Expression::Cast {
+                        target_type: target_type.clone(),
+                        expression: Box::new(inner),
+                    },
+                    target_type,
+                ))
+            }
+            // 操作数不会被求值，只需要它的类型（可以是结构体）；结果直接折叠为常量
+            Expression::SizeOf(operand) => {
+                let operand_type = match operand {
+                    SizeOfOperand::Type(c_type) => c_type,
+                    SizeOfOperand::Expression(expression) => {
+                        // 操作数在结果中不再出现，丢掉它的类型记录
+                        let mark = self.expression_types.len();
+                        let operand_type = self.check_any_expression(*expression)?.1;
+                        self.expression_types.truncate(mark);
+                        operand_type
+                    }
+                };
+                let size = self.size_of(&operand_type)?;
+                Ok((Expression::ULongConstant(size as u64), CType::ULong))
+            }
+        }
+    }
+
+    /// 计算由常量、一元运算、二元算术/位/比较运算和类型转换组成的表达式的值和类型，
+    /// 按表达式的类型截断。二元运算沿用 `tacky_opt` 的折叠规则。
+    /// 用于静态变量的初始化器；其他表达式（以及除以 0）返回 None。
+    fn constant_value(&self, expr: &Expression) -> Option<(i64, CType)> {
+        let (value, c_type) = match expr {
+            Expression::Constant(i) => (*i as i64, CType::Int),
+            Expression::LongConstant(i) => (*i, CType::Long),
+            Expression::UIntConstant(u) => (*u as i64, CType::UInt),
+            Expression::ULongConstant(u) => (*u as i64, CType::ULong),
+            Expression::Cast {
+                target_type,
+                expression,
+            } => (self.constant_value(expression)?.0, target_type.clone()),
+            Expression::Unary {
+                operator: UnaryOperator::Negate,
+                expression,
+            } => {
+                let (value, c_type) = self.constant_value(expression)?;
+                (value.wrapping_neg(), c_type)
+            }
+            Expression::Unary {
+                operator: UnaryOperator::Complement,
+                expression,
+            } => {
+                let (value, c_type) = self.constant_value(expression)?;
+                (!value, c_type)
+            }
+            Expression::Unary {
+                operator: UnaryOperator::Not,
+                expression,
+            } => ((self.constant_value(expression)?.0 == 0) as i64, CType::Int),
+            // 类型检查之后两个操作数已经是相同的类型（移位只看左操作数的类型）
+            Expression::Binary {
+                operator,
+                left,
+                right,
+            } => {
+                let (a, left_type) = self.constant_value(left)?;
+                let (b, _) = self.constant_value(right)?;
+                let op = convert_binaryop(operator, is_unsigned(&left_type)).ok()?;
+                match fold_binary(
+                    op,
+                    &tacky_constant(a, &left_type),
+                    &tacky_constant(b, &left_type),
+                )? {
+                    Val::Constant(i) => (i as i64, CType::Int),
+                    Val::UIntConstant(u) => (u as i64, CType::UInt),
+                    Val::LongConstant(l) => (l, CType::Long),
+                    Val::ULongConstant(u) => (u as i64, CType::ULong),
+                    Val::Var(_) => return None,
+                }
+            }
+            _ => return None,
+        };
+        let value = match c_type {
+            CType::Int => value as i32 as i64,
+            CType::UInt => value as u32 as i64,
+            CType::Bool => (value != 0) as i64,
+            CType::Char => value as i8 as i64,
+            _ => value,
+        };
+        Some((value, c_type))
+    }
+
+    /// 一个类型占用的字节数，与结构体布局和后端的存储大小一致。
+    fn size_of(&self, c_type: &CType) -> Result<u32, String> {
+        match c_type {
+            CType::Struct(tag) => self
+                .struct_types
+                .get(tag)
+                .map(|layout| layout.size)
+                .ok_or_else(|| {
+                    format!(
+                        "Invalid application of 'sizeof' to incomplete type '{}'",
+                        c_type
+                    )
+                }),
+            CType::Function { .. } => Err(format!(
+                "Invalid application of 'sizeof' to function type '{}'",
+                c_type
+            )),
+            scalar => Ok(scalar_size(scalar)),
+        }
+    }
+}
+
+impl TypeChecker {
+    /// 返回检查过程中记录的每个顶层表达式，每个子表达式都标注了检查出的类型。
+    /// 隐式转换以 `(type)` 的形式显式出现。需要在检查之前打开 `record_types`。
+    pub fn dump_types(&self) -> String {
+        self.typed_expressions.join("\n")
+    }
+
+    /// 把刚检查完的顶层表达式连同记录的类型格式化为一行
+    fn record_typed_expression(&mut self, prefix: &str, expr: &Expression) {
+        if self.record_types {
+            let mut types = std::mem::take(&mut self.expression_types).into_iter();
+            let line = format!("{}{}", prefix, format_typed_expression(expr, &mut types));
+            self.typed_expressions.push(line);
+        }
+        self.expression_types.clear();
+    }
+}
+
+/// 格式化一个表达式，并在每个子表达式后标注 `types` 中按后序记录的类型，
+/// 例如 `((int)(a.0: char) + 1: int): int`。
+fn format_typed_expression(expr: &Expression, types: &mut impl Iterator<Item = CType>) -> String {
+    let mut formatter = TypedExpressionFormatter {
+        types,
+        parts: Vec::new(),
+    };
+    formatter.visit_expression(expr);
+    formatter.parts.pop().unwrap_or_default()
+}
+
+/// 按后序遍历表达式：每个子表达式格式化后压入 `parts`，父节点再把它们取出来拼接
+struct TypedExpressionFormatter<'t, I> {
+    types: &'t mut I,
+    parts: Vec<String>,
+}
+
+impl<I: Iterator<Item = CType>> Visitor for TypedExpressionFormatter<'_, I> {
+    fn visit_expression(&mut self, expr: &Expression) {
+        let start = self.parts.len();
+        self.walk_expression(expr);
+        let operands = self.parts.split_off(start);
+        let body = match expr {
+            Expression::Constant(i) => i.to_string(),
+            Expression::LongConstant(i) => format!("{}L", i),
+            Expression::UIntConstant(i) => format!("{}u", i),
+            Expression::ULongConstant(i) => format!("{}UL", i),
+            Expression::Var { name, .. } => name.clone(),
+            // 类型转换的结果类型已经写在括号里，不再重复标注
+            Expression::Cast { target_type, .. } => {
+                self.parts
+                    .push(format!("({})({})", target_type, operands[0]));
+                return;
+            }
+            Expression::Unary { operator, .. } => {
+                let op = match operator {
+                    UnaryOperator::Negate => "-",
+                    UnaryOperator::Complement => "~",
+                    UnaryOperator::Not => "!",
+                };
+                format!("({}{})", op, operands[0])
+            }
+            Expression::Prefix { operator, .. } => {
+                format!("({}{})", inc_dec_symbol(operator), operands[0])
+            }
+            Expression::Postfix { operator, .. } => {
+                format!("({}{})", operands[0], inc_dec_symbol(operator))
+            }
+            Expression::Binary { operator, .. } => {
+                let op = match operator {
+                    BinaryOperator::Add => "+",
+                    BinaryOperator::Subtract => "-",
+                    BinaryOperator::Multiply => "*",
+                    BinaryOperator::Divide => "/",
+                    BinaryOperator::Remainder => "%",
+                    BinaryOperator::BitAnd => "&",
+                    BinaryOperator::BitOr => "|",
+                    BinaryOperator::BitXor => "^",
+                    BinaryOperator::ShiftLeft => "<<",
+                    BinaryOperator::ShiftRight => ">>",
+                    BinaryOperator::And => "&&",
+                    BinaryOperator::Or => "||",
+                    BinaryOperator::Equal => "==",
+                    BinaryOperator::NotEqual => "!=",
+                    BinaryOperator::LessThan => "<",
+                    BinaryOperator::LessOrEqual => "<=",
+                    BinaryOperator::GreaterThan => ">",
+                    BinaryOperator::GreaterOrEqual => ">=",
+                };
+                format!("({} {} {})", operands[0], op, operands[1])
+            }
+            Expression::Assign { .. } => format!("({} = {})", operands[0], operands[1]),
+            Expression::Conditional { .. } => {
+                format!("({} ? {} : {})", operands[0], operands[1], operands[2])
+            }
+            Expression::Comma { .. } => format!("({}, {})", operands[0], operands[1]),
+            Expression::FunctionCall { name, .. } => format!("{}({})", name, operands.join(", ")),
+            Expression::Member { member, .. } => format!("{}.{}", operands[0], member),
+            // sizeof 在检查时已经折叠为常量
+            Expression::SizeOf(_) => unreachable!("sizeof is folded during type checking"),
+        };
+        // 子表达式的类型先于它自己被记录
+        let expr_type = self
+            .types
+            .next()
+            .expect("every checked expression has a recorded type");
+        self.parts.push(format!("{}: {}", body, expr_type));
+    }
+}
+
+/// 标量类型占用的字节数（也是它的对齐要求）。
+/// `_Bool` 和 `char` 占 1 字节，后端用 1 字节的操作数读写它们。
+pub fn scalar_size(t: &CType) -> u32 {
+    match t {
+        CType::Bool | CType::Char => 1,
+        CType::Long | CType::ULong => 8,
+        _ => 4,
+    }
+}
+
+/// 把一个整数常量表示为 TACKY 中对应类型的常量，供 `fold_binary` 使用
+fn tacky_constant(value: i64, c_type: &CType) -> Val {
+    match c_type {
+        CType::Long => Val::LongConstant(value),
+        CType::UInt => Val::UIntConstant(value as u32),
+        CType::ULong => Val::ULongConstant(value as u64),
+        _ => Val::Constant(value as i32),
+    }
+}
+
+/// 整数提升：`_Bool` 和 `char` 参与运算时被提升为 int。
+fn promote(t: &CType) -> CType {
+    match t {
+        CType::Bool | CType::Char => CType::Int,
+        other => other.clone(),
+    }
+}
+
+/// 无符号类型的运算使用无符号的除法、移位和比较
+pub(crate) fn is_unsigned(t: &CType) -> bool {
+    matches!(t, CType::UInt | CType::ULong)
+}
+
+/// 计算两个操作数的公共类型 (usual arithmetic conversions)。
+/// 8 字节的类型胜过 4 字节的类型，long 能表示所有 unsigned int 的值；
+/// 大小相同时无符号类型胜出，例如 int 和 unsigned int 的公共类型是 unsigned int。
+fn get_common_type(t1: &CType, t2: &CType) -> CType {
+    let (t1, t2) = (promote(t1), promote(t2));
+    if t1 == t2 {
+        t1
+    } else if t1 == CType::ULong || t2 == CType::ULong {
+        CType::ULong
+    } else if t1 == CType::Long || t2 == CType::Long {
+        CType::Long
+    } else {
+        CType::UInt
+    }
+}
+
+fn inc_dec_symbol(operator: &IncDecOperator) -> &'static str {
+    match operator {
+        IncDecOperator::Increment => "++",
+        IncDecOperator::Decrement => "--",
+    }
+}
+
+/// 如果表达式的类型与目标类型不同，就用一个 `Cast` 包裹它。
+fn convert_to(expr: Expression, from: &CType, to: &CType) -> Expression {
+    if from == to {
+        expr
+    } else {
+        Expression::Cast {
+            target_type: to.clone(),
+            expression: Box::new(expr),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::try_check_source;
+
+    // 一个辅助函数：运行完整的前端，返回类型标注的输出
+    fn dump_types_of(source: &str) -> String {
+        try_check_source(source, |checker| checker.record_types = true)
+            .unwrap()
+            .type_checker
+            .dump_types()
+    }
+
+    // 一个辅助函数：运行完整的前端，返回类型检查器
+    fn check(source: &str) -> Result<TypeChecker, String> {
+        try_check_source(source, |_| {}).map(|checked| checked.type_checker)
+    }
+
+    #[test]
+    fn test_dump_types_shows_promotion() {
+        let dump = dump_types_of("int main(void) { char a = 1; return a + 1; }");
+        // `char` 被提升为 int，所以 `+` 的类型是 int
+        assert_eq!(
+            dump,
+            "char a.0 = (char)(1: int)\nreturn ((int)(a.0: char) + 1: int): int"
+        );
+        // 每个子表达式的类型都来自检查时的结果：条件表达式取两个分支的公共类型，
+        // 而不是左分支的类型
+        let dump = dump_types_of("long f(int c, long x) { return c ? 1 : x; }");
+        assert_eq!(dump, "return (c.0: int ? (long)(1: int) : x.1: long): long");
+    }
+
+    #[test]
+    fn test_struct_layout_and_member_errors() {
+        let struct_types = check("struct P { int x; _Bool b; int y; };")
+            .unwrap()
+            .struct_types;
+        let layout = struct_types.values().next().unwrap();
+        let offsets: Vec<u32> = layout.members.iter().map(|m| m.offset).collect();
+        assert_eq!(offsets, vec![0, 4, 8]);
+        assert_eq!((layout.size, layout.alignment), (12, 4));
+
+        let err =
+            check("struct P { int x; }; int main(void) { struct P p; return p.y; }").unwrap_err();
+        assert_eq!(err, "'struct P' has no member named 'y'");
+        let err =
+            check("struct P { int x; }; int main(void) { struct P p; return p; }").unwrap_err();
+        assert_eq!(err, "A value of type 'struct P' cannot be used here");
+        let err = check("int main(void) { int a = 1; return a.x; }").unwrap_err();
+        assert_eq!(err, "Member access '.x' on non-struct type 'int'");
+    }
+
+    #[test]
+    fn test_function_redeclaration_must_match_signature() {
+        assert!(check("int f(long a, int b); int f(long x, int y) { return y; }").is_ok());
+        assert_eq!(
+            check("int f(int a); int f(int a, int b) { return a; }").unwrap_err(),
+            "Incompatible declaration for function 'f': previously declared as int (int), now int (int, int)"
+        );
+        assert_eq!(
+            check("int f(int a); int f(long x) { return 0; }").unwrap_err(),
+            "Incompatible declaration for function 'f': previously declared as int (int), now int (long)"
+        );
+        assert_eq!(
+            check("int f(void); long f(void) { return 0; }").unwrap_err(),
+            "Incompatible declaration for function 'f': previously declared as int (void), now long (void)"
+        );
+    }
+
+    #[test]
+    fn test_sizeof_folds_to_constant() {
+        let dump = dump_types_of(
+            "struct P { int x; long y; };
+             int main(void) { int x; long l; struct P p;
+                 return sizeof(int) + sizeof(long) + sizeof x + sizeof (l + 1) + sizeof p; }",
+        );
+        // 操作数不会被求值，整个表达式只剩下 unsigned long 常量
+        assert!(
+            dump.contains(
+                "return (int)(((((4UL: unsigned long + 8UL: unsigned long): unsigned long \
+                 + 4UL: unsigned long): unsigned long + 8UL: unsigned long): unsigned long \
+                 + 16UL: unsigned long): unsigned long)"
+            ),
+            "unexpected dump:\n{}",
+            dump
+        );
+
+        // 与有符号数比较时，另一侧被转换为 unsigned long
+        let dump = dump_types_of("int main(void) { return sizeof(int) > -1; }");
+        assert!(
+            dump.contains("return (4UL: unsigned long > (unsigned long)((-1: int): int)): int"),
+            "unexpected dump:\n{}",
+            dump
+        );
+    }
+
+    #[test]
+    fn test_static_initializers_must_be_constant() {
+        let symbols = check(
+            "int main(void) { static int n; static long l = -1; static unsigned u = -1;
+                               static _Bool b = 5; int x = 2; return 0; }",
+        )
+        .unwrap()
+        .symbols;
+        let init = |name: &str| symbols[name].static_init;
+        // 初始值按变量的类型转换
+        assert_eq!(init("n.0"), Some(0));
+        assert_eq!(init("l.1"), Some(-1));
+        assert_eq!(init("u.2"), Some(4294967295));
+        assert_eq!(init("b.3"), Some(1));
+        assert_eq!(init("x.4"), None);
+
+        assert_eq!(
+            check("int main(void) { int a = 1; static int n = a; return n; }").unwrap_err(),
+            "Initializer for static variable 'n.1' is not a constant"
+        );
+
+        // 全局变量同样在编译期初始化，未初始化的全局变量为 0
+        let symbols = check("int g = 7; long h; int main(void) { return g; }")
+            .unwrap()
+            .symbols;
+        assert_eq!(symbols["g"].static_init, Some(7));
+        assert_eq!(symbols["h"].static_init, Some(0));
+        assert_eq!(
+            check("int f(void) { return 1; } int g = f();").unwrap_err(),
+            "Initializer for global variable 'g' is not a constant"
+        );
+    }
+
+    #[test]
+    fn test_global_initializers_fold_binary_operators() {
+        let symbols = check(
+            "int a = 2 + 3 * 4; long b = (4294967296 << 8) | 5; int c = 7 > 3 && 1;
+             unsigned d = 0u - 1u; int e = -7 / 2 % 3 ^ ~0; int f = 3000000000u > -1;",
+        );
+        // `&&` 不是常量折叠的一部分
+        assert_eq!(
+            symbols.unwrap_err(),
+            "Initializer for global variable 'c' is not a constant"
+        );
+        let symbols = check(
+            "int a = 2 + 3 * 4; long b = (4294967296 << 8) | 5; int c = 7 > 3;
+             unsigned d = 0u - 1u; int e = -7 / 2 % 3 ^ ~0; int f = 3000000000u > -1;",
+        )
+        .unwrap()
+        .symbols;
+        let init = |name: &str| symbols[name].static_init.unwrap();
+        assert_eq!(init("a"), 14);
+        assert_eq!(init("b"), (1 << 40) | 5);
+        assert_eq!(init("c"), 1);
+        assert_eq!(init("d"), 4294967295);
+        assert_eq!(init("e"), -1);
+        // -1 被转换为 unsigned int 后是最大值，比较按无符号规则进行
+        assert_eq!(init("f"), 0);
+        assert_eq!(
+            check("int g = 1 / 0;").unwrap_err(),
+            "Initializer for global variable 'g' is not a constant"
+        );
+    }
+
+    #[test]
+    fn test_global_redeclarations_are_merged() {
+        // 暂定定义可以出现在真正的定义之前或之后
+        let symbols = check("int g; int g = 3; int g; int main(void) { return g; }")
+            .unwrap()
+            .symbols;
+        assert_eq!(symbols["g"].static_init, Some(3));
+        let symbols = check("long h; long h;").unwrap().symbols;
+        assert_eq!(symbols["h"].static_init, Some(0));
+
+        assert_eq!(
+            check("int g = 1; int g = 2;").unwrap_err(),
+            "Variable 'g' is defined more than once"
+        );
+        assert_eq!(
+            check("int g; long g;").unwrap_err(),
+            "Incompatible declaration for 'g': previously declared as int, now long"
+        );
+    }
+}
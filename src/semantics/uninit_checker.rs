@@ -0,0 +1,196 @@
+// src/semantics/uninit_checker.rs
+
+use std::collections::HashSet;
+
+use crate::ast::checked::{
+    Block, BlockItem, CType, Declaration, Expression, Program, Statement, Visitor,
+};
+
+/// `-Wuninitialized`：检查读取未初始化局部变量的简单分析。
+///
+/// 分析只在单个块内按语句顺序进行，不跟踪控制流。为了避免误报，
+/// 嵌套语句（if 分支、循环体、内层块）中的赋值一律视为已经发生，
+/// 其中对外层变量的读取也不报告；只有块内直接执行的语句以及
+/// if/while 条件里的读取会被检查。
+pub struct UninitChecker<'a> {
+    // 当前正在检查的函数名
+    function: String,
+    // 每发现一处问题就立即报告
+    on_warning: &'a mut dyn FnMut(String),
+}
+
+impl<'a> UninitChecker<'a> {
+    pub fn new(on_warning: &'a mut dyn FnMut(String)) -> Self {
+        UninitChecker {
+            function: String::new(),
+            on_warning,
+        }
+    }
+
+    /// 检查整个程序，每条警告产生时立即交给 `on_warning`
+    pub fn check_program(mut self, prog: &Program) {
+        for decl in &prog.declarations {
+            if let Declaration::Function {
+                name,
+                body: Some(body),
+                ..
+            } = decl
+            {
+                self.function = name.clone();
+                self.check_block(body);
+            }
+        }
+    }
+
+    fn check_block(&mut self, block: &Block) {
+        // 本块中声明时没有初始化、且到目前为止还没有被赋值的变量
+        let mut uninit = HashSet::new();
+        for item in &block.blocks {
+            match item {
+                BlockItem::D(Declaration::Variable {
+                    name,
+                    init,
+                    var_type,
+                    storage_class,
+                }) => match init {
+                    Some(init) => self.scan(init, &mut uninit),
+                    // 结构体只能逐个成员赋值，不在这里跟踪；静态变量总是被初始化为 0
+                    None if !matches!(var_type, CType::Struct(_)) && storage_class.is_none() => {
+                        uninit.insert(name.clone());
+                    }
+                    None => {}
+                },
+                BlockItem::D(_) => {}
+                BlockItem::S(stmt) => self.check_statement(stmt, &mut uninit),
+            }
+        }
+    }
+
+    fn check_statement(&mut self, stmt: &Statement, uninit: &mut HashSet<String>) {
+        match stmt {
+            Statement::Return(expr) | Statement::Expression(expr) => self.scan(expr, uninit),
+            // 条件在任何分支执行之前求值，可以放心检查
+            Statement::If { condition, .. } | Statement::While { condition, .. } => {
+                self.scan(condition, uninit);
+            }
+            _ => {}
+        }
+        // 嵌套的块各自独立检查，然后把其中的所有赋值都视为已经发生
+        self.check_nested_blocks(stmt);
+        forget_assigned(stmt, uninit);
+    }
+
+    fn check_nested_blocks(&mut self, stmt: &Statement) {
+        NestedBlocks(self).visit_statement(stmt);
+    }
+
+    /// 按求值顺序扫描一个表达式，报告对未初始化变量的读取
+    fn scan(&mut self, expr: &Expression, uninit: &mut HashSet<String>) {
+        let mut scanner = ReadScanner {
+            uninit,
+            report: true,
+            reads: Vec::new(),
+        };
+        scanner.visit_expression(expr);
+        for name in scanner.reads {
+            (self.on_warning)(format!(
+                "warning: '{}' is used uninitialized in function '{}'",
+                name.split('.').next().unwrap_or(&name),
+                self.function
+            ));
+        }
+    }
+}
+
+/// 把嵌套语句中所有被赋值的变量从未初始化集合中移除
+fn forget_assigned(stmt: &Statement, uninit: &mut HashSet<String>) {
+    let mut scanner = ReadScanner {
+        uninit,
+        report: false,
+        reads: Vec::new(),
+    };
+    scanner.visit_statement(stmt);
+}
+
+/// 找出语句中嵌套的块（不进入块内部），交给 `check_block` 各自检查
+struct NestedBlocks<'c, 'a>(&'c mut UninitChecker<'a>);
+
+impl Visitor for NestedBlocks<'_, '_> {
+    fn visit_block(&mut self, block: &Block) {
+        self.0.check_block(block);
+    }
+
+    // 块外的表达式和 for 的初始化部分由调用方检查
+    fn visit_block_item(&mut self, _item: &BlockItem) {}
+    fn visit_expression(&mut self, _expr: &Expression) {}
+}
+
+struct ReadScanner<'a> {
+    uninit: &'a mut HashSet<String>,
+    // 为 false 时只记录赋值，不报告读取
+    report: bool,
+    reads: Vec<String>,
+}
+
+impl Visitor for ReadScanner<'_> {
+    fn visit_expression(&mut self, expr: &Expression) {
+        match expr {
+            Expression::Var { name, .. } => {
+                // 每个变量只报告一次
+                if self.report && self.uninit.remove(name) {
+                    self.reads.push(name.clone());
+                }
+            }
+            Expression::Assign { left, right } => {
+                if let Expression::Var { name, .. } = left.as_ref() {
+                    // 先求值右侧，`x = x + 1` 中的 x 仍然是未初始化的读取
+                    self.visit_expression(right);
+                    self.uninit.remove(name);
+                } else {
+                    self.walk_expression(expr);
+                }
+            }
+            _ => self.walk_expression(expr),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::check_source;
+
+    fn warnings_of(source: &str) -> Vec<String> {
+        let ast = check_source(source).ast;
+        let mut warnings = Vec::new();
+        UninitChecker::new(&mut |w| warnings.push(w)).check_program(&ast);
+        warnings
+    }
+
+    #[test]
+    fn test_read_before_assignment_warns() {
+        assert_eq!(
+            warnings_of("int main(void) { int x; return x; }"),
+            vec!["warning: 'x' is used uninitialized in function 'main'"]
+        );
+        // 赋值的右侧先于赋值本身求值
+        assert_eq!(
+            warnings_of("int main(void) { int x; x = x + 1; return x; }").len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_assigned_or_conditional_code_does_not_warn() {
+        assert!(warnings_of("int main(void) { int x; x = 1; return x; }").is_empty());
+        assert!(warnings_of("int main(void) { int x = 2; return x; }").is_empty());
+        // 分支和循环中的赋值按保守策略视为已经发生
+        assert!(warnings_of("int main(void) { int x; if (1) x = 1; return x; }").is_empty());
+        assert!(
+            warnings_of(
+                "int main(void) { int x; int i = 0; while (i < 2) { if (i) return x; x = i; i = i + 1; } return 0; }"
+            )
+            .is_empty()
+        );
+    }
+}
@@ -16,6 +16,14 @@ struct IdentifierInfo {
 pub struct Validator<'a> {
     scopes: Vec<HashMap<String, IdentifierInfo>>,
     id_generator: &'a mut UniqueIdGenerator,
+    /// 是否识别 `__builtin_expect` 等内建函数。关闭后它们被当作普通（未声明的）函数调用。
+    pub builtins: bool,
+    /// `-Wshadow`：局部变量遮蔽外层的同名标识符时发出警告
+    pub warn_shadow: bool,
+    /// 验证过程中产生的警告一产生就交给它；为 None 时警告被丢弃
+    pub on_warning: Option<&'a mut dyn FnMut(String)>,
+    /// 已经发现的错误。出错的声明或语句会被跳过，验证继续进行
+    errors: Vec<String>,
 }
 
 impl<'a> Validator<'a> {
@@ -24,6 +32,10 @@ impl<'a> Validator<'a> {
         Validator {
             scopes: Vec::new(),
             id_generator,
+            builtins: true,
+            warn_shadow: false,
+            on_warning: None,
+            errors: Vec::new(),
         }
     }
     /// Generates a new unique name for a variable.
@@ -34,19 +46,29 @@ impl<'a> Validator<'a> {
     }
 
     /// The main entry point for validation.
-    pub fn validate_program(&mut self, program: Program) -> Result<Program, String> {
+    /// 出错时返回发现的所有错误，而不只是第一个。
+    pub fn validate_program(&mut self, program: Program) -> Result<Program, Vec<String>> {
         // 1. 进入全局作用域 (这是所有顶层声明所在的地方)
         self.enter_scope();
         let mut validated_decls = Vec::new();
         for decl in program.declarations {
             // 在全局作用域内验证每个声明
-            let validated_decl = self.validate_declaration(decl, true)?; // true 表示在全局作用域
-            validated_decls.push(validated_decl);
+            match self.validate_declaration(decl, true) {
+                // true 表示在全局作用域
+                Ok(validated_decl) => validated_decls.push(validated_decl),
+                Err(e) => {
+                    self.scopes.truncate(1);
+                    self.errors.push(e);
+                }
+            }
         }
 
         // 注意：全局作用域在整个验证过程中都存在，所以先不退出
         // self.exit_scope();
 
+        if !self.errors.is_empty() {
+            return Err(std::mem::take(&mut self.errors));
+        }
         Ok(Program {
             declarations: validated_decls,
         })
@@ -58,27 +80,25 @@ impl<'a> Validator<'a> {
         is_global: bool,
     ) -> Result<Declaration, String> {
         match decl {
-            Declaration::Function { name, params, body } => {
-                // 如果不是在全局作用域，但遇到了函数定义，这是非法的嵌套函数
-                if !is_global && body.is_some() {
+            Declaration::Function {
+                name,
+                params,
+                param_types,
+                return_type,
+                body,
+                line,
+            } => {
+                // 检查当前作用域是否已有同名且无链接的实体 (如局部变量)
+                if let Some(map) = self.scopes.last()
+                    && let Some(prev_entry) = map.get(&name)
+                    && !prev_entry.has_external_linkage
+                {
                     return Err(format!(
-                        "Nested function definitions are not allowed: '{}'",
+                        "Duplicate declaration: '{}' conflicts with a local variable.",
                         name
                     ));
                 }
 
-                // 检查当前作用域是否已有同名且无链接的实体 (如局部变量)
-                if let Some(map) = self.scopes.last() {
-                    if let Some(prev_entry) = map.get(&name) {
-                        if !prev_entry.has_external_linkage {
-                            return Err(format!(
-                                "Duplicate declaration: '{}' conflicts with a local variable.",
-                                name
-                            ));
-                        }
-                    }
-                }
-
                 // 函数具有外部链接，不重命名
                 let info = IdentifierInfo {
                     unique_name: name.clone(),
@@ -86,6 +106,16 @@ impl<'a> Validator<'a> {
                 };
                 self.scopes.last_mut().unwrap().insert(name.clone(), info);
 
+                // 如果不是在全局作用域，但遇到了函数定义，这是非法的嵌套函数。
+                // 不带函数体的原型声明在块作用域内是合法的。
+                // 名字已经声明过了，之后对它的调用不会再报告为未声明
+                if !is_global && body.is_some() {
+                    return Err(format!(
+                        "Nested function definitions are not allowed: '{}' on line {}",
+                        name, line
+                    ));
+                }
+
                 // --- 【核心修改在这里】---
 
                 // 1. 为函数参数和函数体创建一个共享的新作用域
@@ -94,6 +124,11 @@ impl<'a> Validator<'a> {
                 // 2. 验证并重命名参数，将它们加入这个新作用域
                 let mut validated_params = Vec::new();
                 for param_name in params {
+                    // 原型中省略名字的参数不会被引用，也不参与重名检查
+                    if param_name.is_empty() {
+                        validated_params.push(param_name);
+                        continue;
+                    }
                     // 检查参数是否在当前作用域（也就是参数列表自身）中重复
                     if self.scopes.last().unwrap().contains_key(&param_name) {
                         return Err(format!(
@@ -116,16 +151,12 @@ impl<'a> Validator<'a> {
                 // 3. 验证函数体 (如果存在的话)
                 let validated_body = match body {
                     Some(block) => {
-                        // 直接在参数所在的作用域中，验证函数体内的每一个项目
-                        let mut validated_items = Vec::new();
-                        for item in block.blocks {
-                            // validate_block_item 会调用 validate_declaration(decl, false)
-                            // 它会在参数所在的作用域中检查 'int a = 5'
-                            // 此时，作用域中已经有参数 'a' 了，所以会触发重复声明错误
-                            validated_items.push(self.validate_block_item(item)?);
-                        }
+                        // 直接在参数所在的作用域中，验证函数体内的每一个项目。
+                        // validate_block_item 会调用 validate_declaration(decl, false)
+                        // 它会在参数所在的作用域中检查 'int a = 5'
+                        // 此时，作用域中已经有参数 'a' 了，所以会触发重复声明错误
                         Some(Block {
-                            blocks: validated_items,
+                            blocks: self.validate_block_items(block.blocks),
                         })
                     }
                     None => None,
@@ -137,24 +168,60 @@ impl<'a> Validator<'a> {
                 Ok(Declaration::Function {
                     name,
                     params: validated_params,
+                    param_types,
+                    return_type,
                     body: validated_body,
+                    line,
                 })
             }
-            Declaration::Variable { name, init } => {
-                // 与函数类似，检查当前作用域是否有冲突
-                if self.scopes.last().unwrap().contains_key(&name) {
+            Declaration::Variable {
+                name,
+                init,
+                var_type,
+                storage_class,
+            } => {
+                // 与函数类似，检查当前作用域是否有冲突。文件作用域的变量可以重复声明
+                // （例如先是暂定定义 `int g;` 再是 `int g = 3;`），由类型检查器合并
+                if let Some(previous) = self.scopes.last().unwrap().get(&name)
+                    && !(is_global && previous.has_external_linkage)
+                {
                     return Err(format!("Duplicate variable declaration for '{}'", name));
                 }
+                let var_type = self.resolve_type(var_type)?;
 
                 let unique_name;
                 let has_linkage;
 
                 if is_global {
+                    if storage_class == Some(StorageClass::Static) {
+                        return Err(format!(
+                            "File-scope 'static' variable '{}' is not supported",
+                            name
+                        ));
+                    }
                     // 全局变量，不重命名
                     unique_name = name.clone();
                     has_linkage = true;
                 } else {
-                    // 局部变量，生成唯一名称
+                    if self.warn_shadow
+                        && let Some(outer) = self.find_identifier(&name)
+                    {
+                        let warning = format!(
+                            "warning: declaration of '{}' shadows {}",
+                            name,
+                            if outer.has_external_linkage {
+                                "a global declaration"
+                            } else {
+                                "a previous local"
+                            }
+                        );
+                        if let Some(on_warning) = &mut self.on_warning {
+                            on_warning(warning);
+                        }
+                    }
+                    // 局部变量（包括静态局部变量），生成唯一名称。
+                    // 静态局部变量的唯一名称同时用作它在 .data/.bss 中的符号名，
+                    // 它没有外部链接，所以不会和其他文件中的符号冲突
                     unique_name = self.generate_unique_name(&name);
                     has_linkage = false;
                 }
@@ -174,21 +241,59 @@ impl<'a> Validator<'a> {
                 Ok(Declaration::Variable {
                     name: unique_name, // 使用新的（或原始的）名字
                     init: validated_init,
+                    var_type,
+                    storage_class,
+                })
+            }
+            Declaration::Struct { tag, members } => {
+                // 结构体标签有自己的命名空间，但和变量一样遵循块作用域，
+                // 因此同样重命名为唯一的名字，内层作用域可以重新声明同名的结构体
+                let key = struct_key(&tag);
+                if self.scopes.last().unwrap().contains_key(&key) {
+                    return Err(format!("Duplicate definition of struct '{}'", tag));
+                }
+                for (i, member) in members.iter().enumerate() {
+                    if members[..i].iter().any(|m| m.name == member.name) {
+                        return Err(format!(
+                            "Duplicate member '{}' in struct '{}'",
+                            member.name, tag
+                        ));
+                    }
+                }
+                let unique_tag = self.generate_unique_name(&tag);
+                self.scopes.last_mut().unwrap().insert(
+                    key,
+                    IdentifierInfo {
+                        unique_name: unique_tag.clone(),
+                        has_external_linkage: false,
+                    },
+                );
+                Ok(Declaration::Struct {
+                    tag: unique_tag,
+                    members,
                 })
             }
         }
     }
 
+    /// 把类型中的结构体标签替换为它在当前作用域中解析到的唯一名字。
+    fn resolve_type(&self, c_type: CType) -> Result<CType, String> {
+        match c_type {
+            CType::Struct(tag) => match self.find_identifier(&struct_key(&tag)) {
+                Some(info) => Ok(CType::Struct(info.unique_name)),
+                None => Err(format!("Use of undeclared type 'struct {}'", tag)),
+            },
+            other => Ok(other),
+        }
+    }
+
     /// 验证一个块，处理作用域的进入和退出，并验证其所有子项。
     fn validate_block(&mut self, block: Block) -> Result<Block, String> {
         // 1. 进入新作用域
         self.enter_scope();
 
         // 2. 遍历并验证块内的所有项目
-        let mut validated_items = Vec::new();
-        for item in block.blocks {
-            validated_items.push(self.validate_block_item(item)?);
-        }
+        let validated_items = self.validate_block_items(block.blocks);
 
         // 3. 退出作用域
         self.exit_scope();
@@ -199,6 +304,24 @@ impl<'a> Validator<'a> {
         })
     }
 
+    /// 依次验证块中的每一项。某一项出错时记录错误并跳过它，继续验证后面的项，
+    /// 这样一次编译就能报告所有错误。
+    fn validate_block_items(&mut self, items: Vec<BlockItem>) -> Vec<BlockItem> {
+        let mut validated_items = Vec::new();
+        for item in items {
+            let depth = self.scopes.len();
+            match self.validate_block_item(item) {
+                Ok(item) => validated_items.push(item),
+                Err(e) => {
+                    // 出错的项可能没来得及退出它进入的作用域（如 for 循环）
+                    self.scopes.truncate(depth);
+                    self.errors.push(e);
+                }
+            }
+        }
+        validated_items
+    }
+
     fn validate_block_item(&mut self, item: BlockItem) -> Result<BlockItem, String> {
         match item {
             BlockItem::S(stmt) => {
@@ -283,6 +406,12 @@ impl<'a> Validator<'a> {
             }
             Statement::Break => Ok(Statement::Break),
             Statement::Continue => Ok(Statement::Continue),
+            // 标签名不与变量共享命名空间，留给 LabelResolver 处理
+            Statement::Goto(label) => Ok(Statement::Goto(label)),
+            Statement::Labeled { name, stmt } => Ok(Statement::Labeled {
+                name,
+                stmt: Box::new(self.validate_statement(*stmt)?),
+            }),
             Statement::For {
                 init,
                 condition,
@@ -321,17 +450,57 @@ impl<'a> Validator<'a> {
     fn validate_expression(&mut self, expr: Expression) -> Result<Expression, String> {
         match expr {
             Expression::Constant(c) => Ok(Expression::Constant(c)),
+            Expression::LongConstant(c) => Ok(Expression::LongConstant(c)),
+            Expression::UIntConstant(c) => Ok(Expression::UIntConstant(c)),
+            Expression::ULongConstant(c) => Ok(Expression::ULongConstant(c)),
+            Expression::Cast {
+                target_type,
+                expression,
+            } => {
+                let validated_expr = self.validate_expression(*expression)?;
+                Ok(Expression::Cast {
+                    target_type,
+                    expression: Box::new(validated_expr),
+                })
+            }
+
+            Expression::Member { base, member } => Ok(Expression::Member {
+                base: Box::new(self.validate_expression(*base)?),
+                member,
+            }),
 
-            Expression::Var(name) => {
+            Expression::Var { name, line } => {
                 // 使用新的 find_variable 逻辑
                 if let Some(info) = self.find_identifier(&name) {
                     // 使用 info 中的 unique_name
-                    Ok(Expression::Var(info.unique_name))
+                    Ok(Expression::Var {
+                        name: info.unique_name,
+                        line,
+                    })
                 } else {
-                    Err(format!("Use of undeclared variable '{}'", name))
+                    // 记录错误后继续验证表达式的其余部分，同一个表达式中的
+                    // 其他未声明变量也会被报告
+                    self.errors.push(format!(
+                        "Use of undeclared variable '{}' on line {}",
+                        name, line
+                    ));
+                    Ok(Expression::Var { name, line })
                 }
             }
-            Expression::FunctionCall { name, args } => {
+            Expression::FunctionCall { name, args, line } => {
+                // __builtin_expect(expr, c) 只是分支预测的提示，它的值就是 expr。
+                // 用户自己声明了同名函数时，以用户的声明为准。
+                if self.builtins
+                    && name == "__builtin_expect"
+                    && self.find_identifier(&name).is_none()
+                {
+                    let [value, hint]: [Expression; 2] = args.try_into().map_err(|_| {
+                        "'__builtin_expect' expects exactly 2 arguments".to_string()
+                    })?;
+                    self.validate_expression(hint)?;
+                    return self.validate_expression(value);
+                }
+
                 // 查找函数名
                 let resolved_name = if let Some(info) = self.find_identifier(&name) {
                     // 在这里可以做一个简单的类型检查：这个名字必须指向一个函数
@@ -345,7 +514,11 @@ impl<'a> Validator<'a> {
                     }
                     info.unique_name // 对于函数，这个名字和原始名字一样
                 } else {
-                    return Err(format!("Call to undeclared function '{}'", name));
+                    self.errors.push(format!(
+                        "Call to undeclared function '{}' on line {}",
+                        name, line
+                    ));
+                    name
                 };
 
                 // 递归验证所有参数
@@ -357,11 +530,19 @@ impl<'a> Validator<'a> {
                 Ok(Expression::FunctionCall {
                     name: resolved_name,
                     args: validated_args,
+                    line,
                 })
             }
 
             Expression::Assign { left, right } => {
-                if !matches!(*left, Expression::Var(_)) {
+                // 与 C++ 不同，C 中赋值表达式的结果不是左值，`(a = b) = c` 是错误的
+                if let Expression::Assign { .. } = *left {
+                    return Err(
+                        "Invalid l-value for assignment: assignment result is not an l-value"
+                            .to_string(),
+                    );
+                }
+                if !is_lvalue(&left) {
                     return Err(format!("Invalid l-value for assignment: {:?}", left));
                 }
 
@@ -385,6 +566,37 @@ impl<'a> Validator<'a> {
                 })
             }
 
+            Expression::Prefix {
+                operator,
+                expression,
+            } => {
+                if !is_lvalue(&expression) {
+                    return Err(format!(
+                        "Invalid l-value for increment/decrement: {:?}",
+                        expression
+                    ));
+                }
+                Ok(Expression::Prefix {
+                    operator,
+                    expression: Box::new(self.validate_expression(*expression)?),
+                })
+            }
+            Expression::Postfix {
+                operator,
+                expression,
+            } => {
+                if !is_lvalue(&expression) {
+                    return Err(format!(
+                        "Invalid l-value for increment/decrement: {:?}",
+                        expression
+                    ));
+                }
+                Ok(Expression::Postfix {
+                    operator,
+                    expression: Box::new(self.validate_expression(*expression)?),
+                })
+            }
+
             Expression::Binary {
                 operator,
                 left,
@@ -412,6 +624,16 @@ impl<'a> Validator<'a> {
                     right: Box::new(validated_else),
                 })
             }
+            Expression::Comma { left, right } => Ok(Expression::Comma {
+                left: Box::new(self.validate_expression(*left)?),
+                right: Box::new(self.validate_expression(*right)?),
+            }),
+            Expression::SizeOf(SizeOfOperand::Type(c_type)) => Ok(Expression::SizeOf(
+                SizeOfOperand::Type(self.resolve_type(c_type)?),
+            )),
+            Expression::SizeOf(SizeOfOperand::Expression(expression)) => Ok(Expression::SizeOf(
+                SizeOfOperand::Expression(Box::new(self.validate_expression(*expression)?)),
+            )),
         }
     }
     fn find_identifier(&self, key: &str) -> Option<IdentifierInfo> {
@@ -429,6 +651,20 @@ impl<'a> Validator<'a> {
     }
 }
 
+/// 结构体标签在作用域表中的键。带空格的键不会和普通标识符冲突。
+fn struct_key(tag: &str) -> String {
+    format!("struct {}", tag)
+}
+
+/// 变量，以及左值的成员，才能出现在赋值号左边。
+fn is_lvalue(expr: &Expression) -> bool {
+    match expr {
+        Expression::Var { .. } => true,
+        Expression::Member { base, .. } => is_lvalue(base),
+        _ => false,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -444,7 +680,16 @@ mod tests {
         let ast = Parser::new(&tokens).parse().unwrap();
         let mut id_gen = UniqueIdGenerator::new();
         let mut validator = Validator::new(&mut id_gen);
-        validator.validate_program(ast)
+        validator
+            .validate_program(ast)
+            .map_err(|errors| errors.join("\n"))
+    }
+
+    fn var_name(expr: &Expression) -> &str {
+        match expr {
+            Expression::Var { name, .. } => name,
+            _ => panic!("Expected a variable, found {:?}", expr),
+        }
     }
     #[test]
     fn test_variable_shadowing_and_scopes() {
@@ -487,7 +732,7 @@ mod tests {
         };
         assert_eq!(decl_y1.0, "y.1");
         let init_y1 = decl_y1.1.as_ref().unwrap();
-        assert_eq!(*init_y1, Expression::Var("x.0".to_string()));
+        assert_eq!(var_name(init_y1), "x.0");
 
         // 3. { ... } -> Compound Statement
         let compound_stmt = match &function_body[2] {
@@ -510,12 +755,12 @@ mod tests {
         };
         if let Expression::Assign { left, right } = assign_stmt {
             // 【注意】赋值的左边也是一个 Expression::Var
-            if let Expression::Var(var_name) = &**left {
+            if let Expression::Var { name: var_name, .. } = &**left {
                 assert_eq!(var_name, "y.1");
             } else {
                 panic!("Expected a variable on the left side of assignment");
             }
-            assert_eq!(**right, Expression::Var("x.2".to_string()));
+            assert_eq!(var_name(right), "x.2");
         } else {
             panic!("Expected assignment expression");
         }
@@ -525,7 +770,7 @@ mod tests {
             BlockItem::S(Statement::Return(e)) => e,
             _ => panic!("Expected return statement"),
         };
-        assert_eq!(*return_stmt, Expression::Var("x.0".to_string()));
+        assert_eq!(var_name(return_stmt), "x.0");
 
         println!("--- Variable Shadowing Test Passed! ---");
     }
@@ -589,15 +834,15 @@ mod tests {
 
             // 3b. ...; i < a; ... -> condition 使用 i.2 和 a.0
             if let Some(Expression::Binary { left, right, .. }) = condition {
-                assert_eq!(**left, Expression::Var("i.2".to_string()));
-                assert_eq!(**right, Expression::Var("a.0".to_string()));
+                assert_eq!(var_name(left), "i.2");
+                assert_eq!(var_name(right), "a.0");
             } else {
                 panic!("Expected binary expression in condition");
             }
 
             // 3c. ...; i = i + 1 -> post 使用 i.2
             if let Some(Expression::Assign { left, .. }) = post {
-                if let Expression::Var(var_name) = &**left {
+                if let Expression::Var { name: var_name, .. } = &**left {
                     assert_eq!(var_name, "i.2");
                 } else {
                     panic!("Expected a variable on the left side of assignment");
@@ -616,7 +861,7 @@ mod tests {
                     } = decl_b
                     {
                         assert_eq!(*b_name, "b.3");
-                        if let Some(Expression::Var(name)) = b_init {
+                        if let Some(Expression::Var { name, .. }) = b_init {
                             assert_eq!(*name, "i.2");
                         } else {
                             panic!("Expected var in inner decl init");
@@ -636,7 +881,7 @@ mod tests {
 
         // 4. return i; -> 使用外层的 i.1
         if let BlockItem::S(Statement::Return(expr)) = &function_body[3] {
-            assert_eq!(*expr, Expression::Var("i.1".to_string()));
+            assert_eq!(var_name(expr), "i.1");
         } else {
             panic!("Expected a return statement");
         }
@@ -672,8 +917,8 @@ mod tests {
         // 检查 add 函数的返回语句
         if let BlockItem::S(Statement::Return(expr)) = &add_func.blocks[0] {
             if let Expression::Binary { left, right, .. } = expr {
-                assert_eq!(**left, Expression::Var("a.0".to_string()));
-                assert_eq!(**right, Expression::Var("b.1".to_string()));
+                assert_eq!(var_name(left), "a.0");
+                assert_eq!(var_name(right), "b.1");
             } else {
                 panic!("Expected binary expression in return");
             }
@@ -688,7 +933,7 @@ mod tests {
         };
         // 检查 main 函数的返回语句
         if let BlockItem::S(Statement::Return(expr)) = &main_func.blocks[0] {
-            if let Expression::FunctionCall { name, args } = expr {
+            if let Expression::FunctionCall { name, args, .. } = expr {
                 assert_eq!(*name, "add"); // 函数调用名未变
                 assert_eq!(args.len(), 2);
                 assert_eq!(args[0], Expression::Constant(1));
@@ -772,10 +1017,60 @@ mod tests {
         let result = validate_source(source_code);
         assert!(result.is_err());
         let error_msg = result.unwrap_err();
-        assert!(error_msg.contains("Call to undeclared function 'undeclared_func'"));
+        assert!(error_msg.contains("Call to undeclared function 'undeclared_func' on line 3"));
 
         println!("--- Undeclared Function Error Test Passed! ---");
     }
+
+    #[test]
+    fn test_reports_every_undeclared_variable() {
+        let source_code = r#"
+        int main(void) {
+            int a = first;
+            for (int i = 0; i < second; i = i + 1) {
+                a = a + 1;
+            }
+            return a + second + third;
+        }
+    "#;
+        let tokens: Vec<Token> = Lexer::new(source_code).collect::<Result<_, _>>().unwrap();
+        let ast = Parser::new(&tokens).parse().unwrap();
+        let mut id_gen = UniqueIdGenerator::new();
+        let errors = Validator::new(&mut id_gen)
+            .validate_program(ast)
+            .unwrap_err();
+        // 出错的声明仍然声明了 a，后面对 a 的使用不会产生额外的错误
+        assert_eq!(
+            errors,
+            vec![
+                "Use of undeclared variable 'first' on line 3",
+                "Use of undeclared variable 'second' on line 4",
+                "Use of undeclared variable 'second' on line 7",
+                "Use of undeclared variable 'third' on line 7",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_error_undeclared_variable_reports_line() {
+        let source_code = r#"
+        int main(void) {
+            int count = 0;
+            count = count + 1;
+            return cuont;
+        }
+    "#;
+        let error_msg = validate_source(source_code).unwrap_err();
+        assert_eq!(error_msg, "Use of undeclared variable 'cuont' on line 5");
+
+        // 预处理器行标记给出的原始文件名随 token 一起进入 AST
+        let error_msg =
+            validate_source("# 10 \"foo.c\"\nint main(void) {\n return cuont;\n}").unwrap_err();
+        assert_eq!(
+            error_msg,
+            "Use of undeclared variable 'cuont' on line 11 of foo.c"
+        );
+    }
     //测试 4：检查错误情况 - 重复的局部变量
     #[test]
     fn test_error_duplicate_local_variable() {
@@ -793,4 +1088,172 @@ mod tests {
 
         println!("--- Duplicate Local Variable Error Test Passed! ---");
     }
+
+    #[test]
+    fn test_error_nested_function_definition_reports_line() {
+        let source_code = "int main(void) {\n    int f(void) { return 1; }\n    return f();\n}\n";
+        let error_msg = validate_source(source_code).unwrap_err();
+        assert_eq!(
+            error_msg,
+            "Nested function definitions are not allowed: 'f' on line 2"
+        );
+    }
+
+    #[test]
+    fn test_nested_function_prototype_is_allowed() {
+        let source_code = r#"
+        int main(void) {
+            int f(int a);
+            return f(1);
+        }
+        int f(int a) {
+            return a;
+        }
+    "#;
+        assert!(validate_source(source_code).is_ok());
+    }
+
+    #[test]
+    fn test_builtin_expect_is_replaced_by_its_first_argument() {
+        let source_code = r#"
+        int main(void) {
+            int x = 1;
+            return __builtin_expect(x, 0);
+        }
+    "#;
+        let validated_ast = validate_source(source_code).expect("Validation should succeed");
+        let Declaration::Function {
+            body: Some(body), ..
+        } = &validated_ast.declarations[0]
+        else {
+            panic!("Expected main function");
+        };
+        assert_eq!(
+            body.blocks[1],
+            BlockItem::S(Statement::Return(Expression::Var {
+                name: "x.0".to_string(),
+                line: 4.into(),
+            }))
+        );
+
+        let error_msg =
+            validate_source("int main(void) { return __builtin_expect(1); }").unwrap_err();
+        assert!(error_msg.contains("expects exactly 2 arguments"));
+    }
+
+    #[test]
+    fn test_increment_requires_lvalue() {
+        let validated_ast =
+            validate_source("int main(void) { int x = 1; return x++ + --x; }").unwrap();
+        let Declaration::Function {
+            body: Some(body), ..
+        } = &validated_ast.declarations[0]
+        else {
+            panic!("Expected main function");
+        };
+        let BlockItem::S(Statement::Return(Expression::Binary { left, right, .. })) =
+            &body.blocks[1]
+        else {
+            panic!("Expected a return of a binary expression");
+        };
+        assert_eq!(
+            **left,
+            Expression::Postfix {
+                operator: IncDecOperator::Increment,
+                expression: Box::new(Expression::Var {
+                    name: "x.0".to_string(),
+                    line: 1.into(),
+                }),
+            }
+        );
+        assert!(matches!(
+            &**right,
+            Expression::Prefix {
+                operator: IncDecOperator::Decrement,
+                ..
+            }
+        ));
+
+        for source in [
+            "int main(void) { return ++3; }",
+            "int main(void) { int x = 0; return (x + 1)--; }",
+        ] {
+            let error_msg = validate_source(source).unwrap_err();
+            assert!(
+                error_msg.contains("Invalid l-value for increment/decrement"),
+                "{}",
+                error_msg
+            );
+        }
+    }
+
+    #[test]
+    fn test_assignment_result_is_not_an_lvalue() {
+        let error_msg =
+            validate_source("int main(void) { int a; int b; int c = 1; (a = b) = c; return a; }")
+                .unwrap_err();
+        assert!(
+            error_msg.contains("assignment result is not an l-value"),
+            "{}",
+            error_msg
+        );
+        // 赋值是右结合的：a = (b = c)，只有最外层的左边需要是左值
+        assert!(
+            validate_source("int main(void) { int a; int b; int c = 1; a = b = c; return a; }")
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_prototype_with_unnamed_params() {
+        // 省略的参数名不会被当作重名参数
+        assert!(validate_source("int f(int, int); int main(void) { return f(1, 2); }").is_ok());
+        assert!(validate_source("int f(int, int); int f(int a, int b) { return a + b; }").is_ok());
+    }
+
+    #[test]
+    fn test_shadow_warning() {
+        let source = "int g; int main(void) { int x = 1; { int x = 2; int g = x; } return x; }";
+        let validate = |warn_shadow: bool| {
+            let tokens: Vec<Token> = Lexer::new(source).collect::<Result<_, _>>().unwrap();
+            let ast = Parser::new(&tokens).parse().unwrap();
+            let mut id_gen = UniqueIdGenerator::new();
+            let mut warnings = Vec::new();
+            let mut collect = |w| warnings.push(w);
+            let mut validator = Validator::new(&mut id_gen);
+            validator.warn_shadow = warn_shadow;
+            validator.on_warning = Some(&mut collect);
+            let program = validator.validate_program(ast).unwrap();
+            (program, warnings)
+        };
+
+        let (program, warnings) = validate(true);
+        assert_eq!(
+            warnings,
+            vec![
+                "warning: declaration of 'x' shadows a previous local",
+                "warning: declaration of 'g' shadows a global declaration",
+            ]
+        );
+        // 警告不影响重命名：内层的 x 和 g 仍然是新的变量
+        let Declaration::Function {
+            body: Some(body), ..
+        } = &program.declarations[1]
+        else {
+            panic!("Expected main function");
+        };
+        let BlockItem::S(Statement::Compound(inner)) = &body.blocks[1] else {
+            panic!("Expected an inner block");
+        };
+        assert!(matches!(
+            &inner.blocks[..],
+            [
+                BlockItem::D(Declaration::Variable { name: x, .. }),
+                BlockItem::D(Declaration::Variable { name: g, init: Some(init), .. }),
+            ] if x == "x.1" && g == "g.2" && var_name(init) == "x.1"
+        ));
+
+        let (_, warnings) = validate(false);
+        assert!(warnings.is_empty());
+    }
 }
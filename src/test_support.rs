@@ -0,0 +1,100 @@
+//! src/test_support.rs
+//! 单元测试共用的前端：按 driver 中的顺序运行各个 pass（包括 goto 标签解析），
+//! 任何一步出错都直接 panic。
+
+use crate::ast::checked;
+use crate::backend::asm_gen::AsmGenerator;
+use crate::backend::tacky_gen::TackyGenerator;
+use crate::common::UniqueIdGenerator;
+use crate::ir::{assembly, tacky};
+use crate::lexer::{Lexer, Token};
+use crate::parser::Parser;
+use crate::semantics::label_resolver::LabelResolver;
+use crate::semantics::loop_labeler::LoopLabeler;
+use crate::semantics::type_checker::TypeChecker;
+use crate::semantics::validator::Validator;
+
+/// 经过完整语义分析的程序，以及后续阶段还需要的符号表和 ID 生成器
+pub struct Checked {
+    pub ast: checked::Program,
+    pub type_checker: TypeChecker,
+    pub id_gen: UniqueIdGenerator,
+}
+
+/// 词法分析、语法分析和全部语义分析
+pub fn check_source(source: &str) -> Checked {
+    try_check_source(source, |_| {}).unwrap()
+}
+
+/// 同 `check_source`，但可以先用 `configure` 调整类型检查器的设置，
+/// 并返回语义分析的错误（多条错误以换行分隔）
+pub fn try_check_source(
+    source: &str,
+    configure: impl FnOnce(&mut TypeChecker),
+) -> Result<Checked, String> {
+    let tokens: Vec<Token> = Lexer::new(source).collect::<Result<_, _>>().unwrap();
+    let ast = Parser::new(&tokens).parse().unwrap();
+    let mut id_gen = UniqueIdGenerator::new();
+    let ast = Validator::new(&mut id_gen)
+        .validate_program(ast)
+        .map_err(|errors| errors.join("\n"))?;
+    let ast = LabelResolver::new(&mut id_gen)
+        .resolve_program(ast)
+        .map_err(|errors| errors.join("\n"))?;
+    let mut type_checker = TypeChecker::new();
+    configure(&mut type_checker);
+    let ast = type_checker
+        .check_program(ast)
+        .map_err(|errors| errors.join("\n"))?;
+    let ast = LoopLabeler::new(&mut id_gen)
+        .label_program(ast)
+        .map_err(|errors| errors.join("\n"))?;
+    Ok(Checked {
+        ast,
+        type_checker,
+        id_gen,
+    })
+}
+
+/// 运行前端并生成 TACKY
+pub fn tacky_for_source(source: &str) -> tacky::Program {
+    try_tacky_for_source(source, |_| {}).unwrap()
+}
+
+/// 同 `tacky_for_source`，但可以先用 `configure` 调整生成器的设置，并返回生成阶段的错误
+pub fn try_tacky_for_source(
+    source: &str,
+    configure: impl FnOnce(&mut TackyGenerator),
+) -> Result<tacky::Program, String> {
+    let Checked {
+        ast,
+        mut type_checker,
+        mut id_gen,
+    } = check_source(source);
+    let mut generator = TackyGenerator::new(
+        &mut id_gen,
+        &mut type_checker.symbols,
+        &type_checker.struct_types,
+    );
+    configure(&mut generator);
+    generator.generate_tacky(ast)
+}
+
+/// 运行前端，在 -O0 下生成 TACKY 并降级为汇编（经过全部三个 pass）
+pub fn asm_for_source(source: &str) -> assembly::Program {
+    let Checked {
+        ast,
+        mut type_checker,
+        mut id_gen,
+    } = check_source(source);
+    let tacky = TackyGenerator::new(
+        &mut id_gen,
+        &mut type_checker.symbols,
+        &type_checker.struct_types,
+    )
+    .generate_tacky(ast)
+    .unwrap();
+    AsmGenerator::new(&type_checker.symbols, &type_checker.struct_types, 0)
+        .generate_assembly(tacky)
+        .unwrap()
+}
@@ -0,0 +1,135 @@
+// tests/book_suite.rs
+//! 回归测试套件：按章节组织的 C 程序，结构参考《Writing a C Compiler》的测试集。
+//!
+//! `tests/programs/<chapter>/` 下的每个子目录代表一种期望结果：
+//! - `valid/`：必须编译成功；第一行的 `// expect: N` 给出程序的退出码。
+//! - `invalid_lex/`、`invalid_parse/`、`invalid_semantics/`：必须在对应阶段失败。
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Stage {
+    Lex,
+    Parse,
+    Semantics,
+}
+
+/// 递归收集目录下所有的 `.c` 文件，并排序以保证输出稳定。
+fn collect_programs(dir: &Path, programs: &mut Vec<PathBuf>) {
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            collect_programs(&path, programs);
+        } else if path.extension().is_some_and(|ext| ext == "c") {
+            programs.push(path);
+        }
+    }
+    programs.sort();
+}
+
+/// 在临时目录中编译一个程序，返回编译器的输出以及可执行文件路径。
+fn compile(program: &Path, work_dir: &Path) -> (std::process::Output, PathBuf) {
+    let file_name = program.file_name().unwrap();
+    let source_path = work_dir.join(file_name);
+    fs::copy(program, &source_path).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_my_c_compiler"))
+        .arg(&source_path)
+        .output()
+        .unwrap();
+    (output, source_path.with_extension(""))
+}
+
+/// 解析 `// expect: N` 注释。
+fn expected_exit_code(program: &Path) -> Option<i32> {
+    let source = fs::read_to_string(program).unwrap();
+    let first_line = source.lines().next()?;
+    first_line
+        .strip_prefix("// expect:")
+        .and_then(|code| code.trim().parse().ok())
+}
+
+/// 根据编译器的退出码判断编译在哪个阶段失败（见 `--help` 中的 Exit status）。
+fn failed_stage(status: std::process::ExitStatus) -> Option<Stage> {
+    match status.code() {
+        Some(2) => Some(Stage::Lex),
+        Some(3) => Some(Stage::Parse),
+        Some(4) => Some(Stage::Semantics),
+        _ => None,
+    }
+}
+
+/// 检查单个程序，失败时返回描述问题的字符串。
+fn check_program(program: &Path, work_dir: &Path) -> Result<(), String> {
+    let kind = program
+        .parent()
+        .and_then(|dir| dir.file_name())
+        .and_then(|name| name.to_str())
+        .unwrap();
+    let (output, exe_path) = compile(program, work_dir);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    let expected_stage = match kind {
+        "valid" => {
+            if !output.status.success() {
+                return Err(format!("failed to compile:\n{}", stderr));
+            }
+            let expected = expected_exit_code(program)
+                .ok_or_else(|| "missing `// expect: N` on the first line".to_string())?;
+            let status = Command::new(&exe_path).status().unwrap();
+            return match status.code() {
+                Some(code) if code == expected => Ok(()),
+                code => Err(format!("expected exit code {}, got {:?}", expected, code)),
+            };
+        }
+        "invalid_lex" => Stage::Lex,
+        "invalid_parse" => Stage::Parse,
+        "invalid_semantics" => Stage::Semantics,
+        other => return Err(format!("unknown test category '{}'", other)),
+    };
+
+    if output.status.success() {
+        return Err(format!(
+            "expected a {:?} error, but compilation succeeded",
+            expected_stage
+        ));
+    }
+    match failed_stage(output.status) {
+        Some(stage) if stage == expected_stage => Ok(()),
+        stage => Err(format!(
+            "expected a {:?} error, but failed at {:?}:\n{}",
+            expected_stage, stage, stderr
+        )),
+    }
+}
+
+#[test]
+fn test_book_suite() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/programs");
+    let mut programs = Vec::new();
+    collect_programs(&root, &mut programs);
+    assert!(!programs.is_empty(), "no test programs found");
+
+    let work_dir =
+        std::env::temp_dir().join(format!("my_c_compiler_book_suite_{}", std::process::id()));
+    fs::create_dir_all(&work_dir).unwrap();
+
+    // 收集所有失败而不是在第一个失败处停下，方便一次看到全部问题
+    let mut failures = Vec::new();
+    for program in &programs {
+        if let Err(message) = check_program(program, &work_dir) {
+            let name = program.strip_prefix(&root).unwrap().display();
+            failures.push(format!("{}: {}", name, message));
+        }
+    }
+    fs::remove_dir_all(&work_dir).unwrap();
+
+    assert!(
+        failures.is_empty(),
+        "{} of {} programs failed:\n{}",
+        failures.len(),
+        programs.len(),
+        failures.join("\n")
+    );
+}
@@ -0,0 +1,1768 @@
+// tests/end_to_end.rs
+//! 端到端测试：调用编译器二进制文件，编译 C 源码，运行生成的可执行文件并检查退出码。
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// 为每个测试创建一个独立的临时目录，避免并行测试之间互相干扰。
+fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("my_c_compiler_{}_{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// 把 `source` 写入独立临时目录中的 `<name>.c`，在这个目录中带上 `args` 编译它，
+/// 再把目录和编译器的输出交给 `inspect` 检查生成的文件；返回之前删除临时目录。
+/// `args` 中的相对路径（例如 `-o` 的参数）以临时目录为基准。
+fn with_compiled<T>(
+    name: &str,
+    source: impl AsRef<[u8]>,
+    args: &[&str],
+    inspect: impl FnOnce(&Path, Output) -> T,
+) -> T {
+    let dir = test_dir(name);
+    let source_path = dir.join(format!("{}.c", name));
+    fs::write(&source_path, source).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_my_c_compiler"))
+        .current_dir(&dir)
+        .args(args)
+        .arg(&source_path)
+        .output()
+        .unwrap();
+    let result = inspect(&dir, output);
+    fs::remove_dir_all(&dir).unwrap();
+    result
+}
+
+/// 同 `with_compiled`，只返回编译器的输出。
+fn run_compiler(name: &str, source: impl AsRef<[u8]>, args: &[&str]) -> Output {
+    with_compiled(name, source, args, |_, output| output)
+}
+
+fn assert_compiled(output: &Output) {
+    assert!(
+        output.status.success(),
+        "compilation failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+/// 带上 `args` 编译并运行一段 C 源码，返回程序的退出码。
+fn compile_and_run_with(name: &str, source: &str, args: &[&str]) -> i32 {
+    with_compiled(name, source, args, |dir, output| {
+        assert_compiled(&output);
+        Command::new(dir.join(name))
+            .status()
+            .unwrap()
+            .code()
+            .expect("program was terminated by a signal")
+    })
+}
+
+/// 编译并运行一段 C 源码，返回程序的退出码。
+fn compile_and_run(name: &str, source: &str) -> i32 {
+    compile_and_run_with(name, source, &[])
+}
+
+#[test]
+fn test_build_produces_executable_and_cleans_up() {
+    let (status, mut left) = with_compiled(
+        "return_42",
+        "int main(void) { return 42; }",
+        &[],
+        |dir, output| {
+            assert_compiled(&output);
+            let status = Command::new(dir.join("return_42")).status().unwrap();
+            // 成功后只留下源文件和可执行文件，.i 和 .s 都已被删除
+            let left: Vec<String> = fs::read_dir(dir)
+                .unwrap()
+                .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+                .collect();
+            (status, left)
+        },
+    );
+    left.sort();
+    assert_eq!(status.code(), Some(42));
+    assert_eq!(left, vec!["return_42", "return_42.c"]);
+}
+
+#[test]
+fn test_bool_normalizes_stored_values() {
+    assert_eq!(
+        compile_and_run("bool_true", "int main(void) { _Bool b = 5; return b; }"),
+        1
+    );
+    assert_eq!(
+        compile_and_run("bool_false", "int main(void) { _Bool b = 0; return b; }"),
+        0
+    );
+    // 赋值表达式同样需要规范化，且表达式的值是规范化后的值
+    assert_eq!(
+        compile_and_run(
+            "bool_assign",
+            "int main(void) { _Bool b; int x = (b = -3) + 1; return x + b; }"
+        ),
+        3
+    );
+    // `bool` 是内建的 typedef 名字，不是关键字，仍然可以用作普通标识符
+    assert_eq!(
+        compile_and_run("bool_typedef", "int main(void) { bool b = 7; return b; }"),
+        1
+    );
+    assert_eq!(
+        compile_and_run(
+            "bool_identifier",
+            "int bool(int x) { return x + 1; } int main(void) { int r = bool(3); return r; }"
+        ),
+        4
+    );
+}
+
+#[test]
+fn test_char_wraps_to_eight_bits() {
+    // 存入 char 的值只保留低 8 位并做符号扩展，参与运算时先提升为 int
+    assert_eq!(
+        compile_and_run(
+            "char_wrap",
+            "int main(void) { char c = 300; char d = 127; d++; return c + (d == -128); }"
+        ),
+        45
+    );
+    assert_eq!(
+        compile_and_run(
+            "char_static",
+            "int main(void) { static char s = -1; long l = s; unsigned u = s; return (l == -1) + (u == 4294967295u) + (s + 1 == 0); }"
+        ),
+        3
+    );
+    // 相邻的 char 成员各占 1 字节，读出时做符号扩展
+    assert_eq!(
+        compile_and_run(
+            "char_members",
+            "struct C { char a; char b; char c; int n; };
+             int main(void) {
+                 struct C s;
+                 s.a = 1;
+                 s.b = 200;
+                 s.c = 3;
+                 s.n = 4;
+                 return (sizeof s == 8) * 100 + (s.b == -56) * 10 + s.a + s.c + s.n;
+             }"
+        ),
+        100 + 10 + 8
+    );
+
+    // char 类型的静态变量占 1 字节，初始值用 .byte 发射
+    let source = "char g = 300; char z; int main(void) { return g + z; }";
+    let output = run_compiler("char_static_asm", source, &["--asm-stdout", "--quiet"]);
+    assert_compiled(&output);
+    let assembly = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        assembly.contains("    .data\n.globl g\n    .balign 1\ng:\n    .byte 44\n"),
+        "{}",
+        assembly
+    );
+    assert!(
+        assembly.contains("    .bss\n.globl z\n    .balign 1\nz:\n    .zero 1\n"),
+        "{}",
+        assembly
+    );
+}
+
+#[test]
+fn test_long_parameters() {
+    // 实参按形参类型转换；第 7、8 个参数经栈传递，也要保留完整的 64 位
+    let source = r#"
+        int is_big(long x) { return x > 4294967295; }
+        int pick(int a, int b, int c, int d, int e, int f, long g, long h) {
+            return (g - h) / 4294967296;
+        }
+        int main(void) {
+            return is_big(4294967296) * 10 + is_big(7) + pick(1, 2, 3, 4, 5, 6, 12884901888, 0);
+        }
+    "#;
+    assert_eq!(compile_and_run("long_params", source), 13);
+}
+
+#[test]
+fn test_long_return_type() {
+    // 超出 32 位的常量经由 long 返回值完整地传回调用者
+    let source = r#"
+        long big(void) { return 4294967296 * 3 + 5; }
+        long long twice(long x) { return x + x; }
+        _Bool nonzero(long x) { return x; }
+        int main(void) {
+            long v = twice(big());
+            return (v - 25769803776) + nonzero(4294967296) * 100;
+        }
+    "#;
+    assert_eq!(compile_and_run("long_return", source), 110);
+}
+
+#[test]
+fn test_unsigned_arithmetic() {
+    // 每个检查失败时返回不同的非零值；-O1 下同样的表达式在编译期折叠，结果必须一致
+    let source = r#"
+        unsigned quotient(unsigned a, unsigned b) { return a / b; }
+        unsigned remainder(unsigned a, unsigned b) { return a % b; }
+        int main(void) {
+            unsigned max = 0u - 1u;
+            if (!(0u - 1u > 1u)) return 1;
+            if (max < 1u) return 2;
+            if (-1 < 1u) return 3;
+            if (quotient(max, 2u) != 2147483647u) return 4;
+            if (remainder(max, 10u) != 5u) return 5;
+            if (max / 2u != 2147483647u) return 6;
+            if ((max >> 31) != 1u) return 7;
+            long wide = max;
+            if (wide != 4294967295) return 8;
+            unsigned x = 3000000000u;
+            if (x / 1000000000u != 3u || x <= 2u) return 9;
+            return 0;
+        }
+    "#;
+    for opt_level in ["-O0", "-O1"] {
+        assert_eq!(
+            compile_and_run_with(&format!("unsigned{}", opt_level), source, &[opt_level]),
+            0,
+            "failed at {}",
+            opt_level
+        );
+    }
+}
+
+#[test]
+fn test_bitwise_operators() {
+    assert_eq!(
+        compile_and_run("bit_and", "int main(void) { return 6 & 3; }"),
+        2
+    );
+    assert_eq!(
+        compile_and_run("bit_or", "int main(void) { return 5 | 2; }"),
+        7
+    );
+    assert_eq!(
+        compile_and_run("bit_xor", "int main(void) { return 6 ^ 3; }"),
+        5
+    );
+    // 变量操作数，并与 && 混用
+    assert_eq!(
+        compile_and_run(
+            "bit_mixed",
+            "int main(void) { int a = 12; int b = 10; return (a & b) + (a | b) + (a ^ b) + (a && b); }"
+        ),
+        8 + 14 + 6 + 1
+    );
+    // long 操作数的结果仍是 long
+    assert_eq!(
+        compile_and_run(
+            "bit_long",
+            "int main(void) { return ((4294967296 | 6) > 4294967295) + ((4294967296 ^ 6) & 255); }"
+        ),
+        7
+    );
+}
+
+#[test]
+fn test_shift_operators() {
+    assert_eq!(
+        compile_and_run("shift_left", "int main(void) { return 1 << 4; }"),
+        16
+    );
+    assert_eq!(
+        compile_and_run("shift_right", "int main(void) { return 256 >> 2; }"),
+        64
+    );
+    // 移位次数来自变量时经由 %cl；>> 是算术右移；移位的优先级低于加法
+    assert_eq!(
+        compile_and_run(
+            "shift_vars",
+            "int main(void) { int n = 3; int neg = -16; return (1 << n + 1) + (neg >> n) + 10; }"
+        ),
+        16 - 2 + 10
+    );
+    // long 左操作数配 int 移位次数，结果是 long
+    assert_eq!(
+        compile_and_run(
+            "shift_long",
+            "int main(void) { int n = 30; return 3000000000 >> n; }"
+        ),
+        2
+    );
+}
+
+#[test]
+fn test_increment_and_decrement() {
+    assert_eq!(
+        compile_and_run("postfix_inc", "int main(void) { int x = 5; return x++; }"),
+        5
+    );
+    assert_eq!(
+        compile_and_run("prefix_inc", "int main(void) { int x = 5; return ++x; }"),
+        6
+    );
+    // 副作用都已生效；`a+++b` 按最长匹配解析为 `a++ + b`
+    assert_eq!(
+        compile_and_run(
+            "inc_dec_mixed",
+            "int main(void) { int a = 1; int b = 10; int c = a+++b; int d = b--; return a * 10 + c + --d - b; }"
+        ),
+        2 * 10 + 11 + 9 - 9
+    );
+    // 结构体成员、_Bool 和循环中的自增
+    assert_eq!(
+        compile_and_run(
+            "inc_dec_lvalues",
+            "struct S { int n; };
+             int main(void) {
+                 struct S s;
+                 s.n = 0;
+                 for (int i = 0; i < 4; i++)
+                     s.n++;
+                 _Bool t = 0;
+                 _Bool u = t--;
+                 t++;
+                 t++;
+                 return s.n * 10 + t + u;
+             }"
+        ),
+        41
+    );
+}
+
+#[test]
+fn test_struct_member_access() {
+    assert_eq!(
+        compile_and_run(
+            "struct_members",
+            "struct Point { int x; _Bool b; int y; };
+             int main(void) {
+                 struct Point p;
+                 struct Point q;
+                 p.x = 3;
+                 q.y = 4;
+                 p.b = 7;
+                 p.y = p.x + q.y;
+                 return p.y + p.b + q.y;
+             }"
+        ),
+        12
+    );
+    // 1 字节的成员和变量相邻存放，写入一个不会改动另一个
+    assert_eq!(
+        compile_and_run(
+            "struct_byte_members",
+            "struct Flags { _Bool a; _Bool b; int n; };
+             int main(void) {
+                 struct Flags f;
+                 _Bool x = 0;
+                 _Bool y = 0;
+                 f.a = 0;
+                 f.n = -1;
+                 f.b = 9;
+                 x = 2;
+                 return f.a * 100 + f.b * 10 + (f.n == -1) + y * 50 + x * 2;
+             }"
+        ),
+        10 + 1 + 2
+    );
+    // 内层作用域中的同名结构体标签遮蔽外层的声明
+    assert_eq!(
+        compile_and_run(
+            "struct_shadowing",
+            "struct P { int x; };
+             int main(void) {
+                 struct P a;
+                 a.x = 2;
+                 {
+                     struct P { int y; int x; };
+                     struct P b;
+                     b.x = 3;
+                     b.y = 4;
+                     return a.x + b.x * b.y;
+                 }
+             }"
+        ),
+        14
+    );
+}
+
+#[test]
+fn test_large_constant_is_long() {
+    assert_eq!(
+        compile_and_run("long_compare", "int main(void) { return 3000000000 > 0; }"),
+        1
+    );
+    // long 运算的结果被截断为 int 返回
+    assert_eq!(
+        compile_and_run(
+            "long_arith",
+            "int main(void) { return (4294967296 + 42) - 4294967296; }"
+        ),
+        42
+    );
+}
+
+#[test]
+fn test_pipe_asm_leaves_no_assembly_file() {
+    let (wrote_asm, exe_status) = with_compiled(
+        "pipe_asm",
+        "int main(void) { return 7; }",
+        &["--pipe-asm"],
+        |dir, output| {
+            assert_compiled(&output);
+            (
+                dir.join("pipe_asm.s").exists(),
+                Command::new(dir.join("pipe_asm")).status().unwrap(),
+            )
+        },
+    );
+    assert!(!wrote_asm);
+    assert_eq!(exe_status.code(), Some(7));
+}
+
+#[test]
+fn test_pipe_asm_with_compile_only() {
+    let (wrote_asm, exe_status) = with_compiled(
+        "pipe_asm_obj",
+        "int main(void) { return 9; }",
+        &["--pipe-asm", "-c"],
+        |dir, output| {
+            assert_compiled(&output);
+            // 单独链接生成的目标文件，确认它是有效的
+            let exe_path = dir.join("pipe_asm_obj");
+            let link = Command::new("gcc")
+                .arg(dir.join("pipe_asm_obj.o"))
+                .arg("-o")
+                .arg(&exe_path)
+                .status()
+                .unwrap();
+            assert!(link.success());
+            (
+                dir.join("pipe_asm_obj.s").exists(),
+                Command::new(&exe_path).status().unwrap(),
+            )
+        },
+    );
+    assert!(!wrote_asm);
+    assert_eq!(exe_status.code(), Some(9));
+}
+
+#[test]
+fn test_object_links_with_gcc_compiled_main() {
+    let source = r#"
+        int square(int x) { return x * x; }
+        long shift_sum(int a, int b, int c, int d, int e, int f, int g, long h) {
+            return a + b + c + d + e + f + g * 1000 + h;
+        }
+    "#;
+    let exe_status = with_compiled("interop", source, &["-c"], |dir, output| {
+        assert_compiled(&output);
+        // 调用方由 gcc 编译：负数参数、超过 6 个的栈上参数以及 64 位的返回值
+        // 都必须与 System V 调用约定一致
+        let main_path = dir.join("main.c");
+        fs::write(
+            &main_path,
+            r#"
+            int square(int x);
+            long shift_sum(int a, int b, int c, int d, int e, int f, int g, long h);
+            int main(void) {
+                if (square(7) != 49 || square(-3) != 9) return 1;
+                if (shift_sum(1, 2, 3, 4, 5, 6, 7, 10000000000L) != 10000007021L) return 2;
+                return 0;
+            }
+            "#,
+        )
+        .unwrap();
+        let exe_path = dir.join("interop");
+        let link = Command::new("gcc")
+            .arg(&main_path)
+            .arg(dir.join("interop.o"))
+            .arg("-o")
+            .arg(&exe_path)
+            .status()
+            .unwrap();
+        assert!(link.success());
+        Command::new(&exe_path).status().unwrap()
+    });
+    assert_eq!(exe_status.code(), Some(0));
+}
+
+#[test]
+fn test_output_flag() {
+    let source = "int main(void) { return 11; }";
+
+    // -o 决定可执行文件的位置，--assert-exit 运行的也是它
+    let (exe_status, has_default_exe) = with_compiled(
+        "output_flag",
+        source,
+        &["--assert-exit", "11", "-o", "./custom_exe"],
+        |dir, output| {
+            assert_compiled(&output);
+            (
+                Command::new(dir.join("custom_exe")).status().unwrap(),
+                dir.join("output_flag").exists(),
+            )
+        },
+    );
+    assert_eq!(exe_status.code(), Some(11));
+    assert!(!has_default_exe);
+
+    // 和 -c 一起使用时，-o 指定目标文件的名字
+    let (has_object, has_default_object) = with_compiled(
+        "output_flag_object",
+        source,
+        &["-c", "-o", "custom.o"],
+        |dir, output| {
+            assert_compiled(&output);
+            (
+                dir.join("custom.o").exists(),
+                dir.join("output_flag_object.o").exists(),
+            )
+        },
+    );
+    assert!(has_object);
+    assert!(!has_default_object);
+}
+
+#[test]
+fn test_warn_uninitialized() {
+    let compile = |name: &str, source: &str| {
+        let output = run_compiler(name, source, &["-Wuninitialized", "--quiet"]);
+        assert!(output.status.success());
+        String::from_utf8(output.stderr).unwrap()
+    };
+
+    let stderr = compile("uninit_read", "int main(void) { int x; return x; }");
+    assert!(
+        stderr.contains("warning: 'x' is used uninitialized in function 'main'"),
+        "unexpected stderr:\n{}",
+        stderr
+    );
+    let stderr = compile("uninit_ok", "int main(void) { int x; x = 1; return x; }");
+    assert!(
+        !stderr.contains("warning"),
+        "unexpected stderr:\n{}",
+        stderr
+    );
+}
+
+#[test]
+fn test_analyze_reports_every_category_without_codegen() {
+    let source = r#"
+        int g;
+        int helper(int n) {
+            int unused;
+            if (n = 3) return n;
+        }
+        int main(void) {
+            int x;
+            int y = x;
+            {
+                int y = 2;
+                g = y;
+            }
+            return helper(y);
+            g = 1;
+        }
+    "#;
+
+    let (output, produced_executable) = with_compiled(
+        "analyze",
+        source,
+        &["--analyze", "--quiet"],
+        |dir, output| (output, dir.join("analyze").exists()),
+    );
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success(), "{}", stderr);
+    assert!(!produced_executable);
+    for expected in [
+        "'x' is used uninitialized in function 'main'",
+        "declaration of 'y' shadows a previous local",
+        "unused variable 'unused' in function 'helper'",
+        "unreachable code after 'return' in function 'main'",
+        "assignment used as a condition in function 'helper'",
+        "control reaches the end without a return in function 'helper'",
+    ] {
+        assert!(
+            stderr.contains(expected),
+            "missing '{}' in:\n{}",
+            expected,
+            stderr
+        );
+    }
+
+    let werror_output = run_compiler(
+        "analyze_werror",
+        source,
+        &["--analyze", "--quiet", "--werror"],
+    );
+    let werror_stderr = String::from_utf8(werror_output.stderr).unwrap();
+    assert!(!werror_output.status.success());
+    assert!(
+        werror_stderr.contains("6 warning(s) treated as errors"),
+        "{}",
+        werror_stderr
+    );
+}
+
+#[test]
+fn test_compile_db_entry() {
+    let dir = test_dir("compile_db");
+    fs::write(dir.join("entry.c"), "int main(void) { return 0; }").unwrap();
+    let entry_path = dir.join("entry.json");
+    fs::write(
+        &entry_path,
+        format!(
+            r#"{{"directory": "{}", "file": "entry.c", "arguments": ["cc", "-c", "entry.c"], "output": "entry.o"}}"#,
+            dir.display()
+        ),
+    )
+    .unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_my_c_compiler"))
+        .arg("--compile-db-entry")
+        .arg(&entry_path)
+        .output()
+        .unwrap();
+    assert!(
+        status.status.success(),
+        "compilation failed:\n{}",
+        String::from_utf8_lossy(&status.stderr)
+    );
+    // 条目中的 -c 生效：只产生目标文件，不链接
+    assert!(dir.join("entry.o").exists());
+    assert!(!dir.join("entry").exists());
+
+    // `command` 形式：其他编译器的选项被跳过，-o 以 directory 为基准
+    fs::create_dir_all(dir.join("build")).unwrap();
+    fs::write(
+        &entry_path,
+        format!(
+            r#"{{"directory": "{}", "file": "entry.c", "command": "gcc -I include -DNDEBUG -std=c11 -Wall -g -Wuninitialized -c entry.c -o 'build/entry.o'"}}"#,
+            dir.display()
+        ),
+    )
+    .unwrap();
+    let status = Command::new(env!("CARGO_BIN_EXE_my_c_compiler"))
+        .arg("--compile-db-entry")
+        .arg(&entry_path)
+        .output()
+        .unwrap();
+    assert!(
+        status.status.success(),
+        "compilation failed:\n{}",
+        String::from_utf8_lossy(&status.stderr)
+    );
+    assert!(dir.join("build/entry.o").exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_assert_exit() {
+    let run = |name: &str, source: &str, expected: &str| {
+        run_compiler(name, source, &[&format!("--assert-exit={}", expected)])
+            .status
+            .success()
+    };
+
+    assert!(run("assert_exit_ok", "int main(void){return 42;}", "42"));
+    assert!(!run("assert_exit_bad", "int main(void){return 7;}", "42"));
+}
+
+#[test]
+fn test_main_with_void_or_empty_params() {
+    assert_eq!(
+        compile_and_run("main_void", "int main(void) { return 3; }"),
+        3
+    );
+    assert_eq!(compile_and_run("main_empty", "int main() { return 4; }"), 4);
+}
+
+#[test]
+fn test_print_stack_usage() {
+    let output = run_compiler(
+        "stack_usage",
+        "int main(void) { int a = 1; int b = 2; int c = 3; return c; }",
+        &["--print-stack-usage"],
+    );
+    assert!(output.status.success());
+
+    // 三个 int 局部变量共 12 字节，对齐后为 16 字节
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let row = stdout
+        .lines()
+        .find(|line| line.starts_with("main "))
+        .expect("missing stack usage row for main");
+    assert_eq!(row.split_whitespace().collect::<Vec<_>>(), ["main", "16"]);
+}
+
+#[test]
+fn test_print_temps() {
+    let temps_for_main = |opt_level: &str| {
+        let output = run_compiler(
+            &format!("print_temps{}", opt_level),
+            "int main(void) { return 1 + 2 + 3; }",
+            &["--print-temps", opt_level],
+        );
+        assert!(output.status.success());
+
+        let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+        let row = stdout
+            .lines()
+            .find(|line| line.starts_with("main "))
+            .expect("missing temporaries row for main");
+        row.split_whitespace()
+            .nth(1)
+            .unwrap()
+            .parse::<usize>()
+            .unwrap()
+    };
+
+    // (1 + 2) 和 (... + 3) 各需要一个临时变量；-O1 折叠后只剩 `return 6`
+    assert_eq!(temps_for_main("-O0"), 2);
+    assert_eq!(temps_for_main("-O1"), 0);
+}
+
+#[test]
+fn test_o1_lowers_simple_ternary_to_cmov() {
+    let (assembly, exe_status) = with_compiled(
+        "cmov_min",
+        // 操作数来自参数，-O1 的常量传播无法把比较提前算掉
+        "int min(int a, int b) { int m = a < b ? a : b; return m; }\n\
+         int main(void) { return min(3, 5); }",
+        &["-O1", "--keep-asm"],
+        |dir, output| {
+            assert_compiled(&output);
+            (
+                fs::read_to_string(dir.join("cmov_min.s")).unwrap(),
+                Command::new(dir.join("cmov_min")).status().unwrap(),
+            )
+        },
+    );
+
+    assert!(assembly.contains("cmpl"));
+    assert!(assembly.contains("cmovl"));
+    assert!(!assembly.contains("jmp"), "unexpected jump:\n{}", assembly);
+    assert_eq!(exe_status.code(), Some(3));
+}
+
+#[test]
+fn test_o1_keeps_loop_variables_in_registers() {
+    let (assembly, exe_status) = with_compiled(
+        "reg_loop",
+        "int sum_to(int n) {\n\
+             int sum = 0;\n\
+             for (int i = 0; i < n; i = i + 1) sum = sum + i;\n\
+             return sum;\n\
+         }\n\
+         int main(void) { return sum_to(10); }",
+        &["-O1", "--keep-asm"],
+        |dir, output| {
+            assert_compiled(&output);
+            (
+                fs::read_to_string(dir.join("reg_loop.s")).unwrap(),
+                Command::new(dir.join("reg_loop")).status().unwrap(),
+            )
+        },
+    );
+
+    // 循环里的每个变量都分到了寄存器，不再有任何栈访问
+    assert!(assembly.contains("%ebx"), "{}", assembly);
+    assert!(!assembly.contains("(%rbp)"), "{}", assembly);
+    assert!(assembly.contains("pushq %rbx") && assembly.contains("popq %rbx"));
+    assert_eq!(exe_status.code(), Some(45));
+}
+
+#[test]
+fn test_comma_operator() {
+    assert_eq!(
+        compile_and_run("comma_value", "int main(void) { return (1, 2, 3); }"),
+        3
+    );
+    // 左侧的副作用会发生，实参之间的逗号仍然是分隔符
+    assert_eq!(
+        compile_and_run(
+            "comma_effects",
+            "int sub(int a, int b) { return a - b; }
+             int main(void) {
+                 int i;
+                 int j;
+                 int n = 0;
+                 for (i = 0, j = 10; i < j; i = i + 1, j = j - 1)
+                     n = n + 1;
+                 return sub((n = n * 2, n), 1);
+             }"
+        ),
+        9
+    );
+}
+
+#[test]
+fn test_goto() {
+    // 向前跳过一段代码
+    assert_eq!(
+        compile_and_run(
+            "goto_forward",
+            "int main(void) {
+                 int x = 1;
+                 goto skip;
+                 x = 100;
+             skip:
+                 return x;
+             }"
+        ),
+        1
+    );
+    // 向后跳转构成循环；不同函数中的同名标签互不影响
+    assert_eq!(
+        compile_and_run(
+            "goto_backward",
+            "int count(int n) {
+                 int i = 0;
+             again:
+                 if (i < n) {
+                     i = i + 1;
+                     goto again;
+                 }
+                 return i;
+             }
+             int main(void) {
+                 int sum = 0;
+             again:
+                 sum = sum + count(3);
+                 if (sum < 9)
+                     goto again;
+                 return sum;
+             }"
+        ),
+        9
+    );
+    // 跳过带初始化器的声明：变量仍然在作用域中，只是初始化器没有执行
+    assert_eq!(
+        compile_and_run(
+            "goto_over_declaration",
+            "int main(void) { goto skip; int x = 5; skip: return 0; }"
+        ),
+        0
+    );
+    // 标签之后可以写入被跳过的变量，它有自己的存储位置；-O1 删掉了不可达的初始化器
+    let source = "int main(void) {
+                      int y = 2;
+                      goto skip;
+                      int x = 5;
+                  skip:
+                      x = 3;
+                      return x * 10 + y;
+                  }";
+    for opt_level in ["-O0", "-O1"] {
+        assert_eq!(
+            compile_and_run_with(
+                &format!("goto_over_declaration_write{}", opt_level),
+                source,
+                &[opt_level]
+            ),
+            32,
+            "failed at {}",
+            opt_level
+        );
+    }
+}
+
+#[test]
+fn test_do_while_continue_rechecks_condition() {
+    // continue 跳到条件判断：如果跳回循环开头，循环永远不会结束；
+    // 如果直接跳出循环，sum 会是 0
+    assert_eq!(
+        compile_and_run(
+            "do_while_continue",
+            "int main(void) {
+                 int i = 0;
+                 int sum = 0;
+                 do {
+                     i = i + 1;
+                     if (i % 2)
+                         continue;
+                     sum = sum + i;
+                 } while (i < 10);
+                 return sum;
+             }"
+        ),
+        2 + 4 + 6 + 8 + 10
+    );
+}
+
+#[test]
+fn test_logical_operators_short_circuit() {
+    // 右操作数中的函数一旦被调用，就会以退出码 99 结束程序
+    let source = |expr: &str| {
+        format!(
+            "int exit(int status);
+             int side_effect(void) {{ exit(99); return 1; }}
+             int main(void) {{ int a = {}; return {}; }}",
+            if expr.contains("&&") { 0 } else { 1 },
+            expr
+        )
+    };
+    assert_eq!(
+        compile_and_run("short_circuit_and", &source("a && side_effect()")),
+        0
+    );
+    assert_eq!(
+        compile_and_run("short_circuit_or", &source("a || side_effect()")),
+        1
+    );
+    // 左操作数不能决定结果时，右操作数必须被求值
+    assert_eq!(
+        compile_and_run(
+            "no_short_circuit",
+            "int exit(int status);
+             int side_effect(void) { exit(99); return 1; }
+             int main(void) { int a = 1; return a && side_effect(); }"
+        ),
+        99
+    );
+}
+
+#[test]
+fn test_exit_status_names_the_failing_stage() {
+    let exit_code =
+        |name: &str, source: &str| run_compiler(name, source, &["--quiet"]).status.code();
+
+    // 即使 --quiet 不输出任何进度信息，退出码也能区分失败的阶段
+    assert_eq!(
+        exit_code("stage_lex", "int main(void) { return @; }"),
+        Some(2)
+    );
+    assert_eq!(
+        exit_code("stage_parse", "int main(void) { return; }"),
+        Some(3)
+    );
+    assert_eq!(
+        exit_code("stage_semantics", "int main(void) { return x; }"),
+        Some(4)
+    );
+    assert_eq!(
+        exit_code("stage_ok", "int main(void) { return 0; }"),
+        Some(0)
+    );
+}
+
+#[test]
+fn test_max_stack_rejects_oversized_frames() {
+    let compile = |name: &str, source: &str| {
+        run_compiler(name, source, &["--max-stack=16"])
+            .status
+            .success()
+    };
+
+    // 八个 int 局部变量需要 32 字节的栈帧
+    assert!(!compile(
+        "max_stack_big",
+        "int main(void) { int a = 1; int b = 2; int c = 3; int d = 4;
+                          int e = 5; int f = 6; int g = 7; int h = 8; return h; }"
+    ));
+    assert!(compile(
+        "max_stack_small",
+        "int main(void) { int a = 1; return a; }"
+    ));
+}
+
+#[test]
+fn test_no_implicit_return() {
+    let source = "int main(void) { int a = 5; }";
+    assert_eq!(compile_and_run("implicit_return", source), 0);
+    assert_eq!(compile_and_run("empty_main", "int main(void) {}"), 0);
+
+    let output = run_compiler("no_implicit_return", source, &["--no-implicit-return"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("without returning a value"));
+}
+
+#[test]
+fn test_builtin_expect() {
+    let source = "int main(void) { return __builtin_expect(1, 1); }";
+    assert_eq!(compile_and_run("builtin_expect", source), 1);
+
+    let output = run_compiler("no_builtins", source, &["--no-builtins"]);
+    assert!(!output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stderr)
+            .contains("Call to undeclared function '__builtin_expect'")
+    );
+}
+
+#[test]
+fn test_nested_conditionals() {
+    assert_eq!(
+        compile_and_run(
+            "nested_ternary",
+            "int main(void) {
+                 int a = 1; int b = 1; int c = 0; int d = 1;
+                 return a ? (b ? (c ? (d ? 1 : 2) : 3) : 4) : 5;
+             }"
+        ),
+        3
+    );
+}
+
+#[test]
+fn test_chained_conditionals_are_right_associative() {
+    // `a ? b : c ? d : e` 等价于 `a ? b : (c ? d : e)`
+    for (name, expr, expected) in [
+        ("chained_ternary_first", "1 ? 2 : 0 ? 3 : 4", 2),
+        ("chained_ternary_last", "0 ? 2 : 0 ? 3 : 4", 4),
+    ] {
+        let source = format!("int main(void) {{ return {}; }}", expr);
+        for opt_level in ["-O0", "-O1"] {
+            assert_eq!(
+                compile_and_run_with(&format!("{}{}", name, opt_level), &source, &[opt_level]),
+                expected,
+                "{} at {}",
+                expr,
+                opt_level
+            );
+        }
+    }
+}
+
+#[test]
+fn test_dangling_else_binds_to_inner_if() {
+    // a=1, b=0：只有当 else 属于内层 if 时才会执行 r = 2
+    assert_eq!(
+        compile_and_run(
+            "dangling_else",
+            "int main(void) {
+                 int a = 1; int b = 0; int r = 0;
+                 if (a) if (b) r = 1; else r = 2;
+                 return r;
+             }"
+        ),
+        2
+    );
+}
+
+#[test]
+fn test_bare_variable_and_constant_conditions() {
+    let source = "int pick(int x) { if (x) return 1; return 2; }
+                  int main(void) {
+                      int n = 0;
+                      while (1) {
+                          n = n + 1;
+                          if (n == 5) break;
+                      }
+                      return pick(n) * 10 + pick(0) + n;
+                  }";
+    assert_eq!(compile_and_run("bare_conditions", source), 17);
+
+    // 没有比较运算符的条件直接与 0 比较
+    let output = run_compiler("bare_conditions_asm", source, &["--asm-stdout"]);
+    let assembly = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        assembly.contains("cmpl $0, -4(%rbp)\n    je .L_if_end_"),
+        "{}",
+        assembly
+    );
+}
+
+#[test]
+fn test_emit_asm_prints_assembly_text() {
+    let (output, wrote_files) = with_compiled(
+        "emit_asm",
+        "int main(void) { return 3; }",
+        &["--emit-asm"],
+        |dir, output| {
+            let wrote_files = dir.join("emit_asm.s").exists() || dir.join("emit_asm").exists();
+            (output, wrote_files)
+        },
+    );
+
+    assert!(output.status.success());
+    assert!(!wrote_files);
+    // stdout 只有汇编代码，进度信息都在 stderr
+    let assembly = String::from_utf8(output.stdout).unwrap();
+    assert!(assembly.contains(".globl main\n"), "{}", assembly);
+    assert!(assembly.contains("    ret\n"), "{}", assembly);
+    assert!(!assembly.contains("Lexing"), "{}", assembly);
+}
+
+#[test]
+fn test_stack_passed_parameters() {
+    // 第 7、8 个参数通过栈传递，被调用者从 16(%rbp) 和 24(%rbp) 读取
+    assert_eq!(
+        compile_and_run(
+            "stack_params",
+            "int sum(int a, int b, int c, int d, int e, int f, int g, int h) {
+                 return a + b + c + d + e + f + g + h;
+             }
+             int last_two(int a, int b, int c, int d, int e, int f, int g, int h) {
+                 return g * 10 + h;
+             }
+             int main(void) {
+                 return sum(1, 2, 3, 4, 5, 6, 7, 8) + last_two(0, 0, 0, 0, 0, 0, 7, 8);
+             }"
+        ),
+        36 + 78
+    );
+}
+
+#[test]
+fn test_stack_arguments_with_distinct_expressions() {
+    // 每个参数都是不同的表达式；第 7、8 个经栈传递
+    assert_eq!(
+        compile_and_run(
+            "stack_arg_exprs",
+            "int sum(int a, int b, int c, int d, int e, int f, int g, int h) {
+                 return a + b + c + d + e + f + g + h;
+             }
+             int twice(int x) { return x * 2; }
+             int main(void) {
+                 int x = 3;
+                 return sum(x, x + 1, twice(x), x * x, -x, x / 2, twice(x + 1), 10 - x);
+             }"
+        ),
+        3 + 4 + 6 + 9 - 3 + 1 + 8 + 7
+    );
+}
+
+#[test]
+fn test_quiet_lex_prints_only_tokens() {
+    let output = run_compiler(
+        "quiet_lex",
+        "int main(void) { return 0; }",
+        &["--quiet", "--lex"],
+    );
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.first(), Some(&"--- Generated Tokens ---"));
+    assert_eq!(lines.last(), Some(&"------------------------"));
+}
+
+#[test]
+fn test_dump_tokens_with_spans() {
+    let output = run_compiler(
+        "token_spans",
+        "int x;\n",
+        &["--quiet", "--dump-tokens-with-spans"],
+    );
+    assert!(output.status.success());
+
+    // 每行：种类、字节范围 start:end、起始的 行:列
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(
+        lines,
+        vec![
+            "--- Tokens With Spans ---",
+            "KeywordInt 0:3 1:1",
+            "Identifier(\"x\") 4:5 1:5",
+            "Semicolon 5:6 1:6",
+            "-------------------------",
+        ]
+    );
+}
+
+#[test]
+fn test_progress_goes_to_stderr() {
+    let output = run_compiler(
+        "stderr_progress",
+        "int main(void) { return 0; }",
+        &["--tacky"],
+    );
+    assert!(output.status.success());
+
+    // stdout 只包含请求的 TACKY 输出，进度信息都在 stderr 中
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stdout.starts_with("--- Generated TACKY IR ---"));
+    assert!(!stdout.contains("Lexing"));
+    assert!(stderr.contains("Lexing successful"));
+}
+
+#[test]
+fn test_division_operand_forms() {
+    // 常量除数需要先移入寄存器
+    assert_eq!(
+        compile_and_run("div_const", "int main(void) { int x = 9; return x / 2; }"),
+        4
+    );
+    // 除数是栈上的局部变量
+    assert_eq!(
+        compile_and_run(
+            "div_locals",
+            "int main(void) { int x = 17; int y = 5; return x / y + x % y; }"
+        ),
+        5
+    );
+    // 常量被除数，变量除数
+    assert_eq!(
+        compile_and_run(
+            "div_const_dividend",
+            "int main(void) { int x = 3; return 10 / x; }"
+        ),
+        3
+    );
+}
+
+#[test]
+fn test_listing_shows_source_tacky_and_assembly() {
+    let listing = with_compiled(
+        "listing",
+        "int g = 3;\n\nint twice(int x) {\n    return x * 2;\n}\n\nint main(void) {\n    return 1+2;\n}\n",
+        &["--listing"],
+        |dir, output| {
+            assert_compiled(&output);
+            fs::read_to_string(dir.join("listing.lst")).unwrap()
+        },
+    );
+
+    // 每个函数一节：先是它的源代码，然后是它的 TACKY 和汇编
+    let function = |name: &str| {
+        let header = format!("=== {} ===\n", name);
+        let start = listing.find(&header).unwrap() + header.len();
+        let rest = &listing[start..];
+        rest[..rest.find("===").unwrap_or(rest.len())].to_string()
+    };
+    assert!(listing.starts_with("=== File scope ===\n   1 | int g = 3;\n"));
+    let twice = function("twice");
+    assert!(twice.starts_with(
+        "   3 | int twice(int x) {\n   4 |     return x * 2;\n   5 | }\n--- TACKY ---\n"
+    ));
+    assert!(twice.contains("imull"), "{}", twice);
+    assert!(!twice.contains("1 + 2"), "{}", twice);
+
+    let main = function("main");
+    let (source, rest) = main.split_once("--- TACKY ---\n").unwrap();
+    let (tacky, assembly) = rest.split_once("--- Assembly ---\n").unwrap();
+    assert_eq!(
+        source,
+        "   7 | int main(void) {\n   8 |     return 1+2;\n   9 | }\n"
+    );
+    let temp = tacky
+        .lines()
+        .find_map(|line| line.trim().strip_suffix(" = 1 + 2"))
+        .expect("missing `tmp = 1 + 2`");
+    assert!(tacky.contains(&format!("return {}", temp)));
+    assert!(assembly.contains("main:"));
+    assert!(assembly.contains("movl"));
+    assert!(assembly.contains("addl"));
+    assert!(!assembly.contains("twice:"));
+}
+
+#[test]
+fn test_parse_only_expr() {
+    let output = Command::new(env!("CARGO_BIN_EXE_my_c_compiler"))
+        .arg("--parse-only-expr")
+        .arg("1 + 2 * 3")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    // 根节点是加法，乘法在它的右子树中
+    let add = stdout.find("Add").unwrap();
+    let multiply = stdout.find("Multiply").unwrap();
+    assert!(add < multiply);
+}
+
+#[test]
+fn test_invalid_utf8_input_is_reported() {
+    let mut source = b"int main(void) {\n    return 0; ".to_vec();
+    source.push(0xff);
+    source.extend_from_slice(b"\n}\n");
+
+    let output = run_compiler("bad_utf8", &source, &[]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("is not valid UTF-8 at byte 31 (line 2"),
+        "unexpected error:\n{}",
+        stderr
+    );
+}
+
+#[test]
+fn test_eval_snippet() {
+    let eval = |snippet: &str| {
+        Command::new(env!("CARGO_BIN_EXE_my_c_compiler"))
+            .arg("--eval")
+            .arg(snippet)
+            .status()
+            .unwrap()
+            .code()
+    };
+    assert_eq!(eval("int x=5; x+3"), Some(8));
+    assert_eq!(eval("int x=5; x=x+1; x"), Some(6));
+    assert_eq!(eval("int x=0; if (1) { x = 4; } x"), Some(4));
+}
+
+#[test]
+fn test_run_propagates_exit_code() {
+    let (output, leftovers) = with_compiled(
+        "run_flag",
+        "int main(void){return 7;}",
+        &["--run", "--quiet"],
+        |dir, output| {
+            // 可执行文件放在临时目录中，源文件旁边不留下任何产物
+            let leftovers: Vec<_> = fs::read_dir(dir)
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name())
+                .collect();
+            (output, leftovers)
+        },
+    );
+
+    assert_eq!(output.status.code(), Some(7));
+    assert_eq!(leftovers, ["run_flag.c"]);
+
+    // 编译失败时以出错阶段的退出码退出（语义错误为 4），预处理结果同样不会留下
+    let (output, leftovers) = with_compiled(
+        "run_flag_error",
+        "int main(void){return x;}",
+        &["--run", "--quiet"],
+        |dir, output| {
+            let leftovers: Vec<_> = fs::read_dir(dir)
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name())
+                .collect();
+            (output, leftovers)
+        },
+    );
+    assert_eq!(output.status.code(), Some(4));
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("Run failed"),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(leftovers, ["run_flag_error.c"]);
+}
+
+#[test]
+fn test_asm_stdout() {
+    let (output, wrote_asm, linked) = with_compiled(
+        "asm_stdout",
+        "int main(void) { return 7; }",
+        &["--asm-stdout"],
+        |dir, output| {
+            (
+                output,
+                dir.join("asm_stdout.s").exists(),
+                dir.join("asm_stdout").exists(),
+            )
+        },
+    );
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains(".globl main"), "{}", stdout);
+    assert!(stdout.contains("movl $7, %eax"), "{}", stdout);
+    assert!(stdout.contains("popq %rbp\n    ret"), "{}", stdout);
+    assert!(!wrote_asm && !linked);
+}
+
+#[test]
+fn test_cet_emits_endbr64() {
+    let output = run_compiler(
+        "cet",
+        "int main(void) { return 7; }",
+        &["--cet", "--asm-stdout"],
+    );
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("main:\n    endbr64\n"), "{}", stdout);
+}
+
+#[test]
+fn test_long_long_matches_long() {
+    let asm_of = |name: &str, source: &str| {
+        let output = run_compiler(name, source, &["--asm-stdout", "--quiet"]);
+        assert_compiled(&output);
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    let long_asm = asm_of("long_x", "int main(void) { long x = 5; return x; }");
+    let long_long_asm = asm_of(
+        "long_long_x",
+        "int main(void) { long long x = 5; return x; }",
+    );
+    // int 常量 5 被符号扩展为 long
+    assert!(long_asm.contains("movslq"), "{}", long_asm);
+    assert_eq!(long_asm, long_long_asm);
+}
+
+#[test]
+fn test_extern_putchar_output() {
+    let output = with_compiled(
+        "putchar",
+        "extern int putchar(int c);
+         int main(void) {
+             for (int i = 0; i < 5; i = i + 1)
+                 putchar(65 + i);
+             putchar(10);
+             return 0;
+         }",
+        &[],
+        |dir, output| {
+            assert_compiled(&output);
+            // 捕获程序的 stdout：从 main 返回后，C 运行时会刷新缓冲区
+            Command::new(dir.join("putchar")).output().unwrap()
+        },
+    );
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "ABCDE\n");
+}
+
+#[test]
+fn test_braced_scalar_initializer() {
+    assert_eq!(
+        compile_and_run("braced_scalar", "int main(void) { int x = {5}; return x; }"),
+        5
+    );
+}
+
+#[test]
+fn test_negative_constants_and_negative_zero() {
+    assert_eq!(
+        compile_and_run("negative_constant", "int main(void) { return -5 + 10; }"),
+        5
+    );
+    assert_eq!(
+        compile_and_run("negative_zero", "int main(void) { return -0 + 7; }"),
+        7
+    );
+}
+
+#[test]
+fn test_diagnostics_json() {
+    let (output, source_path) = with_compiled(
+        "diagnostics_json",
+        "int f(void) { int x; if (x) return 1; }\nint main(void) { return f(); }",
+        &[
+            "--diagnostics=json",
+            "-Wuninitialized",
+            "--no-implicit-return",
+        ],
+        |dir, output| (output, dir.join("diagnostics_json.c")),
+    );
+    assert!(!output.status.success());
+    // stderr 中只有一个 JSON 数组：先是未初始化警告，再是缺少返回值的错误
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let stderr = stderr.trim();
+    assert!(
+        stderr.starts_with('[') && stderr.ends_with(']'),
+        "unexpected stderr:\n{}",
+        stderr
+    );
+    assert_eq!(stderr.matches("\"severity\"").count(), 2, "{}", stderr);
+    let warning = stderr.find(r#""severity": "warning""#).unwrap();
+    let error = stderr.find(r#""severity": "error""#).unwrap();
+    assert!(warning < error, "{}", stderr);
+    assert!(stderr.contains("'x' is used uninitialized in function 'f'"));
+    assert!(stderr.contains("without returning a value"));
+    assert!(stderr.contains(&format!(r#""file": "{}""#, source_path.display())));
+}
+
+#[test]
+fn test_errors_name_the_original_file_and_line() {
+    // 头文件放在 run_compiler 将要使用的同一个临时目录中
+    fs::write(
+        test_dir("line_markers").join("defs.h"),
+        "int helper(void) {\n    return missing;\n}\n",
+    )
+    .unwrap();
+    let output = run_compiler(
+        "line_markers",
+        "#include \"defs.h\"\nint main(void) { return helper(); }\n",
+        &["--diagnostics=json"],
+    );
+    assert!(!output.status.success());
+    // 语义错误出现在头文件中，而不是预处理后的 .i 文件或者 line_markers.c
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("Use of undeclared variable 'missing' on line 2 of"),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains(r#"defs.h", "line": 2,"#), "{}", stderr);
+}
+
+#[test]
+fn test_sizeof() {
+    let source = r#"
+        struct Pair { int a; long b; };
+        int main(void) {
+            int x = 0;
+            long l;
+            struct Pair p;
+            /* 操作数不会被求值，x 仍然是 0 */
+            int size = sizeof x++ + sizeof l;
+            if (x != 0) return 1;
+            return sizeof(int) * 100 + sizeof(long) * 10 + size + sizeof p;
+        }
+    "#;
+    assert_eq!(
+        compile_and_run("sizeof", source),
+        (400 + 80 + 12 + 16) % 256
+    );
+
+    // _Bool 和 char 占 1 字节；结构体成员按各自的大小对齐
+    let source = r#"
+        struct Mixed { char c; _Bool b; int i; char d; };
+        int main(void) {
+            _Bool flag;
+            struct Mixed m;
+            return sizeof(_Bool) * 100 + sizeof flag * 10 + sizeof m;
+        }
+    "#;
+    assert_eq!(compile_and_run("sizeof_bool", source), 100 + 10 + 12);
+    let source = "int main(void) { char c; return sizeof c + sizeof(char) * 10; }";
+    assert_eq!(compile_and_run("sizeof_char", source), 11);
+}
+
+#[test]
+fn test_sizeof_is_unsigned_long() {
+    // -1 被转换为 unsigned long 的最大值，比较按无符号进行
+    let source = r#"
+        int main(void) {
+            unsigned long size = sizeof(long);
+            unsigned long max = -1;
+            int wide = sizeof(int) - 5 == max;
+            return (sizeof(int) > -1) * 100 + wide * 10 + (size >> 1);
+        }
+    "#;
+    for opt_level in ["-O0", "-O1"] {
+        assert_eq!(
+            compile_and_run_with(
+                &format!("sizeof_unsigned{}", opt_level),
+                source,
+                &[opt_level]
+            ),
+            10 + 4,
+            "failed at {}",
+            opt_level
+        );
+    }
+}
+
+#[test]
+fn test_static_local() {
+    // -O1 下写入静态变量的指令不能因为本函数之后不再读取它而被删除
+    let source = r#"
+        struct Pair { int a; long b; };
+        int counter(void) {
+            static int count;
+            count = count + 1;
+            return count;
+        }
+        int touch(void) {
+            static long calls = -3000000000;
+            static struct Pair p;
+            calls = calls + 1;
+            p.a = p.a + 2;
+            return p.a;
+        }
+        int main(void) {
+            counter();
+            counter();
+            touch();
+            if (touch() != 4) return 100;
+            return counter();
+        }
+    "#;
+    for opt_level in ["-O0", "-O1"] {
+        assert_eq!(
+            compile_and_run_with(&format!("static_local{}", opt_level), source, &[opt_level]),
+            3,
+            "failed at {}",
+            opt_level
+        );
+    }
+}
+
+#[test]
+fn test_static_local_initializer_runs_once() {
+    // 初始化器在程序启动前就已写入 .data，第二次调用不能把 n 重置为 10
+    let source = r#"
+        int next(void) {
+            static int n = 10;
+            n = n + 1;
+            return n;
+        }
+        int main(void) {
+            int first = next();
+            int second = next();
+            if (first != 11) return 1;
+            return second;
+        }
+    "#;
+    for opt_level in ["-O0", "-O1"] {
+        assert_eq!(
+            compile_and_run_with(
+                &format!("static_init_once{}", opt_level),
+                source,
+                &[opt_level]
+            ),
+            12,
+            "failed at {}",
+            opt_level
+        );
+    }
+}
+
+#[test]
+fn test_global_variable() {
+    // 全局变量在函数之间共享；-O1 下调用 bump 之后不能沿用调用前 g 的值
+    let source = r#"
+        int g = 7;
+        int calls;
+        int bump(int n) {
+            g = g + n;
+            calls = calls + 1;
+            return g;
+        }
+        int main(void) {
+            int before = g;
+            bump(3);
+            bump(2);
+            return g + calls + before;
+        }
+    "#;
+    for opt_level in ["-O0", "-O1"] {
+        assert_eq!(
+            compile_and_run_with(
+                &format!("global_variable{}", opt_level),
+                source,
+                &[opt_level]
+            ),
+            21,
+            "failed at {}",
+            opt_level
+        );
+    }
+}
+
+#[test]
+fn test_global_tentative_definitions_and_folded_initializers() {
+    // 暂定定义和真正的定义合并为一个符号；初始化器中的运算在编译期折叠
+    let source = r#"
+        int g;
+        int g = 2 + 3 * 4;
+        int g;
+        long mask = (4294967296 << 1) | 6;
+        int main(void) {
+            return g + (mask & 255) + (mask > 4294967295);
+        }
+    "#;
+    assert_eq!(compile_and_run("tentative_globals", source), 14 + 6 + 1);
+}
+
+#[test]
+fn test_comma_separated_globals() {
+    let source = "int a = 1, b; int main(void) { return a + b; }";
+    assert_eq!(compile_and_run("comma_globals", source), 1);
+
+    // 有初始值的 a 放在 .data 中，b 默认为 0，放在 .bss 中
+    let output = run_compiler("comma_globals_asm", source, &["--asm-stdout", "--quiet"]);
+    assert_compiled(&output);
+    let assembly = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        assembly.contains("    .data\n.globl a\n    .balign 4\na:\n    .long 1\n"),
+        "{}",
+        assembly
+    );
+    assert!(
+        assembly.contains("    .bss\n.globl b\n    .balign 4\nb:\n    .zero 4\n"),
+        "{}",
+        assembly
+    );
+}
+
+#[test]
+fn test_multiple_functions_have_unique_labels() {
+    // 每个函数都有自己的 if 和循环，生成的局部标签不能在函数之间重复
+    let source = r#"
+        int clamp(int x) {
+            if (x > 10) x = 10;
+            while (x < 0) x = x + 1;
+            return x;
+        }
+        int sum_to(int n) {
+            int total = 0;
+            for (int i = 0; i <= n; i = i + 1) {
+                if (i % 2 == 0) continue;
+                total = total + i;
+            }
+            return total;
+        }
+        int main(void) {
+            int count = 0;
+            do {
+                if (count == 3) break;
+                count = count + 1;
+            } while (1);
+            return clamp(42) + sum_to(5) + count;
+        }
+    "#;
+    let asm = run_compiler("multiple_functions", source, &["--asm-stdout"]);
+    assert!(asm.status.success());
+    let asm = String::from_utf8(asm.stdout).unwrap();
+
+    let labels: Vec<&str> = asm
+        .lines()
+        .filter_map(|line| line.strip_suffix(':'))
+        .collect();
+    let mut unique = labels.clone();
+    unique.sort();
+    unique.dedup();
+    assert_eq!(labels.len(), unique.len(), "duplicate labels in:\n{}", asm);
+    for name in ["clamp", "sum_to", "main"] {
+        assert_eq!(
+            asm.lines()
+                .filter(|line| line.starts_with(".globl") && line.ends_with(name))
+                .count(),
+            1,
+            "{}",
+            asm
+        );
+    }
+
+    // 汇编器接受生成的代码，程序的结果也正确
+    assert_eq!(
+        compile_and_run("multiple_functions_run", source),
+        10 + 9 + 3
+    );
+}
+
+#[test]
+fn test_keep_going_compiles_remaining_files() {
+    let dir = test_dir("keep_going");
+    let broken = dir.join("broken.c");
+    let good = dir.join("good.c");
+    fs::write(&broken, "int main(void) { return missing; }").unwrap();
+    fs::write(&good, "int main(void) { return 4; }").unwrap();
+    let compile = |extra_args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_my_c_compiler"))
+            .arg("--quiet")
+            .args(extra_args)
+            .arg(&broken)
+            .arg(&good)
+            .output()
+            .unwrap()
+    };
+
+    // 默认在第一个失败的文件处停止
+    let output = compile(&[]);
+    assert!(!output.status.success());
+    assert!(!dir.join("good").exists());
+
+    let output = compile(&["--keep-going"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(stderr.contains("broken.c failed"), "{}", stderr);
+    let status = Command::new(dir.join("good")).status().unwrap();
+    assert_eq!(status.code(), Some(4));
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,4 @@
+// expect: 253
+int main(void) {
+    return -7 / 2;
+}
@@ -0,0 +1,4 @@
+// expect: 14
+int main(void) {
+    return 2 + 3 * 4;
+}
@@ -0,0 +1,12 @@
+// expect: 25
+int main(void) {
+    int sum = 0;
+    for (int i = 0; i < 100; i = i + 1) {
+        if (i % 2 == 0)
+            continue;
+        if (i > 9)
+            break;
+        sum = sum + i;
+    }
+    return sum;
+}
@@ -0,0 +1,8 @@
+// expect: 1
+int main(void) {
+    int i = 0;
+    do {
+        i = i + 1;
+    } while (0);
+    return i;
+}
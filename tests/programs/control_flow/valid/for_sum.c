@@ -0,0 +1,7 @@
+// expect: 45
+int main(void) {
+    int sum = 0;
+    for (int i = 0; i < 10; i = i + 1)
+        sum = sum + i;
+    return sum;
+}
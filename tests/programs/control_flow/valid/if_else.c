@@ -0,0 +1,8 @@
+// expect: 2
+int main(void) {
+    int a = 0;
+    if (a)
+        return 1;
+    else
+        return 2;
+}
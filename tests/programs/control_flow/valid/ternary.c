@@ -0,0 +1,5 @@
+// expect: 4
+int main(void) {
+    int a = 1;
+    return a ? 4 : 5;
+}
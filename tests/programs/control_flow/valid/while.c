@@ -0,0 +1,7 @@
+// expect: 10
+int main(void) {
+    int i = 0;
+    while (i < 10)
+        i = i + 1;
+    return i;
+}
@@ -0,0 +1,8 @@
+// expect: 5
+int add(int a, int b) {
+    return a + b;
+}
+
+int main(void) {
+    return add(2, 3);
+}
@@ -0,0 +1,10 @@
+// expect: 6
+int twice(int x);
+
+int main(void) {
+    return twice(3);
+}
+
+int twice(int x) {
+    return x * 2;
+}
@@ -0,0 +1,6 @@
+// expect: 9
+int abs(int x);
+
+int main(void) {
+    return abs(-9);
+}
@@ -0,0 +1,4 @@
+// expect: 1
+int main(void) {
+    return 1 < 2 && 2 <= 2 && 3 > 2 && 3 >= 3;
+}
@@ -0,0 +1,4 @@
+// expect: 0
+int main(void) {
+    return ~-1;
+}
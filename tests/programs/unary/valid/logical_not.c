@@ -0,0 +1,4 @@
+// expect: 1
+int main(void) {
+    return !0;
+}
@@ -0,0 +1,4 @@
+// expect: 251
+int main(void) {
+    return -5;
+}
@@ -0,0 +1,4 @@
+// expect: 3
+int main(void) {
+    return -~2;
+}
@@ -0,0 +1,6 @@
+// expect: 7
+int main(void) {
+    int a;
+    a = 7;
+    return a;
+}
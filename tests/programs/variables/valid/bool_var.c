@@ -0,0 +1,5 @@
+// expect: 1
+int main(void) {
+    _Bool b = 10;
+    return b;
+}
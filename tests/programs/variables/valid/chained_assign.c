@@ -0,0 +1,7 @@
+// expect: 4
+int main(void) {
+    int a;
+    int b;
+    a = b = 2;
+    return a + b;
+}
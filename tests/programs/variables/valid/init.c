@@ -0,0 +1,6 @@
+// expect: 5
+int main(void) {
+    int a = 2;
+    int b = a + 3;
+    return b;
+}
@@ -0,0 +1,8 @@
+// expect: 1
+int main(void) {
+    int x = 1;
+    {
+        int x = 2;
+    }
+    return x;
+}